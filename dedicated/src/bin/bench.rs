@@ -0,0 +1,117 @@
+//! Throughput benchmark: runs each of the engine's stress scenes at several sizes and reports ticks
+//! per second along with the average time spent in each phase of a tick.
+
+use clap::Parser;
+use engine::TickStats;
+use engine::broadphase::SpatialHash;
+use engine::scenarios::{Scenario, falling_boxes, particle_rain, pyramid};
+use engine::sleep::Sleeping;
+use std::error::Error;
+use std::time::Instant;
+
+#[derive(Parser, Debug)]
+#[command(about = "Measures engine throughput on generated stress scenes")]
+struct Args {
+    /// Approximate number of bodies in each scene (comma separated)
+    #[arg(long, value_delimiter = ',', default_values_t = [10, 50, 100])]
+    sizes: Vec<usize>,
+
+    /// Ticks to run each scene for
+    #[arg(long, default_value_t = 200)]
+    ticks: u64,
+
+    /// Seed for the particle rain scene
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Find candidate pairs with a spatial hash of cells this size, rather than sweep-and-prune
+    #[arg(long)]
+    cell: Option<f64>,
+
+    /// Put bodies that come to rest to sleep, with the default thresholds
+    #[arg(long)]
+    sleep: bool,
+
+    /// Start resolving each contact from the impulses it was resolved with last tick
+    #[arg(long)]
+    warm: bool,
+}
+
+// Largest pyramid with at most `size` boxes
+fn pyramid_rows(size: usize) -> usize {
+    (((8 * size + 1) as f64).sqrt() as usize - 1) / 2
+}
+
+fn bench(name: &str, scenario: Scenario, args: &Args) -> Result<(), Box<dyn Error>> {
+    let (bodies, ticks) = (scenario.bodies.len(), args.ticks);
+    let mut engine = scenario.build()?;
+    if let Some(cell) = args.cell {
+        engine.set_broadphase(Box::new(SpatialHash::new(cell)));
+    }
+    if args.sleep {
+        engine.set_sleeping(Some(Sleeping::default()));
+    }
+    engine.set_warm_starting(args.warm);
+    let mut total = TickStats::default();
+
+    let start = Instant::now();
+    for _ in 0..ticks {
+        let stats = engine.tick()?.stats;
+
+        total.evaluation_time += stats.evaluation_time;
+        total.collision_time += stats.collision_time;
+        total.pairs_tested += stats.pairs_tested;
+        total.contacts_resolved += stats.contacts_resolved;
+        total.max_penetration = total.max_penetration.max(stats.max_penetration);
+        total.correction_iterations += stats.correction_iterations;
+        total.root_iterations += stats.root_iterations;
+        total.bodies_asleep += stats.bodies_asleep;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let per_tick = |x: f64| x / ticks as f64;
+    println!(
+        "{:<8} {:>7} {:>10.1} {:>10.3} {:>10.3} {:>9.0} {:>9.1} {:>9.1} {:>9.0} {:>9.2} {:>9.1}",
+        name,
+        bodies,
+        ticks as f64 / elapsed,
+        per_tick(total.evaluation_time) * 1000.0,
+        per_tick(total.collision_time) * 1000.0,
+        per_tick(total.pairs_tested as f64),
+        per_tick(total.contacts_resolved as f64),
+        per_tick(total.correction_iterations as f64),
+        per_tick(total.root_iterations as f64),
+        total.max_penetration,
+        per_tick(total.bodies_asleep as f64),
+    );
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    // Phase timings and counts are averages per tick, the depth is the deepest over the run
+    println!(
+        "{:<8} {:>7} {:>10} {:>10} {:>10} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "scene",
+        "bodies",
+        "ticks/s",
+        "eval ms",
+        "collide ms",
+        "pairs",
+        "contacts",
+        "corr its",
+        "root its",
+        "depth",
+        "asleep"
+    );
+
+    for size in &args.sizes {
+        bench("boxes", falling_boxes(*size), &args)?;
+        bench("pyramid", pyramid(pyramid_rows(*size)), &args)?;
+        bench("rain", particle_rain(*size, args.seed), &args)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Thin viewer for the simulation server: it never runs an engine itself, it only sends a scene and
+//! transport controls, and renders the state snapshots it gets back.
+
+use clap::Parser;
+use clap::builder::PossibleValuesParser;
+use engine::math::Column;
+use engine::scenarios;
+use macroquad::prelude::*;
+use phys_sim::camera::Camera;
+use phys_sim::render::draw_body;
+use phys_sim::scene::load_scene;
+use protocol::{
+    BodySnapshot, ClientMessage, SceneDescription, ServerMessage, read_message, write_message,
+};
+use std::error::Error;
+use std::io::{BufReader, BufWriter};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread::spawn;
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Watches a simulation running on a phys-sim server")]
+struct Args {
+    /// Address of the server
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    address: String,
+
+    /// Scene file to load on the server (.toml, .ron or .json)
+    #[arg(long)]
+    scene: Option<PathBuf>,
+
+    /// Built-in scenario to load on the server in place of --scene
+    #[arg(long, conflicts_with = "scene", value_parser = PossibleValuesParser::new(scenarios::NAMES))]
+    scenario: Option<String>,
+
+    /// Pixels per world unit
+    #[arg(long, default_value_t = 1.0)]
+    scale: f32,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let scene = match (&args.scene, &args.scenario) {
+        (Some(path), _) => Some(
+            load_scene(path)
+                .map_err(|e| format!("Failed to load scene {}: {}", path.display(), e))?,
+        ),
+        (_, Some(name)) => scenarios::by_name(name).map(|x| SceneDescription::describe(&x)),
+        _ => None,
+    };
+
+    let stream = TcpStream::connect(&args.address)
+        .map_err(|e| format!("Failed to connect to {}: {}", args.address, e))?;
+    let mut writer = BufWriter::new(stream.try_clone()?);
+    if let Some(scene) = scene {
+        write_message(&mut writer, &ClientMessage::LoadScene { scene })?;
+    }
+
+    // Messages are read on their own thread so a slow server never stalls rendering
+    let (sender, receiver) = mpsc::channel::<ServerMessage>();
+    let mut reader = BufReader::new(stream);
+    spawn(move || {
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    macroquad::Window::new("phys-sim client", async move {
+        view(args, writer, receiver).await.unwrap();
+    });
+
+    Ok(())
+}
+
+async fn view(
+    args: Args,
+    mut writer: BufWriter<TcpStream>,
+    receiver: mpsc::Receiver<ServerMessage>,
+) -> Result<(), Box<dyn Error>> {
+    let mut camera = Camera::new(args.scale);
+    let mut paused = false;
+
+    let mut tick = 0;
+    let mut bodies = Vec::<BodySnapshot>::new();
+    let mut collisions = Vec::<[f64; 2]>::new();
+    let mut error = None;
+
+    loop {
+        clear_background(WHITE);
+        camera.update();
+
+        // Only the latest state matters, skip straight to it
+        while let Ok(message) = receiver.try_recv() {
+            match message {
+                ServerMessage::SceneLoaded => error = None,
+                ServerMessage::State {
+                    tick: x,
+                    bodies: y,
+                    collisions: z,
+                } => {
+                    (tick, bodies, collisions) = (x, y, z);
+                }
+                ServerMessage::Error { message } => error = Some(message),
+            }
+        }
+
+        if is_key_pressed(KeyCode::Escape) {
+            write_message(&mut writer, &ClientMessage::Disconnect)?;
+            break;
+        }
+        if is_key_pressed(KeyCode::Space) {
+            paused = !paused;
+            let message = if paused {
+                ClientMessage::Pause
+            } else {
+                ClientMessage::Resume
+            };
+            write_message(&mut writer, &message)?;
+        }
+        if paused && is_key_pressed(KeyCode::Period) {
+            write_message(&mut writer, &ClientMessage::Step)?;
+        }
+
+        for x in &bodies {
+            draw_body(&x.body(), &camera, RED);
+        }
+        for x in &collisions {
+            let point = camera.to_screen(&Column::vector(*x));
+            draw_circle(point.x, point.y, 4.0, BLACK);
+        }
+
+        draw_text(
+            &format!(
+                "{}  tick {}  {}",
+                args.address,
+                tick,
+                if paused {
+                    "paused (space: resume, .: step)"
+                } else {
+                    "running (space: pause)"
+                }
+            ),
+            8.0,
+            screen_height() - 8.0,
+            16.0,
+            DARKGRAY,
+        );
+        if let Some(error) = &error {
+            draw_text(error, 8.0, 36.0, 16.0, RED);
+        }
+
+        next_frame().await
+    }
+
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Headless simulation server: every connection gets its own engine, driven by the scene and
+//! inputs the client sends, and receives the state of the world after every tick.
+
+use engine::Engine;
+use engine::math::{Column, Vector};
+use engine::spaces::Space2D;
+use protocol::{BodySnapshot, ClientMessage, ServerMessage, read_message, write_message};
+use std::io::{BufReader, BufWriter};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::sync::mpsc::TryRecvError;
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant};
+
+const ADDRESS: &str = "127.0.0.1:7878";
+// How long to wait for a scene before anything has been loaded
+const IDLE: Duration = Duration::from_millis(10);
+// The longest time step a client can ask for, as the session sleeps for one between ticks
+const MAX_DELTA_T: f64 = 1.0;
+
+// Why the engine can't run at `delta_t` in real time, if it can't
+fn check_delta_t(delta_t: f64) -> Option<String> {
+    (delta_t > MAX_DELTA_T).then(|| {
+        format!(
+            "Time step {} is longer than the server allows ({}).",
+            delta_t, MAX_DELTA_T
+        )
+    })
+}
+
+fn session(stream: TcpStream) -> std::io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<ClientMessage>();
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    spawn(move || {
+        while let Ok(Some(message)) = read_message(&mut reader) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut writer = BufWriter::new(stream);
+    let mut engine: Option<Engine<Space2D>> = None;
+    let mut ticks: u64 = 0;
+
+    loop {
+        let start = Instant::now();
+        let mut step = false;
+
+        loop {
+            let message = match receiver.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Ok(()),
+            };
+
+            match message {
+                ClientMessage::LoadScene { scene } => match scene.build() {
+                    Ok(_) if let Some(message) = check_delta_t(scene.delta_t) => {
+                        write_message(&mut writer, &ServerMessage::Error { message })?
+                    }
+                    Ok(x) => {
+                        engine = Some(x);
+                        ticks = 0;
+                        write_message(&mut writer, &ServerMessage::SceneLoaded)?;
+                    }
+                    Err(e) => write_message(
+                        &mut writer,
+                        &ServerMessage::Error {
+                            message: e.kind.to_string(),
+                        },
+                    )?,
+                },
+                ClientMessage::Pause => engine.iter_mut().for_each(|x| x.pause()),
+                ClientMessage::Resume => engine.iter_mut().for_each(|x| x.resume()),
+                ClientMessage::Step => step = true,
+                ClientMessage::SetDeltaT { delta_t } => {
+                    let Some(engine) = engine.as_mut() else {
+                        continue;
+                    };
+                    let error = check_delta_t(delta_t).or_else(|| {
+                        engine
+                            .set_delta_t(delta_t)
+                            .err()
+                            .map(|e| e.kind.to_string())
+                    });
+                    if let Some(message) = error {
+                        write_message(&mut writer, &ServerMessage::Error { message })?;
+                    }
+                }
+                ClientMessage::ApplyImpulse { body, impulse } => {
+                    let Some(engine) = engine.as_mut() else {
+                        continue;
+                    };
+                    let target = engine
+                        .body(&body)
+                        .and_then(|x| Some((x.id?, x.linear.displacement.clone())));
+                    let Some((id, centroid)) = target else {
+                        let message = format!("No body named {}.", body);
+                        write_message(&mut writer, &ServerMessage::Error { message })?;
+                        continue;
+                    };
+                    engine.apply_impulse(id, Column::vector(impulse), &centroid);
+                }
+                ClientMessage::Disconnect => return Ok(()),
+            }
+        }
+
+        let Some(engine) = engine.as_mut() else {
+            sleep(IDLE);
+            continue;
+        };
+
+        if !engine.is_paused() || step {
+            let tick = match engine.step() {
+                Ok(x) => x,
+                Err(e) => {
+                    write_message(
+                        &mut writer,
+                        &ServerMessage::Error {
+                            message: e.kind.to_string(),
+                        },
+                    )?;
+                    engine.pause();
+                    continue;
+                }
+            };
+            ticks += 1;
+
+            write_message(
+                &mut writer,
+                &ServerMessage::State {
+                    tick: ticks,
+                    bodies: engine.bodies().iter().map(BodySnapshot::capture).collect(),
+                    collisions: tick
+                        .collisions
+                        .iter()
+                        .map(|x| [*x.get(0), *x.get(1)])
+                        .collect(),
+                },
+            )?;
+        }
+
+        // Run in real time
+        let frame = Duration::from_secs_f64(engine.delta_t().max(0.0));
+        if let Some(remaining) = frame.checked_sub(start.elapsed()) {
+            sleep(remaining);
+        }
+    }
+}
+
+fn main() -> std::io::Result<()> {
+    // Engine logging, eg. RUST_LOG=engine::solver=trace
+    env_logger::init();
+    let address = std::env::args().nth(1).unwrap_or(ADDRESS.to_string());
+    let listener = TcpListener::bind(&address)?;
+    println!("Listening on {}", address);
+
+    // A connection that fails before its session starts only loses that client
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(x) => x,
+            Err(e) => {
+                println!("Failed to accept a connection: {}", e);
+                continue;
+            }
+        };
+        let peer = match stream.peer_addr() {
+            Ok(x) => x,
+            Err(e) => {
+                println!("Failed to find who connected: {}", e);
+                continue;
+            }
+        };
+        println!("{} connected", peer);
+
+        spawn(move || {
+            if let Err(e) = session(stream) {
+                println!("{} disconnected: {}", peer, e);
+            } else {
+                println!("{} disconnected", peer);
+            }
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+//! The single world to screen transform used by the viewers. World space is y-up and centred on the
+//! camera, screen space is y-down with the origin in the top left corner.
+
+use engine::math::Column;
+use macroquad::prelude::*;
+
+const ZOOM_STEP: f32 = 1.1;
+const PAN_BUTTON: MouseButton = MouseButton::Right;
+
+pub struct Camera {
+    // World position shown in the middle of the screen
+    pub center: Vec2,
+    // Pixels per world unit
+    pub zoom: f32,
+    last_mouse: Option<Vec2>,
+}
+
+impl Camera {
+    pub fn new(zoom: f32) -> Self {
+        Camera {
+            center: Vec2::ZERO,
+            zoom,
+            last_mouse: None,
+        }
+    }
+
+    pub fn to_screen(&self, point: &Column<2>) -> Vec2 {
+        let point = vec2(point[0] as f32, point[1] as f32) - self.center;
+
+        vec2(
+            point.x * self.zoom + screen_width() / 2.0,
+            screen_height() / 2.0 - point.y * self.zoom,
+        )
+    }
+
+    pub fn to_world(&self, point: Vec2) -> Column<2> {
+        let point = vec2(
+            (point.x - screen_width() / 2.0) / self.zoom,
+            (screen_height() / 2.0 - point.y) / self.zoom,
+        ) + self.center;
+
+        Column::vector([point.x as f64, point.y as f64])
+    }
+
+    /// Converts a world space length to pixels.
+    pub fn length(&self, length: f64) -> f32 {
+        length as f32 * self.zoom
+    }
+
+    /// Zooms around the cursor with the mouse wheel and pans while the right button is held.
+    pub fn update(&mut self) {
+        let mouse: Vec2 = mouse_position().into();
+
+        let (_, wheel) = mouse_wheel();
+        if wheel != 0.0 {
+            let anchor = self.to_world(mouse);
+
+            self.zoom *= ZOOM_STEP.powf(wheel.signum());
+
+            // Keep whatever was under the cursor there
+            let moved = self.to_world(mouse);
+            self.center += vec2((anchor[0] - moved[0]) as f32, (anchor[1] - moved[1]) as f32);
+        }
+
+        if is_mouse_button_down(PAN_BUTTON) {
+            if let Some(last) = self.last_mouse {
+                let delta = mouse - last;
+                self.center += vec2(-delta.x, delta.y) / self.zoom;
+            }
+            self.last_mouse = Some(mouse);
+        } else {
+            self.last_mouse = None;
+        }
+    }
+}
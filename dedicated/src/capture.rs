@@ -0,0 +1,97 @@
+//! Writes what the viewer renders to disk: a single screenshot, a numbered PNG per tick, or an
+//! animated GIF with one frame per tick.
+
+use gif::{Encoder, Frame, Repeat};
+use macroquad::prelude::*;
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+pub const SCREENSHOT: KeyCode = KeyCode::F12;
+
+// Quantization speed of the GIF encoder, 1 (best) to 30 (fastest)
+const GIF_SPEED: i32 = 10;
+
+/// The current screen contents as top to bottom RGBA rows.
+fn screen_rgba() -> (u16, u16, Vec<u8>) {
+    let image = get_screen_data();
+    let row = image.width as usize * 4;
+
+    // OpenGL hands the framebuffer over bottom row first
+    let bytes = image
+        .bytes
+        .chunks_exact(row)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+
+    (image.width, image.height, bytes)
+}
+
+pub fn screenshot(path: &Path) {
+    get_screen_data().export_png(&path.to_string_lossy());
+}
+
+pub enum Capture {
+    Frames {
+        directory: PathBuf,
+        frame: u64,
+    },
+    Gif {
+        file: PathBuf,
+        encoder: Option<Encoder<BufWriter<File>>>,
+        // Centiseconds each frame is shown for
+        delay: u16,
+    },
+}
+
+impl Capture {
+    /// Captures into an animated GIF when `path` ends in `.gif`, otherwise into a directory of PNGs.
+    /// Each frame represents one tick, so the GIF plays back at simulation speed.
+    pub fn new(path: &Path, delta_t: f64) -> Result<Self, Box<dyn Error>> {
+        if path.extension().is_some_and(|x| x == "gif") {
+            Ok(Capture::Gif {
+                file: path.to_path_buf(),
+                encoder: None,
+                delay: (delta_t * 100.0).round().max(1.0) as u16,
+            })
+        } else {
+            fs::create_dir_all(path)?;
+            Ok(Capture::Frames {
+                directory: path.to_path_buf(),
+                frame: 0,
+            })
+        }
+    }
+
+    pub fn capture(&mut self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Capture::Frames { directory, frame } => {
+                screenshot(&directory.join(format!("frame_{:06}.png", frame)));
+                *frame += 1;
+            }
+            Capture::Gif {
+                file,
+                encoder,
+                delay,
+            } => {
+                let (width, height, mut bytes) = screen_rgba();
+
+                if encoder.is_none() {
+                    let mut new = Encoder::new(BufWriter::new(File::create(file)?), width, height, &[])?;
+                    new.set_repeat(Repeat::Infinite)?;
+                    *encoder = Some(new);
+                }
+
+                let mut frame = Frame::from_rgba_speed(width, height, &mut bytes, GIF_SPEED);
+                frame.delay = *delay;
+                encoder.as_mut().unwrap().write_frame(&frame)?;
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use clap::Parser;
+use clap::builder::PossibleValuesParser;
+use engine::scenarios;
+use std::path::PathBuf;
+
+const DEFAULT_SCENE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/scenes/bumper.toml");
+
+#[derive(Parser, Debug, Clone)]
+#[command(about = "Runs a physics scene, either in a window or headless")]
+pub struct Args {
+    /// Scene file to load (.toml, .ron or .json)
+    #[arg(long, default_value = DEFAULT_SCENE)]
+    pub scene: PathBuf,
+
+    /// Built-in scenario to run in place of --scene
+    #[arg(long, conflicts_with = "scene", value_parser = PossibleValuesParser::new(scenarios::NAMES))]
+    pub scenario: Option<String>,
+
+    /// Plain text file of equations (one per line) replacing the scene's, reloaded whenever it changes
+    #[arg(long)]
+    pub equations: Option<PathBuf>,
+
+    /// Simulation time step in seconds, overriding the scene's
+    #[arg(long)]
+    pub dt: Option<f64>,
+
+    /// Frames rendered per second in the viewer
+    #[arg(long, default_value_t = 60.0)]
+    pub fps: f32,
+
+    /// Pixels per world unit in the viewer
+    #[arg(long, default_value_t = 1.0)]
+    pub scale: f32,
+
+    /// Number of ticks to run for, required when headless
+    #[arg(long, required_if_eq("headless", "true"))]
+    pub ticks: Option<u64>,
+
+    /// Seed for anything stochastic in the simulation
+    #[arg(long, default_value_t = 0)]
+    pub seed: u64,
+
+    /// Run without opening a window
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Expression to sample every tick and plot, eg. "0.5*m_B*(v_x_B^2+v_y_B^2)" (repeatable)
+    #[arg(long)]
+    pub plot: Vec<String>,
+
+    /// Write the sampled plot expressions to this file as whitespace separated columns on exit
+    #[arg(long)]
+    pub plot_output: Option<PathBuf>,
+
+    /// Capture every tick the viewer renders, into an animated GIF if this ends in .gif or otherwise a
+    /// directory of numbered PNGs
+    #[arg(long)]
+    pub capture: Option<PathBuf>,
+
+    /// Write the trajectory of every body to this file (.csv, anything else is JSON Lines), or a
+    /// replay of the run which --play can step through if it ends in .replay
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+
+    /// Replay a file written by --record, in place of --scene. Starts paused
+    #[arg(long, conflicts_with_all = ["scene", "scenario", "equations", "dt", "headless"])]
+    pub play: Option<PathBuf>,
+}
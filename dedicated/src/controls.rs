@@ -0,0 +1,64 @@
+//! Keyboard transport controls: space pauses, `.` steps a single tick while paused, `,` and `;`
+//! halve and double the speed of the simulation.
+
+use engine::Engine;
+use engine::spaces::Space2D;
+use macroquad::prelude::*;
+
+const MIN_TIME_SCALE: f64 = 1.0 / 64.0;
+const MAX_TIME_SCALE: f64 = 8.0;
+
+pub struct Transport {
+    // The time step the scene was built with, scaled by time_scale
+    base_delta_t: f64,
+    time_scale: f64,
+}
+
+impl Transport {
+    pub fn new(engine: &Engine<Space2D>) -> Self {
+        Transport {
+            base_delta_t: engine.delta_t(),
+            time_scale: 1.0,
+        }
+    }
+
+    /// Applies this frame's key presses to the engine, returning true if a single step was requested.
+    pub fn update(&mut self, engine: &mut Engine<Space2D>) -> bool {
+        if is_key_pressed(KeyCode::Space) {
+            if engine.is_paused() {
+                engine.resume();
+            } else {
+                engine.pause();
+            }
+        }
+
+        if is_key_pressed(KeyCode::Comma) {
+            self.time_scale = (self.time_scale / 2.0).max(MIN_TIME_SCALE);
+            // Both scales are positive, so only ever a valid step
+            engine.set_delta_t(self.base_delta_t * self.time_scale).ok();
+        }
+
+        if is_key_pressed(KeyCode::Semicolon) {
+            self.time_scale = (self.time_scale * 2.0).min(MAX_TIME_SCALE);
+            engine.set_delta_t(self.base_delta_t * self.time_scale).ok();
+        }
+
+        engine.is_paused() && is_key_pressed(KeyCode::Period)
+    }
+
+    pub fn draw(&self, engine: &Engine<Space2D>) {
+        let status = if engine.is_paused() {
+            "paused (space: resume, .: step)"
+        } else {
+            "running (space: pause)"
+        };
+
+        draw_text(
+            &format!("{}  speed x{} (,/;)", status, self.time_scale),
+            8.0,
+            screen_height() - 8.0,
+            16.0,
+            DARKGRAY,
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Debug overlay for the viewer, drawing what the engine sees rather than what the scene looks like.
+
+use engine::collide::{Collide, Collision};
+use crate::camera::Camera;
+use engine::math::Vector;
+use engine::spaces::Space2D;
+use engine::Body;
+use macroquad::prelude::*;
+
+pub const TOGGLE: KeyCode = KeyCode::F3;
+
+// Seconds of motion the velocity arrows and angular arcs represent
+const LOOKAHEAD: f32 = 0.25;
+// Screen length of contact normals
+const NORMAL_LENGTH: f32 = 20.0;
+
+fn arrow(from: Vec2, to: Vec2, color: Color) {
+    draw_line(from.x, from.y, to.x, to.y, 1.5, color);
+
+    let direction = (to - from).normalize_or_zero();
+    let side = vec2(-direction.y, direction.x);
+    for x in [side, -side] {
+        let tip = to - direction * 6.0 + x * 3.0;
+        draw_line(to.x, to.y, tip.x, tip.y, 1.5, color);
+    }
+}
+
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+impl DebugOverlay {
+    pub fn new() -> Self {
+        DebugOverlay { enabled: false }
+    }
+
+    pub fn update(&mut self) {
+        if is_key_pressed(TOGGLE) {
+            self.enabled = !self.enabled;
+        }
+    }
+
+    /// Draws `bodies` with the vertices `collider` sees for them, greyed out where `asleep`.
+    pub fn draw(
+        &self,
+        bodies: &[Body<Space2D>],
+        asleep: impl Fn(&Body<Space2D>) -> bool,
+        contacts: &[Collision<Space2D>],
+        collider: &dyn Collide<Space2D>,
+        camera: &Camera,
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        for body in bodies {
+            self.draw_body(body, asleep(body), collider, camera);
+        }
+
+        for contact in contacts {
+            let point = camera.to_screen(&contact.point);
+            // Flip y, screen space grows downwards
+            let normal = vec2(*contact.normal.get(0) as f32, -*contact.normal.get(1) as f32);
+
+            draw_circle(point.x, point.y, 3.0, MAGENTA);
+            arrow(point, point + normal * NORMAL_LENGTH, MAGENTA);
+            draw_text(
+                &format!("{:.2}", contact.depth),
+                point.x + 4.0,
+                point.y - 4.0,
+                14.0,
+                MAGENTA,
+            );
+        }
+
+        draw_text("debug (F3)", 8.0, 16.0, 16.0, DARKGRAY);
+    }
+
+    fn draw_body(
+        &self,
+        body: &Body<Space2D>,
+        asleep: bool,
+        collider: &dyn Collide<Space2D>,
+        camera: &Camera,
+    ) {
+        let center = camera.to_screen(&body.linear.displacement);
+
+        let bases = collider.vertices(body);
+
+        if !bases.is_empty() {
+            let (mut min, mut max) = (vec2(f32::MAX, f32::MAX), vec2(f32::MIN, f32::MIN));
+            for x in bases.iter() {
+                let point = camera.to_screen(&x.plus(&body.linear.displacement));
+                min = min.min(point);
+                max = max.max(point);
+            }
+
+            let color = if asleep { GRAY } else { GREEN };
+            draw_rectangle_lines(min.x, min.y, max.x - min.x, max.y - min.y, 1.0, color);
+        }
+
+        let velocity = body.linear.velocity.scale(LOOKAHEAD as f64);
+        arrow(
+            center,
+            camera.to_screen(&body.linear.displacement.plus(&velocity)),
+            BLUE,
+        );
+
+        let omega = *body.angular.velocity.get(0) as f32;
+        if omega != 0.0 {
+            let theta = *body.angular.displacement.get(0) as f32;
+            // draw_arc measures degrees clockwise, the engine radians counter-clockwise
+            let sweep = (omega * LOOKAHEAD).to_degrees();
+            draw_arc(
+                center.x,
+                center.y,
+                24,
+                12.0,
+                -theta.to_degrees() - sweep.max(0.0),
+                1.5,
+                sweep.abs().min(360.0),
+                ORANGE,
+            );
+        }
+
+        draw_circle(center.x, center.y, 2.0, BLACK);
+    }
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,61 @@
+//! Click and drag bodies around the viewer by steering their velocity towards the cursor.
+
+use crate::camera::Camera;
+use engine::math::Column;
+use engine::spaces::Space2D;
+use engine::{BodyId, Engine};
+use macroquad::prelude::*;
+
+// Fraction of the distance to the cursor to close per second
+const STIFFNESS: f64 = 10.0;
+
+pub struct Drag {
+    // Id of the held body, and where it was grabbed relative to its centroid
+    held: Option<(BodyId, Column<2>)>,
+}
+
+impl Drag {
+    pub fn new() -> Self {
+        Drag { held: None }
+    }
+
+    pub fn update(&mut self, engine: &mut Engine<Space2D>, camera: &Camera) {
+        let cursor = camera.to_world(mouse_position().into());
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            self.held = engine.query_point(&cursor).first().and_then(|&id| {
+                let offset = cursor.plus(&engine.get(id)?.linear.displacement.scale(-1.0));
+                Some((id, offset))
+            });
+        }
+
+        if is_mouse_button_released(MouseButton::Left) {
+            self.held = None;
+        }
+
+        if let Some((id, offset)) = &self.held
+            && let Some(body) = engine.get_mut(*id)
+        {
+            let target = cursor.plus(&offset.scale(-1.0));
+            let delta = target.plus(&body.linear.displacement.scale(-1.0));
+
+            body.linear.velocity = delta.scale(STIFFNESS);
+            body.angular.velocity = Column::empty();
+        }
+    }
+
+    /// Id of the body being dragged, if any.
+    pub fn held(&self) -> Option<BodyId> {
+        self.held.as_ref().map(|(id, _)| *id)
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.held.is_some()
+    }
+}
+
+impl Default for Drag {
+    fn default() -> Self {
+        Self::new()
+    }
+}
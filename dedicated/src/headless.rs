@@ -0,0 +1,180 @@
+//! Runs a simulation without a window, streaming the trajectory of every body to a file.
+
+use engine::Engine;
+use engine::math::Vector;
+use engine::series::SeriesRecorder;
+use engine::spaces::Space2D;
+use protocol::BodySnapshot;
+use serde::Serialize;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrajectoryFormat {
+    Csv,
+    JsonLines,
+}
+
+impl TrajectoryFormat {
+    /// Picks a format from a file extension, `.csv` for CSV and anything else for JSON Lines.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|x| x.to_str()) {
+            Some("csv") => TrajectoryFormat::Csv,
+            _ => TrajectoryFormat::JsonLines,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TickRecord<'a> {
+    tick: u64,
+    time: f64,
+    bodies: &'a [BodySnapshot],
+    collisions: &'a [[f64; 2]],
+}
+
+pub struct TrajectoryWriter<W: Write> {
+    output: W,
+    format: TrajectoryFormat,
+}
+
+impl<W: Write> TrajectoryWriter<W> {
+    pub fn new(mut output: W, format: TrajectoryFormat) -> std::io::Result<Self> {
+        if format == TrajectoryFormat::Csv {
+            writeln!(output, "tick,time,kind,body,x,y,theta,v_x,v_y,omega")?;
+        }
+
+        Ok(TrajectoryWriter { output, format })
+    }
+
+    pub fn write(
+        &mut self,
+        tick: u64,
+        time: f64,
+        bodies: &[BodySnapshot],
+        collisions: &[[f64; 2]],
+    ) -> std::io::Result<()> {
+        match self.format {
+            TrajectoryFormat::Csv => {
+                for x in bodies {
+                    writeln!(
+                        self.output,
+                        "{},{},body,{},{},{},{},{},{},{}",
+                        tick,
+                        time,
+                        x.name,
+                        x.position[0],
+                        x.position[1],
+                        x.rotation,
+                        x.velocity[0],
+                        x.velocity[1],
+                        x.angular_velocity
+                    )?;
+                }
+
+                for x in collisions {
+                    writeln!(self.output, "{},{},collision,,{},{},,,,", tick, time, x[0], x[1])?;
+                }
+            }
+            TrajectoryFormat::JsonLines => {
+                serde_json::to_writer(
+                    &mut self.output,
+                    &TickRecord {
+                        tick,
+                        time,
+                        bodies,
+                        collisions,
+                    },
+                )?;
+                writeln!(self.output)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+/// Records the initial state, then ticks the engine `ticks` times recording the state after each.
+/// Expressions in `plot` are sampled alongside.
+pub fn run<W: Write>(
+    engine: &mut Engine<Space2D>,
+    ticks: u64,
+    writer: &mut TrajectoryWriter<W>,
+    mut plot: Option<&mut SeriesRecorder>,
+) -> Result<(), Box<dyn Error>> {
+    let snapshot = |engine: &Engine<Space2D>| {
+        engine
+            .bodies()
+            .iter()
+            .map(BodySnapshot::capture)
+            .collect::<Vec<_>>()
+    };
+
+    writer.write(0, engine.time(), &snapshot(engine), &[])?;
+    if let Some(plot) = plot.as_mut() {
+        plot.sample(engine)?;
+    }
+
+    for i in 1..=ticks {
+        let tick = engine.step()?;
+
+        let collisions = tick
+            .collisions
+            .iter()
+            .map(|x| [*x.get(0), *x.get(1)])
+            .collect::<Vec<_>>();
+
+        writer.write(i, engine.time(), &snapshot(engine), &collisions)?;
+        if let Some(plot) = plot.as_mut() {
+            plot.sample(engine)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use protocol::{BodyDescription, KindDescription, SceneDescription, ShapeDescription};
+
+    #[test]
+    fn test_csv_trajectory() {
+        let mut engine = SceneDescription {
+            bodies: vec![BodyDescription {
+                name: "A".to_string(),
+                shape: ShapeDescription::Rec {
+                    width: 1.0,
+                    height: 1.0,
+                },
+                kind: KindDescription::Dynamic,
+                mass: 1.0,
+                moi: None,
+                position: [0.0, 0.0],
+                rotation: 0.0,
+                velocity: [1.0, 0.0],
+                angular_velocity: 0.0,
+                properties: Default::default(),
+            }],
+            equations: vec![],
+            delta_t: 0.5,
+            restitution: 1.0,
+        }
+        .build()
+        .unwrap();
+
+        let mut writer = TrajectoryWriter::new(Vec::new(), TrajectoryFormat::Csv).unwrap();
+        run(&mut engine, 2, &mut writer, None).unwrap();
+
+        let output = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[3], "2,1,body,A,1,0,0,1,0,0");
+    }
+}
@@ -0,0 +1,12 @@
+pub mod camera;
+pub mod capture;
+pub mod cli;
+pub mod controls;
+pub mod debug;
+pub mod drag;
+pub mod headless;
+pub mod plot;
+pub mod reload;
+pub mod render;
+pub mod replay;
+pub mod scene;
@@ -1,105 +1,240 @@
-extern crate core;
-
-use core::task;
-use engine::collide::Collide2D;
-use engine::math::solve::Environment;
+use clap::Parser;
 use engine::math::{Column, Vector};
-use engine::spaces::Space2D;
-use engine::{Body, BodyProperties, Engine, Shape};
+use engine::record::{Input, Recorder, Replay};
+use engine::scenarios;
+use engine::series::SeriesRecorder;
 use macroquad::prelude::*;
-use std::f64::consts::PI;
-use std::iter::Scan;
-use std::thread::{sleep, yield_now};
+use phys_sim::camera::Camera;
+use phys_sim::capture::{Capture, SCREENSHOT, screenshot};
+use phys_sim::cli::Args;
+use phys_sim::controls::Transport;
+use phys_sim::debug::DebugOverlay;
+use phys_sim::drag::Drag;
+use phys_sim::headless::{TrajectoryFormat, TrajectoryWriter, run};
+use phys_sim::plot::draw_plot;
+use phys_sim::reload::{EquationWatcher, load_equations};
+use phys_sim::render::draw_body;
+use phys_sim::replay::{is_replay, load_replay, save_replay};
+use phys_sim::scene::load_scene;
+use protocol::{BodySnapshot, SceneDescription};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::thread::sleep;
 use std::time::{Duration, Instant};
 
-const FPS: f32 = 60.0;
-const SCALE: f32 = 1.0;
-
-#[macroquad::main("MyGame")]
-async fn main() {
-    // let mut body = Body::<Space2D>::at_rest(
-    //     "A".to_string(),
-    //     Shape::Rec(40.0, 40.0),
-    //     Column::vector([-100.0, -300.0]),
-    //     Column::vector([-PI]),
-    //     BodyProperties::rectangle(10.0, 1.0, 10.0),
-    // );
-    //
-    // body.linear.velocity.content[0][0] = 50.0;
-    // let mut engine = Engine::new(
-    //     vec![
-    //         body,
-    //         Body::at_rest(
-    //             "Pendulum".to_string(),
-    //             Shape::Rec(20.0, 400.0),
-    //             Column::vector([0.0,-200.0]),
-    //             Column::vector([0.0]),
-    //             BodyProperties::rectangle(10.0, 20.0, 400.0),
-    //         ),
-    //     ],
-    //     Environment::build(
-    //         vec![
-    //             "s_Pendulum = (200sin(theta_Pendulum))hati+(-200cos(theta_Pendulum))hatj",
-    //             "alpha_Pendulum*I_cm_Pendulum=(200*(m_Pendulum*-100)*sin(theta_Pendulum))hatk"
-    //         ],
-    //         engine::math::solve::builtin::functions(),
-    //         engine::math::solve::builtin::constants(),
-    //     )
-    //     .unwrap(),
-    //     Box::new(Collide2D::new()),
-    //     1.0 / FPS as f64,
-    //     1.0,
-    // );
-    let mut engine = Engine::new(
-        vec![
-            Body::<Space2D>::at_rest(
-                "B".to_string(),
-                Shape::Rec(40.0, 40.0),
-                Column::vector([-300.0, 200.0]),
-                Column::vector([0.0]),
-                BodyProperties::rectangle(1.0, 40.0, 40.0),
-            ),
-            Body::at_rest(
-                "Bumper 2".to_string(),
-                Shape::Rec(200.0, 20.0),
-                Column::vector([-300.0, -300.0]),
-                Column::vector([0.0]),
-                BodyProperties::rectangle(10000000000.0, 200.0, 20.0),
-            ),
-
-        ],
-        Environment::build(
-            vec![
-                "g=100",
-                "a_B=-g*hatj",
-            ],
-            engine::math::solve::builtin::functions(),
-            engine::math::solve::builtin::constants(),
-        )
-        .unwrap(),
-        Box::new(Collide2D::new()),
-        1.0 / FPS as f64,
-        1.0,
-    );
+type Trajectory = TrajectoryWriter<Box<dyn Write>>;
+
+// Samples kept for plotting in the viewer
+const PLOT_CAPACITY: usize = 600;
+
+fn write_plot(args: &Args, plot: &SeriesRecorder) -> Result<(), Box<dyn Error>> {
+    if let Some(path) = &args.plot_output {
+        let mut file = BufWriter::new(File::create(path)?);
+        plot.write_columns(&mut file)?;
+        file.flush()?;
+    }
+
+    Ok(())
+}
+
+fn trajectory(args: &Args) -> Result<Option<Trajectory>, Box<dyn Error>> {
+    Ok(match &args.record {
+        Some(path) if !is_replay(path) => Some(TrajectoryWriter::new(
+            Box::new(BufWriter::new(File::create(path)?)) as Box<dyn Write>,
+            TrajectoryFormat::from_path(path),
+        )?),
+        _ => None,
+    })
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // Engine logging, eg. RUST_LOG=engine::collider=debug
+    env_logger::init();
+    let args = Args::parse();
+
+    let (scene, replay) = match &args.play {
+        Some(path) => {
+            let (scene, recording) = load_replay(path)
+                .map_err(|e| format!("Failed to load replay {}: {}", path.display(), e))?;
+            (scene, Some(Replay::new(recording)))
+        }
+        None => {
+            let mut scene = match &args.scenario {
+                Some(name) => SceneDescription::describe(
+                    &scenarios::by_name(name).ok_or(format!("Unknown scenario {}", name))?,
+                ),
+                None => load_scene(&args.scene).map_err(|e| {
+                    format!("Failed to load scene {}: {}", args.scene.display(), e)
+                })?,
+            };
+            if let Some(dt) = args.dt {
+                scene.delta_t = dt;
+            }
+            if let Some(path) = &args.equations {
+                scene.equations = load_equations(path)?;
+            }
+            (scene, None)
+        }
+    };
+    let mut engine = scene.build()?;
+    engine.set_seed(args.seed);
+
+    if args.headless {
+        if args.record.as_deref().is_some_and(is_replay) {
+            return Err("Replays can only be recorded in the viewer".into());
+        }
+
+        let mut writer = match trajectory(&args)? {
+            Some(x) => x,
+            None => TrajectoryWriter::new(
+                Box::new(std::io::stdout()) as Box<dyn Write>,
+                TrajectoryFormat::JsonLines,
+            )?,
+        };
+
+        let mut plot = SeriesRecorder::new(args.plot.clone());
+        run(
+            &mut engine,
+            args.ticks.unwrap_or(0),
+            &mut writer,
+            Some(&mut plot),
+        )?;
+        writer.finish()?;
+        write_plot(&args, &plot)?;
+
+        return Ok(());
+    }
+
+    let trajectory = trajectory(&args)?;
+    macroquad::Window::new("MyGame", async move {
+        view(engine, args, scene, trajectory, replay).await.unwrap();
+    });
+
+    Ok(())
+}
+
+async fn view(
+    mut engine: engine::Engine<engine::spaces::Space2D>,
+    args: Args,
+    scene: SceneDescription,
+    mut trajectory: Option<Trajectory>,
+    mut replay: Option<Replay>,
+) -> Result<(), Box<dyn Error>> {
+    let fps = args.fps;
+    let mut camera = Camera::new(args.scale);
 
     let mut last_tick = Instant::now();
+    let mut ticks: u64 = 0;
+    let mut plot = SeriesRecorder::new(args.plot.clone()).with_capacity(PLOT_CAPACITY);
 
     let mut collisions = Vec::<(Column<2>, Instant)>::new();
+    let mut contacts = Vec::new();
+    let mut debug = DebugOverlay::new();
+    let mut drag = Drag::new();
+    let mut transport = Transport::new(&engine);
+    let mut watcher = args.equations.clone().map(EquationWatcher::new);
+    let mut capture = match &args.capture {
+        Some(path) => Some(Capture::new(path, engine.delta_t())?),
+        None => None,
+    };
+    let mut recorder = args
+        .record
+        .as_deref()
+        .is_some_and(is_replay)
+        .then(Recorder::new);
+    if replay.is_some() {
+        engine.pause();
+    }
 
     loop {
         clear_background(WHITE);
+        debug.update();
+        camera.update();
+        // While replaying, the recording is the only source of input
+        if replay.is_none() {
+            if let Some(watcher) = watcher.as_mut()
+                && watcher.poll(&mut engine)
+                && let Some(recorder) = recorder.as_mut()
+            {
+                recorder.input(Input::SetEquations(watcher.equations.clone()));
+            }
+
+            drag.update(&mut engine, &camera);
+            if let Some(i) = drag.held().and_then(|x| engine.index_of_id(x))
+                && let Some(recorder) = recorder.as_mut()
+            {
+                let body = &engine.bodies()[i];
+                recorder.input(Input::SetVelocity {
+                    body: i,
+                    linear: vec![*body.linear.velocity.get(0), *body.linear.velocity.get(1)],
+                    angular: vec![*body.angular.velocity.get(0)],
+                });
+            }
+        }
+
+        // Leaving through escape (rather than closing the window) flushes recordings
+        if args.ticks.is_some_and(|x| ticks >= x) || is_key_pressed(KeyCode::Escape) {
+            break;
+        }
+
+        let step = transport.update(&mut engine);
+        let mut advanced = step || !engine.is_paused();
+
+        let tick = match replay.as_mut() {
+            Some(replay) if advanced => match replay.advance(&mut engine)? {
+                Some(tick) => tick,
+                None => {
+                    // Hold on the final state once the recording runs out
+                    engine.pause();
+                    advanced = false;
+                    engine.tick()?
+                }
+            },
+            _ if step => engine.step()?,
+            _ => engine.tick()?,
+        };
+        if advanced {
+            ticks += 1;
+            plot.sample(&engine)?;
 
-        let tick = engine.tick().unwrap();
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.tick(&engine);
+            }
+            if let Some(trajectory) = trajectory.as_mut() {
+                trajectory.write(
+                    ticks,
+                    engine.time(),
+                    &engine
+                        .bodies()
+                        .iter()
+                        .map(BodySnapshot::capture)
+                        .collect::<Vec<_>>(),
+                    &tick
+                        .collisions
+                        .iter()
+                        .map(|x| [*x.get(0), *x.get(1)])
+                        .collect::<Vec<_>>(),
+                )?;
+            }
+        }
+
+        if advanced {
+            contacts = tick.contacts;
+        }
         for x in tick.collisions {
             collisions.push((x, Instant::now()));
         }
-        if last_tick.elapsed().as_secs_f32() > 1.0 / FPS {
-            println!(
-                "WARNING: Engine overloaded by {} ms",
-                ((last_tick.elapsed().as_millis() as f32) - (1000.0 / FPS)).abs()
-            );
-        } else {
-            sleep(Duration::from_secs_f32(1.0 / FPS) - last_tick.elapsed());
+        // When capturing, frames map to ticks rather than wall time so there is no need to keep pace
+        if capture.is_none() {
+            if last_tick.elapsed().as_secs_f32() > 1.0 / fps {
+                println!(
+                    "WARNING: Engine overloaded by {} ms",
+                    ((last_tick.elapsed().as_millis() as f32) - (1000.0 / fps)).abs()
+                );
+            } else {
+                sleep(Duration::from_secs_f32(1.0 / fps) - last_tick.elapsed());
+            }
         }
 
         // sleep(Duration::from_secs_f32(3.0 / FPS));
@@ -107,32 +242,17 @@ async fn main() {
         last_tick = Instant::now();
 
         for x in engine.bodies() {
-            match x.shape {
-                Shape::Rec(width, height) => {
-                    draw_rectangle_ex(
-                        (*x.linear.displacement.get(0) as f32) * SCALE + screen_width() / 2.0,
-                        screen_height() / 2.0 - (*x.linear.displacement.get(1) as f32) * SCALE,
-                        width as f32 * SCALE,
-                        height as f32 * SCALE,
-                        DrawRectangleParams {
-                            rotation: -*x.angular.displacement.get(0) as f32,
-                            offset: vec2(0.5, 0.5),
-                            color: RED,
-                            ..Default::default()
-                        },
-                    );
-                }
-                _ => panic!(),
-            }
+            draw_body(x, &camera, RED);
         }
         collisions.retain(|x| x.1.elapsed().as_secs_f32() < 0.5);
         for (i, x) in collisions.iter().enumerate() {
             let scale = x.1.elapsed().as_secs_f32() / 0.5;
             let size = scale * 10.0;
 
+            let point = camera.to_screen(&x.0);
             draw_ellipse(
-                ((*x.0.get(0) as f32) * SCALE) + screen_width() / 2.0,
-                screen_height() / 2.0 - (*x.0.get(1) as f32) * SCALE,
+                point.x,
+                point.y,
                 size,
                 size,
                 0.0,
@@ -144,6 +264,47 @@ async fn main() {
             )
         }
 
+        debug.draw(
+            engine.bodies(),
+            |x| x.id.is_some_and(|x| engine.is_asleep(x)),
+            &contacts,
+            engine.collider(),
+            &camera,
+        );
+        transport.draw(&engine);
+        draw_plot(&plot);
+
+        if advanced && let Some(capture) = capture.as_mut() {
+            capture.capture()?;
+        }
+        if is_key_pressed(SCREENSHOT) {
+            screenshot(format!("screenshot_{:06}.png", ticks).as_ref());
+        }
+        if let Some(error) = watcher.as_ref().and_then(|x| x.error.as_ref()) {
+            draw_text(error, 8.0, 36.0, 16.0, RED);
+        }
+        if let Some(replay) = replay.as_ref() {
+            draw_text(
+                &format!("replay tick {}/{}", replay.position(), replay.len()),
+                8.0,
+                screen_height() - 28.0,
+                16.0,
+                DARKGRAY,
+            );
+        }
+
         next_frame().await
     }
+
+    if let Some(trajectory) = trajectory {
+        trajectory.finish()?;
+    }
+    if let (Some(recorder), Some(path)) = (recorder, &args.record) {
+        save_replay(path, &scene, &recorder.finish())?;
+    }
+    write_plot(&args, &plot)?;
+    // Dropping the encoder writes the GIF trailer
+    drop(capture);
+
+    Ok(())
 }
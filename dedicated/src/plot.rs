@@ -0,0 +1,70 @@
+//! Live line plot of a SeriesRecorder, drawn in the top right corner of the viewer.
+
+use engine::series::SeriesRecorder;
+use macroquad::prelude::*;
+
+const WIDTH: f32 = 320.0;
+const HEIGHT: f32 = 160.0;
+const MARGIN: f32 = 8.0;
+const COLORS: [Color; 5] = [BLUE, RED, DARKGREEN, ORANGE, PURPLE];
+
+pub fn draw_plot(recorder: &SeriesRecorder) {
+    let series = recorder.series();
+    if series.is_empty() {
+        return;
+    }
+
+    let left = screen_width() - WIDTH - MARGIN;
+    let top = MARGIN;
+    draw_rectangle(left, top, WIDTH, HEIGHT, Color::new(1.0, 1.0, 1.0, 0.8));
+    draw_rectangle_lines(left, top, WIDTH, HEIGHT, 1.0, DARKGRAY);
+
+    // Shared axes so related quantities (eg. kinetic and potential energy) compare directly
+    let (mut min, mut max) = (f64::MAX, f64::MIN);
+    let (mut start, mut end) = (f64::MAX, f64::MIN);
+    for x in series {
+        if let Some((low, high)) = x.range() {
+            min = min.min(low);
+            max = max.max(high);
+        }
+        if let (Some(first), Some(last)) = (x.samples.first(), x.samples.last()) {
+            start = start.min(first.0);
+            end = end.max(last.0);
+        }
+    }
+    if min > max || start >= end {
+        return;
+    }
+    if (max - min).abs() < f64::EPSILON {
+        min -= 1.0;
+        max += 1.0;
+    }
+
+    let to_screen = |(t, value): (f64, f64)| {
+        vec2(
+            left + ((t - start) / (end - start)) as f32 * WIDTH,
+            top + HEIGHT - ((value - min) / (max - min)) as f32 * HEIGHT,
+        )
+    };
+
+    for (i, x) in series.iter().enumerate() {
+        let color = COLORS[i % COLORS.len()];
+
+        for (a, b) in x.samples.iter().zip(x.samples.iter().skip(1)) {
+            let (a, b) = (to_screen(*a), to_screen(*b));
+            draw_line(a.x, a.y, b.x, b.y, 1.0, color);
+        }
+
+        let last = x.samples.last().map(|x| x.1).unwrap_or(0.0);
+        draw_text(
+            &format!("{} = {:.4}", x.expression, last),
+            left + 4.0,
+            top + HEIGHT + 16.0 * (i as f32 + 1.0),
+            16.0,
+            color,
+        );
+    }
+
+    draw_text(&format!("{:.3}", max), left + 4.0, top + 14.0, 14.0, DARKGRAY);
+    draw_text(&format!("{:.3}", min), left + 4.0, top + HEIGHT - 4.0, 14.0, DARKGRAY);
+}
@@ -0,0 +1,89 @@
+//! Watches a plain text file of equations (one per line, `#` starts a comment) and rebuilds the
+//! engine's environment whenever it is saved.
+
+use engine::Engine;
+use engine::math::solve::{Environment, builtin};
+use engine::spaces::Space2D;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+pub fn load_equations(path: &PathBuf) -> io::Result<Vec<String>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .map(|x| x.to_string())
+        .collect())
+}
+
+pub struct EquationWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // Problem with the latest version of the file, if any
+    pub error: Option<String>,
+    // Equations of the environment last swapped in
+    pub equations: Vec<String>,
+}
+
+impl EquationWatcher {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = fs::metadata(&path).and_then(|x| x.modified()).ok();
+
+        EquationWatcher {
+            path,
+            modified,
+            error: None,
+            equations: Vec::new(),
+        }
+    }
+
+    /// Swaps in a new environment if the file changed since the last poll. A file that fails to
+    /// parse leaves the current environment in place and is reported through `error`.
+    pub fn poll(&mut self, engine: &mut Engine<Space2D>) -> bool {
+        let modified = fs::metadata(&self.path).and_then(|x| x.modified()).ok();
+        if modified.is_none() || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+
+        let result = load_equations(&self.path)
+            .map_err(|e| e.to_string())
+            .and_then(|equations| {
+                Environment::build(
+                    equations.iter().map(|x| x.as_str()).collect(),
+                    builtin::functions(),
+                    builtin::constants(),
+                )
+                .map(|env| (env, equations))
+                .map_err(|e| e.to_string())
+            });
+
+        match result {
+            Ok((env, equations)) => {
+                engine.set_environment(env);
+                self.error = None;
+                self.equations = equations;
+                true
+            }
+            Err(e) => {
+                self.error = Some(format!("{}: {}", self.path.display(), e));
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_equations() {
+        let path = std::env::temp_dir().join("phys-sim-test-equations.txt");
+        fs::write(&path, "# gravity\ng=100\n\n  a_B=-g*hatj  \n").unwrap();
+
+        assert_eq!(load_equations(&path).unwrap(), vec!["g=100", "a_B=-g*hatj"]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Drawing of bodies, for every variant of `Shape`.
+
+use crate::camera::Camera;
+use engine::collide::Collide2D;
+use engine::math::{Column, Vector};
+use engine::spaces::Space2D;
+use engine::{Body, Shape, Space};
+use macroquad::prelude::*;
+
+pub fn draw_body(body: &Body<Space2D>, camera: &Camera, color: Color) {
+    let center = camera.to_screen(&body.linear.displacement);
+    let theta = *body.angular.displacement.get(0) as f32;
+
+    match &body.shape {
+        Shape::Rec(width, height) => {
+            draw_rectangle_ex(
+                center.x,
+                center.y,
+                camera.length(*width),
+                camera.length(*height),
+                DrawRectangleParams {
+                    // Screen space is flipped, so counter-clockwise in the world is clockwise here
+                    rotation: -theta,
+                    offset: vec2(0.5, 0.5),
+                    color,
+                },
+            );
+        }
+        Shape::Ellipse(major, minor) => {
+            // Axes are full lengths along the body's x and y, draw_ellipse wants radii
+            draw_ellipse(
+                center.x,
+                center.y,
+                camera.length(*major) / 2.0,
+                camera.length(*minor) / 2.0,
+                -theta.to_degrees(),
+                color,
+            );
+        }
+        Shape::Manifold(_) => {
+            let points = Collide2D::bases(body)
+                .iter()
+                .map(|x| camera.to_screen(&x.plus(&body.linear.displacement)))
+                .collect::<Vec<_>>();
+
+            // Fan out from the centroid, which is inside any convex manifold
+            for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+                draw_triangle(center, *a, *b, color);
+            }
+        }
+        Shape::Chain(_) => {
+            let points = Collide2D::bases(body)
+                .iter()
+                .map(|x| camera.to_screen(&x.plus(&body.linear.displacement)))
+                .collect::<Vec<_>>();
+
+            // No thickness, so a line of a fixed width on screen
+            for x in points.windows(2) {
+                draw_line(x[0].x, x[0].y, x[1].x, x[1].y, 2.0, color);
+            }
+        }
+        Shape::HalfPlane(normal, offset) => {
+            let normal = Space2D::rotate(&body.angular.displacement, &normal.unit());
+            let surface = body.linear.displacement.plus(&normal.scale(*offset));
+            let along = Column::vector([-normal[1], normal[0]]).scale(1e6);
+
+            // Its surface, as far as anyone could scroll
+            let (a, b) = (
+                camera.to_screen(&surface.plus(&along)),
+                camera.to_screen(&surface.plus(&along.scale(-1.0))),
+            );
+            draw_line(a.x, a.y, b.x, b.y, 2.0, color);
+        }
+    }
+}
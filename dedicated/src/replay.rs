@@ -0,0 +1,149 @@
+//! Replay files: the scene a run started from plus the engine's recording of it, saved as JSON.
+
+use engine::record::{Input, RecordedTick, Recording};
+use protocol::SceneDescription;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+enum InputDescription {
+    SetVelocity {
+        body: usize,
+        linear: Vec<f64>,
+        angular: Vec<f64>,
+    },
+    SetEquations {
+        equations: Vec<String>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct TickDescription {
+    delta_t: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    inputs: Vec<InputDescription>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ReplayFile {
+    scene: SceneDescription,
+    ticks: Vec<TickDescription>,
+}
+
+/// Whether a `--record` path asks for a replay rather than a trajectory.
+pub fn is_replay(path: &Path) -> bool {
+    path.extension().and_then(|x| x.to_str()) == Some("replay")
+}
+
+pub fn save_replay(
+    path: &Path,
+    scene: &SceneDescription,
+    recording: &Recording,
+) -> Result<(), Box<dyn Error>> {
+    let file = ReplayFile {
+        scene: scene.clone(),
+        ticks: recording
+            .ticks
+            .iter()
+            .map(|tick| TickDescription {
+                delta_t: tick.delta_t,
+                inputs: tick
+                    .inputs
+                    .iter()
+                    .map(|x| match x {
+                        Input::SetVelocity {
+                            body,
+                            linear,
+                            angular,
+                        } => InputDescription::SetVelocity {
+                            body: *body,
+                            linear: linear.clone(),
+                            angular: angular.clone(),
+                        },
+                        Input::SetEquations(equations) => InputDescription::SetEquations {
+                            equations: equations.clone(),
+                        },
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    fs::write(path, serde_json::to_string(&file)?)?;
+
+    Ok(())
+}
+
+pub fn load_replay(path: &Path) -> Result<(SceneDescription, Recording), Box<dyn Error>> {
+    let file: ReplayFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+    let recording = Recording {
+        ticks: file
+            .ticks
+            .into_iter()
+            .map(|tick| RecordedTick {
+                delta_t: tick.delta_t,
+                inputs: tick
+                    .inputs
+                    .into_iter()
+                    .map(|x| match x {
+                        InputDescription::SetVelocity {
+                            body,
+                            linear,
+                            angular,
+                        } => Input::SetVelocity {
+                            body,
+                            linear,
+                            angular,
+                        },
+                        InputDescription::SetEquations { equations } => {
+                            Input::SetEquations(equations)
+                        }
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    Ok((file.scene, recording))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::load_scene;
+
+    #[test]
+    fn test_replay_round_trip() {
+        let scene =
+            load_scene(&Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/bumper.toml")).unwrap();
+        let recording = Recording {
+            ticks: vec![
+                RecordedTick {
+                    delta_t: 0.01,
+                    inputs: vec![],
+                },
+                RecordedTick {
+                    delta_t: 0.02,
+                    inputs: vec![
+                        Input::SetVelocity {
+                            body: 1,
+                            linear: vec![1.0, 2.0],
+                            angular: vec![0.0],
+                        },
+                        Input::SetEquations(vec!["a_B=-hatj".to_string()]),
+                    ],
+                },
+            ],
+        };
+
+        let path = std::env::temp_dir().join("phys-sim-test.replay");
+        save_replay(&path, &scene, &recording).unwrap();
+
+        assert!(is_replay(&path));
+        assert_eq!(load_replay(&path).unwrap(), (scene, recording));
+    }
+}
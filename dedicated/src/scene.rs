@@ -0,0 +1,47 @@
+//! Loading of scene files, in whichever format their extension names.
+
+use protocol::SceneDescription;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Reads a scene from a `.toml`, `.ron` or `.json` file.
+pub fn load_scene(path: &Path) -> Result<SceneDescription, Box<dyn Error>> {
+    let content = fs::read_to_string(path)?;
+
+    let scene = match path.extension().and_then(|x| x.to_str()) {
+        Some("toml") => toml::from_str(&content)?,
+        Some("ron") => ron::from_str(&content)?,
+        Some("json") => serde_json::from_str(&content)?,
+        _ => {
+            return Err(format!(
+                "Unknown scene format '{}', expected .toml, .ron or .json",
+                path.display()
+            )
+            .into());
+        }
+    };
+
+    Ok(scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_scenes() {
+        let scenes = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+
+        for entry in fs::read_dir(scenes).unwrap() {
+            let path = entry.unwrap().path();
+            let scene = load_scene(&path).unwrap();
+
+            scene
+                .build()
+                .unwrap()
+                .tick()
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! Controller logic attached to a body (thrusters, homing, AI steering), run by the engine at the
+//! start of every tick rather than by the host between ticks.
+
+use crate::{Body, BodyId, Space};
+
+/// What a behavior knows about the tick it is running before.
+#[derive(Debug, Clone, Copy)]
+pub struct TickContext {
+    // Simulated seconds elapsed before this tick
+    pub time: f64,
+    pub delta_t: f64,
+    // Ticks run before this one
+    pub ticks: u64,
+}
+
+pub trait Behavior<S: Space> {
+    /// Called before the tick's equations are evaluated, free to change anything about the body.
+    /// Changes are seen by the equations of this tick.
+    fn update(&mut self, body: &mut Body<S>, ctx: &TickContext);
+}
+
+pub(crate) struct Attached<S: Space> {
+    pub body: BodyId,
+    pub behavior: Box<dyn Behavior<S>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::Collide2D;
+    use crate::math::solve::{Environment, builtin};
+    use crate::math::{Column, Vector};
+    use crate::spaces::Space2D;
+    use crate::{BodyProperties, Engine, Shape};
+
+    // Fires for a fixed time, then cuts out
+    struct Thruster {
+        burn: f64,
+        thrust: f64,
+    }
+
+    impl Behavior<Space2D> for Thruster {
+        fn update(&mut self, body: &mut Body<Space2D>, ctx: &TickContext) {
+            if ctx.time < self.burn {
+                let delta_v = self.thrust / body.properties.mass * ctx.delta_t;
+                body.linear.velocity = body.linear.velocity.plus(&Column::vector([0.0, delta_v]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_behavior() {
+        let mut engine = Engine::new(
+            vec![Body::at_rest(
+                "Rocket".to_string(),
+                Shape::Rec(1.0, 3.0),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::rectangle(2.0, 1.0, 3.0),
+            )],
+            Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        );
+        let rocket = engine.id_of("Rocket").unwrap();
+        engine.attach(
+            rocket,
+            Box::new(Thruster {
+                burn: 0.5,
+                thrust: 10.0,
+            }),
+        );
+
+        for _ in 0..10 {
+            engine.tick().unwrap();
+        }
+        // Five ticks of 0.5 each
+        let velocity = *engine.bodies()[0].linear.velocity.get(1);
+        assert!((velocity - 2.5).abs() < 1e-9, "{}", velocity);
+
+        engine.attach(
+            rocket,
+            Box::new(Thruster {
+                burn: f64::INFINITY,
+                thrust: 10.0,
+            }),
+        );
+        engine.detach(rocket);
+        engine.tick().unwrap();
+        assert_eq!(*engine.bodies()[0].linear.velocity.get(1), velocity);
+    }
+}
@@ -0,0 +1,334 @@
+//! Finds the pairs of bodies close enough that they might be touching, so the collider only has to
+//! test those rather than every pair.
+
+use crate::bvh::Bvh;
+use crate::math::Vector;
+use crate::pairs::reach;
+use crate::{Body, Space};
+use std::collections::HashMap;
+
+/// Picks out the pairs of bodies the engine goes on to test for contact, see
+/// `Engine::set_broadphase`. It may be called with a different set of bodies every tick.
+pub trait Broadphase<S: Space> {
+    /// Every pair `(i, j)` with `i < j` that might be touching, in order. Pairs left out are never
+    /// tested, so none may be left out that are, other than two static bodies (which can't move
+    /// each other).
+    fn pairs(&mut self, bodies: &[Body<S>]) -> Vec<(usize, usize)>;
+}
+
+// Whether the boxes of two bodies, reaching `reaches` from their centroids, overlap along every
+// axis from `from` on
+fn overlap<S: Space>(a: &Body<S>, b: &Body<S>, reaches: f64, from: usize) -> bool {
+    let (a, b) = (&a.linear.displacement, &b.linear.displacement);
+    (from..S::Linear::dof()).all(|k| (a.get(k) - b.get(k)).abs() <= reaches)
+}
+
+/// Bounds every body by a box reaching as far as any of its points can from its centroid however
+/// it's turned, sorts the boxes by where they start along the first axis and sweeps along it, so
+/// only boxes overlapping along that axis are compared. The order is kept between ticks, and since
+/// bodies barely move from one tick to the next an insertion sort puts it right in close to linear
+/// time.
+#[derive(Default)]
+pub struct SweepAndPrune {
+    // Body indices by the start of their box along the first axis
+    order: Vec<usize>,
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        SweepAndPrune::default()
+    }
+}
+
+impl SweepAndPrune {
+    // Every pair among `members` (in order) whose boxes overlap. The order is kept while it's the
+    // same bodies being swept
+    fn sweep<S: Space>(
+        &mut self,
+        bodies: &[Body<S>],
+        reaches: &[f64],
+        members: &[usize],
+    ) -> Vec<(usize, usize)> {
+        let start = |i: usize| bodies[i].linear.displacement.get(0) - reaches[i];
+        let end = |i: usize| bodies[i].linear.displacement.get(0) + reaches[i];
+
+        let kept = self.order.len() == members.len()
+            && self.order.iter().all(|i| members.binary_search(i).is_ok());
+        if !kept {
+            self.order = members.to_vec();
+        }
+        for i in 1..self.order.len() {
+            let mut j = i;
+            while j > 0 && start(self.order[j - 1]) > start(self.order[j]) {
+                self.order.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+
+        let mut pairs = Vec::new();
+        // Boxes the sweep is inside of
+        let mut active: Vec<usize> = Vec::new();
+        for &i in &self.order {
+            active.retain(|&j| end(j) >= start(i));
+            for &j in &active {
+                if overlap(&bodies[i], &bodies[j], reaches[i] + reaches[j], 1) {
+                    pairs.push((i.min(j), i.max(j)));
+                }
+            }
+            active.push(i);
+        }
+
+        pairs.sort_unstable();
+        pairs
+    }
+}
+
+impl<S: Space> Broadphase<S> for SweepAndPrune {
+    fn pairs(&mut self, bodies: &[Body<S>]) -> Vec<(usize, usize)> {
+        let reaches = bodies.iter().map(|x| reach(&x.shape)).collect::<Vec<_>>();
+        let members = (0..bodies.len()).collect::<Vec<_>>();
+        self.sweep(bodies, &reaches, &members)
+    }
+}
+
+/// Puts every body in each cell of a uniform grid its box covers, and pairs up the bodies sharing a
+/// cell. It does best when the bodies are about the size of a cell or smaller, eg. many particles
+/// of the same size, where sweep-and-prune can end up comparing every body in a wide column of
+/// them. A body much larger than a cell (a floor) covers many cells, which is fine for a few, and
+/// a half-plane is paired with every other body.
+pub struct SpatialHash {
+    cell: f64,
+    // Bodies in each cell, kept to reuse their allocations
+    cells: HashMap<Vec<i64>, Vec<usize>>,
+}
+
+impl SpatialHash {
+    /// Cells `cell` wide along every axis.
+    pub fn new(cell: f64) -> Self {
+        SpatialHash {
+            cell,
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Space> Broadphase<S> for SpatialHash {
+    fn pairs(&mut self, bodies: &[Body<S>]) -> Vec<(usize, usize)> {
+        for x in self.cells.values_mut() {
+            x.clear();
+        }
+
+        let reaches = bodies.iter().map(|x| reach(&x.shape)).collect::<Vec<_>>();
+        // Half-planes would cover every cell, so they're paired with everything instead
+        let mut unbounded = Vec::new();
+        for (i, body) in bodies.iter().enumerate() {
+            if reaches[i].is_infinite() {
+                unbounded.push(i);
+                continue;
+            }
+
+            // Range of cells covered along each axis, walked through like an odometer
+            let (low, high): (Vec<i64>, Vec<i64>) = (0..S::Linear::dof())
+                .map(|k| {
+                    let x = body.linear.displacement.get(k);
+                    let cell = |x: f64| (x / self.cell).floor() as i64;
+                    (cell(x - reaches[i]), cell(x + reaches[i]))
+                })
+                .unzip();
+
+            let mut key = low.clone();
+            'cells: loop {
+                self.cells.entry(key.clone()).or_default().push(i);
+
+                for k in 0..key.len() {
+                    if key[k] < high[k] {
+                        key[k] += 1;
+                        continue 'cells;
+                    }
+                    key[k] = low[k];
+                }
+                break;
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for cell in self.cells.values() {
+            for (x, &i) in cell.iter().enumerate() {
+                for &j in &cell[x + 1..] {
+                    if overlap(&bodies[i], &bodies[j], reaches[i] + reaches[j], 0) {
+                        pairs.push((i.min(j), i.max(j)));
+                    }
+                }
+            }
+        }
+        for &i in &unbounded {
+            pairs.extend((0..bodies.len()).filter(|&j| j != i).map(|j| (i.min(j), i.max(j))));
+        }
+        // Cells emptied this tick are dropped so the map doesn't grow as bodies wander
+        self.cells.retain(|_, x| !x.is_empty());
+
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
+
+/// Keeps the static bodies in a [`Bvh`], so a scene with thousands of static tiles only compares
+/// each moving body with the few tiles around it, in about log n steps each. The moving bodies are
+/// swept and pruned among themselves. Two static bodies can't push each other, so they're never
+/// paired, and contacts between them (tiles laid edge to edge) aren't reported as they are by the
+/// other broadphases. The tree is only built again when a static body is added, removed or moved.
+pub struct StaticBvh<S: Space> {
+    tree: Bvh<S>,
+    sweep: SweepAndPrune,
+}
+
+impl<S: Space> StaticBvh<S> {
+    pub fn new() -> Self {
+        StaticBvh {
+            tree: Bvh::default(),
+            sweep: SweepAndPrune::new(),
+        }
+    }
+}
+
+impl<S: Space> Default for StaticBvh<S> {
+    fn default() -> Self {
+        StaticBvh::new()
+    }
+}
+
+impl<S: Space> Broadphase<S> for StaticBvh<S> {
+    fn pairs(&mut self, bodies: &[Body<S>]) -> Vec<(usize, usize)> {
+        self.tree.refresh(bodies);
+        let reaches = bodies.iter().map(|x| reach(&x.shape)).collect::<Vec<_>>();
+        let moving = (0..bodies.len())
+            .filter(|&i| !self.tree.contains(i))
+            .collect::<Vec<_>>();
+
+        let mut pairs = self.sweep.sweep(bodies, &reaches, &moving);
+        for &i in &moving {
+            let statics = match reaches[i].is_finite() {
+                true => {
+                    let x = &bodies[i].linear.displacement;
+                    let reach = S::Linear::new(vec![reaches[i]; S::Linear::dof()]).unwrap();
+                    self.tree.overlapping(&x.plus(&reach.scale(-1.0)), &x.plus(&reach))
+                }
+                // A moving half-plane can meet any of them
+                false => self.tree.bodies().collect(),
+            };
+            pairs.extend(statics.into_iter().map(|j| (i.min(j), i.max(j))));
+        }
+
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::{Collide, Collide2D};
+    use crate::math::Column;
+    use crate::noise::Rng;
+    use crate::spaces::Space2D;
+    use crate::{BodyKind, BodyProperties, Shape};
+
+    #[test]
+    fn test_finds_every_contact() {
+        let mut rng = Rng::new(3);
+        let mut bodies = (0..200)
+            .map(|i| {
+                Body::<Space2D>::at_rest(
+                    crate::scenarios::label("Box", i),
+                    Shape::Rec(10.0, 4.0),
+                    Column::vector([rng.uniform() * 200.0, rng.uniform() * 200.0]),
+                    Column::vector([rng.uniform() * 3.0]),
+                    BodyProperties::rectangle(1.0, 10.0, 4.0),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let collide = Collide2D::new();
+        let mut sweep = SweepAndPrune::new();
+        for _ in 0..3 {
+            let pairs = Broadphase::<Space2D>::pairs(&mut sweep, &bodies);
+            assert!(pairs.len() < 200 * 199 / 10, "{}", pairs.len());
+
+            for i in 0..bodies.len() {
+                for j in i + 1..bodies.len() {
+                    if collide.collide(&bodies[i], &bodies[j]).is_some() {
+                        assert!(pairs.binary_search(&(i, j)).is_ok(), "{} {}", i, j);
+                    }
+                }
+            }
+
+            let mut hash = SpatialHash::new(15.0);
+            assert_eq!(hash.pairs(&bodies), pairs);
+
+            // Shuffle them about, the kept order is no longer sorted
+            for x in bodies.iter_mut() {
+                x.linear.displacement =
+                    Column::vector([rng.uniform() * 200.0, x.linear.displacement[1]]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_static_bvh() {
+        let mut rng = Rng::new(4);
+        let mut bodies = (0..300)
+            .map(|i| {
+                let kind = match i % 10 {
+                    0 => BodyKind::Dynamic,
+                    _ => BodyKind::Static,
+                };
+                Body::<Space2D>::at_rest(
+                    crate::scenarios::label("Tile", i),
+                    Shape::Rec(10.0, 10.0),
+                    Column::vector([rng.uniform() * 300.0, rng.uniform() * 300.0]),
+                    Column::vector([rng.uniform()]),
+                    BodyProperties::rectangle(1.0, 10.0, 10.0).with_kind(kind),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut tree = StaticBvh::new();
+
+        for _ in 0..3 {
+            // The same pairs as a sweep finds, but for those of two tiles
+            let still = |x: &Body<Space2D>| x.properties.kind == BodyKind::Static;
+            let expected = Broadphase::<Space2D>::pairs(&mut SweepAndPrune::new(), &bodies)
+                .into_iter()
+                .filter(|&(i, j)| !still(&bodies[i]) || !still(&bodies[j]))
+                .collect::<Vec<_>>();
+            assert!(!expected.is_empty());
+            assert_eq!(tree.pairs(&bodies), expected);
+
+            // Tiles moved by hand are found where they've been moved to
+            for x in bodies.iter_mut().step_by(7) {
+                x.linear.displacement = Column::vector([rng.uniform() * 300.0, 150.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_same_simulation() {
+        let run = |broadphase: Option<Box<dyn Broadphase<Space2D>>>| {
+            let mut engine = crate::scenarios::pyramid(5).build().unwrap();
+            if let Some(x) = broadphase {
+                engine.set_broadphase(x);
+            }
+            let contacts = (0..60)
+                .map(|_| engine.tick().unwrap().stats.contacts_resolved)
+                .sum::<usize>();
+            (engine.state_hash(), contacts)
+        };
+
+        let (hash, contacts) = run(None);
+        assert!(contacts > 0);
+        assert_eq!(run(Some(Box::new(SpatialHash::new(20.0)))), (hash, contacts));
+        // The floor is the only static body, so nothing is left out
+        assert_eq!(run(Some(Box::new(StaticBvh::new()))), (hash, contacts));
+    }
+}
@@ -0,0 +1,381 @@
+//! A bounding-volume hierarchy over the static bodies of a scene: a tree of boxes, each bounding
+//! every body below it, so a query only goes down into the boxes it touches and finds what it's
+//! after among thousands of static tiles in about log n steps. Static bodies don't move, so the
+//! tree is only built again when one is added, removed or moved by hand.
+
+use crate::math::Vector;
+use crate::pairs::reach;
+use crate::{Body, BodyKind, Space};
+
+// A box bounding everything below it
+struct Node<S: Space> {
+    min: S::Linear,
+    max: S::Linear,
+    below: Below,
+}
+
+enum Below {
+    Body(usize),
+    Nodes(usize, usize),
+}
+
+// A body and the corners of its box
+type Leaf<S> = (usize, <S as Space>::Linear, <S as Space>::Linear);
+
+/// Boxes the static bodies of a scene the same way the broadphase does, by how far any of their
+/// points reaches from their centroid, and arranges the boxes in a tree. Half-planes reach forever
+/// and can't be boxed, so they're part of every query instead.
+pub struct Bvh<S: Space> {
+    // The root first
+    nodes: Vec<Node<S>>,
+    unbounded: Vec<usize>,
+    // Every static body the tree was built over, with its centroid and reach at the time
+    built: Vec<(usize, S::Linear, f64)>,
+}
+
+impl<S: Space> Default for Bvh<S> {
+    fn default() -> Self {
+        Bvh {
+            nodes: Vec::new(),
+            unbounded: Vec::new(),
+            built: Vec::new(),
+        }
+    }
+}
+
+impl<S: Space> Bvh<S> {
+    /// A tree over the static bodies among `bodies`.
+    pub fn new(bodies: &[Body<S>]) -> Self {
+        let mut tree = Bvh::default();
+        let mut leaves = Vec::new();
+        for (i, body) in Self::statics(bodies) {
+            let (centroid, reach) = (body.linear.displacement.clone(), reach(&body.shape));
+            if reach.is_finite() {
+                let offset = S::Linear::new(vec![reach; S::Linear::dof()]).unwrap();
+                leaves.push((
+                    i,
+                    centroid.plus(&offset.scale(-1.0)),
+                    centroid.plus(&offset),
+                ));
+            } else {
+                tree.unbounded.push(i);
+            }
+            tree.built.push((i, centroid, reach));
+        }
+        if !leaves.is_empty() {
+            tree.split(&mut leaves);
+        }
+
+        tree
+    }
+
+    fn statics(bodies: &[Body<S>]) -> impl Iterator<Item = (usize, &Body<S>)> {
+        bodies
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.properties.kind == BodyKind::Static)
+    }
+
+    /// Builds the tree again if the static bodies aren't those it was built over, where they were
+    /// then. Checking takes a moment per static body, far less than building it.
+    pub fn refresh(&mut self, bodies: &[Body<S>]) {
+        let mut statics = Self::statics(bodies);
+        let same =
+            |a: &S::Linear, b: &S::Linear| (0..S::Linear::dof()).all(|k| a.get(k) == b.get(k));
+        let current = self.built.iter().all(|(i, centroid, extent)| {
+            statics.next().is_some_and(|(j, body)| {
+                *i == j
+                    && same(centroid, &body.linear.displacement)
+                    && *extent == reach(&body.shape)
+            })
+        }) && statics.next().is_none();
+
+        if !current {
+            *self = Bvh::new(bodies);
+        }
+    }
+
+    /// Whether `i` is one of the static bodies the tree was built over.
+    pub fn contains(&self, i: usize) -> bool {
+        self.built.binary_search_by_key(&i, |(j, ..)| *j).is_ok()
+    }
+
+    /// The static bodies the tree was built over, in order.
+    pub fn bodies(&self) -> impl Iterator<Item = usize> + '_ {
+        self.built.iter().map(|(i, ..)| *i)
+    }
+
+    /// Half-planes among the static bodies, which every query includes.
+    pub fn unbounded(&self) -> &[usize] {
+        &self.unbounded
+    }
+
+    // Adds a node over the boxes in `leaves`, and the nodes below it, returning its index. Each
+    // node splits its boxes in half along the axis their centres are spread furthest over
+    fn split(&mut self, leaves: &mut [Leaf<S>]) -> usize {
+        let dof = S::Linear::dof();
+        let bound = |pick: fn(f64, f64) -> f64, x: fn(&Leaf<S>) -> &S::Linear| {
+            S::Linear::new(
+                (0..dof)
+                    .map(|k| leaves.iter().map(|l| *x(l).get(k)).reduce(pick).unwrap())
+                    .collect(),
+            )
+            .unwrap()
+        };
+        let (min, max) = (bound(f64::min, |x| &x.1), bound(f64::max, |x| &x.2));
+
+        let index = self.nodes.len();
+        if let [(body, ..)] = leaves {
+            self.nodes.push(Node {
+                min,
+                max,
+                below: Below::Body(*body),
+            });
+            return index;
+        }
+        self.nodes.push(Node {
+            min,
+            max,
+            below: Below::Nodes(0, 0),
+        });
+
+        // Twice the centre, which orders and spreads them all the same
+        let centre = |x: &Leaf<S>, k: usize| x.1.get(k) + x.2.get(k);
+        let spread = |k: usize| {
+            let centres = leaves.iter().map(|x| centre(x, k));
+            centres.clone().fold(f64::MIN, f64::max) - centres.fold(f64::MAX, f64::min)
+        };
+        let axis = (0..dof)
+            .max_by(|a, b| spread(*a).total_cmp(&spread(*b)))
+            .unwrap();
+        let half = leaves.len() / 2;
+        // Ties are split by index, so the same bodies always give the same tree
+        leaves.select_nth_unstable_by(half, |a, b| {
+            centre(a, axis)
+                .total_cmp(&centre(b, axis))
+                .then(a.0.cmp(&b.0))
+        });
+
+        let (left, right) = leaves.split_at_mut(half);
+        let below = Below::Nodes(self.split(left), self.split(right));
+        self.nodes[index].below = below;
+        index
+    }
+
+    /// The static bodies whose boxes overlap the box from `min` to `max`, in order.
+    pub fn overlapping(&self, min: &S::Linear, max: &S::Linear) -> Vec<usize> {
+        let mut found = self.unbounded.clone();
+        let mut stack = match self.nodes.is_empty() {
+            true => vec![],
+            false => vec![0],
+        };
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            let overlaps = (0..S::Linear::dof())
+                .all(|k| node.min.get(k) <= max.get(k) && min.get(k) <= node.max.get(k));
+            if !overlaps {
+                continue;
+            }
+
+            match node.below {
+                Below::Body(i) => found.push(i),
+                Below::Nodes(a, b) => stack.extend([a, b]),
+            }
+        }
+
+        found.sort_unstable();
+        found
+    }
+
+    /// The nearest static body along the ray from `origin` in the unit `direction`, no further
+    /// than `length`, with how far along it is. `hit` tells how far along the ray a body is hit, if
+    /// it is. Boxes are visited nearest first and only while they start nearer than the nearest
+    /// hit so far, so only the bodies near the ray are tested. Bodies hit at the same distance go
+    /// by index.
+    pub fn cast(
+        &self,
+        origin: &S::Linear,
+        direction: &S::Linear,
+        length: f64,
+        mut hit: impl FnMut(usize) -> Option<f64>,
+    ) -> Option<(usize, f64)> {
+        let mut nearest: Option<(usize, f64)> = None;
+        let mut test = |i: usize, nearest: &mut Option<(usize, f64)>| {
+            let Some(distance) = hit(i).filter(|x| *x <= length) else {
+                return;
+            };
+            if nearest.is_none_or(|(j, x)| (distance, i) < (x, j)) {
+                *nearest = Some((i, distance));
+            }
+        };
+        for &i in &self.unbounded {
+            test(i, &mut nearest);
+        }
+
+        let enters = |n: usize| {
+            let node = &self.nodes[n];
+            entry::<S>(&node.min, &node.max, origin, direction, length).map(|x| (n, x))
+        };
+        // Nodes left to visit with where the ray enters them, the nearest last
+        let mut stack = match self.nodes.is_empty() {
+            true => vec![],
+            false => enters(0).into_iter().collect(),
+        };
+        while let Some((n, entered)) = stack.pop() {
+            if nearest.is_some_and(|(_, x)| entered > x) {
+                continue;
+            }
+
+            match self.nodes[n].below {
+                Below::Body(i) => test(i, &mut nearest),
+                Below::Nodes(a, b) => {
+                    let (a, b) = (enters(a), enters(b));
+                    let mut next = a.into_iter().chain(b).collect::<Vec<_>>();
+                    next.sort_by(|x, y| y.1.total_cmp(&x.1));
+                    stack.extend(next);
+                }
+            }
+        }
+
+        nearest
+    }
+}
+
+/// How far along the ray from `origin` in `direction` it first enters the box from `min` to `max`,
+/// if it does within `length`. Starting inside the box counts as entering it straight away.
+pub fn entry<S: Space>(
+    min: &S::Linear,
+    max: &S::Linear,
+    origin: &S::Linear,
+    direction: &S::Linear,
+    length: f64,
+) -> Option<f64> {
+    let (mut near, mut far) = (0.0_f64, length);
+    for k in 0..S::Linear::dof() {
+        let (o, d) = (*origin.get(k), *direction.get(k));
+        let (low, high) = (*min.get(k), *max.get(k));
+        // Running alongside the slab, it's either always between its sides or never
+        if d == 0.0 {
+            if o < low || o > high {
+                return None;
+            }
+            continue;
+        }
+
+        let (a, b) = ((low - o) / d, (high - o) / d);
+        near = near.max(a.min(b));
+        far = far.min(a.max(b));
+    }
+
+    (near <= far).then_some(near)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Column;
+    use crate::noise::Rng;
+    use crate::spaces::Space2D;
+    use crate::{BodyProperties, Shape};
+
+    fn tiles(rng: &mut Rng) -> Vec<Body<Space2D>> {
+        (0..500)
+            .map(|i| {
+                let kind = match i % 5 {
+                    0 => BodyKind::Dynamic,
+                    _ => BodyKind::Static,
+                };
+                Body::at_rest(
+                    crate::scenarios::label("Tile", i),
+                    Shape::Rec(4.0, 4.0),
+                    Column::vector([rng.uniform() * 400.0, rng.uniform() * 400.0]),
+                    Column::vector([0.0]),
+                    BodyProperties::rectangle(1.0, 4.0, 4.0).with_kind(kind),
+                )
+            })
+            .collect()
+    }
+
+    // The box the tree gives a body
+    fn bounds(body: &Body<Space2D>) -> (Column<2>, Column<2>) {
+        let reach = Column::vector([reach(&body.shape); 2]);
+        let x = &body.linear.displacement;
+        (x.plus(&reach.scale(-1.0)), x.plus(&reach))
+    }
+
+    #[test]
+    fn test_overlapping() {
+        let mut rng = Rng::new(5);
+        let bodies = tiles(&mut rng);
+        let tree = Bvh::new(&bodies);
+        assert_eq!(tree.bodies().count(), 400);
+
+        for _ in 0..50 {
+            let min = Column::vector([rng.uniform() * 400.0, rng.uniform() * 400.0]);
+            let max = min.plus(&Column::vector([
+                rng.uniform() * 40.0,
+                rng.uniform() * 40.0,
+            ]));
+
+            let expected = (0..bodies.len())
+                .filter(|&i| bodies[i].properties.kind == BodyKind::Static)
+                .filter(|&i| {
+                    let (low, high) = bounds(&bodies[i]);
+                    (0..2).all(|k| low[k] <= max[k] && min[k] <= high[k])
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(tree.overlapping(&min, &max), expected);
+        }
+    }
+
+    #[test]
+    fn test_cast() {
+        let mut rng = Rng::new(9);
+        let bodies = tiles(&mut rng);
+        let tree = Bvh::new(&bodies);
+
+        for _ in 0..50 {
+            let origin = Column::vector([rng.uniform() * 400.0, rng.uniform() * 400.0]);
+            let angle = rng.uniform() * std::f64::consts::TAU;
+            let direction = Column::vector([angle.cos(), angle.sin()]);
+            let hit = |i: usize| {
+                let (low, high) = bounds(&bodies[i]);
+                entry::<Space2D>(&low, &high, &origin, &direction, f64::INFINITY)
+            };
+
+            let mut tested = 0;
+            let nearest = tree.cast(&origin, &direction, 300.0, |i| {
+                tested += 1;
+                hit(i)
+            });
+            let expected = tree
+                .bodies()
+                .filter_map(|i| hit(i).filter(|x| *x <= 300.0).map(|x| (i, x)))
+                .min_by(|a, b| (a.1, a.0).partial_cmp(&(b.1, b.0)).unwrap());
+            assert_eq!(nearest, expected);
+            assert!(tested < 100, "{}", tested);
+        }
+    }
+
+    #[test]
+    fn test_refresh() {
+        let mut bodies = tiles(&mut Rng::new(2));
+        let mut tree = Bvh::new(&bodies);
+        let far = Column::vector([1000.0, 1000.0]);
+        let (min, max) = (far.clone(), far.plus(&Column::vector([1.0, 1.0])));
+
+        // Moving a dynamic body leaves it as it was
+        bodies[0].linear.displacement = far.clone();
+        tree.refresh(&bodies);
+        assert!(tree.overlapping(&min, &max).is_empty());
+
+        bodies[1].linear.displacement = far;
+        tree.refresh(&bodies);
+        assert_eq!(tree.overlapping(&min, &max), vec![1]);
+
+        bodies[0].properties.kind = BodyKind::Static;
+        tree.refresh(&bodies);
+        assert_eq!(tree.overlapping(&min, &max), vec![0, 1]);
+        assert!(tree.contains(0));
+    }
+}
@@ -0,0 +1,265 @@
+//! Constraints on how pairs of bodies move, each written as a row of a Jacobian `J`: the bodies
+//! move along a row at `J·v`, and an impulse `λ` along it changes their velocities by `M⁻¹·Jᵀ·λ`.
+//! Joints and contacts are then all solved the same way, by projected Gauss-Seidel: every row in
+//! turn is brought to its target velocity with its total impulse kept within its limits, over a
+//! few passes so they settle on impulses that satisfy all of them at once.
+
+use crate::math::Vector;
+use crate::{Body, Space};
+
+/// How far a row's total impulse may go.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    /// Between two bounds, eg. `(0, f64::INFINITY)` for a contact, which can only push.
+    Bounds(f64, f64),
+    /// Either way up to `coefficient` times the total impulse of another row (by index, among the
+    /// rows solved together), for friction against a contact's normal.
+    Friction { row: usize, coefficient: f64 },
+}
+
+impl Limit {
+    /// No limit either way, for joints.
+    pub fn free() -> Self {
+        Limit::Bounds(f64::NEG_INFINITY, f64::INFINITY)
+    }
+}
+
+#[derive(Clone)]
+pub struct Row<S: Space> {
+    /// The two bodies, by index into whatever the row is solved with.
+    pub a: usize,
+    pub b: usize,
+    /// How much each body's linear velocity adds to the row's velocity.
+    pub linear: [S::Linear; 2],
+    /// How much each body's angular velocity adds to the row's velocity.
+    pub angular: [S::Angular; 2],
+    /// The velocity the row is brought to, 0 to hold it still.
+    pub target: f64,
+    pub limit: Limit,
+    /// The total impulse applied along the row. Solving starts from whatever it is, so a row can be
+    /// warm started with the impulse it ended up with last tick.
+    pub impulse: f64,
+}
+
+impl<S: Space> Row<S> {
+    /// A row between the rotations of two bodies alone, with no limit.
+    pub fn angular(a: usize, b: usize, angular: [S::Angular; 2], target: f64) -> Self {
+        Row {
+            a,
+            b,
+            linear: [S::Linear::empty(), S::Linear::empty()],
+            angular,
+            target,
+            limit: Limit::free(),
+            impulse: 0.0,
+        }
+    }
+
+    /// A row along the unit `direction` at `point`, where `bodies` are the row's two bodies: its
+    /// velocity is how fast `b` is moving along it relative to `a` there.
+    pub fn point(
+        (a, b): (usize, usize),
+        bodies: [&Body<S>; 2],
+        point: &S::Linear,
+        direction: &S::Linear,
+        target: f64,
+        limit: Limit,
+    ) -> Self {
+        let arm = |x: &Body<S>| {
+            S::cross_linear(&point.plus(&x.linear.displacement.scale(-1.0)), direction)
+        };
+
+        Row {
+            a,
+            b,
+            linear: [direction.scale(-1.0), direction.clone()],
+            angular: [arm(bodies[0]).scale(-1.0), arm(bodies[1])],
+            target,
+            limit,
+            impulse: 0.0,
+        }
+    }
+
+    /// How fast the bodies are moving along the row.
+    pub fn velocity(&self, bodies: &[&mut Body<S>]) -> f64 {
+        [self.a, self.b]
+            .into_iter()
+            .enumerate()
+            .map(|(k, i)| {
+                self.linear[k].dot(&bodies[i].linear.velocity)
+                    + self.angular[k].dot(&bodies[i].angular.velocity)
+            })
+            .sum()
+    }
+
+    // How much a unit impulse along the row changes its velocity
+    fn inverse_mass(&self, bodies: &[&mut Body<S>]) -> f64 {
+        [self.a, self.b]
+            .into_iter()
+            .enumerate()
+            .map(|(k, i)| {
+                let properties = &bodies[i].properties;
+                properties.inverse_mass() * self.linear[k].magnitude().powi(2)
+                    + properties.inverse_moi() * self.angular[k].magnitude().powi(2)
+            })
+            .sum()
+    }
+
+    fn apply(&self, bodies: &mut [&mut Body<S>], impulse: f64) {
+        for (k, i) in [self.a, self.b].into_iter().enumerate() {
+            let body = &mut *bodies[i];
+            let (inverse_mass, inverse_moi) = (
+                body.properties.inverse_mass(),
+                body.properties.inverse_moi(),
+            );
+
+            body.linear.velocity = body
+                .linear
+                .velocity
+                .plus(&self.linear[k].scale(impulse * inverse_mass));
+            body.angular.velocity = body
+                .angular
+                .velocity
+                .plus(&self.angular[k].scale(impulse * inverse_moi));
+        }
+    }
+}
+
+/// Solves `rows` together over `iterations` passes, applying their impulses to `bodies`, and
+/// leaves each row's total impulse in it. Rows are first applied with the impulse they already
+/// carry. A row between two bodies neither of which can move is left alone.
+pub fn solve<S: Space>(bodies: &mut [&mut Body<S>], rows: &mut [Row<S>], iterations: usize) {
+    for row in rows.iter() {
+        if row.impulse != 0.0 {
+            row.apply(bodies, row.impulse);
+        }
+    }
+
+    for _ in 0..iterations {
+        for k in 0..rows.len() {
+            let denominator = rows[k].inverse_mass(bodies);
+            if denominator == 0.0 {
+                continue;
+            }
+
+            let (lower, upper) = match rows[k].limit {
+                Limit::Bounds(lower, upper) => (lower, upper),
+                Limit::Friction { row, coefficient } => {
+                    let most = coefficient * rows.get(row).map_or(0.0, |x| x.impulse.abs());
+                    (-most, most)
+                }
+            };
+
+            let row = &mut rows[k];
+            let total = (row.impulse + (row.target - row.velocity(bodies)) / denominator)
+                .clamp(lower, upper);
+            let impulse = total - row.impulse;
+            row.impulse = total;
+            row.apply(bodies, impulse);
+        }
+    }
+}
+
+/// A joint written as rows of a Jacobian, see `Engine::add_constraint`.
+pub trait Constraint<S: Space> {
+    /// The rows to solve this tick, with bodies by index into the engine's, once they've been
+    /// integrated. A friction row's limit refers to the others by their index in this list.
+    fn rows(&mut self, bodies: &[Body<S>]) -> Vec<Row<S>>;
+
+    /// Takes out whatever error in the bodies' positions is left once velocities are solved, which
+    /// rows alone would only ever stop from growing. Nothing by default.
+    fn correct(&mut self, _bodies: &mut [Body<S>]) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Column;
+    use crate::spaces::Space2D;
+    use crate::{BodyKind, BodyProperties, Shape};
+
+    fn ball(x: f64, kind: BodyKind) -> Body<Space2D> {
+        let shape = Shape::Ellipse(1.0, 1.0);
+        Body::at_rest(
+            "Ball".to_string(),
+            shape.clone(),
+            Column::vector([x, 0.0]),
+            Column::vector([0.0]),
+            BodyProperties::from_density(1.0, &shape).with_kind(kind),
+        )
+    }
+
+    #[test]
+    fn test_contact_with_friction() {
+        // A ball sliding and sinking into the floor, which pushes it back and slows its sliding
+        let mut floor = ball(0.0, BodyKind::Static);
+        let mut slider = ball(0.0, BodyKind::Dynamic);
+        slider.linear.displacement = Column::vector([0.0, 1.5]);
+        slider.linear.velocity = Column::vector([10.0, -4.0]);
+
+        let point = Column::vector([0.0, 1.0]);
+        let normal = Row::point(
+            (0, 1),
+            [&floor, &slider],
+            &point,
+            &Column::vector([0.0, 1.0]),
+            0.0,
+            Limit::Bounds(0.0, f64::INFINITY),
+        );
+        let friction = Row::point(
+            (0, 1),
+            [&floor, &slider],
+            &point,
+            &Column::vector([1.0, 0.0]),
+            0.0,
+            Limit::Friction {
+                row: 0,
+                coefficient: 0.5,
+            },
+        );
+
+        let mut rows = vec![normal, friction];
+        solve(&mut [&mut floor, &mut slider], &mut rows, 10);
+
+        let mass = slider.properties.mass;
+        assert!((rows[0].impulse - 4.0 * mass).abs() < 1e-9);
+        // Kinetic friction is at its limit, half the normal impulse
+        assert!(
+            (rows[1].impulse + 2.0 * mass).abs() < 1e-9,
+            "{}",
+            rows[1].impulse
+        );
+        assert!(slider.linear.velocity.get(1).abs() < 1e-9);
+        assert!(*slider.linear.velocity.get(0) < 10.0);
+        assert_eq!(floor.linear.velocity, Column::vector([0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_rows_solved_together() {
+        // Three balls in a row, each held to the next, with only the last pushed
+        let mut balls = [0.0, 2.0, 4.0].map(|x| ball(x, BodyKind::Dynamic));
+        balls[2].linear.velocity = Column::vector([3.0, 0.0]);
+        let hold = |a: usize, b: usize| Row::<Space2D> {
+            a,
+            b,
+            linear: [Column::vector([-1.0, 0.0]), Column::vector([1.0, 0.0])],
+            angular: [Column::vector([0.0]), Column::vector([0.0])],
+            target: 0.0,
+            limit: Limit::free(),
+            impulse: 0.0,
+        };
+
+        let mut rows = vec![hold(0, 1), hold(1, 2)];
+        let mut bodies = balls.iter_mut().collect::<Vec<_>>();
+        solve(&mut bodies, &mut rows, 50);
+
+        // Momentum shared out evenly
+        for x in &balls {
+            assert!(
+                (x.linear.velocity.get(0) - 1.0).abs() < 1e-6,
+                "{:?}",
+                x.linear.velocity
+            );
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Quadratic drag through a fluid (air, water), which slows bodies with a drag coefficient without
+//! an equation per body written out by hand.
+
+use crate::math::Vector;
+use crate::{Body, Shape, Space};
+
+/// How wide the shape is across the unit `direction` it's moving in, turned by `rotation`. Only the
+/// plane of the first two axes is considered, where it's the area facing the flow per unit depth.
+pub fn reference_area<S: Space>(
+    shape: &Shape<S>,
+    rotation: &S::Angular,
+    direction: &S::Linear,
+) -> f64 {
+    // Across the direction, in the frame of the unturned shape
+    let local = S::rotate(&rotation.scale(-1.0), direction);
+    let (x, y) = (-*local.get(1), *local.get(0));
+
+    match shape {
+        Shape::Rec(width, height) => width * x.abs() + height * y.abs(),
+        // Axes are full lengths
+        Shape::Ellipse(major, minor) => ((major * x).powi(2) + (minor * y).powi(2)).sqrt(),
+        Shape::Manifold(points) | Shape::Chain(points) => {
+            let along = points.iter().map(|p| p.get(0) * x + p.get(1) * y);
+            let (low, high) = along.fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), x| {
+                (low.min(x), high.max(x))
+            });
+            (high - low).max(0.0)
+        }
+        Shape::HalfPlane(..) => f64::INFINITY,
+    }
+}
+
+/// The drag on `body` moving through a fluid of `density`, `1/2 * density * C_d * A * |v| * v`
+/// against its velocity, never more than would stop it over `delta_t`. None if it feels none.
+pub(crate) fn force<S: Space>(body: &Body<S>, density: f64, delta_t: f64) -> Option<S::Linear> {
+    let velocity = &body.linear.velocity;
+    let speed = velocity.magnitude();
+    if body.properties.drag == 0.0 || speed == 0.0 {
+        return None;
+    }
+
+    let direction = velocity.scale(1.0 / speed);
+    let area = reference_area(&body.shape, &body.angular.displacement, &direction);
+    let drag = 0.5 * density * body.properties.drag * area * speed.powi(2);
+    let stopping = body.properties.mass * speed / delta_t;
+
+    Some(direction.scale(-drag.min(stopping)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::NoCollide;
+    use crate::math::Column;
+    use crate::math::solve::{Environment, builtin};
+    use crate::spaces::Space2D;
+    use crate::{BodyProperties, Engine};
+    use std::f64::consts::PI;
+
+    #[test]
+    fn test_reference_area() {
+        let across = |shape: &Shape<Space2D>, rotation: f64, direction: [f64; 2]| {
+            reference_area(
+                shape,
+                &Column::vector([rotation]),
+                &Column::vector(direction),
+            )
+        };
+
+        let rec = Shape::Rec(4.0, 2.0);
+        assert!((across(&rec, 0.0, [1.0, 0.0]) - 2.0).abs() < 1e-9);
+        assert!((across(&rec, 0.0, [0.0, 1.0]) - 4.0).abs() < 1e-9);
+        // Falling flat side first once turned a quarter
+        assert!((across(&rec, PI / 2.0, [0.0, 1.0]) - 2.0).abs() < 1e-9);
+        assert!((across(&Shape::Ellipse(3.0, 1.0), 0.0, [0.0, -1.0]) - 3.0).abs() < 1e-9);
+
+        let triangle = Shape::Manifold(
+            [[0.0, 0.0], [2.0, 0.0], [0.0, 1.0]]
+                .map(Column::vector)
+                .to_vec(),
+        );
+        assert!((across(&triangle, 0.0, [1.0, 0.0]) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_terminal_velocity() {
+        let shape = Shape::Ellipse(1.0, 1.0);
+        let body = Body::<Space2D>::at_rest(
+            "A".to_string(),
+            shape.clone(),
+            Column::vector([0.0, 0.0]),
+            Column::vector([0.0]),
+            BodyProperties::from_density(1.0, &shape).with_drag(0.5),
+        );
+        let mass = body.properties.mass;
+
+        let mut engine = Engine::new(
+            vec![body],
+            Environment::build(
+                vec!["a_A=-10*hatj"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(NoCollide),
+            0.01,
+            1.0,
+        );
+        engine.set_drag(Some(1.2));
+        for _ in 0..2000 {
+            engine.tick().unwrap();
+        }
+
+        // Where drag balances gravity, m * g = 1/2 * density * C_d * A * v^2
+        let terminal = (2.0 * mass * 10.0 / (1.2 * 0.5 * 1.0)).sqrt();
+        let speed = -*engine.bodies()[0].linear.velocity.get(1);
+        assert!((speed - terminal).abs() < 0.01, "{} {}", speed, terminal);
+    }
+}
@@ -1,15 +1,23 @@
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 
 pub type EngineResult<T> = Result<T, Error>;
 
 #[derive(Debug)]
 pub struct Error {
     pub kind: ErrorKind,
+    // Character range in the source equation this error refers to, if known
+    pub span: Option<Range<usize>>,
 }
 
 impl Error {
     pub fn new(kind: ErrorKind) -> Self {
-        Error { kind }
+        Error { kind, span: None }
+    }
+
+    pub fn with_span(mut self, span: Range<usize>) -> Self {
+        self.span = Some(span);
+        self
     }
 }
 
@@ -26,9 +34,26 @@ pub enum ErrorKind {
     RootFindingDepthExceeded,
     InvalidDimensions,
     InvalidToken(String),
-    InvalidMathSyntax(&'static str)
+    InvalidMathSyntax(&'static str),
+    InvalidBody {
+        name: String, reason: String,
+    },
+    DuplicateName(String),
+    InvalidShape(String),
+    InvalidTimeStep(f64),
 }
 
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.span {
+            Some(span) => write!(f, "{} (at {}..{})", self.kind, span.start, span.end),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl Display for ErrorKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -62,6 +87,18 @@ impl Display for ErrorKind {
             ErrorKind::InvalidMathSyntax(x) => {
                 write!(f, "Invalid math syntax: {}", x)
             }
+            ErrorKind::InvalidBody { name, reason } => {
+                write!(f, "Body {} can't be simulated: {}.", name, reason)
+            }
+            ErrorKind::DuplicateName(x) => {
+                write!(f, "More than one body is named {}, they would share variables.", x)
+            }
+            ErrorKind::InvalidShape(x) => {
+                write!(f, "Invalid shape: {}.", x)
+            }
+            ErrorKind::InvalidTimeStep(x) => {
+                write!(f, "Invalid time step: {}, it must be a positive, finite number of seconds.", x)
+            }
         }
     }
 }
\ No newline at end of file
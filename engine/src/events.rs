@@ -0,0 +1,132 @@
+//! Things worth telling the host about that happened during a tick, reported in
+//! [`crate::Tick::events`].
+
+use crate::math::Vector;
+use crate::{Body, BodyId, Space};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The body left the world bounds (or its position stopped being finite).
+    LeftBounds { body: BodyId },
+    /// The body started moving faster than the speed limit.
+    SpeedExceeded { body: BodyId, speed: f64 },
+    /// Resolving contacts added `gained` kinetic energy in total over the tick, most of it at the
+    /// contact between `a` and `b`.
+    EnergyGained { a: BodyId, b: BodyId, gained: f64 },
+    /// The body came to rest and stopped being simulated, see `Engine::set_sleeping`.
+    FellAsleep { body: BodyId },
+    /// The body was woken, by something moving into it or a force or impulse applied to it.
+    Woke { body: BodyId },
+}
+
+/// A contact resolved during a tick, reported in [`crate::Tick::collision_events`] for driving
+/// game logic (sounds, damage, scoring) off of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CollisionEvent<S: Space> {
+    pub a: BodyId,
+    pub b: BodyId,
+    pub point: S::Linear,
+    pub normal: S::Linear, // Relative to A
+    // How far they overlapped, before being pushed apart
+    pub depth: f64,
+    // Magnitude of the impulse along the normal, not counting friction
+    pub impulse: f64,
+}
+
+/// Checks bodies against the limits configured on the engine. Each event is only reported when a
+/// body crosses a limit, not on every tick it stays past it.
+pub(crate) struct Monitor<S: Space> {
+    // Smallest and largest corner of the world
+    pub bounds: Option<(S::Linear, S::Linear)>,
+    pub speed_limit: Option<f64>,
+    // Energy contact resolution may add in one tick before it is reported
+    pub energy_tolerance: Option<f64>,
+    // Bodies currently past each limit
+    outside: HashSet<BodyId>,
+    speeding: HashSet<BodyId>,
+}
+
+impl<S: Space> Default for Monitor<S> {
+    fn default() -> Self {
+        Monitor {
+            bounds: None,
+            speed_limit: None,
+            energy_tolerance: None,
+            outside: HashSet::new(),
+            speeding: HashSet::new(),
+        }
+    }
+}
+
+impl<S: Space> Monitor<S> {
+    pub fn check(&mut self, bodies: &[Body<S>], events: &mut Vec<Event>) {
+        // Every body has an id once validated
+        for (body, id) in bodies.iter().filter_map(|x| Some((x, x.id?))) {
+            if let Some((min, max)) = &self.bounds {
+                let position = &body.linear.displacement;
+                // Written so NaN counts as outside
+                let inside = (0..S::Linear::dof())
+                    .all(|x| min.get(x) <= position.get(x) && position.get(x) <= max.get(x));
+
+                if Self::crossed(&mut self.outside, id, !inside) {
+                    events.push(Event::LeftBounds { body: id });
+                }
+            }
+
+            if let Some(limit) = self.speed_limit {
+                let speed = body.linear.velocity.magnitude();
+                // NaN counts as too fast
+                let too_fast = speed > limit || speed.is_nan();
+
+                if Self::crossed(&mut self.speeding, id, too_fast) {
+                    events.push(Event::SpeedExceeded { body: id, speed });
+                }
+            }
+        }
+    }
+
+    /// Reports the energy each contact pair gained while being resolved, if it adds up to more than
+    /// the tolerance.
+    pub fn check_energy(
+        &self,
+        bodies: &[Body<S>],
+        gains: &[((usize, usize), f64)],
+        events: &mut Vec<Event>,
+    ) {
+        let Some(tolerance) = self.energy_tolerance else {
+            return;
+        };
+
+        let gained = gains.iter().map(|(_, x)| x).sum::<f64>();
+        if gained > tolerance || gained.is_nan() {
+            let worst = gains
+                .iter()
+                .max_by(|(_, x), (_, y)| x.total_cmp(y))
+                .map(|(pair, _)| *pair);
+
+            if let Some((Some(a), Some(b))) = worst.map(|(a, b)| (bodies[a].id, bodies[b].id)) {
+                events.push(Event::EnergyGained { a, b, gained });
+            }
+        }
+    }
+
+    // Updates whether a body is past a limit, returning whether it only just went past it
+    fn crossed(past: &mut HashSet<BodyId>, body: BodyId, is_past: bool) -> bool {
+        if is_past {
+            past.insert(body)
+        } else {
+            past.remove(&body);
+            false
+        }
+    }
+}
+
+/// Linear and rotational kinetic energy of a body.
+pub fn kinetic_energy<S: Space>(body: &Body<S>) -> f64 {
+    let linear = &body.linear.velocity;
+    let angular = &body.angular.velocity;
+
+    0.5 * body.properties.mass * linear.dot(linear)
+        + 0.5 * body.properties.moi * angular.dot(angular)
+}
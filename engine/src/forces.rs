@@ -0,0 +1,148 @@
+//! Generators for force laws acting between every pair of bodies, which would otherwise take an
+//! equation per body listing every other body by hand.
+
+use crate::{Body, Space};
+
+/// Coulomb's law between bodies carrying a charge as a custom scalar (see
+/// [`crate::BodyProperties::with`]). Bodies without one are left alone, as are unnamed bodies,
+/// which have no variables for an equation to refer to.
+#[derive(Debug, Clone)]
+pub struct Coulomb {
+    // Name of the custom scalar holding each body's charge
+    pub property: String,
+    // Coulomb's constant, in whatever units the scene uses
+    pub constant: f64,
+    // Its square is added to the square of every distance (Plummer softening), so bodies passing
+    // through each other aren't flung apart
+    pub softening: f64,
+}
+
+impl Default for Coulomb {
+    fn default() -> Self {
+        Coulomb {
+            property: "charge".to_string(),
+            constant: 1.0,
+            softening: 1.0,
+        }
+    }
+}
+
+impl Coulomb {
+    fn charged<'a, S: Space>(&self, bodies: &'a [Body<S>]) -> impl Iterator<Item = &'a str> {
+        bodies
+            .iter()
+            .filter(|x| !x.name.is_empty() && x.properties.custom.contains_key(&self.property))
+            .map(|x| x.name.as_str())
+    }
+
+    /// The force on `body` from every other charged body, as a vector expression (eg. over `hati`
+    /// and `hatj`) to be combined with any other forces on it. `0` if nothing acts on it.
+    pub fn force<S: Space>(&self, body: &str, bodies: &[Body<S>]) -> String {
+        let q = &self.property;
+
+        let terms = self
+            .charged(bodies)
+            .filter(|x| *x != body)
+            .map(|other| {
+                let separation = |axis: &str| format!("({axis}_{body}-{axis}_{other})");
+                let direction = S::LINEAR_BASES
+                    .iter()
+                    .map(|x| format!("{}*{}", separation(x.axis), x.name))
+                    .collect::<Vec<_>>()
+                    .join("+");
+                let distance = S::LINEAR_BASES
+                    .iter()
+                    .map(|x| format!("{}^2", separation(x.axis)))
+                    .collect::<Vec<_>>()
+                    .join("+");
+
+                format!(
+                    "({})*{q}_{body}*{q}_{other}*({direction})/({distance}+({})^2)^1.5",
+                    self.constant, self.softening
+                )
+            })
+            .collect::<Vec<_>>();
+
+        if terms.is_empty() {
+            "0".to_string()
+        } else {
+            terms.join("+")
+        }
+    }
+
+    /// An acceleration equation for every charged body, for scenes where nothing else acts on
+    /// them.
+    pub fn equations<S: Space>(&self, bodies: &[Body<S>]) -> Vec<String> {
+        self.charged(bodies)
+            .map(|x| format!("a_{x}=({})/m_{x}", self.force(x, bodies)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::Collide2D;
+    use crate::math::solve::{Environment, builtin};
+    use crate::math::{Column, Vector};
+    use crate::spaces::Space2D;
+    use crate::{BodyProperties, Engine, Shape};
+
+    fn particle(name: &str, x: f64, charge: Option<f64>) -> Body<Space2D> {
+        let mut properties = BodyProperties::rectangle(1.0, 1.0, 1.0);
+        if let Some(charge) = charge {
+            properties = properties.with("charge", charge);
+        }
+
+        Body::at_rest(
+            name.to_string(),
+            Shape::Rec(1.0, 1.0),
+            Column::vector([x, 0.0]),
+            Column::vector([0.0]),
+            properties,
+        )
+    }
+
+    #[test]
+    fn test_coulomb() {
+        let bodies = vec![
+            particle("A", -10.0, Some(1.0)),
+            particle("B", 0.0, Some(1.0)),
+            particle("C", 10.0, Some(-1.0)),
+            particle("D", 30.0, None),
+            // Charged, but with no variables to write its equation or anyone else's in
+            particle("", 50.0, Some(1.0)),
+        ];
+        let coulomb = Coulomb {
+            constant: 1000.0,
+            ..Coulomb::default()
+        };
+
+        let equations = coulomb.equations(&bodies);
+        assert_eq!(equations.len(), 3);
+        assert_eq!(coulomb.force("D", &bodies[3..]), "0");
+
+        let mut engine = Engine::new(
+            bodies,
+            Environment::build(
+                equations.iter().map(|x| x.as_str()).collect(),
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(Collide2D::new()),
+            0.01,
+            1.0,
+        );
+        engine.tick().unwrap();
+
+        let velocity = |i: usize| *engine.bodies()[i].linear.velocity.get(0);
+        // A is pushed away from B harder than it is pulled towards C
+        assert!(velocity(0) < 0.0);
+        // C is pulled towards both
+        assert!(velocity(2) < 0.0);
+        // B is pushed and pulled the same way
+        assert!(velocity(1) > 0.0);
+        assert_eq!(velocity(3), 0.0);
+    }
+}
@@ -0,0 +1,472 @@
+//! Joints between pairs of bodies, applied by the engine every tick on top of whatever their
+//! equations give, so soft attachments and axles don't have to be written out as equations by hand.
+
+use crate::constraint::{Constraint, Limit, Row};
+use crate::math::Vector;
+use crate::{Body, BodyId, Space, index_by_id};
+
+/// A point fixed to a body.
+#[derive(Clone)]
+pub struct Anchor<S: Space> {
+    pub body: BodyId,
+    /// Relative to the body's centroid, in its own orientation.
+    pub offset: S::Linear,
+}
+
+impl<S: Space> Anchor<S> {
+    pub fn new(body: BodyId, offset: S::Linear) -> Self {
+        Anchor { body, offset }
+    }
+
+    /// The body's centroid.
+    pub fn centroid(body: BodyId) -> Self {
+        Anchor::new(body, S::Linear::empty())
+    }
+
+    // The offset as it's turned in the world, and the velocity of the point
+    fn locate(&self, body: &Body<S>) -> (S::Linear, S::Linear) {
+        let r = S::rotate(&body.angular.displacement, &self.offset);
+        let velocity = body
+            .linear
+            .velocity
+            .plus(&S::cross_both(&body.angular.velocity, &r));
+
+        (r, velocity)
+    }
+}
+
+// A force on the body at an index, and the torque it gives about the body's centroid
+pub(crate) type Push<S> = (usize, <S as Space>::Linear, <S as Space>::Angular);
+
+// Indices of the bodies with these ids, if both exist and they're different bodies
+fn pair<S: Space>(bodies: &[Body<S>], a: BodyId, b: BodyId) -> Option<(usize, usize)> {
+    let (i, j) = (index_by_id(bodies, a)?, index_by_id(bodies, b)?);
+    (i != j).then_some((i, j))
+}
+
+// The bodies at both indices of a pair at once
+fn pair_mut<S: Space>(bodies: &mut [Body<S>], (i, j): (usize, usize)) -> [&mut Body<S>; 2] {
+    let (left, right) = bodies.split_at_mut(i.max(j));
+    match i < j {
+        true => [&mut left[i], &mut right[0]],
+        false => [&mut right[0], &mut left[j]],
+    }
+}
+
+/// A damped spring pulling (or pushing) two anchors towards `rest_length` apart, see
+/// `Engine::add_spring`.
+#[derive(Clone)]
+pub struct Spring<S: Space> {
+    pub a: Anchor<S>,
+    pub b: Anchor<S>,
+    pub rest_length: f64,
+    /// Force per unit the spring is stretched or squashed by.
+    pub stiffness: f64,
+    /// Force per unit of speed the anchors move apart or together at.
+    pub damping: f64,
+}
+
+impl<S: Space> Spring<S> {
+    pub fn new(a: Anchor<S>, b: Anchor<S>, rest_length: f64, stiffness: f64, damping: f64) -> Self {
+        Spring {
+            a,
+            b,
+            rest_length,
+            stiffness,
+            damping,
+        }
+    }
+
+    /// The push on the body at `a` and then the one at `b`. None if either body doesn't exist or
+    /// the anchors are in the same place, when there's no telling which way to push.
+    pub(crate) fn forces(&self, bodies: &[Body<S>]) -> Option<[Push<S>; 2]> {
+        let (i, j) = (
+            index_by_id(bodies, self.a.body)?,
+            index_by_id(bodies, self.b.body)?,
+        );
+        let (a, b) = (&bodies[i], &bodies[j]);
+        let ((ra, va), (rb, vb)) = (self.a.locate(a), self.b.locate(b));
+
+        let separation = b
+            .linear
+            .displacement
+            .plus(&rb)
+            .plus(&a.linear.displacement.plus(&ra).scale(-1.0));
+        let length = separation.magnitude();
+        if length < 1e-12 {
+            return None;
+        }
+        let direction = separation.scale(1.0 / length);
+
+        // How fast the anchors are moving apart
+        let speed = vb.plus(&va.scale(-1.0)).dot(&direction);
+        let force =
+            direction.scale(self.stiffness * (length - self.rest_length) + self.damping * speed);
+        let reaction = force.scale(-1.0);
+
+        Some([
+            (i, force.clone(), S::cross_linear(&ra, &force)),
+            (j, reaction.clone(), S::cross_linear(&rb, &reaction)),
+        ])
+    }
+}
+
+/// Couples the rotation of two bodies as if they were meshed gears, see `Engine::add_gear`. The
+/// angle of `a` plus `ratio` times the angle of `b` is held where it was when the gear was added,
+/// so with a ratio of 2 `b` turns half as fast as `a` and the other way.
+#[derive(Clone)]
+pub struct Gear<S: Space> {
+    pub a: BodyId,
+    pub b: BodyId,
+    pub ratio: f64,
+    // The angle held, taken once both bodies exist
+    offset: Option<S::Angular>,
+}
+
+impl<S: Space> Gear<S> {
+    pub fn new(a: BodyId, b: BodyId, ratio: f64) -> Self {
+        Gear {
+            a,
+            b,
+            ratio,
+            offset: None,
+        }
+    }
+
+    /// Holds the bodies at the angles they're at now, unless they already are.
+    pub(crate) fn hold(&mut self, bodies: &[Body<S>]) {
+        if let (None, Some((i, j))) = (&self.offset, pair(bodies, self.a, self.b)) {
+            let (a, b) = (&bodies[i], &bodies[j]);
+            let offset = a
+                .angular
+                .displacement
+                .plus(&b.angular.displacement.scale(self.ratio));
+            self.offset = Some(offset);
+        }
+    }
+}
+
+impl<S: Space> Constraint<S> for Gear<S> {
+    // One row for each axis, holding its part of the angular velocity of `a` plus `ratio` times
+    // that of `b` at 0. An immovable body drives the other, and nothing happens between two.
+    fn rows(&mut self, bodies: &[Body<S>]) -> Vec<Row<S>> {
+        let Some((i, j)) = pair(bodies, self.a, self.b) else {
+            return Vec::new();
+        };
+        self.hold(bodies);
+
+        (0..S::Angular::dof())
+            .map(|k| {
+                let axis = basis::<S::Angular>(k);
+                Row::angular(i, j, [axis.clone(), axis.scale(self.ratio)], 0.0)
+            })
+            .collect()
+    }
+
+    // Spreads the drift from the held angle between the bodies, like their velocities
+    fn correct(&mut self, bodies: &mut [Body<S>]) {
+        let (Some(offset), Some(indices)) = (self.offset.clone(), pair(bodies, self.a, self.b))
+        else {
+            return;
+        };
+        let [a, b] = pair_mut(bodies, indices);
+
+        let (ia, ib) = (a.properties.inverse_moi(), b.properties.inverse_moi());
+        let mass = ia + self.ratio.powi(2) * ib;
+        if mass <= 0.0 {
+            return;
+        }
+
+        let error = a
+            .angular
+            .displacement
+            .plus(&b.angular.displacement.scale(self.ratio))
+            .plus(&offset.scale(-1.0));
+        a.angular.displacement = a.angular.displacement.plus(&error.scale(-ia / mass));
+        b.angular.displacement = b
+            .angular
+            .displacement
+            .plus(&error.scale(-self.ratio * ib / mass));
+    }
+}
+
+// The unit vector along the `k`th axis
+fn basis<V: Vector>(k: usize) -> V {
+    V::new(
+        (0..V::dof())
+            .map(|i| if i == k { 1.0 } else { 0.0 })
+            .collect(),
+    )
+    .unwrap_or_else(|_| V::empty())
+}
+
+/// Holds a wheel's centroid on a line through an anchor on another body, leaving it free to slide
+/// along the line and to turn, see `Engine::add_constraint`. A spring between the anchor and the
+/// wheel makes it a suspension, and a motor drives the wheel round as well.
+#[derive(Clone)]
+pub struct Axle<S: Space> {
+    pub anchor: Anchor<S>,
+    pub wheel: BodyId,
+    /// The direction of the line, in the anchor body's own orientation.
+    pub axis: S::Linear,
+    /// The angular velocity the wheel is driven at relative to the anchor body, if it's driven.
+    pub motor: Option<S::Angular>,
+}
+
+impl<S: Space> Axle<S> {
+    pub fn new(anchor: Anchor<S>, wheel: BodyId, axis: S::Linear) -> Self {
+        Axle {
+            anchor,
+            wheel,
+            axis: axis.unit(),
+            motor: None,
+        }
+    }
+
+    pub fn with_motor(mut self, speed: S::Angular) -> Self {
+        self.motor = Some(speed);
+        self
+    }
+
+    // The line's direction in the world and how far the wheel at `indices` is off it
+    fn locate(&self, bodies: &[Body<S>], indices: (usize, usize)) -> (S::Linear, S::Linear) {
+        let (a, b) = (&bodies[indices.0], &bodies[indices.1]);
+
+        let axis = S::rotate(&a.angular.displacement, &self.axis);
+        let offset = b.linear.displacement.plus(
+            &a.linear
+                .displacement
+                .plus(&self.anchor.locate(a).0)
+                .scale(-1.0),
+        );
+        let error = offset.plus(&axis.scale(-offset.dot(&axis)));
+
+        (axis, error)
+    }
+}
+
+impl<S: Space> Constraint<S> for Axle<S> {
+    // A row across the line for every direction square to it, holding the wheel's centroid to the
+    // point of the line it's at, then one for each axis of the motor
+    fn rows(&mut self, bodies: &[Body<S>]) -> Vec<Row<S>> {
+        let Some(pair) = pair(bodies, self.anchor.body, self.wheel) else {
+            return Vec::new();
+        };
+        let (axis, _) = self.locate(bodies, pair);
+        let (a, b) = (&bodies[pair.0], &bodies[pair.1]);
+
+        // Every axis with its part along the line and along those already kept taken out
+        let mut across: Vec<S::Linear> = Vec::new();
+        for k in 0..S::Linear::dof() {
+            let direction = across
+                .iter()
+                .chain([&axis])
+                .fold(basis::<S::Linear>(k), |x, y| x.plus(&y.scale(-x.dot(y))));
+            if direction.magnitude() > 1e-6 {
+                across.push(direction.unit());
+            }
+        }
+
+        let point = &b.linear.displacement;
+        let mut rows: Vec<Row<S>> = across
+            .iter()
+            .map(|x| Row::point(pair, [a, b], point, x, 0.0, Limit::free()))
+            .collect();
+        if let Some(speed) = &self.motor {
+            rows.extend((0..S::Angular::dof()).map(|k| {
+                let axis = basis::<S::Angular>(k);
+                Row::angular(pair.0, pair.1, [axis.scale(-1.0), axis], *speed.get(k))
+            }));
+        }
+
+        rows
+    }
+
+    // Moves the wheel back onto the line, and the body the other way, in proportion to their
+    // inverse masses
+    fn correct(&mut self, bodies: &mut [Body<S>]) {
+        let Some(indices) = pair(bodies, self.anchor.body, self.wheel) else {
+            return;
+        };
+        let (_, error) = self.locate(bodies, indices);
+        let [a, b] = pair_mut(bodies, indices);
+
+        let (ia, ib) = (a.properties.inverse_mass(), b.properties.inverse_mass());
+        if ia + ib <= 0.0 {
+            return;
+        }
+        a.linear.displacement = a.linear.displacement.plus(&error.scale(ia / (ia + ib)));
+        b.linear.displacement = b.linear.displacement.plus(&error.scale(-ib / (ia + ib)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::NoCollide;
+    use crate::math::Column;
+    use crate::math::solve::{Environment, builtin};
+    use crate::spaces::Space2D;
+    use crate::{BodyKind, BodyProperties, Engine, Shape};
+    use std::f64::consts::FRAC_PI_2;
+
+    // A weight hung from a fixed hook by a spring
+    fn hanging(offset: [f64; 2]) -> Engine<Space2D> {
+        let body = |name: &str, kind: BodyKind| {
+            Body::at_rest(
+                name.to_string(),
+                Shape::Rec(1.0, 1.0),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::rectangle(2.0, 1.0, 1.0).with_kind(kind),
+            )
+        };
+        let mut engine = Engine::new(
+            vec![
+                body("Hook", BodyKind::Static),
+                body("Weight", BodyKind::Dynamic),
+            ],
+            Environment::build(
+                vec!["a_Weight=-10*hatj"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(NoCollide),
+            0.01,
+            1.0,
+        );
+        engine.bodies_mut()[1].linear.displacement = Column::vector([0.0, -5.0]);
+        let [hook, weight] = ["Hook", "Weight"].map(|x| engine.id_of(x).unwrap());
+        engine.add_spring(Spring::new(
+            Anchor::centroid(hook),
+            Anchor::new(weight, Column::vector(offset)),
+            5.0,
+            40.0,
+            8.0,
+        ));
+
+        engine
+    }
+
+    #[test]
+    fn test_spring_settles() {
+        let mut engine = hanging([0.0, 0.0]);
+        for _ in 0..2000 {
+            engine.tick().unwrap();
+        }
+
+        // Stretched by m * g / k, give or take the time step
+        let weight = &engine.bodies()[1];
+        assert!(
+            (weight.linear.displacement.get(1) + 5.5).abs() < 0.02,
+            "{:?}",
+            weight.linear.displacement
+        );
+        let settled = weight.linear.displacement.clone();
+        engine.tick().unwrap();
+        let moved = engine.bodies()[1]
+            .linear
+            .displacement
+            .plus(&settled.scale(-1.0));
+        assert!(moved.magnitude() < 1e-6);
+        assert_eq!(
+            engine.bodies()[0].linear.displacement,
+            Column::vector([0.0, 0.0])
+        );
+    }
+
+    #[test]
+    fn test_spring_turns() {
+        // Hung from a corner, it swings round until the corner is above its centroid
+        let mut engine = hanging([0.5, 0.5]);
+        for _ in 0..100 {
+            engine.tick().unwrap();
+        }
+
+        assert!(*engine.bodies()[1].angular.displacement.get(0) > 0.1);
+    }
+
+    fn wheels(kinds: [BodyKind; 2], equations: Vec<&str>) -> Engine<Space2D> {
+        let wheel = |name: &str, x: f64, kind: BodyKind| {
+            let shape = Shape::Ellipse(1.0, 1.0);
+            Body::at_rest(
+                name.to_string(),
+                shape.clone(),
+                Column::vector([x, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::from_density(1.0, &shape).with_kind(kind),
+            )
+        };
+
+        Engine::new(
+            vec![wheel("A", 0.0, kinds[0]), wheel("B", 2.0, kinds[1])],
+            Environment::build(equations, builtin::functions(), builtin::constants()).unwrap(),
+            Box::new(NoCollide),
+            0.1,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn test_gear_driven() {
+        let mut engine = wheels(
+            [BodyKind::Kinematic, BodyKind::Dynamic],
+            vec!["omega_A=2*hatk"],
+        );
+        let [a, b] = ["A", "B"].map(|x| engine.id_of(x).unwrap());
+        engine.add_gear(Gear::new(a, b, 2.0));
+        for _ in 0..10 {
+            engine.tick().unwrap();
+        }
+
+        let [a, b] = [0, 1].map(|i| engine.bodies()[i].angular.clone());
+        assert!((*b.velocity.get(0) + 1.0).abs() < 1e-9, "{:?}", b.velocity);
+        assert!((*a.displacement.get(0) - 2.0).abs() < 1e-9);
+        assert!((*b.displacement.get(0) + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_gear_shares_momentum() {
+        let mut engine = wheels([BodyKind::Dynamic, BodyKind::Dynamic], vec![]);
+        engine.bodies_mut()[0].angular.velocity = Column::vector([3.0]);
+        let [a, b] = ["A", "B"].map(|x| engine.id_of(x).unwrap());
+        engine.add_gear(Gear::new(a, b, 1.0));
+        engine.tick().unwrap();
+
+        // Equal wheels turning the opposite ways, with the angular momentum of both conserved
+        // about their own axles
+        let [a, b] = [0, 1].map(|i| *engine.bodies()[i].angular.velocity.get(0));
+        assert!((a - 1.5).abs() < 1e-9, "{}", a);
+        assert!((b + 1.5).abs() < 1e-9, "{}", b);
+    }
+
+    #[test]
+    fn test_axle() {
+        // Falling and thrown sideways beside a fixed body, turned a quarter so that the axle
+        // running down from it runs sideways in the world
+        let mut engine = wheels([BodyKind::Static, BodyKind::Dynamic], vec!["a_B=-10*hatj"]);
+        engine.bodies_mut()[0].angular.displacement = Column::vector([FRAC_PI_2]);
+        engine.bodies_mut()[1].linear.velocity = Column::vector([3.0, 0.0]);
+        let [a, b] = ["A", "B"].map(|x| engine.id_of(x).unwrap());
+        let axle = Axle::new(Anchor::centroid(a), b, Column::vector([0.0, -1.0]));
+        engine.add_constraint(Box::new(axle.with_motor(Column::vector([2.0]))));
+        for _ in 0..10 {
+            engine.tick().unwrap();
+        }
+
+        // Free to slide sideways along it but not to fall, and driven round
+        let b = &engine.bodies()[1];
+        assert!(
+            (*b.linear.displacement.get(0) - 5.0).abs() < 1e-6,
+            "{:?}",
+            b.linear.displacement
+        );
+        assert!(
+            b.linear.displacement.get(1).abs() < 1e-6,
+            "{:?}",
+            b.linear.displacement
+        );
+        assert!((*b.angular.velocity.get(0) - 2.0).abs() < 1e-9);
+    }
+}
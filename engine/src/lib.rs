@@ -1,12 +1,63 @@
+use crate::behavior::{Attached, Behavior, TickContext};
+use crate::broadphase::{Broadphase, SweepAndPrune};
+use crate::bvh::Bvh;
 use crate::collide::{Collide, Collision};
-use crate::err::{EngineResult, ErrorKind};
-use crate::math::integration::{leapfrog_displacement, leapfrog_velocity};
-use crate::math::solve::Environment;
-use crate::math::{Vector};
-use std::collections::HashMap;
-
+use crate::err::{EngineResult, Error, ErrorKind};
+use crate::events::{CollisionEvent, Event, Monitor, kinetic_energy};
+use crate::math::integration::{
+    Integrator, Reconstruction, backward_difference, central_difference, leapfrog_displacement,
+    leapfrog_velocity, runge_kutta4,
+};
+use crate::math::solve::{Environment, builtin};
+use crate::math::symbol::{Bindings, Symbol};
+use crate::math::validate::{Diagnostic, validate};
+use crate::constraint::{Constraint, Limit, Row};
+use crate::joints::{Gear, Spring};
+use crate::listener::EngineListener;
+use crate::noise::{Noise, Noisy, Rng};
+use crate::observe::{Callback, Observers, Subject};
+use crate::pairs::{PairCache, reach};
+use crate::sleep::{Sleepers, Sleeping};
+use crate::schedule::{At, Command, Schedule};
+use crate::paths::{Follower, Path};
+use crate::state::{BodyKeys, StateForm, StateTable};
+use crate::math::{Column, Vector, geometry};
+use crate::spaces::Space2D;
+use crate::zones::{ForceField, GravityZone, Region};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::f64::consts::PI;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::OnceLock;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+pub mod behavior;
+pub mod broadphase;
+pub mod bvh;
+pub mod constraint;
+pub mod drag;
 pub mod err;
+pub mod events;
+pub mod forces;
 pub mod math;
+pub mod joints;
+pub mod listener;
+pub mod narrowphase;
+pub mod noise;
+pub mod observe;
+mod pairs;
+pub mod paths;
+pub mod prelude;
+pub mod record;
+pub mod scenarios;
+pub mod schedule;
+pub mod series;
+pub mod sleep;
+mod state;
+pub mod sweep;
+pub mod trace;
+pub mod vehicle;
+pub mod zones;
 
 pub struct Basis {
     pub name: &'static str,
@@ -22,20 +73,76 @@ pub trait Space {
 
     fn cross_both(w: &Self::Angular, r: &Self::Linear) -> Self::Linear;
     fn cross_linear(a: &Self::Linear, b: &Self::Linear) -> Self::Angular;
+    /// `v` turned by `rotation`, as a body's angular displacement turns its shape.
+    fn rotate(rotation: &Self::Angular, v: &Self::Linear) -> Self::Linear;
+    /// `rotation` turned further by `by`, as a body's angular velocity turns it over a step. Adding
+    /// them is exact while every rotation shares one axis, as it always does in two dimensions.
+    fn turn(rotation: &Self::Angular, by: &Self::Angular) -> Self::Angular {
+        rotation.plus(by)
+    }
+
+    /// Checks the points of a manifold are an outline the collider can handle, describing what's
+    /// wrong with it otherwise.
+    fn validate_outline(points: &[Self::Linear]) -> Result<(), String> {
+        if points.len() <= Self::Linear::dof() {
+            return Err(format!(
+                "a manifold needs at least {} points",
+                Self::Linear::dof() + 1
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(serialize = "V: serde::Serialize", deserialize = "V: serde::Deserialize<'de>"))
+)]
 pub struct BodyState<V: Vector> {
     pub displacement: V,
     pub velocity: V,
     pub acceleration: V,
 }
 
+/// What moves a body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BodyKind {
+    /// Nothing, whatever its equations or velocity say. For floors, walls and other scenery.
+    Static,
+    /// Its equations, contacts, and any forces and impulses applied to it.
+    #[default]
+    Dynamic,
+    /// Its equations alone. Contacts push other bodies out of its way but never move it, so it
+    /// suits moving platforms and anything else scripted.
+    Kinematic,
+}
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BodyProperties {
     pub mass: f64,
     // moment of inertia
     pub moi: f64,
+    // Any other scalars (charge, temperature etc.), exported to equations as `name_Body`
+    // alongside `m_Body` and `I_Body`
+    pub custom: BTreeMap<String, f64>,
+    // Groups the body with others for per-pair contact overrides (see Engine::set_contact_override)
+    pub tag: Option<String>,
+    // Coulomb friction coefficients, combined with the other body's at each contact. Static holds
+    // a contact still against up to that many times the normal impulse, kinetic slows it once it
+    // slides. Both default to 0, frictionless.
+    pub static_friction: f64,
+    pub kinetic_friction: f64,
+    pub kind: BodyKind,
+    // What the body is made of, which takes the place of the engine's restitution and of the
+    // friction coefficients above in its contacts
+    pub material: Option<Material>,
+    // Quadratic drag coefficient, see Engine::set_drag. 0 by default, which feels no drag
+    pub drag: f64,
 }
 
 impl BodyProperties {
@@ -43,33 +150,454 @@ impl BodyProperties {
         BodyProperties {
             mass: 0.0,
             moi: 0.0,
+            custom: BTreeMap::new(),
+            tag: None,
+            static_friction: 0.0,
+            kinetic_friction: 0.0,
+            kind: BodyKind::Dynamic,
+            material: None,
+            drag: 0.0,
         }
     }
 
     pub fn rectangle(mass: f64, width: f64, height: f64) -> BodyProperties {
         let moi = mass / 12.0 * (width.powi(2) + height.powi(2));
 
-        BodyProperties { mass, moi }
+        BodyProperties {
+            mass,
+            moi,
+            custom: BTreeMap::new(),
+            tag: None,
+            static_friction: 0.0,
+            kinetic_friction: 0.0,
+            kind: BodyKind::Dynamic,
+            material: None,
+            drag: 0.0,
+        }
+    }
+
+    /// A solid disc, `m * r^2 / 2`.
+    pub fn circle(mass: f64, radius: f64) -> Self {
+        BodyProperties::annulus(mass, 0.0, radius)
+    }
+
+    /// A thin ring (a hoop), all of its mass at `radius`, `m * r^2`.
+    pub fn ring(mass: f64, radius: f64) -> Self {
+        BodyProperties::annulus(mass, radius, radius)
+    }
+
+    /// A thick ring between two radii, `m * (r_1^2 + r_2^2) / 2`.
+    pub fn annulus(mass: f64, inner: f64, outer: f64) -> Self {
+        BodyProperties {
+            mass,
+            moi: mass * (inner.powi(2) + outer.powi(2)) / 2.0,
+            ..BodyProperties::weightless()
+        }
+    }
+
+    /// A thin rod turning about its middle, `m * L^2 / 12`.
+    pub fn rod(mass: f64, length: f64) -> Self {
+        BodyProperties::rectangle(mass, length, 0.0)
+    }
+
+    /// An equilateral triangle turning about its centroid, `m * s^2 / 12`.
+    pub fn triangle(mass: f64, side: f64) -> Self {
+        BodyProperties {
+            mass,
+            moi: mass * side.powi(2) / 12.0,
+            ..BodyProperties::weightless()
+        }
+    }
+
+    /// A thin spherical shell, `2 * m * r^2 / 3`. Moments in `Space3D` are always taken as a
+    /// sphere's.
+    pub fn shell(mass: f64, radius: f64) -> Self {
+        BodyProperties {
+            mass,
+            moi: 2.0 * mass * radius.powi(2) / 3.0,
+            ..BodyProperties::weightless()
+        }
+    }
+
+    /// A solid sphere, `2 * m * r^2 / 5`.
+    pub fn sphere(mass: f64, radius: f64) -> Self {
+        BodyProperties {
+            mass,
+            moi: 2.0 * mass * radius.powi(2) / 5.0,
+            ..BodyProperties::weightless()
+        }
+    }
+
+    /// The mass and moment of inertia of `shape` made of a material of uniform `density` (mass per
+    /// unit area). The moment is taken about the shape's origin, which is what the body turns
+    /// around, so manifolds should be centred on their [`Shape::centroid`].
+    pub fn from_density(density: f64, shape: &Shape<Space2D>) -> Self {
+        // A half-plane is a boundary nothing moves, however dense
+        let (mass, moi) = match shape {
+            Shape::HalfPlane(..) => (0.0, 0.0),
+            _ => (density * shape.area(), density * shape.second_moment()),
+        };
+
+        BodyProperties {
+            mass,
+            moi,
+            custom: BTreeMap::new(),
+            tag: None,
+            static_friction: 0.0,
+            kinetic_friction: 0.0,
+            kind: BodyKind::Dynamic,
+            material: None,
+            drag: 0.0,
+        }
+    }
+
+    /// The mass properties of a polygon of `mass` spread evenly over it, with its vertices in order
+    /// either way round. The moment of inertia is taken about the polygon's centroid wherever its
+    /// origin is, so it's right for an outline shifted onto its [`Shape::centroid`] (which
+    /// [`Shape::centred`] does). A polygon with no area has no moment to give it.
+    pub fn from_polygon(mass: f64, points: &[Column<2>]) -> Self {
+        let area = math::geometry::area(points);
+        let moi = match math::geometry::centroid(points) {
+            // Parallel axis theorem, from the moment about the origin
+            Some(centroid) => {
+                mass * math::geometry::second_moment(points) / area
+                    - mass * centroid.magnitude().powi(2)
+            }
+            None => 0.0,
+        };
+
+        BodyProperties {
+            mass,
+            moi,
+            ..BodyProperties::weightless()
+        }
+    }
+
+    /// Adds a custom scalar, eg. `with("charge", 2.0)` makes `charge_A` available to equations for
+    /// a body named `A`.
+    pub fn with(mut self, name: &str, value: f64) -> Self {
+        self.custom.insert(name.to_string(), value);
+        self
+    }
+
+    pub fn tagged(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn with_friction(mut self, static_friction: f64, kinetic_friction: f64) -> Self {
+        self.static_friction = static_friction;
+        self.kinetic_friction = kinetic_friction;
+        self
+    }
+
+    /// The mass and moment of inertia of `shape` made of `material`, see
+    /// [`BodyProperties::from_density`].
+    pub fn from_material(material: Material, shape: &Shape<Space2D>) -> Self {
+        BodyProperties::from_density(material.density, shape).with_material(material)
+    }
+
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn with_drag(mut self, coefficient: f64) -> Self {
+        self.drag = coefficient;
+        self
+    }
+
+    pub fn with_kind(mut self, kind: BodyKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// One over the mass as far as contacts, forces and impulses are concerned, 0 for a body
+    /// they can't move (one that isn't dynamic, or has no mass).
+    pub fn inverse_mass(&self) -> f64 {
+        match self.kind {
+            BodyKind::Dynamic if self.mass > 0.0 => 1.0 / self.mass,
+            _ => 0.0,
+        }
+    }
+
+    /// One over the moment of inertia, like [`BodyProperties::inverse_mass`].
+    pub fn inverse_moi(&self) -> f64 {
+        match self.kind {
+            BodyKind::Dynamic if self.moi > 0.0 => 1.0 / self.moi,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Contact settings used between bodies of two particular tags instead of the engine's, for
+/// interactions no single per-engine value can describe (rubber on ice vs rubber on concrete).
+/// Unset fields fall back to the engine's.
+#[derive(Debug, Clone, Default)]
+pub struct ContactOverride {
+    pub restitution: Option<f64>,
+    // Used as both the static and kinetic coefficient, rather than combining the bodies'
+    pub friction: Option<f64>,
+    pub rolling_resistance: Option<f64>,
+}
+
+/// What a body is made of. Contacts between two bodies combine their materials (see
+/// `Engine::set_combine`), so a rubber ball can bounce off a concrete floor while a lump of clay
+/// dropped next to it doesn't.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Material {
+    /// From 0 (stops dead) to 1 (bounces back as fast as it came in).
+    pub restitution: f64,
+    /// Coulomb friction coefficient, used for both static and kinetic friction.
+    pub friction: f64,
+    /// Mass per unit area, for [`BodyProperties::from_material`].
+    pub density: f64,
+}
+
+/// How the restitution or friction of two bodies is combined into that of their contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Combine {
+    Min,
+    Max,
+    Average,
+    /// Zero if either is, eg. a frictionless body slides over anything.
+    GeometricMean,
+}
+
+impl Combine {
+    pub fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Combine::Min => a.min(b),
+            Combine::Max => a.max(b),
+            Combine::Average => (a + b) / 2.0,
+            Combine::GeometricMean => (a * b).sqrt(),
+        }
+    }
+}
+
+/// How far bodies left overlapping by a contact are pushed back apart, see
+/// `Engine::set_correction`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correction {
+    /// Overlap left alone, so bodies resting on each other stay touching rather than being pushed
+    /// clear and falling back every tick.
+    pub slop: f64,
+    /// Fraction of the rest of the overlap removed each tick, from 0 (none) to 1 (all of it).
+    /// Less than all of it spreads the push over a few ticks, so bodies aren't shoved through
+    /// whatever else they're touching.
+    pub factor: f64,
+}
+
+impl Default for Correction {
+    fn default() -> Self {
+        Correction {
+            slop: 0.0,
+            factor: 1.0,
+        }
     }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "S::Linear: serde::Serialize, S::Angular: serde::Serialize",
+        deserialize = "S::Linear: serde::Deserialize<'de>, S::Angular: serde::Deserialize<'de>"
+    ))
+)]
 pub enum Shape<S: Space> {
     Rec(f64, f64),     // Width, height
     Ellipse(f64, f64), // major/minor axis
     Manifold(Vec<S::Linear>),
+    // Points joined in order by segments with no thickness, for static boundaries (floors, walls,
+    // terrain). It has no area, so it has no mass and nothing moves it.
+    Chain(Vec<S::Linear>),
+    // Everything behind the line (or plane) facing the normal, the offset along it from the body's
+    // origin. An unbounded static boundary, it has no mass and nothing moves it.
+    HalfPlane(S::Linear, f64),
+}
+
+/// Which variant a shape is, for picking how to collide it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShapeKind {
+    Rec,
+    Ellipse,
+    Manifold,
+    Chain,
+    HalfPlane,
+}
+
+impl<S: Space> Shape<S> {
+    /// A single segment from `a` to `b`.
+    pub fn segment(a: S::Linear, b: S::Linear) -> Self {
+        Shape::Chain(vec![a, b])
+    }
+
+    pub fn kind(&self) -> ShapeKind {
+        match self {
+            Shape::Rec(..) => ShapeKind::Rec,
+            Shape::Ellipse(..) => ShapeKind::Ellipse,
+            Shape::Manifold(_) => ShapeKind::Manifold,
+            Shape::Chain(_) => ShapeKind::Chain,
+            Shape::HalfPlane(..) => ShapeKind::HalfPlane,
+        }
+    }
+
+    /// Checks the collider can handle the shape: rectangles and ellipses need a positive size, and
+    /// manifolds a proper outline (at least 3 points, none in line with its neighbours, and no
+    /// edges crossing), which would otherwise give NaN contacts partway through a tick. Chains
+    /// need at least 2 points and no segment of zero length, and half-planes a normal with a
+    /// direction.
+    pub fn validate(&self) -> EngineResult<()> {
+        self.problem()
+            .map_or(Ok(()), |x| Err(Error::new(ErrorKind::InvalidShape(x))))
+    }
+
+    fn problem(&self) -> Option<String> {
+        let positive = |x: f64| x > 0.0 && x.is_finite();
+        let finite = |points: &[S::Linear]| {
+            points
+                .iter()
+                .all(|x| (0..S::Linear::dof()).all(|i| x.get(i).is_finite()))
+        };
+
+        match self {
+            Shape::Rec(width, height) if !positive(*width) || !positive(*height) => {
+                Some(format!("{} by {} is not a rectangle", width, height))
+            }
+            Shape::Ellipse(major, minor) if !positive(*major) || !positive(*minor) => {
+                Some(format!("{} by {} is not an ellipse", major, minor))
+            }
+            Shape::Manifold(points) => {
+                if !finite(points) {
+                    return Some("manifold points must be finite".to_string());
+                }
+                S::validate_outline(points).err()
+            }
+            Shape::Chain(points) => {
+                if !finite(points) {
+                    return Some("chain points must be finite".to_string());
+                }
+                if points.len() < 2 {
+                    return Some("a chain needs at least 2 points".to_string());
+                }
+                points
+                    .windows(2)
+                    .position(|x| x[1].plus(&x[0].scale(-1.0)).magnitude() == 0.0)
+                    .map(|i| format!("chain segment {} has no length", i))
+            }
+            Shape::HalfPlane(normal, offset) => {
+                if !finite(std::slice::from_ref(normal)) || !offset.is_finite() {
+                    return Some("half-plane must be finite".to_string());
+                }
+                (normal.magnitude() == 0.0)
+                    .then(|| "half-plane normal has no direction".to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Shape<Space2D> {
+    /// A manifold of the convex hull of `points`, which can be in any order. The collider only
+    /// handles convex outlines wound counterclockwise, which arbitrary point data often isn't.
+    pub fn convex(points: Vec<Column<2>>) -> Self {
+        Shape::Manifold(math::geometry::convex_hull(&points))
+    }
+
+    /// A manifold of `points` shifted so its centroid is at the origin, which is the point the
+    /// body turns around. Other shapes are already centred.
+    pub fn centred(self) -> Self {
+        match self {
+            Shape::Manifold(points) => {
+                let centroid = math::geometry::centroid(&points).unwrap_or_else(Column::empty);
+                Shape::Manifold(points.iter().map(|x| x.plus(&centroid.scale(-1.0))).collect())
+            }
+            shape => shape,
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        match self {
+            Shape::Rec(width, height) => width * height,
+            Shape::Ellipse(major, minor) => PI * major * minor / 4.0,
+            Shape::Manifold(points) => math::geometry::area(points),
+            Shape::Chain(_) => 0.0,
+            Shape::HalfPlane(..) => f64::INFINITY,
+        }
+    }
+
+    /// The centre of mass of the shape relative to its origin, zero for everything but manifolds
+    /// not drawn around it.
+    pub fn centroid(&self) -> Column<2> {
+        match self {
+            Shape::Manifold(points) => {
+                math::geometry::centroid(points).unwrap_or_else(Column::empty)
+            }
+            _ => Column::empty(),
+        }
+    }
+
+    // Moment of inertia about the origin at unit density
+    fn second_moment(&self) -> f64 {
+        match self {
+            Shape::Rec(width, height) => width * height * (width.powi(2) + height.powi(2)) / 12.0,
+            // Axes are full lengths, the moment is area * (a^2 + b^2) / 4 over the semi-axes
+            Shape::Ellipse(major, minor) => {
+                self.area() * ((major / 2.0).powi(2) + (minor / 2.0).powi(2)) / 4.0
+            }
+            Shape::Manifold(points) => math::geometry::second_moment(points),
+            Shape::Chain(_) => 0.0,
+            Shape::HalfPlane(..) => f64::INFINITY,
+        }
+    }
 }
 
+/// Handle to a body, given out by the engine it's added to and kept for as long as the body stays
+/// in it, whatever it's renamed to or wherever it's moved in the list of bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BodyId(u64);
+
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "S::Linear: serde::Serialize, S::Angular: serde::Serialize",
+        deserialize = "S::Linear: serde::Deserialize<'de>, S::Angular: serde::Deserialize<'de>"
+    ))
+)]
 pub struct Body<S: Space> {
+    // Used as a label and to name the body's variables (`x_A`), a body with an empty name has none
     pub name: String,
+    // None until the body is added to an engine
+    pub id: Option<BodyId>,
     pub shape: Shape<S>,
     pub linear: BodyState<S::Linear>,
     pub angular: BodyState<S::Angular>,
     pub properties: BodyProperties,
+    // Force and torque about the centroid from `apply_force`, acting over the next tick only
+    pub force: S::Linear,
+    pub torque: S::Angular,
 }
 
 impl<S: Space> Body<S> {
+    /// A point in the world relative to the body's centroid, in the frame of its unrotated shape.
+    pub fn to_local(&self, point: &S::Linear) -> S::Linear {
+        S::rotate(
+            &self.angular.displacement.scale(-1.0),
+            &point.plus(&self.linear.displacement.scale(-1.0)),
+        )
+    }
+
+    /// The inverse of [`Body::to_local`].
+    pub fn to_world(&self, point: &S::Linear) -> S::Linear {
+        S::rotate(&self.angular.displacement, point).plus(&self.linear.displacement)
+    }
+
     pub fn at_rest(
         name: String,
         shape: Shape<S>,
@@ -79,6 +607,7 @@ impl<S: Space> Body<S> {
     ) -> Body<S> {
         Body {
             name,
+            id: None,
             shape,
             linear: BodyState {
                 displacement: position,
@@ -91,23 +620,343 @@ impl<S: Space> Body<S> {
                 acceleration: S::Angular::empty(),
             },
             properties,
+            force: S::Linear::empty(),
+            torque: S::Angular::empty(),
+        }
+    }
+
+    /// Pushes the body with `force` at the world-space `point` over the next tick, on top of
+    /// anything its equations give, turning it too unless the force is through its centroid.
+    /// Forces applied before the same tick add up, and only move dynamic bodies.
+    pub fn apply_force(&mut self, force: S::Linear, point: &S::Linear) {
+        let arm = point.plus(&self.linear.displacement.scale(-1.0));
+        self.torque = self.torque.plus(&S::cross_linear(&arm, &force));
+        self.force = self.force.plus(&force);
+    }
+
+    /// Checks the body can be simulated (eg. that its shape has an area and its mass is finite),
+    /// which would otherwise show up as NaNs partway through a tick.
+    pub fn validate(&self) -> EngineResult<()> {
+        self.problem().map_or(Ok(()), |reason| {
+            Err(Error::new(ErrorKind::InvalidBody {
+                name: self.name.clone(),
+                reason,
+            }))
+        })
+    }
+
+    fn problem(&self) -> Option<String> {
+        if let Some(reason) = self.shape.problem() {
+            return Some(reason);
         }
+
+        let BodyProperties { mass, moi, .. } = self.properties;
+        if !(mass >= 0.0 && mass.is_finite() && moi >= 0.0 && moi.is_finite()) {
+            return Some(format!(
+                "mass ({}) and moment of inertia ({}) must be finite and not negative",
+                mass, moi
+            ));
+        }
+        // Zero for both is fine, it's a body nothing can move
+        if (mass == 0.0) != (moi == 0.0) {
+            return Some(format!(
+                "mass ({}) and moment of inertia ({}) must both be zero or both positive",
+                mass, moi
+            ));
+        }
+
+        None
     }
 }
 
+/// The bodies and clock of an engine part way through a simulation, see `Engine::snapshot`.
+/// Equations, colliders, settings and joints aren't included, being set up by whatever builds the
+/// engine rather than changed by running it.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "S::Linear: serde::Serialize, S::Angular: serde::Serialize",
+        deserialize = "S::Linear: serde::Deserialize<'de>, S::Angular: serde::Deserialize<'de>"
+    ))
+)]
+pub struct EngineState<S: Space> {
+    pub bodies: Vec<Body<S>>,
+    pub time: f64,
+    pub ticks: u64,
+    pub delta_t: f64,
+}
+
 pub struct Engine<S: Space> {
     bodies: Vec<Body<S>>,
     env: Environment,
     collider: Box<dyn Collide<S>>,
     delta_t: f64,
     restitution: f64,
+    // Opposes the spin of bodies at their contacts, see Engine::set_rolling_resistance
+    rolling_resistance: f64,
+    paused: bool,
+    // Simulated seconds elapsed
+    time: f64,
+    // Ticks run so far
+    ticks: u64,
+    // Monotonic time in seconds, only used for diagnostics
+    clock: fn() -> f64,
+    // Leaves the clock unread, see Engine::set_deterministic
+    deterministic: bool,
+    // Spreads each tick over threads, see Engine::set_parallel
+    #[cfg(feature = "rayon")]
+    parallel: bool,
+    // Reused across ticks to avoid resolving variable names again
+    state: StateTable,
+    // Collision results from previous ticks
+    pairs: PairCache<S>,
+    broadphase: Box<dyn Broadphase<S>>,
+    // Torque on each body to be applied over the next tick
+    torques: HashMap<BodyId, S::Angular>,
+    // Impulses on each body to be given over the next tick, and the angular impulses they make
+    impulses: HashMap<BodyId, S::Linear>,
+    angular_impulses: HashMap<BodyId, S::Angular>,
+    // World bounds and speed limit, and which bodies are past them
+    monitor: Monitor<S>,
+    // Keyed by pairs of tags, in order
+    overrides: BTreeMap<(String, String), ContactOverride>,
+    // Upwards, when contacts are resolved a second time from the bottom up
+    shock_propagation: Option<S::Linear>,
+    // Whether contacts start from the impulses they were resolved with last tick
+    warm_starting: bool,
+    // Integration and collision steps each tick is split into
+    substeps: u32,
+    correction: Correction,
+    // How the restitution and the friction of the bodies in a contact are combined
+    combine: (Combine, Combine),
+    // Whether every body has been validated since they were last changed
+    validated: bool,
+    zones: Vec<GravityZone<S>>,
+    fields: Vec<ForceField<S>>,
+    springs: Vec<Spring<S>>,
+    // Density of the fluid bodies with a drag coefficient are slowed by
+    drag: Option<f64>,
+    constraints: Vec<Box<dyn Constraint<S>>>,
+    // Passes over the rows of every constraint each tick
+    constraint_iterations: usize,
+    // Bodies being moved along paths rather than by their linear equations
+    followers: Vec<Follower<S>>,
+    schedule: Schedule<S>,
+    behaviors: Vec<Attached<S>>,
+    observers: Observers<S>,
+    listener: Option<Box<dyn EngineListener<S>>>,
+    // Bodies pushed around by random forces, all drawn from rng
+    noise: Vec<Noisy<S>>,
+    rng: Rng,
+    reconstruction: Reconstruction,
+    integrator: Integrator,
+    // Linear and angular displacement of each body (by index) before the last tick, kept only while
+    // it's moved by a displacement equation
+    history: Vec<History<S>>,
+    // Given to the next body added without an id
+    next_id: u64,
+    sleepers: Sleepers,
+    // The static bodies in a tree for queries, up to date whenever the engine is validated
+    statics: Bvh<S>,
+}
+
+type History<S> = (Option<<S as Space>::Linear>, Option<<S as Space>::Angular>);
+
+/// Where a ray first meets a body, see `Engine::raycast`.
+pub struct RayHit<S: Space> {
+    pub body: BodyId,
+    pub point: S::Linear,
+    // How far along the ray
+    pub distance: f64,
 }
 
 pub struct Tick<S: Space> {
     pub collisions: Vec<S::Linear>,
+    // Full contact information (normal and depth) for each entry in collisions
+    pub contacts: Vec<Collision<S>>,
+    // Who touched whom and how hard, for each entry in collisions
+    pub collision_events: Vec<CollisionEvent<S>>,
+    pub stats: TickStats,
+    pub events: Vec<Event>,
+}
+
+impl<S: Space> Tick<S> {
+    fn empty() -> Self {
+        Tick {
+            collisions: Vec::new(),
+            contacts: Vec::new(),
+            collision_events: Vec::new(),
+            stats: TickStats::default(),
+            events: Vec::new(),
+        }
+    }
+
+    // Adds a substep of the tick to it
+    fn merge(&mut self, substep: Tick<S>) {
+        self.collisions.extend(substep.collisions);
+        self.contacts.extend(substep.contacts);
+        self.collision_events.extend(substep.collision_events);
+        self.events.extend(substep.events);
+
+        let (stats, other) = (&mut self.stats, substep.stats);
+        stats.evaluation_time += other.evaluation_time;
+        stats.collision_time += other.collision_time;
+        stats.pairs_tested += other.pairs_tested;
+        stats.contacts_resolved += other.contacts_resolved;
+        stats.max_penetration = stats.max_penetration.max(other.max_penetration);
+        stats.correction_iterations += other.correction_iterations;
+        stats.root_iterations += other.root_iterations;
+        stats.bodies_asleep = other.bodies_asleep;
+    }
+}
+
+/// Solver statistics gathered over a single tick.
+#[derive(Debug, Clone, Default)]
+pub struct TickStats {
+    // Seconds spent evaluating equations and integrating
+    pub evaluation_time: f64,
+    // Seconds spent detecting and resolving collisions
+    pub collision_time: f64,
+    pub pairs_tested: usize,
+    pub contacts_resolved: usize,
+    // Deepest overlap of any contact, before it was corrected
+    pub max_penetration: f64,
+    // Times a body was moved out of an overlap, over every contact
+    pub correction_iterations: usize,
+    pub root_iterations: usize,
+    // Bodies asleep at the end of the tick
+    pub bodies_asleep: usize,
+}
+
+// FNV-1a, for Engine::state_hash since std's hashers are free to change between releases
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_f64(&mut self, x: f64) {
+        let x = if x == 0.0 {
+            0.0
+        } else if x.is_nan() {
+            f64::NAN
+        } else {
+            x
+        };
+        self.write(&x.to_bits().to_le_bytes());
+    }
+}
+
+// Pairs tested for collision on rayon's thread pool, see Engine::set_parallel. No contact is
+// resolved until every pair has been tested, so the results are those the pairs would give tested
+// one after another
+struct Lookahead<S: Space> {
+    // By position in the order the pairs are tested, None where the pair wasn't tested
+    results: Vec<Option<Option<Collision<S>>>>,
+}
+
+impl<S: Space> Lookahead<S> {
+    fn none() -> Self {
+        Lookahead {
+            results: Vec::new(),
+        }
+    }
+
+    // Tests every pair that needs it, against where the bodies are now
+    #[cfg(feature = "rayon")]
+    fn test(
+        bodies: &[Body<S>],
+        pairs: &[(usize, usize)],
+        cache: &PairCache<S>,
+        collider: &dyn Collide<S>,
+    ) -> Self {
+        use rayon::prelude::*;
+
+        Lookahead {
+            results: pairs
+                .par_iter()
+                .map(|&(i, j)| {
+                    let (a, b) = (&bodies[i], &bodies[j]);
+                    cache.needs_test(a, b).then(|| collider.collide(a, b))
+                })
+                .collect(),
+        }
+    }
+
+    // The result for the `n`th pair, if it was tested
+    fn take(&mut self, n: usize) -> Option<Option<Collision<S>>> {
+        self.results.get_mut(n).and_then(Option::take)
+    }
+}
+
+// A contact found this tick, waiting to be solved along with every other and the joints
+struct Contact<S: Space> {
+    pair: (usize, usize),
+    collision: Collision<S>,
+    restitution: f64,
+    friction: (f64, f64),
+    rolling_resistance: f64,
+    // The impulse each point started from last tick, with warm starting
+    warm: Option<Vec<f64>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn default_clock() -> f64 {
+    static START: OnceLock<Instant> = OnceLock::new();
+
+    START.get_or_init(Instant::now).elapsed().as_secs_f64()
+}
+
+// std::time is unavailable on wasm, hosts are expected to provide a clock through Engine::set_clock
+#[cfg(target_arch = "wasm32")]
+fn default_clock() -> f64 {
+    0.0
+}
+
+// A tick must move time forward by some amount, or velocities can't be recovered from positions
+fn check_delta_t(delta_t: f64) -> EngineResult<()> {
+    match delta_t > 0.0 && delta_t.is_finite() {
+        true => Ok(()),
+        false => Err(Error::new(ErrorKind::InvalidTimeStep(delta_t))),
+    }
+}
+
+// Adds `x` to whatever is at index `i`, growing `into` as needed
+fn accumulate<V: Vector>(into: &mut Vec<Option<V>>, i: usize, x: V) {
+    if into.len() <= i {
+        into.resize(i + 1, None);
+    }
+    into[i] = Some(match into[i].take() {
+        Some(y) => y.plus(&x),
+        None => x,
+    });
+}
+
+// The same, for a body by its id
+fn accumulate_by<V: Vector>(into: &mut HashMap<BodyId, V>, id: BodyId, x: V) {
+    let sum = match into.remove(&id) {
+        Some(y) => y.plus(&x),
+        None => x,
+    };
+    into.insert(id, sum);
 }
 
-const CORRECTIVE_FRAMES: usize = 5;
+/// Index of the body with this id among `bodies`.
+pub(crate) fn index_by_id<S: Space>(bodies: &[Body<S>], id: BodyId) -> Option<usize> {
+    bodies.iter().position(|x| x.id == Some(id))
+}
 impl<S: Space + Clone> Engine<S> {
     pub fn new(
         bodies: Vec<Body<S>>,
@@ -116,74 +965,135 @@ impl<S: Space + Clone> Engine<S> {
         delta_t: f64,
         restitution: f64,
     ) -> Self {
-        Engine {
+        let mut engine = Engine {
             bodies,
             env,
             collider,
             delta_t,
             restitution,
-        }
-    }
+            rolling_resistance: 0.0,
+            paused: false,
+            time: 0.0,
+            ticks: 0,
+            clock: default_clock,
+            deterministic: false,
+            #[cfg(feature = "rayon")]
+            parallel: false,
+            state: StateTable::default(),
+            pairs: PairCache::default(),
+            broadphase: Box::new(SweepAndPrune::new()),
+            torques: HashMap::new(),
+            impulses: HashMap::new(),
+            angular_impulses: HashMap::new(),
+            monitor: Monitor::default(),
+            overrides: BTreeMap::new(),
+            shock_propagation: None,
+            warm_starting: false,
+            substeps: 1,
+            correction: Correction::default(),
+            combine: (Combine::Max, Combine::GeometricMean),
+            validated: false,
+            zones: Vec::new(),
+            fields: Vec::new(),
+            springs: Vec::new(),
+            drag: None,
+            constraints: Vec::new(),
+            constraint_iterations: 8,
+            followers: Vec::new(),
+            schedule: Schedule::default(),
+            behaviors: Vec::new(),
+            observers: Observers::default(),
+            listener: None,
+            noise: Vec::new(),
+            rng: Rng::new(0),
+            reconstruction: Reconstruction::default(),
+            integrator: Integrator::default(),
+            history: Vec::new(),
+            next_id: 0,
+            sleepers: Sleepers::default(),
+            statics: Bvh::default(),
+        };
+        engine.assign_ids();
 
-    fn eval_impl<Primary: Vector, Secondary: Vector>(
-        var: &'static str,
-        owner: String,
+        engine
+    }
 
-        bases: &'static [Basis],
-        selector: fn(&Body<S>) -> &BodyState<Primary>,
+    /// Replaces the clock used to time tick phases, it should return monotonic time in seconds.
+    pub fn set_clock(&mut self, clock: fn() -> f64) {
+        self.clock = clock;
+    }
 
-        extra_bases: &'static [Basis],
-        extra_selector: fn(&Body<S>) -> &BodyState<Secondary>,
+    /// Never reads the clock, so the time spent on each phase of a tick is reported as 0 and every
+    /// part of a `Tick` is the same from one run to the next, not only the bodies. Off by default.
+    /// Ticks are always otherwise deterministic, see docs/ticking.md.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
 
-        env: &Environment,
-        bodies: &Vec<Body<S>>,
-    ) -> EngineResult<Option<Vec<f64>>> {
-        let mut result = Vec::new();
-        let mut overrides = HashMap::new();
+    /// Integrates the bodies and tests their pairs for collision on rayon's thread pool. Contacts
+    /// are still resolved one after another in the same order, so a tick gives exactly the same
+    /// result as it would on one thread. Off by default, only worth it for hundreds of bodies.
+    #[cfg(feature = "rayon")]
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
 
-        // Set all bases to 0 (hati, hatj, hatk etc.)
-        for i in 0..Primary::dof() {
-            overrides.insert(bases[i].name.to_string(), 0.0);
+    // The clock, or 0 when deterministic
+    fn now(&self) -> f64 {
+        match self.deterministic {
+            true => 0.0,
+            false => (self.clock)(),
         }
+    }
 
-        // Initialize body constants / properties
-        for x in bodies {
-            for i in 0..Primary::dof() {
-                overrides.insert(format!("{}_{}", bases[i].axis, x.name), *selector(x).displacement.get(i));
-            }
+    /// Whether the engine injects `name` into the environment when evaluating body state, going by
+    /// the naming convention used in `StateTable` (eg. `x_A`, `v_theta_A`, `m_A`, `hatj`).
+    fn is_provided(name: &str) -> bool {
+        let bases = S::LINEAR_BASES.iter().chain(S::ANGULAR_BASES.iter());
 
-            for i in 0..Primary::dof() {
-                overrides.insert(format!("v_{}_{}", bases[i].axis, x.name), *selector(x).velocity.get(i));
-            }
+        name.starts_with("m_")
+            || name.starts_with("I_")
+            || bases.clone().any(|x| x.name == name)
+            || bases.clone().any(|x| name.starts_with(&format!("{}_", x.axis)))
+            || bases.clone().any(|x| name.starts_with(&format!("v_{}_", x.axis)))
+    }
 
-            for i in 0..Secondary::dof() {
-                overrides.insert(
-                    format!("{}_{}", extra_bases[i].axis, x.name),
-                    *extra_selector(x).displacement.get(i),
-                );
-            }
+    /// Whether `name` is one of the state forms the engine asks the environment for each tick.
+    fn is_queried(name: &str) -> bool {
+        ["s_", "v_", "a_", "F_", "q_", "omega_", "alpha_", "tau_"]
+            .iter()
+            .any(|x| name.starts_with(x))
+    }
 
-            for i in 0..Secondary::dof() {
-                overrides.insert(
-                    format!("v_{}_{}", extra_bases[i].axis, x.name),
-                    *extra_selector(x).velocity.get(i),
-                );
-            }
+    /// Statically checks equations (with the builtin functions and constants available) without
+    /// constructing an engine. See [`validate`].
+    pub fn validate_equations(expressions: &[&str]) -> Vec<Diagnostic> {
+        validate(
+            expressions,
+            &builtin::functions(),
+            &builtin::constants(),
+            Self::is_provided,
+            Self::is_queried,
+        )
+    }
 
-            overrides.insert(format!("m_{}", x.name), x.properties.mass);
-            overrides.insert(format!("I_{}", x.name), x.properties.moi);
-        }
+    fn eval_impl(
+        form: Symbol,
+        bases: &[Option<Symbol>],
+        env: &Environment,
+        values: &mut Bindings,
+    ) -> EngineResult<Option<Vec<f64>>> {
+        let mut result = Vec::new();
 
-        for i in 0..Primary::dof() {
-            // Work on basis i (set it to 1).
-            overrides.insert(bases[i].name.to_string(), 1.0);
+        for basis in bases {
+            // Work on this basis (set it to 1).
+            state::set(values, *basis, 1.0);
 
-            let form = format!("{}_{}", var, owner);
-            let part = match env.evaluate(form.clone(), overrides.clone()) {
+            let part = match env.evaluate_symbol(form, values) {
                 Ok(x) => Ok(Some(x)),
                 Err(e) => match e.kind.clone() {
                     ErrorKind::UnsatisfiedVariable(x) => {
-                        if x == form {
+                        if x == env.name(form) {
                             Ok(None)
                         } else {
                             Err(e)
@@ -193,7 +1103,7 @@ impl<S: Space + Clone> Engine<S> {
                 },
             };
             result.push(part);
-            overrides.insert(bases[i].name.to_string(), 0.0);
+            state::set(values, *basis, 0.0);
         }
 
         result
@@ -201,6 +1111,34 @@ impl<S: Space + Clone> Engine<S> {
             .collect::<EngineResult<Option<Vec<f64>>>>()
     }
 
+    // v_b - v_a at `point`, each body's velocity there being v_linear + (w x r)
+    fn relative_velocity(a: &Body<S>, b: &Body<S>, point: &S::Linear) -> S::Linear {
+        let at = |x: &Body<S>| {
+            let r = point.plus(&x.linear.displacement.scale(-1.0));
+            x.linear.velocity.plus(&S::cross_both(&x.angular.velocity, &r))
+        };
+
+        at(b).plus(&at(a).scale(-1.0))
+    }
+
+    // 1/m_a + 1/m_b + ((r_a x d)^2) / I_a + ((r_b x d)^2) / I_b, how much a unit impulse along the
+    // unit `direction` at `point` changes the relative velocity there
+    fn inverse_effective_mass(
+        a: &Body<S>,
+        b: &Body<S>,
+        point: &S::Linear,
+        direction: &S::Linear,
+    ) -> f64 {
+        let inverse = |x: &Body<S>| {
+            let r = point.plus(&x.linear.displacement.scale(-1.0));
+            let angular = S::cross_linear(&r, direction).magnitude().powi(2);
+
+            x.properties.inverse_mass() + angular * x.properties.inverse_moi()
+        };
+
+        inverse(a) + inverse(b)
+    }
+
     fn calculate_impulse(
         a: &Body<S>,
         b: &Body<S>,
@@ -210,276 +1148,1707 @@ impl<S: Space + Clone> Engine<S> {
         let n = collision.normal.unit();
 
         // --- 1. Calculate Relative Velocity (v_rel) ---
-        // v_point = v_linear + (w x r)
-        let pen_a = &collision.point.plus(&a.linear.displacement.scale(-1.0));
-        let pen_b = &collision.point.plus(&b.linear.displacement.scale(-1.0));
-
-        let v_a_point = a
-            .linear
-            .velocity
-            .plus(&S::cross_both(&a.angular.velocity, pen_a));
-
-        let v_b_point = b
-            .linear
-            .velocity
-            .plus(&S::cross_both(&b.angular.velocity, pen_b));
-
-        // v_rel = v_b - v_a
-        let v_rel = v_b_point.plus(&v_a_point.scale(-1.0));
-        let v_rel_n = v_rel.dot(&n);
+        let v_rel_n = Self::relative_velocity(a, b, &collision.point).dot(&n);
 
         // If separating, no impulse
         if v_rel_n > 0.0 {
             return 0.0;
         }
 
-        // --- 2. Inverse Mass Terms ---
-        // 1/m_a + 1/m_b
-        let inv_mass_sum = (if a.properties.mass > 0.0 {
-            1.0 / a.properties.mass
-        } else {
-            0.0
-        }) + (if b.properties.mass > 0.0 {
-            1.0 / b.properties.mass
-        } else {
-            0.0
-        });
+        // --- 2. Inverse Mass and Angular Terms ---
+        let denominator = Self::inverse_effective_mass(a, b, &collision.point, &n);
 
-        // --- 3. Angular Terms ---
-        // ((r x n)^2) / I
-        let rxn_a = S::cross_linear(&pen_a, &n);
-        let rxn_b = S::cross_linear(&pen_b, &n);
+        // --- 3. Final Calculation ---
+        // j = -(1 + e) * v_rel_norm / (1/m + 1/m + ang_a + ang_b)
+        let numerator = -(1.0 + restitution) * v_rel_n;
 
-        let ang_a = if a.properties.moi > 0.0 {
-            rxn_a.magnitude().powi(2) / a.properties.moi
-        } else {
+        if denominator == 0.0 {
             0.0
+        } else {
+            numerator / denominator
+        }
+    }
+
+    // A row along the normal at every point of a contact, which can only push, followed by one
+    // against the bodies sliding past each other there if they have any friction. `base` is where
+    // the rows will start among those solved together.
+    fn contact_rows(bodies: &[Body<S>], contact: &Contact<S>, base: usize) -> Vec<Row<S>> {
+        let (i, j) = contact.pair;
+        let (a, b) = (&bodies[i], &bodies[j]);
+        let collision = &contact.collision;
+        let n = collision.normal.unit();
+        let points = match collision.points.is_empty() {
+            true => std::slice::from_ref(&collision.point),
+            false => &collision.points[..],
         };
 
-        let ang_b = if b.properties.moi > 0.0 {
-            rxn_b.magnitude().powi(2) / b.properties.moi
-        } else {
-            0.0
-        };
+        let mut rows = Vec::new();
+        // How much impulse each point looks to need along the normal, before any is applied
+        let mut pressing = Vec::new();
+        for (k, point) in points.iter().enumerate() {
+            // Points coming together bounce apart, those already separating are left to
+            let speed = Self::relative_velocity(a, b, point).dot(&n);
+            let target = (-contact.restitution * speed).max(0.0);
+            let limit = Limit::Bounds(0.0, f64::INFINITY);
+            let mut row = Row::point((i, j), [a, b], point, &n, target, limit);
+            row.impulse = contact.warm.as_ref().and_then(|x| x.get(k)).map_or(0.0, |x| x.max(0.0));
+            rows.push(row);
+
+            let denominator = Self::inverse_effective_mass(a, b, point, &n);
+            pressing.push(match denominator {
+                0.0 => 0.0,
+                _ => (target - speed).max(0.0) / denominator,
+            });
+        }
+
+        let (static_friction, kinetic_friction) = contact.friction;
+        if (static_friction, kinetic_friction) == (0.0, 0.0) {
+            return rows;
+        }
+        for (k, point) in points.iter().enumerate() {
+            let v_rel = Self::relative_velocity(a, b, point);
+            let sliding = v_rel.plus(&n.scale(-v_rel.dot(&n)));
+            let speed = sliding.magnitude();
+            if speed == 0.0 {
+                continue;
+            }
+
+            // Static friction holds the point if stopping it takes no more than its coefficient
+            // times the impulse pressing it together, otherwise it slides against kinetic
+            let tangent = sliding.scale(1.0 / speed);
+            let stopping = speed / Self::inverse_effective_mass(a, b, point, &tangent);
+            let coefficient = match stopping <= static_friction * pressing[k] {
+                true => static_friction,
+                false => kinetic_friction,
+            };
+            let limit = Limit::Friction {
+                row: base + k,
+                coefficient,
+            };
+            rows.push(Row::point((i, j), [a, b], point, &tangent, 0.0, limit));
+        }
+
+        rows
+    }
+
+    // The friction coefficients of a contact between `a` and `b`, each body's (its material's if
+    // it has one) combined
+    fn friction(
+        a: &Body<S>,
+        b: &Body<S>,
+        settings: Option<&ContactOverride>,
+        combine: Combine,
+    ) -> (f64, f64) {
+        if let Some(friction) = settings.and_then(|x| x.friction) {
+            return (friction, friction);
+        }
+
+        let coefficients = |x: &Body<S>| match x.properties.material {
+            Some(material) => (material.friction, material.friction),
+            None => (x.properties.static_friction, x.properties.kinetic_friction),
+        };
+        let ((static_a, kinetic_a), (static_b, kinetic_b)) = (coefficients(a), coefficients(b));
+        (
+            combine.apply(static_a, static_b),
+            combine.apply(kinetic_a, kinetic_b),
+        )
+    }
+
+    // The restitution of a contact between `a` and `b`, each body's material's (or the engine's
+    // `restitution` for a body without one) combined
+    fn restitution(
+        a: &Body<S>,
+        b: &Body<S>,
+        settings: Option<&ContactOverride>,
+        restitution: f64,
+        combine: Combine,
+    ) -> f64 {
+        if let Some(restitution) = settings.and_then(|x| x.restitution) {
+            return restitution;
+        }
+
+        let of = |x: &Body<S>| x.properties.material.map_or(restitution, |x| x.restitution);
+        combine.apply(of(a), of(b))
+    }
+
+    // Slows a body's spin at a contact by a torque of `coefficient` times the normal impulse times
+    // the distance to the contact point, without ever reversing it.
+    fn apply_rolling_resistance(
+        body: &mut Body<S>,
+        collision: &Collision<S>,
+        impulse: f64,
+        coefficient: f64,
+    ) {
+        let omega = body.angular.velocity.magnitude();
+        let inverse_moi = body.properties.inverse_moi();
+        if inverse_moi == 0.0 || omega == 0.0 {
+            return;
+        }
+
+        let radius = collision
+            .point
+            .plus(&body.linear.displacement.scale(-1.0))
+            .magnitude();
+        let slowed = (coefficient * impulse.abs() * radius * inverse_moi).min(omega);
+
+        body.angular.velocity = body.angular.velocity.scale(1.0 - slowed / omega);
+    }
+
+    // Applies an impulse along `direction` (the collision normal, or the surface for friction) at
+    // `point` to one body
+    fn push(body: &mut Body<S>, point: &S::Linear, direction: &S::Linear, impulse: f64) {
+        let (inverse_mass, inverse_moi) =
+            (body.properties.inverse_mass(), body.properties.inverse_moi());
+        if inverse_mass == 0.0 {
+            return;
+        }
+
+        let delta_v = &direction.scale(impulse * inverse_mass);
+        body.linear.velocity = body.linear.velocity.plus(delta_v);
+
+        if inverse_moi > 0.0 {
+            let delta_omega = S::cross_linear(
+                &point.plus(&body.linear.displacement.scale(-1.0)),
+                &direction.scale(impulse * inverse_moi),
+            );
+            body.angular.velocity = body.angular.velocity.plus(&delta_omega);
+        }
+    }
+
+    // Solves the rows of every constraint and contact together, then lets each constraint correct
+    // the drift in positions, returning the impulse along the normal at every point of each
+    // contact. A sleeping body is only woken by a body it's joined to moving, until then rows of
+    // constraints with it in are left out.
+    fn solve_constraints(&mut self, contacts: &[Contact<S>]) -> Vec<Vec<f64>> {
+        let mut rows = Vec::new();
+        // Whether each constraint had any rows left out
+        let mut skipped = Vec::new();
+        for constraint in &mut self.constraints {
+            let base = rows.len();
+            let mut asleep = false;
+            for mut row in constraint.rows(&self.bodies) {
+                if row.a >= self.bodies.len() || row.b >= self.bodies.len() {
+                    continue;
+                }
+                let (a, b) = (&self.bodies[row.a], &self.bodies[row.b]);
+                self.sleepers.wake_joined(a, b);
+                if self.sleepers.is_asleep(a) || self.sleepers.is_asleep(b) {
+                    asleep = true;
+                    row.limit = Limit::Bounds(0.0, 0.0);
+                }
+                if let Limit::Friction { row, .. } = &mut row.limit {
+                    *row += base;
+                }
+                rows.push(row);
+            }
+            skipped.push(asleep);
+        }
+
+        // Where each contact's rows start, those along the normal first
+        let mut starts = Vec::new();
+        for contact in contacts {
+            starts.push(rows.len());
+            let contact_rows = Self::contact_rows(&self.bodies, contact, rows.len());
+            rows.extend(contact_rows);
+        }
+        if rows.is_empty() {
+            return Vec::new();
+        }
+
+        log::trace!(target: trace::SOLVER, "solving {} constraint rows", rows.len());
+        let mut bodies = self.bodies.iter_mut().collect::<Vec<_>>();
+        constraint::solve(&mut bodies, &mut rows, self.constraint_iterations);
+        for (constraint, skipped) in self.constraints.iter_mut().zip(skipped) {
+            if !skipped {
+                constraint.correct(&mut self.bodies);
+            }
+        }
+
+        contacts
+            .iter()
+            .zip(starts)
+            .map(|(contact, start)| {
+                let points = contact.collision.points.len().max(1);
+                rows[start..start + points].iter().map(|x| x.impulse).collect()
+            })
+            .collect()
+    }
+
+    // Resolves the tick's contacts again from the bottom up, each against the body below it as
+    // though that one were static. Bodies higher up can then only be pushed away from what they
+    // rest on, never push it further down.
+    fn propagate_shock(&mut self, up: &S::Linear, contacts: &[Contact<S>]) {
+        let height = |x: usize| self.bodies[x].linear.displacement.dot(up);
+
+        let mut order = (0..contacts.len()).collect::<Vec<_>>();
+        order.sort_by(|x, y| {
+            let ((a, b), (c, d)) = (contacts[*x].pair, contacts[*y].pair);
+            height(a).min(height(b)).total_cmp(&height(c).min(height(d)))
+        });
+
+        for x in order {
+            let ((i, j), collision) = (contacts[x].pair, &contacts[x].collision);
+            let (left, right) = self.bodies.split_at_mut(j);
+            let (a, b) = (&mut left[i], &mut right[0]);
+            let a_below = a.linear.displacement.dot(up) < b.linear.displacement.dot(up);
+
+            let below = if a_below { &mut *a } else { &mut *b };
+            let kind = std::mem::replace(&mut below.properties.kind, BodyKind::Static);
+            let impulse = Self::calculate_impulse(a, b, collision, 0.0);
+            let below = if a_below { &mut *a } else { &mut *b };
+            below.properties.kind = kind;
+
+            if a_below {
+                Self::push(b, &collision.point, &collision.normal, impulse);
+            } else {
+                Self::push(a, &collision.point, &collision.normal, -impulse);
+            }
+        }
+    }
+
+    // Whether nothing can move the body and it isn't moving itself
+    fn immovable(body: &Body<S>) -> bool {
+        body.properties.kind == BodyKind::Static
+            || (body.properties.inverse_mass() == 0.0
+                && body.linear.velocity.magnitude() == 0.0
+                && body.angular.velocity.magnitude() == 0.0)
+    }
+
+    fn contact_override<'a>(
+        overrides: &'a BTreeMap<(String, String), ContactOverride>,
+        a: &Body<S>,
+        b: &Body<S>,
+    ) -> Option<&'a ContactOverride> {
+        if overrides.is_empty() {
+            return None;
+        }
+
+        let (a, b) = (a.properties.tag.as_ref()?, b.properties.tag.as_ref()?);
+        overrides.get(&ordered(a, b))
+    }
+
+    // Moves a pair of bodies out of each other along the normal by a fraction of however much
+    // deeper than the slop they overlap. It's done with an impulse at the contact point split
+    // between them like any other, so the lighter moves further, one hit off-centre turns and one
+    // contacts can't move doesn't move at all, but it moves them rather than changing their
+    // velocities so they don't fly apart afterwards. Returns the number of corrections made.
+    fn apply_correction(
+        a: &mut Body<S>,
+        b: &mut Body<S>,
+        collision: &Collision<S>,
+        correction: &Correction,
+    ) -> usize {
+        let depth = collision.depth - correction.slop;
+        if depth <= 0.0 {
+            return 0;
+        }
+
+        let normal = collision.normal.unit();
+        let denominator = Self::inverse_effective_mass(a, b, &collision.point, &normal);
+        if denominator == 0.0 {
+            return 0;
+        }
+
+        let impulse = correction.factor * depth / denominator;
+        Self::shift(a, &collision.point, &normal, -impulse);
+        Self::shift(b, &collision.point, &normal, impulse);
+
+        1
+    }
+
+    // Moves and turns a body as far as an impulse along `direction` at `point` would over a unit
+    // of time, for positional correction
+    fn shift(body: &mut Body<S>, point: &S::Linear, direction: &S::Linear, impulse: f64) {
+        let (inverse_mass, inverse_moi) =
+            (body.properties.inverse_mass(), body.properties.inverse_moi());
+        if inverse_mass == 0.0 {
+            return;
+        }
+
+        let delta_s = direction.scale(impulse * inverse_mass);
+        let delta_q = S::cross_linear(
+            &point.plus(&body.linear.displacement.scale(-1.0)),
+            &direction.scale(impulse * inverse_moi),
+        );
+        body.linear.displacement = body.linear.displacement.plus(&delta_s);
+        body.angular.displacement = S::turn(&body.angular.displacement, &delta_q);
+    }
+
+    /// Advances the simulation by `delta_t`, unless the engine is paused in which case nothing
+    /// happens and an empty tick is returned.
+    ///
+    /// Fails without changing anything if a body was left invalid (see [`Body::validate`]) since
+    /// the last tick. Anything else going wrong is only found partway through: a body made invalid
+    /// by a scheduled command, the listener or a behavior fails the tick once they have all run,
+    /// and an error solving the equations of a later substep leaves the earlier ones applied. The
+    /// forces, torques and impulses applied for the tick are used up either way.
+    pub fn tick(&mut self) -> EngineResult<Tick<S>> {
+        if self.paused {
+            return Ok(Tick::empty());
+        }
+
+        self.advance()
+    }
+
+    /// Advances the simulation by exactly one tick, regardless of whether it is paused.
+    pub fn step(&mut self) -> EngineResult<Tick<S>> {
+        self.advance()
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    fn advance(&mut self) -> EngineResult<Tick<S>> {
+        // Before anything is run that can't be undone
+        if !self.validated {
+            self.validate()?;
+        }
+
+        for command in self.schedule.due(self.ticks, self.time) {
+            self.run(command);
+        }
+
+        let ctx = TickContext {
+            time: self.time,
+            delta_t: self.delta_t,
+            ticks: self.ticks,
+        };
+        if let Some(listener) = &mut self.listener {
+            listener.pre_tick(&mut self.bodies, &ctx);
+            for body in &self.bodies {
+                body.validate()?;
+            }
+        }
+        for attached in &mut self.behaviors {
+            if let Some(body) = self.bodies.iter_mut().find(|x| x.id == Some(attached.body)) {
+                attached.behavior.update(body, &ctx);
+                body.validate()?;
+            }
+        }
+
+        // Commands may have added or removed bodies
+        if !self.validated {
+            self.validate()?;
+        }
+
+        // Torques and forces act over the whole tick, however many substeps it's split into. Any
+        // left for bodies removed since they were applied are dropped
+        let (mut torques, mut forces) = (Vec::new(), Vec::new());
+        let mut applied = std::mem::take(&mut self.torques);
+        let mut impulses = std::mem::take(&mut self.impulses);
+        let mut angular_impulses = std::mem::take(&mut self.angular_impulses);
+        for (i, body) in self.bodies.iter_mut().enumerate() {
+            if let Some(x) = body.id.and_then(|x| applied.remove(&x)) {
+                accumulate(&mut torques, i, x);
+            }
+            let force = std::mem::replace(&mut body.force, S::Linear::empty());
+            let torque = std::mem::replace(&mut body.torque, S::Angular::empty());
+            if force.magnitude() > 0.0 {
+                accumulate(&mut forces, i, force);
+            }
+            if torque.magnitude() > 0.0 {
+                accumulate(&mut torques, i, torque);
+            }
+            // An impulse is the force giving the same change in momentum over the tick
+            if let Some(x) = body.id.and_then(|x| impulses.remove(&x)) {
+                accumulate(&mut forces, i, x.scale(1.0 / self.delta_t));
+            }
+            if let Some(x) = body.id.and_then(|x| angular_impulses.remove(&x)) {
+                accumulate(&mut torques, i, x.scale(1.0 / self.delta_t));
+            }
+        }
+        let delta_t = self.delta_t;
+        self.delta_t = delta_t / self.substeps as f64;
+
+        let mut tick = Tick::empty();
+        let mut result = Ok(());
+        for _ in 0..self.substeps {
+            match self.substep(&torques, &forces) {
+                Ok(x) => tick.merge(x),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+        self.delta_t = delta_t;
+        // Behaviors, listeners and paths are free to move static bodies too
+        self.statics.refresh(&self.bodies);
+        result?;
+
+        self.ticks += 1;
+        if let Some(listener) = &mut self.listener {
+            listener.post_tick(&self.bodies, &tick);
+        }
+        Ok(tick)
+    }
+
+    // Integrates every body over `delta_t` (a whole tick, or one substep of it) and resolves the
+    // contacts they end up in, with `torques` and `forces` applied from outside on top
+    fn substep(
+        &mut self,
+        torques: &[Option<S::Angular>],
+        forces: &[Option<S::Linear>],
+    ) -> EngineResult<Tick<S>> {
+        let mut tick = Tick::empty();
+        let ctx = TickContext {
+            time: self.time,
+            delta_t: self.delta_t,
+            ticks: self.ticks,
+        };
+        let evaluation_start = self.now();
+        self.env.take_root_iterations();
+
+        // Everything is evaluated against the state at the start of the tick. Only the scalars the
+        // evaluator reads are copied, rather than every body along with its shape.
+        let mut prev_state = std::mem::take(&mut self.state);
+        prev_state.update(&self.bodies, &self.env);
+
+        macro_rules! update_state {
+            ($form:expr, $bases:expr, $vec_kind:ty, $state:expr, $advance:expr, $skip_accel:expr, $external:expr, $inertia:expr, $history:expr, $keys:expr, $values:expr) => {{
+                // Accelerations applied from outside (eg. torques) act over this tick on top of
+                // whatever the equations give, unless the equations set the velocity outright
+                let integrated = $form.is_dynamic() || $form == StateForm::Free;
+                if let (true, Some(external)) = (integrated, $external) {
+                    $state.velocity = $state.velocity.plus(&external.scale(self.delta_t));
+                }
+
+                // Forces give no acceleration to a body without mass, which nothing can move
+                let per_inertia = |force: Vec<f64>| {
+                    force
+                        .into_iter()
+                        .map(|x| if $inertia > 0.0 { x / $inertia } else { 0.0 })
+                        .collect::<Vec<_>>()
+                };
+                let evaluate = |values: &mut Bindings| -> EngineResult<Option<Vec<f64>>> {
+                    let mut eval = |x| Engine::<S>::eval_impl(x, $bases, &self.env, values);
+                    Ok(match $form {
+                        StateForm::Free => None,
+                        StateForm::Displacement(x, _)
+                        | StateForm::Velocity(x)
+                        | StateForm::Acceleration(x) => eval(x)?,
+                        StateForm::Force(f) => eval(f)?.map(per_inertia),
+                        StateForm::AccelerationAndForce(a, f) => {
+                            match (eval(a)?, eval(f)?.map(per_inertia)) {
+                                (Some(a), Some(f)) => {
+                                    Some(a.iter().zip(f).map(|(a, f)| a + f).collect())
+                                }
+                                (a, f) => a.or(f),
+                            }
+                        }
+                    })
+                };
+                let values = evaluate(&mut *$values)?;
+                let mut eval =
+                    |x| Engine::<S>::eval_impl(x, $bases, &self.env, &mut *$values);
+
+                let history: Option<$vec_kind> = $history.take();
+                match ($form, values) {
+                    (StateForm::Displacement(_, velocity), Some(s)) => {
+                        let old_displacement = $state.displacement.clone();
+                        $state.displacement = <$vec_kind>::new(s)?;
+
+                        // An equation for the velocity knows better than any estimate
+                        $state.velocity = match velocity.map(&mut eval).transpose()?.flatten() {
+                            Some(v) => <$vec_kind>::new(v)?,
+                            None => <$vec_kind>::new(
+                                (0..<$vec_kind>::dof())
+                                    .map(|i| {
+                                        let previous = *old_displacement.get(i);
+                                        let next = *$state.displacement.get(i);
+                                        match (self.reconstruction, &history) {
+                                            (Reconstruction::CentralDifference, Some(before)) => {
+                                                central_difference(
+                                                    self.delta_t,
+                                                    *before.get(i),
+                                                    previous,
+                                                    next,
+                                                )
+                                            }
+                                            _ => backward_difference(self.delta_t, previous, next),
+                                        }
+                                    })
+                                    .collect::<Vec<f64>>(),
+                            )?,
+                        };
+                        *$history = Some(old_displacement);
+                    }
+                    (StateForm::Velocity(_), Some(v)) => {
+                        let v = <$vec_kind>::new(v)?;
+
+                        $state.velocity = v.clone();
+                        $state.displacement =
+                            $advance(&$state.displacement, &v.scale(self.delta_t));
+                    }
+                    (form, Some(a))
+                        if form.is_dynamic()
+                            && !$skip_accel
+                            && self.integrator == Integrator::RungeKutta4 =>
+                    {
+                        let components = |x: &$vec_kind| {
+                            (0..<$vec_kind>::dof()).map(|i| *x.get(i)).collect::<Vec<f64>>()
+                        };
+                        // The table is left as it was, for the bodies evaluated after this one
+                        let symbols: &[(Option<Symbol>, Option<Symbol>)] = $keys;
+                        let values = &mut *$values;
+                        let get =
+                            |x: &Option<Symbol>| x.and_then(|x| values.get(x)).unwrap_or(0.0);
+                        let saved =
+                            symbols.iter().map(|(s, v)| (get(s), get(v))).collect::<Vec<_>>();
+
+                        let stepped = runge_kutta4(
+                            self.delta_t,
+                            &components(&$state.displacement),
+                            &components(&$state.velocity),
+                            &a,
+                            |s, v| {
+                                for (i, (x, y)) in symbols.iter().enumerate() {
+                                    state::set(values, *x, s[i]);
+                                    state::set(values, *y, v[i]);
+                                }
+                                Ok(evaluate(values)?.unwrap_or_else(|| vec![0.0; s.len()]))
+                            },
+                        );
+                        for ((x, y), (s, v)) in symbols.iter().zip(saved) {
+                            state::set(values, *x, s);
+                            state::set(values, *y, v);
+                        }
+
+                        let (s, v): (Vec<f64>, Vec<f64>) = stepped?;
+                        let moved = <$vec_kind>::new(s)?.plus(&$state.displacement.scale(-1.0));
+                        $state.displacement = $advance(&$state.displacement, &moved);
+                        $state.velocity = <$vec_kind>::new(v)?;
+                        $state.acceleration = <$vec_kind>::new(a)?;
+                    }
+                    (form, Some(a)) if form.is_dynamic() && !$skip_accel => {
+                        $state.velocity = <$vec_kind>::new(
+                            a.iter()
+                                .enumerate()
+                                .map(|(i, component)| {
+                                    leapfrog_velocity(
+                                        self.delta_t,
+                                        *$state.velocity.get(i),
+                                        *$state.acceleration.get(i),
+                                        *component,
+                                    )
+                                })
+                                .collect::<Vec<f64>>(),
+                        )?;
+
+                        let moved = <$vec_kind>::new(
+                            a.iter()
+                                .enumerate()
+                                .map(|(i, component)| {
+                                    leapfrog_displacement(
+                                        self.delta_t,
+                                        0.0,
+                                        *$state.velocity.get(i),
+                                        *component,
+                                    )
+                                })
+                                .collect::<Vec<f64>>(),
+                        )?;
+                        $state.displacement = $advance(&$state.displacement, &moved);
+
+                        $state.acceleration = <$vec_kind>::new(a)?;
+                    }
+                    _ => {
+                        // If no definitions are present, just integrate velocity
+                        $state.displacement =
+                            $advance(&$state.displacement, &$state.velocity.scale(self.delta_t));
+                    }
+                }
+            }};
+        }
+
+        // Anything pushing a body wakes it
+        for (i, _) in forces.iter().enumerate().filter(|(_, x)| x.is_some()) {
+            self.sleepers.wake(&self.bodies[i]);
+        }
+        let mut forces = forces.to_vec();
+        for noisy in &mut self.noise {
+            let force = noisy.sample(&mut self.rng, self.delta_t);
+            if let Some(i) = index_by_id(&self.bodies, noisy.body) {
+                accumulate(&mut forces, i, force);
+            }
+        }
+        // Springs push on both their bodies, as they were at the start of the tick
+        let mut spring_torques: Vec<Option<S::Angular>> = Vec::new();
+        for spring in &self.springs {
+            let Some(ends) = spring.forces(&self.bodies) else {
+                continue;
+            };
+            let [(a, ..), (b, ..)] = &ends;
+            self.sleepers.wake_joined(&self.bodies[*a], &self.bodies[*b]);
+
+            for (body, force, torque) in ends {
+                accumulate(&mut forces, body, force);
+                accumulate(&mut spring_torques, body, torque);
+            }
+        }
+        for (i, body) in self.bodies.iter().enumerate() {
+            if let Some(force) = zones::force(&self.fields, body) {
+                accumulate(&mut forces, i, force);
+            }
+        }
+        if let Some(density) = self.drag {
+            for (i, body) in self.bodies.iter().enumerate() {
+                if let Some(force) = drag::force(body, density, self.delta_t) {
+                    accumulate(&mut forces, i, force);
+                }
+            }
+        }
+        for (i, _) in torques.iter().enumerate().filter(|(_, x)| x.is_some()) {
+            self.sleepers.wake(&self.bodies[i]);
+        }
+        // Bodies are integrated independently of one another, against the state of the last tick
+        let integrate = |i: usize,
+                         body: &mut Body<S>,
+                         keys: &BodyKeys,
+                         history: &mut History<S>,
+                         values: &mut Bindings|
+         -> EngineResult<()> {
+            if self.sleepers.is_asleep(body) {
+                return Ok(());
+            }
+            if body.properties.kind == BodyKind::Static {
+                body.linear.velocity = S::Linear::empty();
+                body.angular.velocity = S::Angular::empty();
+                return Ok(());
+            }
+
+            // Only dynamic bodies are moved by anything but their equations
+            let (inverse_mass, inverse_moi) =
+                (body.properties.inverse_mass(), body.properties.inverse_moi());
+            let force = forces
+                .get(i)
+                .cloned()
+                .flatten()
+                .filter(|_| inverse_mass > 0.0)
+                .map(|x| x.scale(inverse_mass));
+            let zone = zones::acceleration(&self.zones, body)
+                .filter(|_| body.properties.kind == BodyKind::Dynamic);
+            let a = match (zone, force) {
+                (Some(a), Some(b)) => Some(a.plus(&b)),
+                (a, b) => a.or(b),
+            };
+            update_state!(
+                keys.linear_form,
+                &prev_state.linear_bases,
+                S::Linear,
+                body.linear,
+                |s: &S::Linear, by: &S::Linear| s.plus(by),
+                false,
+                a,
+                body.properties.mass,
+                &mut history.0,
+                &keys.linear,
+                values
+            );
+
+            let torque = match (torques.get(i).cloned().flatten(), spring_torques.get(i)) {
+                (Some(a), Some(Some(b))) => Some(a.plus(b)),
+                (a, b) => a.or(b.cloned().flatten()),
+            };
+            let alpha = torque
+                .filter(|_| inverse_moi > 0.0)
+                .map(|x| x.scale(inverse_moi));
+            update_state!(
+                keys.angular_form,
+                &prev_state.angular_bases,
+                S::Angular,
+                body.angular,
+                S::turn,
+                false,
+                alpha,
+                body.properties.moi,
+                &mut history.1,
+                &keys.angular,
+                values
+            );
+            Ok(())
+        };
+
+        let mut history = std::mem::take(&mut self.history);
+        history.resize(self.bodies.len(), (None, None));
+        #[cfg(feature = "rayon")]
+        if self.parallel {
+            use rayon::prelude::*;
+
+            // Evaluating a body only changes the table for a moment, so each thread works on a
+            // copy of its own
+            self.bodies
+                .par_iter_mut()
+                .zip(prev_state.keys.par_iter())
+                .zip(history.par_iter_mut())
+                .enumerate()
+                .try_for_each_init(
+                    || prev_state.values.clone(),
+                    |values, (i, ((body, keys), history))| {
+                        integrate(i, body, keys, history, values)
+                    },
+                )?;
+        }
+        #[cfg(feature = "rayon")]
+        let serial = !self.parallel;
+        #[cfg(not(feature = "rayon"))]
+        let serial = true;
+        if serial {
+            let bodies = self.bodies.iter_mut().zip(&prev_state.keys).zip(&mut history);
+            for (i, ((body, keys), history)) in bodies.enumerate() {
+                integrate(i, body, keys, history, &mut prev_state.values)?;
+            }
+        }
+        self.history = history;
+        log::debug!(
+            target: trace::INTEGRATOR,
+            "integrated {} bodies over {}s",
+            self.bodies.len(),
+            self.delta_t
+        );
+
+        self.state = prev_state;
+        self.time += self.delta_t;
+
+        for follower in &self.followers {
+            let Some(body) = self.bodies.iter_mut().find(|x| x.id == Some(follower.body)) else {
+                continue;
+            };
+
+            // The equations have already moved it, so its velocity comes from the path alone
+            let t = self.time - follower.start;
+            let (previous, next) = (
+                follower.path.position(t - self.delta_t),
+                follower.path.position(t),
+            );
+            body.linear.velocity = next
+                .plus(&previous.scale(-1.0))
+                .scale(1.0 / self.delta_t);
+            body.linear.acceleration = S::Linear::empty();
+            body.linear.displacement = next;
+        }
+        if let Some(listener) = &mut self.listener {
+            listener.post_integrate(&self.bodies, &ctx);
+        }
+        tick.stats.evaluation_time = self.now() - evaluation_start;
+        tick.stats.root_iterations = self.env.take_root_iterations();
+
+        let collision_start = self.now();
+        // Pairs that touched this tick, only tracked while anything is subscribed
+        let mut touched = Vec::new();
+        let observing = !self.observers.is_empty();
+        let candidates = self.broadphase.pairs(&self.bodies);
+        let order = self.pairs.order(&self.bodies, candidates);
+        #[cfg(feature = "rayon")]
+        let mut lookahead = match self.parallel {
+            true => Lookahead::test(&self.bodies, &order, &self.pairs, self.collider.as_ref()),
+            false => Lookahead::none(),
+        };
+        #[cfg(not(feature = "rayon"))]
+        let mut lookahead = Lookahead::none();
+        let mut contacts = Vec::new();
+        for (n, (i, j)) in order.into_iter().enumerate() {
+            let (a, b) = (&self.bodies[i], &self.bodies[j]);
+
+            // Nothing changes between a sleeping body and another or something that can't move
+            let asleep = [a, b].map(|x| self.sleepers.is_asleep(x));
+            let still = |asleep: bool, x: &Body<S>| asleep || Self::immovable(x);
+            if (asleep[0] || asleep[1]) && still(asleep[0], a) && still(asleep[1], b) {
+                continue;
+            }
+            if !self.pairs.needs_test(a, b) {
+                continue;
+            }
+
+            tick.stats.pairs_tested += 1;
+            let collision = lookahead
+                .take(n)
+                .unwrap_or_else(|| self.collider.collide(a, b));
+            self.pairs.record(a, b, collision.clone());
+
+            if let Some(collision) = collision {
+                log::trace!(
+                    target: trace::COLLIDER,
+                    "{} and {} overlap by {} at {} points",
+                    a.name,
+                    b.name,
+                    collision.depth,
+                    collision.points.len().max(1)
+                );
+                // Something moving into a sleeping body wakes it, otherwise it's held still
+                match asleep {
+                    [true, _] if self.sleepers.disturbs(b) => self.sleepers.wake(a),
+                    [_, true] if self.sleepers.disturbs(a) => self.sleepers.wake(b),
+                    _ => {}
+                }
+
+                tick.collisions.push(collision.point.clone());
+                tick.contacts.push(collision.clone());
+                tick.stats.contacts_resolved += 1;
+                tick.stats.max_penetration = tick.stats.max_penetration.max(collision.depth.abs());
+
+                let settings = Self::contact_override(&self.overrides, a, b);
+                let (restitution_combine, friction_combine) = self.combine;
+                contacts.push(Contact {
+                    pair: (i, j),
+                    restitution: Self::restitution(
+                        a,
+                        b,
+                        settings,
+                        self.restitution,
+                        restitution_combine,
+                    ),
+                    friction: Self::friction(a, b, settings, friction_combine),
+                    rolling_resistance: settings
+                        .and_then(|x| x.rolling_resistance)
+                        .unwrap_or(self.rolling_resistance),
+                    warm: self.warm_starting.then(|| self.pairs.impulses(a, b).to_vec()),
+                    collision,
+                });
+                if observing {
+                    touched.push((i, j));
+                }
+            }
+        }
+
+        // Bodies still asleep once everything touching them has been found are held still
+        let held = self
+            .bodies
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, x)| self.sleepers.is_asleep(x))
+            .map(|(i, x)| (i, std::mem::replace(&mut x.properties.kind, BodyKind::Static)))
+            .collect::<Vec<_>>();
+        let watch_energy = self.monitor.energy_tolerance.is_some();
+        let energy = |bodies: &[Body<S>], (i, j): (usize, usize)| {
+            kinetic_energy(&bodies[i]) + kinetic_energy(&bodies[j])
+        };
+        // Kinetic energy of both bodies of each contact, only tracked while watching for it
+        let before = match watch_energy {
+            true => contacts.iter().map(|x| energy(&self.bodies, x.pair)).collect(),
+            false => Vec::new(),
+        };
+
+        let impulses = self.solve_constraints(&contacts);
+        for (contact, impulses) in contacts.iter().zip(impulses) {
+            let (i, j) = contact.pair;
+            let (left, right) = self.bodies.split_at_mut(j);
+            let (a, b) = (&mut left[i], &mut right[0]);
+            let collision = &contact.collision;
+
+            let impulse = impulses.iter().sum::<f64>();
+            log::trace!(
+                target: trace::SOLVER,
+                "impulse of {} between {} and {}",
+                impulse,
+                a.name,
+                b.name
+            );
+            if self.warm_starting {
+                self.pairs.set_impulses(a, b, impulses);
+            }
+            // Every body has an id once validated
+            if let (Some(x), Some(y)) = (a.id, b.id) {
+                let event = CollisionEvent {
+                    a: x,
+                    b: y,
+                    point: collision.point.clone(),
+                    normal: collision.normal.clone(),
+                    depth: collision.depth,
+                    impulse: impulse.abs(),
+                };
+                if let Some(listener) = &mut self.listener {
+                    listener.on_collision(&event);
+                }
+                tick.collision_events.push(event);
+            }
+            if contact.rolling_resistance > 0.0 {
+                Self::apply_rolling_resistance(a, collision, impulse, contact.rolling_resistance);
+                Self::apply_rolling_resistance(b, collision, impulse, contact.rolling_resistance);
+            }
+        }
+        // Kinetic energy each contact's pair gained
+        let gains = contacts
+            .iter()
+            .zip(before)
+            .map(|(x, before)| (x.pair, energy(&self.bodies, x.pair) - before))
+            .collect::<Vec<_>>();
+
+        for contact in &contacts {
+            let (i, j) = contact.pair;
+            let (left, right) = self.bodies.split_at_mut(j);
+            tick.stats.correction_iterations += Self::apply_correction(
+                &mut left[i],
+                &mut right[0],
+                &contact.collision,
+                &self.correction,
+            );
+        }
+        if let Some(up) = self.shock_propagation.clone() {
+            self.propagate_shock(&up, &contacts);
+        }
+        for (x, kind) in held {
+            self.bodies[x].properties.kind = kind;
+        }
+        tick.stats.collision_time = self.now() - collision_start;
+        log::debug!(
+            target: trace::COLLIDER,
+            "{} pairs tested, {} in contact",
+            tick.stats.pairs_tested,
+            tick.stats.contacts_resolved
+        );
+
+        if self.sleepers.settings.is_some() {
+            let eligible = (0..self.bodies.len())
+                .map(|i| {
+                    let (keys, id) = (&self.state.keys[i], self.bodies[i].id);
+                    let integrated = |x: StateForm| x.is_dynamic() || x == StateForm::Free;
+                    // Immovable bodies stay awake, and don't wake anything
+                    self.bodies[i].properties.inverse_mass() > 0.0
+                        && integrated(keys.linear_form)
+                        && integrated(keys.angular_form)
+                        && forces.get(i).is_none_or(|x| x.is_none())
+                        && self.behaviors.iter().all(|x| Some(x.body) != id)
+                        && self.followers.iter().all(|x| Some(x.body) != id)
+                })
+                .collect::<Vec<_>>();
+            self.sleepers.update(
+                &mut self.bodies,
+                self.substeps,
+                |i| eligible[i],
+                &mut tick.events,
+            );
+            tick.stats.bodies_asleep = self.sleepers.asleep();
+        }
+
+        self.monitor.check(&self.bodies, &mut tick.events);
+        self.monitor.check_energy(&self.bodies, &gains, &mut tick.events);
+        if observing {
+            self.observers.notify(&self.bodies, &touched, &tick.events);
+        }
+
+        Ok(tick)
+    }
+
+    /// Checks every body can be simulated and that no two share a name, which is otherwise done
+    /// before the next tick. Names are checked because a body's variables (`x_A`, `m_A`) are named
+    /// after it, so two bodies named `A` would overwrite each other's. Unnamed bodies have no
+    /// variables, so any number of them is fine.
+    pub fn validate(&mut self) -> EngineResult<()> {
+        check_delta_t(self.delta_t)?;
+        let mut names = HashSet::new();
+        for body in &self.bodies {
+            body.validate()?;
+            if !body.name.is_empty() && !names.insert(body.name.as_str()) {
+                return Err(Error::new(ErrorKind::DuplicateName(body.name.clone())));
+            }
+        }
+
+        self.assign_ids();
+        // Bodies may have been added, removed or moved by hand since the positions were kept
+        self.history.clear();
+        self.sleepers.retain(&self.bodies);
+        self.collider.retain(&self.bodies);
+        self.statics.refresh(&self.bodies);
+        self.validated = true;
+        Ok(())
+    }
+
+    // Gives every body without an id (added through bodies_mut) or with the same id as one before
+    // it (a clone of it) a new one
+    fn assign_ids(&mut self) {
+        self.next_id = self
+            .bodies
+            .iter()
+            .filter_map(|x| x.id)
+            .map(|BodyId(x)| x + 1)
+            .fold(self.next_id, u64::max);
+
+        let mut ids = HashSet::new();
+        for body in self.bodies.iter_mut() {
+            if body.id.is_none_or(|x| !ids.insert(x)) {
+                body.id = Some(BodyId(self.next_id));
+                ids.insert(BodyId(self.next_id));
+                self.next_id += 1;
+            }
+        }
+    }
+
+    /// Adds a body after all the others, returning its id. It is validated before the next tick.
+    pub fn add_body(&mut self, mut body: Body<S>) -> BodyId {
+        let id = BodyId(self.next_id);
+        self.next_id += 1;
+
+        body.id = Some(id);
+        self.bodies.push(body);
+        self.validated = false;
+        id
+    }
+
+    /// `base` if no body has that name yet, otherwise `base` followed by the first letters (`B`, `C`,
+    /// ..., `BA`) that make it unique. For naming bodies spawned while running, since names can't
+    /// contain digits.
+    pub fn unique_name(&self, base: &str) -> String {
+        let taken = |name: &str| self.bodies.iter().any(|x| x.name == name);
+        if !taken(base) {
+            return base.to_string();
+        }
+
+        (1..)
+            .map(|i| scenarios::label(base, i))
+            .find(|x| !taken(x))
+            .unwrap()
+    }
+
+    pub fn collider(&self) -> &dyn Collide<S> {
+        self.collider.as_ref()
+    }
+
+    pub fn bodies(&self) -> &Vec<Body<S>> {
+        &self.bodies
+    }
+
+    /// The bodies, for changing between ticks. They are validated again before the next tick.
+    pub fn bodies_mut(&mut self) -> &mut Vec<Body<S>> {
+        self.validated = false;
+        &mut self.bodies
+    }
+
+    pub fn body(&self, name: &str) -> Option<&Body<S>> {
+        self.bodies.iter().find(|x| x.name == name)
+    }
+
+    /// The body named `name`, for changing between ticks. It is validated again before the next
+    /// tick.
+    pub fn body_mut(&mut self, name: &str) -> Option<&mut Body<S>> {
+        self.validated = false;
+        self.bodies.iter_mut().find(|x| x.name == name)
+    }
+
+    /// Index of the body named `name` in [`Engine::bodies`].
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.bodies.iter().position(|x| x.name == name)
+    }
+
+    /// Id of the body named `name`, as taken by the per-body methods (`follow_path`, `attach`,
+    /// ...). A body added through `bodies_mut` has none until the engine is next validated.
+    pub fn id_of(&self, name: &str) -> Option<BodyId> {
+        self.body(name)?.id
+    }
+
+    pub fn get(&self, id: BodyId) -> Option<&Body<S>> {
+        self.bodies.iter().find(|x| x.id == Some(id))
+    }
+
+    /// The body with this id, for changing between ticks. It is validated again before the next
+    /// tick.
+    pub fn get_mut(&mut self, id: BodyId) -> Option<&mut Body<S>> {
+        self.validated = false;
+        self.bodies.iter_mut().find(|x| x.id == Some(id))
+    }
+
+    /// Index of the body with this id, which changes as bodies before it are removed.
+    pub fn index_of_id(&self, id: BodyId) -> Option<usize> {
+        index_by_id(&self.bodies, id)
+    }
+
+    /// Ids of the bodies whose shape contains the world-space `point`, in the order of the bodies,
+    /// eg. to find the body under the cursor. Shapes are the outlines the collider sees, turned the
+    /// same way, so an ellipse is its polygon and a body the collider gives no vertices contains
+    /// nothing.
+    pub fn query_point(&self, point: &S::Linear) -> Vec<BodyId> {
+        // Outlines are planar
+        let planar = |x: &S::Linear| Column::vector([*x.get(0), *x.get(1)]);
+
+        self.near(point, point)
+            .into_iter()
+            .filter(|&i| {
+                let body = &self.bodies[i];
+                let outline = self.collider.vertices(body).iter().map(planar).collect::<Vec<_>>();
+                let local = point.plus(&body.linear.displacement.scale(-1.0));
+                geometry::contains(&outline, &planar(&local))
+            })
+            .filter_map(|i| self.bodies[i].id)
+            .collect()
+    }
+
+    /// The nearest body the ray from `origin` in `direction` meets within `length`, as for
+    /// `query_point` by the outline the collider sees, in the plane of the first two axes. A ray
+    /// starting inside a body meets it straight away. Static bodies are kept in a tree (see
+    /// [`Bvh`]), so only those near the ray are tested however many there are.
+    pub fn raycast(
+        &self,
+        origin: &S::Linear,
+        direction: &S::Linear,
+        length: f64,
+    ) -> Option<RayHit<S>> {
+        if direction.magnitude() == 0.0 {
+            return None;
+        }
+        let direction = direction.unit();
+        let planar = |x: &S::Linear| Column::vector([*x.get(0), *x.get(1)]);
+
+        let hit = |i: usize| {
+            let body = &self.bodies[i];
+            let x = &body.linear.displacement;
+            // Missing its box is far cheaper to find out than missing its outline
+            let reach = reach(&body.shape);
+            if reach.is_finite() {
+                let reach = S::Linear::new(vec![reach; S::Linear::dof()]).unwrap();
+                let (min, max) = (x.plus(&reach.scale(-1.0)), x.plus(&reach));
+                bvh::entry::<S>(&min, &max, origin, &direction, length)?;
+            }
+
+            let outline = self.collider.vertices(body).iter().map(planar).collect::<Vec<_>>();
+            let local = planar(&origin.plus(&x.scale(-1.0)));
+            if geometry::contains(&outline, &local) {
+                return Some(0.0);
+            }
+            outline
+                .iter()
+                .zip(outline.iter().cycle().skip(1))
+                .filter_map(|(a, b)| {
+                    let edge = b.plus(&a.scale(-1.0));
+                    let (t, s) = geometry::intersect_lines(&local, &planar(&direction), a, &edge)?;
+                    (t >= 0.0 && (0.0..=1.0).contains(&s)).then_some(t)
+                })
+                .min_by(f64::total_cmp)
+        };
+        let nearest = |hits: &mut dyn Iterator<Item = (usize, f64)>| {
+            hits.filter(|(_, x)| *x <= length)
+                .min_by(|(i, x), (j, y)| x.total_cmp(y).then(i.cmp(j)))
+        };
+
+        let (body, distance) = match self.validated {
+            true => {
+                let statics = self.statics.cast(origin, &direction, length, &hit);
+                let mut moving = (0..self.bodies.len())
+                    .filter(|&i| !self.statics.contains(i))
+                    .filter_map(|i| hit(i).map(|x| (i, x)));
+                nearest(&mut statics.into_iter().chain(&mut moving))
+            }
+            // Bodies may have been moved since the tree was built
+            false => nearest(&mut (0..self.bodies.len()).filter_map(|i| hit(i).map(|x| (i, x)))),
+        }?;
+
+        Some(RayHit {
+            body: self.bodies[body].id?,
+            point: origin.plus(&direction.scale(distance)),
+            distance,
+        })
+    }
+
+    // Indices of the bodies whose boxes (as the broadphase bounds them) overlap the box from `min`
+    // to `max`, in order. Static bodies are looked up in the tree while it's up to date
+    fn near(&self, min: &S::Linear, max: &S::Linear) -> Vec<usize> {
+        let overlaps = |i: &usize| {
+            let (x, reach) = (&self.bodies[*i].linear.displacement, reach(&self.bodies[*i].shape));
+            (0..S::Linear::dof())
+                .all(|k| x.get(k) - reach <= *max.get(k) && *min.get(k) <= x.get(k) + reach)
+        };
+        if !self.validated {
+            return (0..self.bodies.len()).filter(overlaps).collect();
+        }
+
+        let mut near = self.statics.overlapping(min, max);
+        near.extend((0..self.bodies.len()).filter(|i| !self.statics.contains(*i) && overlaps(i)));
+        near.sort_unstable();
+        near
+    }
+
+    /// Every body `predicate` accepts, with its id. Bodies added through `bodies_mut` are left out
+    /// until they're given ids, when the engine is next validated.
+    pub fn bodies_where<'a>(
+        &'a self,
+        predicate: impl Fn(&Body<S>) -> bool + 'a,
+    ) -> impl Iterator<Item = (BodyId, &'a Body<S>)> + 'a {
+        self.bodies
+            .iter()
+            .filter(move |x| predicate(x))
+            .filter_map(|x| Some((x.id?, x)))
+    }
+
+    /// Every body tagged `tag`, with its id.
+    pub fn tagged<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> impl Iterator<Item = (BodyId, &'a Body<S>)> + 'a {
+        self.bodies_where(move |x| x.properties.tag.as_deref() == Some(tag))
+    }
+
+    /// Every body whose centroid is inside `region`, with its id.
+    pub fn within<'a>(
+        &'a self,
+        region: &'a Region<S>,
+    ) -> impl Iterator<Item = (BodyId, &'a Body<S>)> + 'a {
+        // A body's centroid is inside its box, which has to overlap the region's
+        let near = match region.bounds() {
+            Some((min, max)) => self.near(&min, &max),
+            None => (0..self.bodies.len()).collect(),
+        };
+        near.into_iter()
+            .map(|i| &self.bodies[i])
+            .filter(move |x| region.contains(&x.linear.displacement))
+            .filter_map(|x| Some((x.id?, x)))
+    }
+
+    pub fn environment(&self) -> &Environment {
+        &self.env
+    }
+
+    /// The environment used to evaluate equations, for changing it between ticks (eg. turning
+    /// gravity on with [`Environment::set_constant`]). Body state and contact caches are kept.
+    pub fn environment_mut(&mut self) -> &mut Environment {
+        // Whatever changes may affect which names the environment mentions
+        self.state = StateTable::default();
+        &mut self.env
+    }
+
+    /// Replaces the environment used to evaluate equations from the next tick on, keeping all body
+    /// state and contact caches.
+    pub fn set_environment(&mut self, env: Environment) {
+        self.env = env;
+        self.state = StateTable::default();
+    }
+
+    /// A hash of the name, position, velocity and acceleration (linear and angular) of every body,
+    /// in order. It only depends on those values, not the platform or compiler, so two engines
+    /// (eg. lockstep peers) that hash the same after a tick haven't diverged. Zeros of either sign
+    /// hash the same, as do all NaNs.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv::default();
+        for body in &self.bodies {
+            hash.write(body.name.as_bytes());
+            // Separates the name from the values, so names can't run into them
+            hash.write(&[0]);
+
+            for x in [
+                &body.linear.displacement,
+                &body.linear.velocity,
+                &body.linear.acceleration,
+            ] {
+                (0..S::Linear::dof()).for_each(|i| hash.write_f64(*x.get(i)));
+            }
+            for x in [
+                &body.angular.displacement,
+                &body.angular.velocity,
+                &body.angular.acceleration,
+            ] {
+                (0..S::Angular::dof()).for_each(|i| hash.write_f64(*x.get(i)));
+            }
+        }
+
+        hash.0
+    }
+
+    /// The bodies and clock as they are now, to save or to go back to with `restore`.
+    pub fn snapshot(&self) -> EngineState<S> {
+        EngineState {
+            bodies: self.bodies.clone(),
+            time: self.time,
+            ticks: self.ticks,
+            delta_t: self.delta_t,
+        }
+    }
+
+    /// Puts the bodies and clock back as they were in `state`, taken from this engine or one set
+    /// up the same way. Torques still to be applied and how long bodies have rested for are
+    /// forgotten, and the bodies are validated again before the next tick.
+    pub fn restore(&mut self, state: EngineState<S>) {
+        self.bodies = state.bodies;
+        self.time = state.time;
+        self.ticks = state.ticks;
+        self.delta_t = state.delta_t;
+        self.torques.clear();
+        self.impulses.clear();
+        self.angular_impulses.clear();
+        // The bodies may have been anywhere since their pairs were last tested
+        self.pairs.forget();
+        self.validated = false;
+    }
+
+    /// Evaluates an arbitrary expression (eg. `0.5*m_A*(v_x_A^2+v_y_A^2)`) against the environment,
+    /// with the current state of every body injected just as it is during a tick.
+    pub fn probe(&self, expression: &str) -> EngineResult<f64> {
+        self.env
+            .evaluate_expression(expression, &state::named_values(&self.bodies))
+    }
+
+    /// Applies `torque` to the body with id `body` over the next tick, on top of anything its
+    /// equations give. Torques applied before the same tick add up.
+    pub fn apply_torque(&mut self, body: BodyId, torque: S::Angular) {
+        accumulate_by(&mut self.torques, body, torque);
+    }
+
+    /// Changes the angular velocity of the body with id `body` by `impulse` over its moment of
+    /// inertia during the next tick, given as the torque giving the same change over the tick like
+    /// [`Engine::apply_impulse`]. Angular impulses applied before the same tick add up, and only
+    /// turn dynamic bodies with a moment of inertia.
+    pub fn apply_angular_impulse(&mut self, body: BodyId, impulse: S::Angular) {
+        let Some(i) = self.index_of_id(body) else {
+            return;
+        };
+        // Asleep, it would be skipped over
+        self.sleepers.wake(&self.bodies[i]);
+
+        accumulate_by(&mut self.angular_impulses, body, impulse);
+    }
+
+    /// Changes the velocity of the body with id `id` by `impulse` over its mass during the next
+    /// tick, and its angular velocity by the torque the impulse gives at the world-space `point`
+    /// (as it is now). It's given as the force (and torque) giving the same change over the tick,
+    /// so it goes wherever a force would. Impulses applied before the same tick add up, and only
+    /// move dynamic bodies.
+    pub fn apply_impulse(&mut self, id: BodyId, impulse: S::Linear, point: &S::Linear) {
+        let Some(i) = self.index_of_id(id) else {
+            return;
+        };
+        // Asleep, it would be skipped over
+        self.sleepers.wake(&self.bodies[i]);
+
+        let arm = point.plus(&self.bodies[i].linear.displacement.scale(-1.0));
+        accumulate_by(&mut self.angular_impulses, id, S::cross_linear(&arm, &impulse));
+        accumulate_by(&mut self.impulses, id, impulse);
+    }
+
+    /// The contact between the bodies with ids `a` and `b` the last time they were tested against
+    /// each other, if they were touching, seen from `a`.
+    pub fn contact(&self, a: BodyId, b: BodyId) -> Option<Collision<S>> {
+        match self.pairs.contact(a, b) {
+            Some(x) => Some(x.clone()),
+            None => self.pairs.contact(b, a).cloned().map(Collision::swapped),
+        }
+    }
+
+    /// Puts bodies to sleep once they've been resting (moving slower than `settings` allows) for
+    /// long enough, after which their equations aren't evaluated and contacts between sleeping
+    /// bodies aren't tested until something moves into them or a torque or impulse is applied.
+    /// Only bodies moved by their accelerations (or forces) and contacts fall asleep. `None` (the
+    /// default) keeps every body awake.
+    pub fn set_sleeping(&mut self, settings: Option<Sleeping>) {
+        self.sleepers.settings = settings;
+        if settings.is_none() {
+            self.sleepers.clear();
+        }
+    }
+
+    /// Whether the body with id `body` is asleep.
+    pub fn is_asleep(&self, body: BodyId) -> bool {
+        self.get(body).is_some_and(|x| self.sleepers.is_asleep(x))
+    }
+
+    /// Wakes the body with id `body`, if it's asleep.
+    pub fn wake(&mut self, body: BodyId) {
+        if let Some(i) = self.index_of_id(body) {
+            self.sleepers.wake(&self.bodies[i]);
+        }
+    }
+
+    /// Replaces how pairs of bodies that might be touching are found, sweep-and-prune by default.
+    /// A `SpatialHash` is quicker for many small bodies of about the same size.
+    pub fn set_broadphase(&mut self, broadphase: Box<dyn Broadphase<S>>) {
+        self.broadphase = broadphase;
+    }
+
+    /// Sets how far (in world units) two separated bodies may move relative to each other before
+    /// they are tested for collision again. Zero, the default, tests every pair every time.
+    pub fn set_pair_tolerance(&mut self, tolerance: f64) {
+        self.pairs.tolerance = tolerance;
+    }
+
+    /// Uses `settings` for contacts between bodies tagged `a` and bodies tagged `b` (in either
+    /// order, and `a` may equal `b`). `None` removes the override.
+    pub fn set_contact_override(&mut self, a: &str, b: &str, settings: Option<ContactOverride>) {
+        let key = ordered(a, b);
+
+        match settings {
+            Some(x) => self.overrides.insert(key, x),
+            None => self.overrides.remove(&key),
+        };
+    }
+
+    /// Slows the spin of bodies at every contact by a torque of `coefficient` times the normal
+    /// force times the distance to the contact, so things rolling along the floor come to rest.
+    /// Zero (the default) turns it off.
+    pub fn set_rolling_resistance(&mut self, coefficient: f64) {
+        self.rolling_resistance = coefficient;
+    }
+
+    /// After resolving contacts, resolves them once more from the bottom up (along `up`) with each
+    /// lower body treated as static, which stops tall stacks sagging and bursting apart under their
+    /// own weight. `None` turns it off.
+    pub fn set_shock_propagation(&mut self, up: Option<S::Linear>) {
+        self.shock_propagation = up;
+    }
+
+    /// Starts resolving each contact from the impulses it was resolved with last tick, matched up
+    /// by the faces touching and where along them, rather than from nothing. Resting contacts need
+    /// about the same impulse every tick, so stacks hold up with far less sinking and jitter. Off
+    /// by default.
+    pub fn set_warm_starting(&mut self, on: bool) {
+        self.warm_starting = on;
+    }
+
+    /// Changes how far bodies left overlapping by their contacts are pushed back apart each tick.
+    pub fn set_correction(&mut self, correction: Correction) {
+        self.correction = correction;
+    }
+
+    /// Changes how the restitution and friction of the two bodies in a contact are combined. By
+    /// default the bouncier body's restitution is used, so a ball bounces off anything, and the
+    /// geometric mean of their friction, so a frictionless body slides over anything. Bodies
+    /// without a material have the engine's restitution and their own friction coefficients.
+    pub fn set_combine(&mut self, restitution: Combine, friction: Combine) {
+        self.combine = (restitution, friction);
+    }
+
+    /// Queues `command` to run just before the tick `at` refers to, or before the next tick if that
+    /// has already passed.
+    pub fn schedule(&mut self, at: At, command: Command<S>) {
+        self.schedule.push(at, command);
+    }
+
+    /// How many scheduled commands have yet to run.
+    pub fn scheduled(&self) -> usize {
+        self.schedule.len()
+    }
+
+    fn run(&mut self, command: Command<S>) {
+        match command {
+            Command::Impulse { body, impulse } => {
+                if let Some(centroid) = self.get(body).map(|x| x.linear.displacement.clone()) {
+                    self.apply_impulse(body, impulse, &centroid);
+                }
+            }
+            Command::AngularImpulse { body, impulse } => {
+                self.apply_angular_impulse(body, impulse);
+            }
+            Command::SetProperties { body, properties } => {
+                if let Some(body) = self.bodies.iter_mut().find(|x| x.id == Some(body)) {
+                    body.properties = properties;
+                    self.validated = false;
+                }
+            }
+            Command::Spawn(body) => {
+                self.add_body(body);
+            }
+        }
+    }
+
+    /// Pushes the body with id `body` around with a random force from now on, replacing any it
+    /// had. `None` stops it.
+    pub fn set_noise(&mut self, body: BodyId, noise: Option<Noise>) {
+        self.noise.retain(|x| x.body != body);
+        if let Some(noise) = noise {
+            self.noise.push(Noisy::new(body, noise));
+        }
+    }
+
+    /// Restarts the random numbers behind every noise force from `seed`, so a run with the same
+    /// seed, bodies and noise is the same every time. Engines start with a seed of 0.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
 
-        // --- 4. Final Calculation ---
-        // j = -(1 + e) * v_rel_norm / (1/m + 1/m + ang_a + ang_b)
-        let numerator = -(1.0 + restitution) * v_rel_n;
-        let denominator = inv_mass_sum + ang_a + ang_b;
+    /// Runs `behavior` on the body with id `body` at the start of every tick, after any body it
+    /// was already attached to.
+    pub fn attach(&mut self, body: BodyId, behavior: Box<dyn Behavior<S>>) {
+        self.behaviors.push(Attached { body, behavior });
+    }
 
-        if denominator == 0.0 {
-            0.0
-        } else {
-            numerator / denominator
-        }
+    /// Removes every behavior attached to the body with id `body`.
+    pub fn detach(&mut self, body: BodyId) {
+        self.behaviors.retain(|x| x.body != body);
     }
 
-    // Just applying impulse to A.
-    fn apply_impulse(a: &mut Body<S>, b: &mut Body<S>, collision: Collision<S>, restitution: f64) {
-        let impulse = Self::calculate_impulse(a, b, &collision, restitution);
+    /// Calls `callback` at the end of every tick with what changed about the bodies `subject`
+    /// names: each time one ends up more than `epsilon` from where it was last reported, and each
+    /// contact it was in. Returns an id for [`Engine::unsubscribe`].
+    pub fn subscribe(&mut self, subject: Subject, epsilon: f64, callback: Callback<S>) -> usize {
+        self.observers.subscribe(subject, epsilon, callback)
+    }
 
-        println!("impulse = {:?}", impulse);
-        macro_rules! do_apply {
-            ($body:ident, $inv:literal) => {{
-                let delta_v = &collision
-                    .normal
-                    .scale($inv * impulse / $body.properties.mass);
+    pub fn unsubscribe(&mut self, id: usize) {
+        self.observers.unsubscribe(id);
+    }
 
-                let delta_omega = S::cross_linear(
-                    &collision.point.plus(&$body.linear.displacement.scale(-1.0)),
-                    &collision
-                        .normal
-                        .scale($inv * impulse / $body.properties.moi),
-                );
+    /// Calls `listener` at each stage of every tick from now on, in place of any listener set
+    /// before. See [`EngineListener`] for when each of its methods is called.
+    pub fn set_listener(&mut self, listener: Box<dyn EngineListener<S>>) {
+        self.listener = Some(listener);
+    }
 
-                $body.linear.velocity = $body.linear.velocity.plus(&delta_v);
-                $body.angular.velocity = $body.angular.velocity.plus(&delta_omega);
-            }};
-        }
+    /// Stops calling the listener, returning it.
+    pub fn take_listener(&mut self) -> Option<Box<dyn EngineListener<S>>> {
+        self.listener.take()
+    }
 
-        do_apply!(a, -1.0);
-        do_apply!(b, 1.0);
-        // do_apply!(b, -1.0);
+    /// Moves the body with id `body` along `path` from now on, starting from the path's beginning,
+    /// instead of by its linear equations. Replaces any path it was already following.
+    pub fn follow_path(&mut self, body: BodyId, path: Path<S>) {
+        self.stop_following(body);
+        self.followers.push(Follower {
+            body,
+            path,
+            start: self.time,
+        });
     }
 
-    // Push A away
-    fn apply_correction(
-        collider: &Box<dyn Collide<S>>,
-        a: &mut Body<S>,
-        b: &Body<S>,
-        mut collision: Collision<S>,
-    ) {
-        // Start with the inverse of the distance between them. (Small distance, large correction, etc.) and account for mass.
-        for _ in 0..CORRECTIVE_FRAMES {
-            let correction = collision.depth / a.properties.mass;
+    /// Hands the body with id `body` back to its equations, keeping its current velocity.
+    pub fn stop_following(&mut self, body: BodyId) {
+        self.followers.retain(|x| x.body != body);
+    }
 
-            a.linear.displacement = a
-                .linear
-                .displacement
-                .plus(&collision.normal.scale(-correction));
+    /// Adds a region with its own gravity, applied on top of the equations of every body whose
+    /// centroid is inside it.
+    pub fn add_gravity_zone(&mut self, zone: GravityZone<S>) {
+        self.zones.push(zone);
+    }
 
-            if let Some(c) = collider.collide(a, b) {
-                collision = c;
-            } else {
-                break;
-            }
-        }
+    pub fn gravity_zones_mut(&mut self) -> &mut Vec<GravityZone<S>> {
+        &mut self.zones
     }
 
-    pub fn tick(&mut self) -> EngineResult<Tick<S>> {
-        let prev_state = self.bodies.clone();
-
-        macro_rules! eval {
-            ($var:literal, $name:expr, Linear) => {
-                Engine::<S>::eval_impl::<S::Linear, S::Angular>(
-                    $var,
-                    $name.clone(),
-                    S::LINEAR_BASES,
-                    |x| &x.linear,
-                    S::ANGULAR_BASES,
-                    |x| &x.angular,
-                    &self.env,
-                    &(prev_state),
-                )
-            };
-            ($var:literal, $name:expr, Angular) => {
-                Engine::<S>::eval_impl::<S::Angular, S::Linear>(
-                    $var,
-                    $name.clone(),
-                    S::ANGULAR_BASES,
-                    |x| &x.angular,
-                    S::LINEAR_BASES,
-                    |x| &x.linear,
-                    &self.env,
-                    &(prev_state),
-                )
-            };
-        }
+    /// Adds a region with a force (wind, a pull towards or push from a point, a vortex) on every
+    /// body whose centroid is inside it, sampled at the start of every tick before integrating.
+    /// Unlike a gravity zone's acceleration, it moves heavier bodies less.
+    pub fn add_force_field(&mut self, field: ForceField<S>) {
+        self.fields.push(field);
+    }
 
-        macro_rules! update_state {
-            (
-                $body:expr,
-                $displacement:literal,
-                $velocity:literal,
-                $acceleration:literal,
-                $vec_kind:ty,
-                $state:expr,
-                $kind:ident,
-                $skip_accel:expr
-            ) => {{
-                let s = eval!($displacement, $body.name, $kind)?;
-
-                if let Some(s) = s {
-                    let old_displacement = $state.displacement.clone();
-                    $state.displacement = <$vec_kind>::new(s)?;
-
-                    $state.velocity = $state
-                        .displacement
-                        .plus(&old_displacement.scale(-1.0))
-                        .scale(self.delta_t);
-                } else {
-                    let v = eval!($velocity, $body.name, $kind)?;
-
-                    if let Some(v) = v {
-                        let v = <$vec_kind>::new(v)?;
+    pub fn force_fields_mut(&mut self) -> &mut Vec<ForceField<S>> {
+        &mut self.fields
+    }
 
-                        $state.velocity = v.clone();
-                        $state.displacement = $state.displacement.plus(&v.scale(self.delta_t));
-                    } else {
-                        let a = eval!($acceleration, $body.name, $kind)?;
+    /// Slows every body with a drag coefficient (`BodyProperties::drag`) by quadratic drag through
+    /// a still fluid of `density`, `1/2 * density * C_d * A * v^2` against its velocity. `A` is how
+    /// wide the body's shape is across the way it's moving. `None` (the default) turns drag off.
+    pub fn set_drag(&mut self, density: Option<f64>) {
+        self.drag = density;
+    }
 
-                        if let Some(a) = a
-                            && !$skip_accel
-                        {
-                            $state.velocity = <$vec_kind>::new(
-                                a.iter()
-                                    .enumerate()
-                                    .map(|(i, component)| {
-                                        leapfrog_velocity(
-                                            self.delta_t,
-                                            *$state.velocity.get(i),
-                                            *$state.acceleration.get(i),
-                                            *component,
-                                        )
-                                    })
-                                    .collect::<Vec<f64>>(),
-                            )?;
-
-                            $state.displacement = <$vec_kind>::new(
-                                a.iter()
-                                    .enumerate()
-                                    .map(|(i, component)| {
-                                        leapfrog_displacement(
-                                            self.delta_t,
-                                            *$state.displacement.get(i),
-                                            *$state.velocity.get(i),
-                                            *component,
-                                        )
-                                    })
-                                    .collect::<Vec<f64>>(),
-                            )?;
+    /// Connects two bodies with a damped spring, applied as a force at both its anchors every tick
+    /// on top of whatever their equations give. Only dynamic bodies are moved by it.
+    pub fn add_spring(&mut self, spring: Spring<S>) {
+        self.springs.push(spring);
+    }
 
-                            $state.acceleration = <$vec_kind>::new(a)?;
-                        } else {
-                            // If no definitions are present, just integrate velocity
-                            $state.displacement = $state
-                                .displacement
-                                .plus(&($state.velocity).scale(self.delta_t));
-                        }
-                    }
-                }
-            }};
-        }
+    pub fn springs_mut(&mut self) -> &mut Vec<Spring<S>> {
+        &mut self.springs
+    }
 
-        for body in self.bodies.iter_mut() {
-            update_state!(body, "s", "v", "a", S::Linear, body.linear, Linear, false);
+    /// Meshes two bodies together like gears from now on, at the angles they're at now. Their
+    /// angular velocities are brought into mesh every tick after integrating, so one driven by its
+    /// equations (or a kinematic one) turns the other.
+    pub fn add_gear(&mut self, mut gear: Gear<S>)
+    where
+        S: 'static,
+    {
+        gear.hold(&self.bodies);
+        self.add_constraint(Box::new(gear));
+    }
 
-            update_state!(
-                body,
-                "q",
-                "omega",
-                "alpha",
-                S::Angular,
-                body.angular,
-                Angular,
-                false
-            );
-        }
+    /// Adds a joint whose rows are solved together with those of every other constraint and every
+    /// contact each tick, once bodies have been integrated.
+    pub fn add_constraint(&mut self, constraint: Box<dyn Constraint<S>>) {
+        self.constraints.push(constraint);
+    }
 
-        let mut tick = Tick {
-            collisions: Vec::<S::Linear>::new(),
-        };
+    /// Passes over the rows of every constraint each tick, 8 by default. More make long chains of
+    /// joints stiffer.
+    pub fn set_constraint_iterations(&mut self, iterations: usize) {
+        self.constraint_iterations = iterations;
+    }
 
-        for i in 0..self.bodies.len() {
-            let (left, right) = self.bodies.split_at_mut(i + 1);
-            let a = &mut left[i];
+    /// Reports [`Event::LeftBounds`] whenever a body leaves the box between the `min` and `max`
+    /// corners. `None` stops checking.
+    pub fn set_bounds(&mut self, bounds: Option<(S::Linear, S::Linear)>) {
+        self.monitor.bounds = bounds;
+    }
 
-            for j in 0..right.len() {
-                let b = &mut right[j];
+    /// Reports [`Event::SpeedExceeded`] whenever a body starts moving faster than `limit`. `None`
+    /// stops checking.
+    pub fn set_speed_limit(&mut self, limit: Option<f64>) {
+        self.monitor.speed_limit = limit;
+    }
 
-                if let Some(collision) = self.collider.collide(a, b) {
-                    tick.collisions.push(collision.point.clone());
+    /// Reports [`Event::EnergyGained`] whenever resolving contacts adds more than `tolerance`
+    /// kinetic energy in a tick, which a correct solver never does. `None` stops checking.
+    pub fn set_energy_tolerance(&mut self, tolerance: Option<f64>) {
+        self.monitor.energy_tolerance = tolerance;
+    }
 
-                    Self::apply_impulse(a, b, collision.clone(), self.restitution);
+    /// Number of ticks run so far.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
 
-                    Self::apply_correction(&self.collider, a, b, collision.clone());
-                    Self::apply_correction(&self.collider, b, a, collision);
+    /// Simulated time elapsed, in seconds.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
 
-                    // TODO: Friction
-                }
-            }
+    pub fn delta_t(&self) -> f64 {
+        self.delta_t
+    }
+
+    /// Changes the time step used by subsequent ticks (including velocity reconstruction for
+    /// bodies driven by displacement equations), allowing slow motion and fast-forward. Fails
+    /// without changing it unless `delta_t` is positive and finite.
+    pub fn set_delta_t(&mut self, delta_t: f64) -> EngineResult<()> {
+        check_delta_t(delta_t)?;
+        // Positions from before the change aren't evenly spaced with those after it
+        if delta_t != self.delta_t {
+            self.history.clear();
         }
+        self.delta_t = delta_t;
+        Ok(())
+    }
 
-        Ok(tick)
+    /// Splits every tick into `substeps` steps of integrating and resolving contacts, each over an
+    /// equal part of `delta_t`. Stiff equations (strong springs) and fast bodies stay stable
+    /// for the extra work, without the caller ticking any more often. Scheduled commands,
+    /// behaviors and torques still apply once per tick, and the tick reports what every substep
+    /// found. 1 by default, and 0 is taken as 1.
+    pub fn set_substeps(&mut self, substeps: u32) {
+        self.substeps = substeps.max(1);
     }
 
-    pub fn bodies(&self) -> &Vec<Body<S>> {
-        &self.bodies
+    /// Changes how the velocity of bodies moved by displacement equations (`s_A`, `q_A`) is
+    /// estimated, which is what their collisions use. A velocity equation (`v_A`, `omega_A`) for the
+    /// same body takes precedence over either estimate.
+    pub fn set_reconstruction(&mut self, reconstruction: Reconstruction) {
+        self.reconstruction = reconstruction;
+    }
+
+    /// Changes how bodies driven by an acceleration or force equation (`a_A`, `F_A`, `alpha_A`,
+    /// `tau_A`) are integrated. With `Integrator::RungeKutta4` each body's equations are evaluated
+    /// again at three points within the tick, moving only that body, while every other body stays
+    /// where it was at the start of the tick.
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.integrator = integrator;
+    }
+}
+
+fn ordered(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
     }
 }
 
 pub mod spaces {
-    use crate::math::{Column, Vector};
+    use crate::math::{Column, Vector, geometry};
     use crate::{Basis, Space};
 
     #[derive(Debug, Clone)]
@@ -512,13 +2881,170 @@ pub mod spaces {
         fn cross_linear(a: &Self::Linear, b: &Self::Linear) -> Self::Angular {
             Column::vector([(a.get(0) * b.get(1)) - (a.get(1) * b.get(0))])
         }
+
+        fn rotate(rotation: &Self::Angular, v: &Self::Linear) -> Self::Linear {
+            let (sin, cos) = rotation.get(0).sin_cos();
+            Column::vector([
+                cos * v.get(0) - sin * v.get(1),
+                sin * v.get(0) + cos * v.get(1),
+            ])
+        }
+
+        fn validate_outline(points: &[Self::Linear]) -> Result<(), String> {
+            if points.len() < 3 {
+                return Err("a manifold needs at least 3 points".to_string());
+            }
+            if geometry::signed_area(points) <= 0.0 {
+                return Err("manifold points must run counterclockwise".to_string());
+            }
+
+            // Every corner has to turn the same way, and turn enough that it's a corner at all
+            let n = points.len();
+            let edge = |i: usize| points[(i + 1) % n].plus(&points[i % n].scale(-1.0));
+            for i in 0..n {
+                let (a, b) = (edge(i), edge(i + 1));
+                let turn = *Self::cross_linear(&a, &b).get(0);
+
+                if turn < 0.0 {
+                    return Err(format!("manifold is not convex at point {}", (i + 1) % n));
+                }
+                if turn <= 1e-9 * a.magnitude() * b.magnitude() {
+                    return Err(format!(
+                        "manifold point {} is in line with its neighbours",
+                        (i + 1) % n
+                    ));
+                }
+            }
+
+            // Turning the same way a star still can, by going round more than once
+            for i in 0..n {
+                for j in i + 2..n {
+                    if (j + 1) % n == i {
+                        continue;
+                    }
+                    if geometry::intersect_segments(&points[i], &edge(i), &points[j], &edge(j))
+                        .is_some()
+                    {
+                        return Err(format!("manifold edges {} and {} cross", i, j));
+                    }
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Three dimensions. Rotations are rotation vectors, their direction the axis and their length
+    /// the angle in radians, so angular displacement, velocity and acceleration all have the same
+    /// three components (`theta_x_A`, `v_theta_x_A` and so on) along `hati`, `hatj` and `hatk`.
+    /// Angular velocity is in world axes, and each step's turn is composed with the body's
+    /// rotation as quaternions, so a body whose axis changes ends up facing the right way. A body
+    /// turning about a fixed axis keeps adding up its angle past a whole turn, as in 2D. Moments
+    /// of inertia are a single scalar, as though every body were a sphere.
+    #[derive(Debug, Clone)]
+    pub struct Space3D {}
+
+    impl Space for Space3D {
+        type Linear = Column<3>;
+        type Angular = Column<3>;
+
+        const LINEAR_BASES: &'static [Basis] = &[
+            Basis {
+                name: "hati",
+                axis: "x",
+            },
+            Basis {
+                name: "hatj",
+                axis: "y",
+            },
+            Basis {
+                name: "hatk",
+                axis: "z",
+            },
+        ];
+
+        const ANGULAR_BASES: &'static [Basis] = &[
+            Basis {
+                name: "hati",
+                axis: "theta_x",
+            },
+            Basis {
+                name: "hatj",
+                axis: "theta_y",
+            },
+            Basis {
+                name: "hatk",
+                axis: "theta_z",
+            },
+        ];
+
+        fn cross_both(w: &Self::Angular, r: &Self::Linear) -> Self::Linear {
+            Self::cross_linear(w, r)
+        }
+
+        fn cross_linear(a: &Self::Linear, b: &Self::Linear) -> Self::Angular {
+            Column::vector([
+                a.get(1) * b.get(2) - a.get(2) * b.get(1),
+                a.get(2) * b.get(0) - a.get(0) * b.get(2),
+                a.get(0) * b.get(1) - a.get(1) * b.get(0),
+            ])
+        }
+
+        // Rodrigues' rotation formula
+        fn rotate(rotation: &Self::Angular, v: &Self::Linear) -> Self::Linear {
+            let angle = rotation.magnitude();
+            if angle == 0.0 {
+                return v.clone();
+            }
+
+            let axis = rotation.scale(1.0 / angle);
+            let (sin, cos) = angle.sin_cos();
+            v.scale(cos)
+                .plus(&Self::cross_linear(&axis, v).scale(sin))
+                .plus(&axis.scale(axis.dot(v) * (1.0 - cos)))
+        }
+
+        // `by` applied after `rotation`, multiplied out as quaternions
+        fn turn(rotation: &Self::Angular, by: &Self::Angular) -> Self::Angular {
+            if Self::cross_linear(rotation, by).magnitude() == 0.0 {
+                return rotation.plus(by);
+            }
+
+            let ((w1, v1), (w2, v2)) = (quaternion(by), quaternion(rotation));
+            let w = w1 * w2 - v1.dot(&v2);
+            let v = v2
+                .scale(w1)
+                .plus(&v1.scale(w2))
+                .plus(&Self::cross_linear(&v1, &v2));
+
+            let sin = v.magnitude();
+            if sin == 0.0 {
+                return Column::empty();
+            }
+            v.scale(2.0 * sin.atan2(w) / sin)
+        }
+    }
+
+    // The unit quaternion of a rotation vector, as its scalar and vector parts
+    fn quaternion(rotation: &Column<3>) -> (f64, Column<3>) {
+        let angle = rotation.magnitude();
+        if angle == 0.0 {
+            return (1.0, Column::empty());
+        }
+
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        (cos, rotation.scale(sin / angle))
     }
 }
 
 pub mod collide {
-    use crate::math::{Column, Matrix, Vector};
+    use crate::math::{Column, Matrix, Vector, geometry};
+    use crate::narrowphase::{CollideChain, CollideHalfPlane};
     use crate::spaces::Space2D;
-    use crate::{Body, Shape, Space};
+    use crate::{Body, BodyId, Shape, ShapeKind, Space};
+    use std::f64::consts::PI;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::{Arc, Mutex};
 
     fn rot_2d(deg: f64) -> Matrix<2, 2> {
         Matrix::new([[deg.cos(), -deg.sin()], [deg.sin(), deg.cos()]])
@@ -528,117 +3054,286 @@ pub mod collide {
     pub struct Collision<S: Space> {
         pub point: S::Linear,
         pub normal: S::Linear, // Relative to A
-        pub depth: f64,        // Relative to A
+        pub depth: f64,
+        // The point in the local frame of A and of B (see Body::to_local), which stays put as the
+        // bodies move and turn
+        pub local: (S::Linear, S::Linear),
+        // Index of the face of A and of B nearest the point, face i running from vertex i to i + 1,
+        // if the collider deals in faces
+        pub faces: Option<(usize, usize)>,
+        // Where the bodies touch, in world space: up to two points at the ends of the surfaces
+        // pressed together (eg. both corners of a box lying on the floor), or just `point`
+        pub points: Vec<S::Linear>,
+    }
+
+    impl<S: Space> Collision<S> {
+        pub fn new(
+            point: S::Linear,
+            normal: S::Linear,
+            depth: f64,
+            a: &Body<S>,
+            b: &Body<S>,
+        ) -> Self {
+            Collision {
+                local: (a.to_local(&point), b.to_local(&point)),
+                points: vec![point.clone()],
+                point,
+                normal,
+                depth,
+                faces: None,
+            }
+        }
+
+        /// The same contact seen from B.
+        pub fn swapped(self) -> Self {
+            Collision {
+                normal: self.normal.scale(-1.0),
+                local: (self.local.1, self.local.0),
+                faces: self.faces.map(|(a, b)| (b, a)),
+                ..self
+            }
+        }
     }
 
-    pub trait Collide<S: Space> {
+    pub trait Collide<S: Space>: Sync {
         fn collide(&self, a: &Body<S>, b: &Body<S>) -> Option<Collision<S>>;
+
+        /// The vertices of a body's shape, rotated into world orientation but relative to its
+        /// centroid, as used when colliding it.
+        fn vertices(&self, body: &Body<S>) -> Arc<[S::Linear]>;
+
+        /// Drops anything kept for bodies other than `bodies`, called whenever the engine's
+        /// bodies are validated. Nothing is kept by default.
+        fn retain(&self, _bodies: &[Body<S>]) {}
     }
 
-    pub struct Collide2D {}
+    /// Picks a collider by the kinds of the two shapes, so a specialised algorithm (eg. for two
+    /// ellipses) can be added for some pairs without replacing the collider for every other one.
+    /// Pairs nothing is registered for go to the fallback, which also gives the vertices of bodies.
+    pub struct Dispatch<S: Space> {
+        table: HashMap<(ShapeKind, ShapeKind), Box<dyn Collide<S>>>,
+        fallback: Box<dyn Collide<S>>,
+    }
 
-    impl Collide2D {
-        pub fn new() -> Self {
-            Collide2D {}
+    impl<S: Space> Dispatch<S> {
+        pub fn new(fallback: Box<dyn Collide<S>>) -> Self {
+            Dispatch {
+                table: HashMap::new(),
+                fallback,
+            }
+        }
+
+        /// Uses `collider` for contacts between shapes of kinds `a` and `b`, replacing whatever
+        /// was registered for the pair. It is always given the body of kind `a` first, bodies the
+        /// other way around are swapped and the contact's normal flipped back.
+        pub fn register(&mut self, a: ShapeKind, b: ShapeKind, collider: Box<dyn Collide<S>>) {
+            self.table.insert((a, b), collider);
+        }
+
+        pub fn with(mut self, a: ShapeKind, b: ShapeKind, collider: Box<dyn Collide<S>>) -> Self {
+            self.register(a, b, collider);
+            self
+        }
+    }
+
+    impl Default for Dispatch<Space2D> {
+        fn default() -> Self {
+            Dispatch::new(Box::new(Collide2D::new()))
+                .with(ShapeKind::Ellipse, ShapeKind::Ellipse, Box::new(CollideRound::new()))
+                .with(ShapeKind::Ellipse, ShapeKind::Rec, Box::new(CollideRound::new()))
+                .with(ShapeKind::Ellipse, ShapeKind::Manifold, Box::new(CollideRound::new()))
+                .with(ShapeKind::Chain, ShapeKind::Rec, Box::new(CollideChain::new()))
+                .with(ShapeKind::Chain, ShapeKind::Ellipse, Box::new(CollideChain::new()))
+                .with(ShapeKind::Chain, ShapeKind::Manifold, Box::new(CollideChain::new()))
+                .with(ShapeKind::Chain, ShapeKind::Chain, Box::new(NoCollide))
+                .with(ShapeKind::HalfPlane, ShapeKind::Rec, Box::new(CollideHalfPlane::new()))
+                .with(ShapeKind::HalfPlane, ShapeKind::Ellipse, Box::new(CollideHalfPlane::new()))
+                .with(ShapeKind::HalfPlane, ShapeKind::Manifold, Box::new(CollideHalfPlane::new()))
+                .with(ShapeKind::HalfPlane, ShapeKind::Chain, Box::new(CollideHalfPlane::new()))
+                .with(ShapeKind::HalfPlane, ShapeKind::HalfPlane, Box::new(NoCollide))
+        }
+    }
+
+    impl<S: Space> Collide<S> for Dispatch<S> {
+        fn collide(&self, a: &Body<S>, b: &Body<S>) -> Option<Collision<S>> {
+            let (kind_a, kind_b) = (a.shape.kind(), b.shape.kind());
+            if let Some(collider) = self.table.get(&(kind_a, kind_b)) {
+                return collider.collide(a, b);
+            }
+
+            match self.table.get(&(kind_b, kind_a)) {
+                Some(collider) => collider.collide(b, a).map(Collision::swapped),
+                None => self.fallback.collide(a, b),
+            }
+        }
+
+        fn vertices(&self, body: &Body<S>) -> Arc<[S::Linear]> {
+            self.fallback.vertices(body)
         }
 
-        fn bases(body: &Body<Space2D>) -> Vec<Column<2>> {
-            let shape = &body.shape;
+        fn retain(&self, bodies: &[Body<S>]) {
+            self.fallback.retain(bodies);
+            for collider in self.table.values() {
+                collider.retain(bodies);
+            }
+        }
+    }
+
+    /// Never finds a contact, for scenes whose bodies only interact through their equations (eg.
+    /// orbits) and for spaces without a collider of their own yet. Bodies have no vertices.
+    pub struct NoCollide;
+
+    impl<S: Space> Collide<S> for NoCollide {
+        fn collide(&self, _: &Body<S>, _: &Body<S>) -> Option<Collision<S>> {
+            None
+        }
+
+        fn vertices(&self, _: &Body<S>) -> Arc<[S::Linear]> {
+            Arc::new([])
+        }
+    }
+
+    struct CachedVertices {
+        rotation: f64,
+        shape: Shape<Space2D>,
+        vertices: Arc<[Column<2>]>,
+    }
+
+    /// The vertices of each body by id, only recomputed once it has turned or changed shape. A
+    /// body that has only turned keeps its entry, and its vertices are written over the old ones
+    /// in place once nothing else holds them, so a spinning body allocates nothing each tick.
+    /// Bodies not yet in an engine have no id, and theirs are worked out every time. The lock is
+    /// only held to look an entry up or put it back, never while vertices are worked out.
+    #[derive(Default)]
+    pub struct VertexCache {
+        entries: Mutex<HashMap<BodyId, CachedVertices>>,
+    }
+
+    impl VertexCache {
+        pub fn get(&self, body: &Body<Space2D>) -> Arc<[Column<2>]> {
+            let Some(id) = body.id else {
+                return Collide2D::bases(body).into();
+            };
+            let rotation = body.angular.displacement[0];
 
-            let untransformed = match shape {
-                Shape::Rec(width, height) => {
-                    vec![
-                        Matrix::vector([width / 2.0, height / 2.0]),  // Top Right
-                        Matrix::vector([-width / 2.0, height / 2.0]), // Top left
-                        Matrix::vector([-width / 2.0, -height / 2.0]), // Bottom left
-                        Matrix::vector([width / 2.0, -height / 2.0]), // Bottom Right
-                    ]
+            let cached = {
+                let mut entries = self.entries.lock().unwrap();
+                if let Some(x) = entries.get(&id)
+                    && x.rotation == rotation
+                    && same_shape(&x.shape, &body.shape)
+                {
+                    return x.vertices.clone();
                 }
-                Shape::Ellipse(_, _) => {
-                    todo!()
+                // Taken out while it's recomputed, so it can be written over in place
+                entries.remove(&id)
+            };
+
+            let entry = match cached {
+                Some(mut x) if same_shape(&x.shape, &body.shape) => {
+                    match Arc::get_mut(&mut x.vertices) {
+                        Some(old) => Collide2D::bases_into(body, old),
+                        None => x.vertices = Collide2D::bases(body).into(),
+                    }
+                    x.rotation = rotation;
+                    x
                 }
-                Shape::Manifold(p) => p.clone(),
+                _ => CachedVertices {
+                    rotation,
+                    shape: body.shape.clone(),
+                    vertices: Collide2D::bases(body).into(),
+                },
             };
+            let vertices = entry.vertices.clone();
+            self.entries.lock().unwrap().insert(id, entry);
 
-            let transformation = rot_2d(body.angular.displacement.content[0][0]);
+            vertices
+        }
 
-            untransformed
-                .iter()
-                .map(|x| transformation.multiply(&x))
-                .collect::<Vec<_>>()
+        /// Forgets every body but `bodies`.
+        pub fn retain(&self, bodies: &[Body<Space2D>]) {
+            let ids = bodies.iter().filter_map(|x| x.id).collect::<HashSet<_>>();
+            self.entries.lock().unwrap().retain(|id, _| ids.contains(id));
         }
+    }
 
-        fn intersect(
-            a: &Column<2>,
-            a_basis: &Column<2>,
-            b: &Column<2>,
-            b_basis: &Column<2>,
-        ) -> Option<(f64, f64)> {
-            fn slope(col: &Column<2>) -> f64 {
-                col.get(1) / col.get(0)
+    fn same_shape(a: &Shape<Space2D>, b: &Shape<Space2D>) -> bool {
+        match (a, b) {
+            (Shape::Rec(w1, h1), Shape::Rec(w2, h2)) => w1 == w2 && h1 == h2,
+            (Shape::Ellipse(a1, b1), Shape::Ellipse(a2, b2)) => a1 == a2 && b1 == b2,
+            (Shape::Manifold(p1), Shape::Manifold(p2)) | (Shape::Chain(p1), Shape::Chain(p2)) => {
+                p1 == p2
             }
+            (Shape::HalfPlane(n1, o1), Shape::HalfPlane(n2, o2)) => n1 == n2 && o1 == o2,
+            _ => false,
+        }
+    }
 
-            // If they are both in-line and parallel, t will be inf as any value of t satisfies the series of
-            // equations. We have to manually check for this case.
-            let (t_a, t_b) = if slope(&a_basis) == slope(&b_basis) {
-                // There is no more calculation we can do with the data here
-                return None;
-                // return CollisionPoint::Parallel;
-            } else {
-                // If slopes aren't the same, find the parameterized point of intersection
-                // Ax=B => x=A^-1*B
-                // Of the system of equations: (where a_basis, a, b_basis, and b are all column vectors)
-                // t_a * a_basis + a = t_b * b_basis + b
-                let product = Matrix::vector([b.get(0) - a.get(0), b.get(1) - a.get(1)]);
-                let a_inv = Matrix::new([
-                    [-*b_basis.get(1), *b_basis.get(0)],
-                    [-*a_basis.get(1), *a_basis.get(0)],
-                ])
-                .scale(1.0 / (b_basis.get(0) * a_basis.get(1) - a_basis.get(0) * b_basis.get(1)));
-
-                let x = a_inv.multiply(&product);
-
-                (*x.get(0), *x.get(1))
-            };
+    const ELLIPSE_VERTICES: usize = 32;
+
+    #[derive(Default)]
+    pub struct Collide2D {
+        vertices: VertexCache,
+    }
 
-            Some((t_a, t_b))
+    impl Collide2D {
+        pub fn new() -> Self {
+            Collide2D::default()
         }
 
-        /// Determine the collision boundary between two basis
-        fn intersection_point(
-            a: &Column<2>,
-            a_basis: &Column<2>,
-            b: &Column<2>,
-            b_basis: &Column<2>,
-            //       a  , b
-        ) -> Option<Column<2>> {
-            let (t_a, t_b) = Self::intersect(a, a_basis, b, b_basis)?;
+        /// The vertices of a body's shape, rotated into world orientation but relative to its centroid.
+        pub fn bases(body: &Body<Space2D>) -> Vec<Column<2>> {
+            let transformation = rot_2d(body.angular.displacement.content[0][0]);
 
-            if t_a >= 0.0 && t_a <= 1.0 && t_b >= 0.0 && t_b <= 1.0 {
-                Some(a.plus(&a_basis.scale(t_a)))
-                // CollisionPoint::Parameterization { t_a, t_b }
-            } else {
-                None
+            outline(&body.shape)
+                .map(|x| transformation.multiply(&x))
+                .collect()
+        }
+
+        /// `bases` written over `into`, which holds as many vertices as the body's shape has, so a
+        /// body that has only turned needs nothing allocated.
+        pub fn bases_into(body: &Body<Space2D>, into: &mut [Column<2>]) {
+            debug_assert_eq!(into.len(), outline(&body.shape).len());
+            let transformation = rot_2d(body.angular.displacement.content[0][0]);
+
+            for (x, y) in into.iter_mut().zip(outline(&body.shape)) {
+                *x = transformation.multiply(&y);
             }
         }
     }
 
-    // Point to line (defined by two points) distance
-    fn ptl_distance(point: &Column<2>, a: &Column<2>, b: &Column<2>) -> f64 {
-        let numerator =
-            (b[1] - a[1]) * point[0] - (b[0] - a[0]) * point[1] + b[0] * a[1] - b[1] * a[0];
-
-        let denominator = ((b[1] - a[1]).powi(2) + (b[0] - a[0]).powi(2)).sqrt();
+    // The vertices of a shape before it's turned, worked out one at a time
+    fn outline(shape: &Shape<Space2D>) -> impl ExactSizeIterator<Item = Column<2>> + '_ {
+        let count = match shape {
+            Shape::Rec(..) => 4,
+            Shape::Ellipse(..) => ELLIPSE_VERTICES,
+            Shape::Manifold(p) | Shape::Chain(p) => p.len(),
+            // Unbounded, so it has no outline
+            Shape::HalfPlane(..) => 0,
+        };
 
-        numerator.abs() / denominator
+        (0..count).map(move |i| match shape {
+            Shape::Rec(width, height) => {
+                // Top right, top left, bottom left, bottom right
+                let (x, y) = [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)][i];
+                Matrix::vector([x * width / 2.0, y * height / 2.0])
+            }
+            // A polygon close enough to draw and to stand in where there's no exact test
+            Shape::Ellipse(major, minor) => {
+                let (sin, cos) = (2.0 * PI * i as f64 / ELLIPSE_VERTICES as f64).sin_cos();
+                Matrix::vector([major / 2.0 * cos, minor / 2.0 * sin])
+            }
+            // Collide2D closes a chain into a loop, chains are collided by CollideChain
+            Shape::Manifold(p) | Shape::Chain(p) => p[i].clone(),
+            Shape::HalfPlane(..) => unreachable!(),
+        })
     }
 
     impl Collide<Space2D> for Collide2D {
         // Runs in NlogN
         fn collide(&self, a: &Body<Space2D>, b: &Body<Space2D>) -> Option<Collision<Space2D>> {
             // Each basis is a vector from the centroid of the object to a point of its face
-            let a_bases = Self::bases(a);
-            let b_bases = Self::bases(b);
+            let a_bases = self.vertices.get(a);
+            let b_bases = self.vertices.get(b);
 
             // World-Space
             let mut intersection_groups = Vec::<Vec<Column<2>>>::new();
@@ -664,7 +3359,7 @@ pub mod collide {
                     let basis_b = basis!(b_bases, j);
 
                     let intersection =
-                        Self::intersection_point(&point_a, basis_a, &point_b, basis_b);
+                        geometry::intersect_segments(&point_a, basis_a, &point_b, basis_b);
 
                     if let Some(intersection) = intersection {
                         intersections.push(intersection);
@@ -707,7 +3402,7 @@ pub mod collide {
                 .iter()
                 .zip(intersections.iter().cycle().skip(1))
                 .min_by(|(a1, b1), (a2, b2)| {
-                    ptl_distance(&collision_point, a1, b1).total_cmp(&ptl_distance(
+                    geometry::line_distance(&collision_point, a1, b1).total_cmp(&geometry::line_distance(
                         &collision_point,
                         a2,
                         b2,
@@ -717,94 +3412,224 @@ pub mod collide {
             let normal_face = normal_face.0.plus(&normal_face.1.scale(-1.0));
             let normal = Column::vector([-normal_face[1], normal_face[0]]);
 
-            // A vector of polygons alternating side
-            let mut polygons: Vec<Vec<Column<2>>> = vec![vec![]];
-            let mut side = 0.0;
+            // Facing out of A into B, A's outline ends just past where B's starts. Facing the other
+            // way, the two overlap by the whole of both, so the shallower reach gives the side
+            let reach = |normal: &Column<2>| {
+                let furthest = |body: &Body<Space2D>, bases: &[Column<2>], sign: f64| {
+                    bases
+                        .iter()
+                        .map(|base| base.plus(&body.linear.displacement).dot(normal) * sign)
+                        .fold(f64::NEG_INFINITY, f64::max)
+                };
+                furthest(a, &a_bases, 1.0) + furthest(b, &b_bases, -1.0)
+            };
+            let normal = normal.unit();
+            let normal = if reach(&normal.scale(-1.0)) < reach(&normal) {
+                normal.scale(-1.0)
+            } else {
+                normal
+            };
+
+            // How far the collision point is inside a body, from its nearest face, and that face
+            let inside = |body: &Body<Space2D>, bases: &[Column<2>]| {
+                bases
+                    .iter()
+                    .zip(bases.iter().cycle().skip(1))
+                    .map(|(base_a, base_b)| {
+                        geometry::line_distance(
+                            &collision_point,
+                            &body.linear.displacement.plus(base_a),
+                            &body.linear.displacement.plus(base_b),
+                        )
+                    })
+                    .enumerate()
+                    .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                    .unwrap_or((0, 0.0))
+            };
+            let ((face_a, depth_a), (face_b, depth_b)) = (inside(a, &a_bases), inside(b, &b_bases));
+
+            // The point sits on the face of whichever body only grazes the other (a wheel sunk into
+            // the floor has it on the floor's surface), so the overlap is the deeper of the two
+            // The outlines cross at either end of the patch where the bodies overlap, so the
+            // crossings furthest apart along the surface make up the manifold
+            let tangent = Column::vector([-normal[1], normal[0]]);
+            let along = |x: &&Column<2>| x.plus(&collision_point.scale(-1.0)).dot(&tangent);
+            let ends = intersections
+                .iter()
+                .min_by(|x, y| along(x).total_cmp(&along(y)))
+                .zip(intersections.iter().max_by(|x, y| along(x).total_cmp(&along(y))))
+                .filter(|(first, last)| along(first) < along(last))
+                .map(|(first, last)| vec![first.clone(), last.clone()]);
+
+            let mut collision =
+                Collision::new(collision_point, normal, depth_a.max(depth_b), a, b);
+            collision.faces = Some((face_a, face_b));
+            if let Some(ends) = ends {
+                collision.points = ends;
+            }
+
+            Some(collision)
+        }
 
-            for (i, point) in a_bases.iter().enumerate() {
-                let point = point.plus(&a.linear.displacement);
+        fn vertices(&self, body: &Body<Space2D>) -> Arc<[Column<2>]> {
+            self.vertices.get(body)
+        }
 
-                if side == 0.0 {
-                    let (t, _) =
-                        Self::intersect(&point, &normal, &collision_point, &normal_face).unwrap();
+        fn retain(&self, bodies: &[Body<Space2D>]) {
+            self.vertices.retain(bodies);
+        }
+    }
 
-                    if t != 0.0 {
-                        side = t / t.abs()
-                    }
-                }
+    /// Contacts between an ellipse (as `a`) and a circle or polygon, found from the shapes
+    /// themselves rather than their outlines. Two circles are tested exactly. Anything else is
+    /// tested as a polygon (an ellipse other than a circle by its outline) against the ellipse
+    /// squashed into a circle. `Dispatch::default` uses it for every pair with an ellipse.
+    #[derive(Default)]
+    pub struct CollideRound {
+        // What pairs without an ellipse first are left to, and which keeps the vertices of bodies
+        polygons: Collide2D,
+    }
 
-                let len = polygons.len();
-                polygons[len - 1].push(point.clone());
+    impl CollideRound {
+        pub fn new() -> Self {
+            CollideRound::default()
+        }
 
-                let next = a_bases
-                    .get((i + 1) % a_bases.len())
-                    .unwrap()
-                    .plus(&a.linear.displacement);
+        fn circles(a: &Body<Space2D>, b: &Body<Space2D>) -> Option<Collision<Space2D>> {
+            let (Shape::Ellipse(a_diameter, _), Shape::Ellipse(b_diameter, _)) =
+                (&a.shape, &b.shape)
+            else {
+                return None;
+            };
+            let (a_radius, b_radius) = (a_diameter / 2.0, b_diameter / 2.0);
 
-                let face = next.plus(&point.scale(-1.0));
+            let offset = b.linear.displacement.plus(&a.linear.displacement.scale(-1.0));
+            let distance = offset.magnitude();
+            if distance >= a_radius + b_radius {
+                return None;
+            }
 
-                if let Some((t, _)) = Self::intersect(&point, &face, &collision_point, &normal_face)
-                    && t >= 0.0
-                    && t < 1.0
-                {
-                    let intersection_point = point.plus(&face.scale(t));
+            // Right on top of each other any direction will do
+            let normal = match distance {
+                0.0 => Column::vector([1.0, 0.0]),
+                _ => offset.scale(1.0 / distance),
+            };
+            let depth = a_radius + b_radius - distance;
+            // Halfway through the overlap
+            let point = a.linear.displacement.plus(&normal.scale(a_radius - depth / 2.0));
 
-                    polygons[len - 1].push(intersection_point.clone());
+            Some(Collision::new(point, normal, depth, a, b))
+        }
 
-                    let mut new_polygon = vec![];
-                    new_polygon.push(intersection_point);
-                    polygons.push(new_polygon);
+        // The ellipse `a` against the outline of `b`, in a frame where `a` is the unit circle at
+        // the origin. Straight lines stay straight in it so the outline is still a convex polygon,
+        // but distances don't carry over, so the depth is measured again along the normal once
+        // it's back in the world.
+        fn ellipse_polygon(
+            a: &Body<Space2D>,
+            b: &Body<Space2D>,
+            outline: &[Column<2>],
+        ) -> Option<Collision<Space2D>> {
+            let Shape::Ellipse(major, minor) = a.shape else {
+                return None;
+            };
+            let radii = (major / 2.0, minor / 2.0);
+            let rotation = &a.angular.displacement;
+            let unturn = rotation.scale(-1.0);
+
+            let to_frame = |x: &Column<2>| {
+                let local = Space2D::rotate(
+                    &unturn,
+                    &x.plus(&b.linear.displacement).plus(&a.linear.displacement.scale(-1.0)),
+                );
+                Column::vector([local[0] / radii.0, local[1] / radii.1])
+            };
+            let polygon = outline.iter().map(to_frame).collect::<Vec<_>>();
+            let origin = Column::<2>::empty();
+
+            // Outward normal and signed distance of the origin from each face, positive outside
+            let faces = (0..polygon.len()).map(|i| {
+                let (start, end) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+                let edge = end.plus(&start.scale(-1.0));
+                let outward = Column::vector([edge[1], -edge[0]]).unit();
+                (i, outward.dot(&origin.plus(&start.scale(-1.0))))
+            });
+
+            // From the ellipse towards the polygon, and the nearest point of the polygon's surface
+            let (normal, surface) = match faces.max_by(|x, y| x.1.total_cmp(&y.1)) {
+                None => return None,
+                // Inside the polygon, it's nearest the face it's least deep behind
+                Some((i, distance)) if distance <= 0.0 => {
+                    let (start, end) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+                    let edge = end.plus(&start.scale(-1.0));
+                    let outward = Column::vector([edge[1], -edge[0]]).unit();
+                    (outward.scale(-1.0), outward.scale(-distance))
                 }
-
-                if i == a_bases.len() - 1
-                    && let Some(pop) = polygons.pop()
-                    && let Some(first) = polygons.first_mut()
-                {
-                    for x in pop {
-                        first.push(x)
+                Some(_) => {
+                    let surface = (0..polygon.len())
+                        .map(|i| {
+                            let end = &polygon[(i + 1) % polygon.len()];
+                            geometry::closest_on_segment(&origin, &polygon[i], end)
+                        })
+                        .min_by(|x, y| x.magnitude().total_cmp(&y.magnitude()))?;
+                    if surface.magnitude() >= 1.0 {
+                        return None;
                     }
+                    (surface.unit(), surface)
                 }
-            }
+            };
 
-            // https://en.wikipedia.org/wiki/Shoelace_formula
-            fn do_sum(polygons: &Vec<Vec<Column<2>>>, skip: usize) -> f64 {
-                polygons
-                    .iter()
-                    .skip(skip)
-                    .step_by(2)
-                    .map(|poly| {
-                        poly.iter()
-                            .zip(poly.iter().cycle().skip(1))
-                            .map(|(a, b)| Matrix::new([[a[0], b[0]], [a[1], b[1]]]).det())
-                            .sum::<f64>()
-                    })
-                    .sum::<f64>()
-                    / 2.0
+            // Normals go back through the transpose of the inverse, which for a scale is the scale
+            let local_normal = Column::vector([normal[0] / radii.0, normal[1] / radii.1]).unit();
+            let normal = Space2D::rotate(rotation, &local_normal);
+            let surface = a.linear.displacement.plus(&Space2D::rotate(
+                rotation,
+                &Column::vector([surface[0] * radii.0, surface[1] * radii.1]),
+            ));
+
+            // The furthest the ellipse reaches along the normal, against the nearest the polygon
+            // does
+            let reach = ((radii.0 * local_normal[0]).powi(2) + (radii.1 * local_normal[1]).powi(2))
+                .sqrt();
+            let support = a.linear.displacement.plus(&Space2D::rotate(
+                rotation,
+                &Column::vector([
+                    radii.0.powi(2) * local_normal[0] / reach,
+                    radii.1.powi(2) * local_normal[1] / reach,
+                ]),
+            ));
+            let nearest = outline
+                .iter()
+                .map(|x| x.plus(&b.linear.displacement).dot(&normal))
+                .fold(f64::INFINITY, f64::min);
+            let depth = support.dot(&normal) - nearest;
+            if depth <= 0.0 {
+                return None;
             }
 
-            let (area_a, area_b) = (do_sum(&polygons, 0), do_sum(&polygons, 1));
-
-            let area_modifier = if area_a > area_b { 1.0 } else { -1.0 };
+            let point = surface.plus(&support).scale(0.5);
+            Some(Collision::new(point, normal, depth, a, b))
+        }
+    }
 
-            let normal = normal.scale(side * area_modifier).unit();
+    impl Collide<Space2D> for CollideRound {
+        fn collide(&self, a: &Body<Space2D>, b: &Body<Space2D>) -> Option<Collision<Space2D>> {
+            match (&a.shape, &b.shape) {
+                (Shape::Ellipse(x, y), Shape::Ellipse(z, w)) if x == y && z == w => {
+                    Self::circles(a, b)
+                }
+                (Shape::Ellipse(..), _) => Self::ellipse_polygon(a, b, &self.polygons.vertices(b)),
+                // Not given an ellipse first, so leave it to the polygon collider
+                _ => self.polygons.collide(a, b),
+            }
+        }
 
-            let penetration_depth = a_bases
-                .iter()
-                .zip(a_bases.iter().cycle().skip(1))
-                .map(|(base_a, base_b)| {
-                    ptl_distance(
-                        &collision_point,
-                        &a.linear.displacement.plus(&base_a),
-                        &a.linear.displacement.plus(&base_b),
-                    )
-                })
-                .min_by(|a, b| a.partial_cmp(b).unwrap());
+        fn vertices(&self, body: &Body<Space2D>) -> Arc<[Column<2>]> {
+            self.polygons.vertices(body)
+        }
 
-            Some(Collision {
-                point: collision_point,
-                normal,
-                depth: penetration_depth.unwrap_or(0.0),
-            })
+        fn retain(&self, bodies: &[Body<Space2D>]) {
+            self.polygons.retain(bodies);
         }
     }
 
@@ -814,9 +3639,125 @@ pub mod collide {
         use crate::{BodyProperties, BodyState};
         use std::f64::consts::PI;
 
+        // Reports every pair it is given as touching at the first body's centroid, with a normal
+        // along x
+        struct Always;
+
+        impl Collide<Space2D> for Always {
+            fn collide(
+                &self,
+                a: &Body<Space2D>,
+                _: &Body<Space2D>,
+            ) -> Option<Collision<Space2D>> {
+                Some(Collision::new(
+                    a.linear.displacement.clone(),
+                    Column::vector([1.0, 0.0]),
+                    0.0,
+                    a,
+                    a,
+                ))
+            }
+
+            fn vertices(&self, _: &Body<Space2D>) -> Arc<[Column<2>]> {
+                Arc::from(Vec::new())
+            }
+        }
+
+        #[test]
+        fn test_dispatch() {
+            let body = |name: &str, shape: Shape<Space2D>, x: f64| {
+                Body::at_rest(
+                    name.to_string(),
+                    shape,
+                    Column::vector([x, 0.0]),
+                    Column::vector([0.0]),
+                    BodyProperties::rectangle(1.0, 1.0, 1.0),
+                )
+            };
+            let (ellipse, near, far) = (
+                body("E", Shape::Ellipse(1.0, 1.0), 5.0),
+                body("A", Shape::Rec(1.0, 1.0), 0.0),
+                body("B", Shape::Rec(1.0, 1.0), 0.5),
+            );
+
+            let dispatch = Dispatch::default().with(
+                ShapeKind::Ellipse,
+                ShapeKind::Rec,
+                Box::new(Always),
+            );
+            // Given the ellipse first either way, with the normal turned back towards the rectangle
+            let collision = dispatch.collide(&ellipse, &near).unwrap();
+            assert_eq!(collision.point, ellipse.linear.displacement);
+            assert_eq!(*collision.normal.get(0), 1.0);
+            let collision = dispatch.collide(&near, &ellipse).unwrap();
+            assert_eq!(collision.point, ellipse.linear.displacement);
+            assert_eq!(*collision.normal.get(0), -1.0);
+
+            // Anything else is left to the fallback
+            assert!(dispatch.collide(&near, &far).is_some());
+            let far = body("B", Shape::Rec(1.0, 1.0), 5.0);
+            assert!(dispatch.collide(&near, &far).is_none());
+            assert_eq!(dispatch.vertices(&near).len(), 4);
+        }
+
+        #[test]
+        fn test_round_collision() {
+            let body = |shape: Shape<Space2D>, position: [f64; 2], rotation: f64| {
+                Body::at_rest(
+                    "A".to_string(),
+                    shape,
+                    Column::vector(position),
+                    Column::vector([rotation]),
+                    BodyProperties::rectangle(1.0, 1.0, 1.0),
+                )
+            };
+            let close =
+                |x: &Column<2>, y: [f64; 2]| (x[0] - y[0]).abs() + (x[1] - y[1]).abs() < 1e-9;
+            let collide = Dispatch::default();
+
+            // Circles of radius 1 and 2, overlapping by 0.5
+            let circle = body(Shape::Ellipse(2.0, 2.0), [0.0, 0.0], 0.0);
+            let other = body(Shape::Ellipse(4.0, 4.0), [2.5, 0.0], 0.0);
+            let collision = collide.collide(&circle, &other).unwrap();
+            assert!((collision.depth - 0.5).abs() < 1e-9);
+            assert!(close(&collision.normal, [1.0, 0.0]));
+            assert!(close(&collision.point, [0.75, 0.0]));
+            let apart = body(Shape::Ellipse(4.0, 4.0), [3.5, 0.0], 0.0);
+            assert!(collide.collide(&circle, &apart).is_none());
+
+            // Sunk 0.25 into the top of a floor, from either side
+            let floor = body(Shape::Rec(10.0, 2.0), [0.0, -1.75], 0.0);
+            let collision = collide.collide(&circle, &floor).unwrap();
+            assert!((collision.depth - 0.25).abs() < 1e-9);
+            assert!(close(&collision.normal, [0.0, -1.0]));
+            assert!(close(&collision.point, [0.0, -0.875]));
+            let collision = collide.collide(&floor, &circle).unwrap();
+            assert!(close(&collision.normal, [0.0, 1.0]));
+
+            // Past the floor's corner the circle only touches the corner
+            let corner = body(Shape::Ellipse(2.0, 2.0), [5.6, -0.3], 0.0);
+            let collision = collide.collide(&corner, &floor).unwrap();
+            assert!((collision.depth - 0.25).abs() < 1e-9, "{}", collision.depth);
+            assert!(close(&collision.normal, [-0.8, -0.6]));
+
+            // Centre inside the floor, pushed out of the top
+            let sunk = body(Shape::Ellipse(2.0, 2.0), [0.0, -1.5], 0.0);
+            let collision = collide.collide(&sunk, &floor).unwrap();
+            assert!((collision.depth - 1.75).abs() < 1e-9);
+            assert!(close(&collision.normal, [0.0, -1.0]));
+
+            // An ellipse 4 wide and 1 tall stood on end, so 2 of it reaches down into the floor
+            let ellipse = body(Shape::Ellipse(4.0, 1.0), [0.0, 1.0], PI / 2.0);
+            let collision = collide.collide(&ellipse, &floor).unwrap();
+            assert!((collision.depth - 0.25).abs() < 1e-9, "{}", collision.depth);
+            assert!(close(&collision.normal, [0.0, -1.0]));
+            let lying = body(Shape::Ellipse(4.0, 1.0), [0.0, 1.0], 0.0);
+            assert!(collide.collide(&lying, &floor).is_none());
+        }
+
         #[test]
         fn test_rotated_collision() {
-            let collide = Collide2D {};
+            let collide = Collide2D::new();
             let collision = collide.collide(
                 &Body::at_rest(
                     "A".to_string(),
@@ -839,6 +3780,112 @@ pub mod collide {
             }
         }
 
+        #[test]
+        fn test_manifold() {
+            let body = |name: &str, width: f64, y: f64| {
+                Body::at_rest(
+                    name.to_string(),
+                    Shape::Rec(width, 2.0),
+                    Column::vector([0.0, y]),
+                    Column::vector([0.0]),
+                    BodyProperties::rectangle(1.0, width, 2.0),
+                )
+            };
+            // A box sunk a little into a wider floor touches it at both bottom corners
+            let (floor, box_) = (body("Floor", 10.0, 0.0), body("Box", 2.0, 1.9));
+
+            let collision = Collide2D::new().collide(&box_, &floor).unwrap();
+            assert_eq!(collision.points.len(), 2);
+            let mut xs = collision.points.iter().map(|x| x[0]).collect::<Vec<_>>();
+            xs.sort_by(f64::total_cmp);
+            assert!((xs[0] + 1.0).abs() < 1e-9 && (xs[1] - 1.0).abs() < 1e-9, "{:?}", xs);
+            assert!(collision.points.iter().all(|x| (x[1] - 1.0).abs() < 1e-9));
+
+            // Circles only ever touch at one
+            let circle = |y: f64| {
+                let mut body = body("Circle", 2.0, y);
+                body.shape = Shape::Ellipse(2.0, 2.0);
+                body
+            };
+            let collision = CollideRound::new().collide(&circle(0.0), &circle(1.9)).unwrap();
+            assert_eq!(collision.points, vec![collision.point.clone()]);
+        }
+
+        #[test]
+        fn test_local_contact() {
+            let body = |name: &str, y: f64, rotation: f64| {
+                Body::at_rest(
+                    name.to_string(),
+                    Shape::Rec(2.0, 2.0),
+                    Column::vector([0.0, y]),
+                    Column::vector([rotation]),
+                    BodyProperties::rectangle(1.0, 2.0, 2.0),
+                )
+            };
+            // B turned upside down and a bit, sunk into the top of A
+            let (a, b) = (body("A", 0.0, 0.0), body("B", 1.8, PI + 0.1));
+
+            let collision = Collide2D::new().collide(&a, &b).unwrap();
+            for (body, local) in [(&a, &collision.local.0), (&b, &collision.local.1)] {
+                let world = body.to_world(local);
+                assert!(world.plus(&collision.point.scale(-1.0)).magnitude() < 1e-9);
+            }
+            // A's top, from its top right corner, and what was B's top before it turned over. The
+            // point is near A's surface, about 0.8 below B's centroid
+            assert_eq!(collision.faces, Some((0, 0)));
+            assert!((collision.local.0.get(1) - 1.0).abs() < 0.1);
+            assert!((collision.local.1.get(1) - 0.8).abs() < 0.1);
+
+            let swapped = collision.clone().swapped();
+            assert_eq!(swapped.faces, Some((0, 0)));
+            assert_eq!(swapped.local.0, collision.local.1);
+        }
+
+        #[test]
+        fn test_vertex_cache() {
+            let cache = VertexCache::default();
+            let mut body = Body::at_rest(
+                String::new(),
+                Shape::Rec(2.0, 2.0),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::weightless(),
+            );
+            body.id = Some(BodyId(0));
+
+            let first = cache.get(&body);
+            body.linear.displacement = Column::vector([5.0, 5.0]);
+            assert!(Arc::ptr_eq(&first, &cache.get(&body)));
+
+            body.angular.displacement = Column::vector([PI / 2.0]);
+            let turned = cache.get(&body);
+            assert!(!Arc::ptr_eq(&first, &turned));
+            assert_eq!(turned.to_vec(), Collide2D::bases(&body));
+
+            // Once nothing else holds them, turning again writes over the same vertices
+            let address = Arc::as_ptr(&turned);
+            drop((first, turned));
+            body.angular.displacement = Column::vector([PI / 3.0]);
+            let turned = cache.get(&body);
+            assert_eq!(Arc::as_ptr(&turned), address);
+            assert_eq!(turned.to_vec(), Collide2D::bases(&body));
+
+            body.shape = Shape::Rec(4.0, 2.0);
+            assert!(!Arc::ptr_eq(&turned, &cache.get(&body)));
+
+            // Another unnamed body has its own
+            let mut other = body.clone();
+            other.id = Some(BodyId(1));
+            other.shape = Shape::Rec(1.0, 1.0);
+            assert_eq!(cache.get(&other).to_vec(), Collide2D::bases(&other));
+            assert_eq!(cache.get(&body).to_vec(), Collide2D::bases(&body));
+
+            // Bodies no longer in the engine are forgotten
+            cache.retain(std::slice::from_ref(&other));
+            let entries = cache.entries.lock().unwrap();
+            assert_eq!(entries.keys().collect::<Vec<_>>(), vec![&BodyId(1)]);
+        }
+
         // #[test]
         // fn test_collinear_collision_basis() {
         //     // Same basis
@@ -937,6 +3984,7 @@ pub mod collide {
         fn test_form_basis() {
             let body = Body {
                 name: "A".to_string(),
+                id: None,
                 shape: Shape::Rec(2.0, 2.0),
                 linear: BodyState {
                     displacement: Matrix::vector([0.0, 0.0]),
@@ -949,6 +3997,8 @@ pub mod collide {
                     acceleration: Matrix::empty(),
                 },
                 properties: BodyProperties::weightless(),
+                force: Matrix::empty(),
+                torque: Matrix::empty(),
             };
 
             let basis = Collide2D::bases(&body);
@@ -965,6 +4015,7 @@ pub mod collide {
 
             let body = Body {
                 name: "A".to_string(),
+                id: None,
                 shape: Shape::Rec(4.0, 2.0),
                 linear: BodyState {
                     displacement: Matrix::vector([0.0, 0.0]),
@@ -977,6 +4028,8 @@ pub mod collide {
                     acceleration: Matrix::empty(),
                 },
                 properties: BodyProperties::weightless(),
+                force: Matrix::empty(),
+                torque: Matrix::empty(),
             };
 
             let basis = Collide2D::bases(&body);
@@ -989,10 +4042,11 @@ pub mod collide {
 
         #[test]
         fn test_face_collision() {
-            let c2d = Collide2D {};
+            let c2d = Collide2D::new();
 
             let a = Body {
                 name: "A".to_string(),
+                id: None,
                 shape: Shape::Rec(2.0, 2.0),
                 linear: BodyState {
                     displacement: Matrix::vector([0.0, 0.0]),
@@ -1005,10 +4059,13 @@ pub mod collide {
                     acceleration: Matrix::empty(),
                 },
                 properties: BodyProperties::weightless(),
+                force: Matrix::empty(),
+                torque: Matrix::empty(),
             };
 
             let b = Body {
                 name: "B".to_string(),
+                id: None,
                 shape: Shape::Rec(2.0, 2.0),
                 linear: BodyState {
                     displacement: Matrix::vector([2.0, 0.0]),
@@ -1021,6 +4078,8 @@ pub mod collide {
                     acceleration: Matrix::empty(),
                 },
                 properties: BodyProperties::weightless(),
+                force: Matrix::empty(),
+                torque: Matrix::empty(),
             };
 
             let collision = c2d.collide(&a, &b);
@@ -1029,3 +4088,1203 @@ pub mod collide {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadphase::SpatialHash;
+    use crate::collide::{Collide2D, Dispatch};
+    use crate::math::solve::builtin;
+    use crate::math::Column;
+    use crate::spaces::Space2D;
+
+    fn falling_engine() -> Engine<Space2D> {
+        Engine::new(
+            vec![Body::at_rest(
+                "A".to_string(),
+                Shape::Rec(1.0, 1.0),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::rectangle(1.0, 1.0, 1.0),
+            )],
+            Environment::build(vec!["a_A=-10*hatj"], builtin::functions(), builtin::constants())
+                .unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn test_velocity_reconstruction() {
+        let run = |reconstruction: Reconstruction, equations: Vec<&str>| {
+            let mut engine = falling_engine();
+            let mut b = engine.bodies()[0].clone();
+            b.name = "B".to_string();
+            b.linear.displacement = Column::vector([0.0, 100.0]);
+            engine.bodies_mut().push(b);
+            // B moves along at 1 per second, A follows the square of how far it has got
+            engine.set_environment(
+                Environment::build(equations, builtin::functions(), builtin::constants()).unwrap(),
+            );
+            engine.set_reconstruction(reconstruction);
+            for _ in 0..5 {
+                engine.tick().unwrap();
+            }
+
+            *engine.bodies()[0].linear.velocity.get(0)
+        };
+
+        let equations = vec!["v_B=hati", "s_A=x_B^2*hati"];
+        assert!((run(Reconstruction::BackwardDifference, equations.clone()) - 0.7).abs() < 1e-9);
+        assert!((run(Reconstruction::CentralDifference, equations.clone()) - 0.8).abs() < 1e-9);
+        let equations = vec!["v_B=hati", "s_A=x_B^2*hati", "v_A=5*hati"];
+        assert_eq!(run(Reconstruction::CentralDifference, equations), 5.0);
+    }
+
+    #[test]
+    fn test_force_equations() {
+        let mut engine = falling_engine();
+        engine.bodies_mut()[0].properties = BodyProperties::rectangle(2.0, 1.0, 1.0);
+        // Gravity as an acceleration, a push up as a force and a twist as a torque
+        engine.set_environment(
+            Environment::build(
+                vec!["a_A=-10*hatj", "F_A=4*hatj+2*hati", "tau_A=3*hatk"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+        );
+        engine.tick().unwrap();
+
+        // Leapfrog halves the first tick's acceleration
+        let body = &engine.bodies()[0];
+        let [x, y] = [0, 1].map(|i| *body.linear.velocity.get(i));
+        assert!((x - 0.05).abs() < 1e-9, "{}", x);
+        assert!((y + 0.4).abs() < 1e-9, "{}", y);
+        let moi = body.properties.moi;
+        assert!((body.angular.velocity.get(0) - 0.15 / moi).abs() < 1e-9);
+
+        // Massless bodies aren't accelerated by forces
+        engine.bodies_mut()[0].properties = BodyProperties::weightless();
+        engine.set_environment(
+            Environment::build(vec!["F_A=4*hatj"], builtin::functions(), builtin::constants())
+                .unwrap(),
+        );
+        engine.tick().unwrap();
+        assert_eq!(engine.bodies()[0].linear.acceleration.magnitude(), 0.0);
+    }
+
+    #[test]
+    fn test_pause_and_step() {
+        let mut engine = falling_engine();
+
+        engine.pause();
+        engine.tick().unwrap();
+        assert_eq!(*engine.bodies()[0].linear.displacement.get(1), 0.0);
+
+        engine.step().unwrap();
+        assert!(*engine.bodies()[0].linear.displacement.get(1) < 0.0);
+    }
+
+    #[test]
+    fn test_change_environment() {
+        let mut engine = falling_engine();
+        engine.set_environment(
+            Environment::build(vec!["g=0", "a_A=-g*hatj"], builtin::functions(), builtin::constants())
+                .unwrap(),
+        );
+
+        engine.tick().unwrap();
+        assert_eq!(*engine.bodies()[0].linear.velocity.get(1), 0.0);
+
+        // Gravity on halfway through
+        engine.environment_mut().set_constant("g", 10.0);
+        assert_eq!(engine.environment().constant("g"), Some(10.0));
+        engine.tick().unwrap();
+        assert!(*engine.bodies()[0].linear.velocity.get(1) < 0.0);
+    }
+
+    #[test]
+    fn test_apply_torque() {
+        let mut engine = falling_engine();
+        let (moi, id) = (engine.bodies()[0].properties.moi, engine.bodies()[0].id.unwrap());
+
+        engine.apply_torque(id, Column::vector([0.5]));
+        engine.apply_torque(id, Column::vector([0.5]));
+        engine.tick().unwrap();
+        let omega = *engine.bodies()[0].angular.velocity.get(0);
+        assert!((omega - 1.0 / moi * 0.1).abs() < 1e-9, "{}", omega);
+
+        // Only for the one tick
+        engine.tick().unwrap();
+        assert_eq!(*engine.bodies()[0].angular.velocity.get(0), omega);
+
+        // Over the next tick, like a torque
+        engine.apply_angular_impulse(id, Column::vector([-moi * omega]));
+        assert_eq!(*engine.bodies()[0].angular.velocity.get(0), omega);
+        engine.tick().unwrap();
+        assert!(engine.bodies()[0].angular.velocity.get(0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bounds_and_speed_events() {
+        let mut engine = falling_engine();
+        engine.set_bounds(Some((Column::vector([-1.0, -1.0]), Column::vector([1.0, 1.0]))));
+        engine.set_speed_limit(Some(5.0));
+        let a = engine.bodies()[0].id.unwrap();
+
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(engine.tick().unwrap().events);
+        }
+
+        // Each is only reported once, when it happens
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert_eq!(events[0], Event::LeftBounds { body: a });
+        assert!(matches!(
+            events[1],
+            Event::SpeedExceeded { body, speed } if body == a && speed > 5.0
+        ));
+
+        // A body launched to infinity is out of bounds
+        engine.set_speed_limit(None);
+        engine.bodies_mut()[0].linear.displacement = Column::vector([0.0, 0.0]);
+        engine.bodies_mut()[0].linear.velocity = Column::vector([0.0, 0.0]);
+        assert!(engine.tick().unwrap().events.is_empty());
+        engine.bodies_mut()[0].linear.velocity = Column::vector([f64::NAN, 0.0]);
+        assert_eq!(engine.tick().unwrap().events, vec![Event::LeftBounds { body: a }]);
+    }
+
+    #[test]
+    fn test_collision_events() {
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        let ids = engine.bodies().iter().map(|x| x.id.unwrap()).collect::<Vec<_>>();
+
+        let tick = (0..120)
+            .map(|_| engine.tick().unwrap())
+            .find(|x| !x.collisions.is_empty())
+            .unwrap();
+        assert_eq!(tick.collision_events.len(), tick.contacts.len());
+
+        // The striker reaches the first ball first
+        let event = &tick.collision_events[0];
+        assert_eq!((event.a, event.b), (ids[0], ids[1]));
+        assert_eq!(event.point, tick.collisions[0]);
+        assert_eq!(event.depth, tick.contacts[0].depth);
+        // Enough to stop the striker dead
+        let mass = engine.bodies()[0].properties.mass;
+        assert!((event.impulse - mass * 100.0).abs() < 1.0, "{}", event.impulse);
+    }
+
+    #[test]
+    fn test_energy_watchdog() {
+        let run = |restitution: f64| {
+            let mut scenario = scenarios::newtons_cradle();
+            scenario.restitution = restitution;
+            let mut engine = scenario.build().unwrap();
+            engine.set_energy_tolerance(Some(1.0));
+
+            let mut events = Vec::new();
+            for _ in 0..120 {
+                events.extend(engine.tick().unwrap().events);
+            }
+            events
+        };
+
+        assert!(run(0.5).is_empty());
+
+        // The striker reaches the first ball first, ids are given out in order
+        let events = run(2.0);
+        assert!(matches!(
+            events[0],
+            Event::EnergyGained { a: BodyId(0), b: BodyId(1), gained } if gained > 1.0
+        ));
+    }
+
+    #[test]
+    fn test_contact_override() {
+        let run = |settings: Option<ContactOverride>| {
+            let mut scenario = scenarios::newtons_cradle();
+            scenario.bodies.truncate(2);
+            scenario.bodies[0].properties = scenario.bodies[0].properties.clone().tagged("rubber");
+            scenario.bodies[1].properties = scenario.bodies[1].properties.clone().tagged("ice");
+            // The collider can't handle perfectly parallel faces staying in contact
+            scenario.bodies[1].angular.displacement = Column::vector([0.01]);
+
+            let mut engine = scenario.build().unwrap();
+            engine.set_contact_override("ice", "rubber", settings);
+            for _ in 0..120 {
+                engine.tick().unwrap();
+            }
+
+            *engine.bodies()[0].linear.velocity.get(0)
+        };
+
+        // Elastic, the striker stops dead
+        assert!(run(None).abs() < 1.0);
+
+        // Perfectly inelastic, they move off together
+        let velocity = run(Some(ContactOverride {
+            restitution: Some(0.0),
+            ..ContactOverride::default()
+        }));
+        assert!((velocity - 50.0).abs() < 1.0, "{}", velocity);
+    }
+
+    #[test]
+    fn test_shock_propagation() {
+        // The lowest any box gets over ten seconds and the furthest any gets from the middle, with
+        // enough friction that none slide off and the top one `heavier` times as heavy
+        let run = |propagation: bool, heavier: f64| {
+            let mut scenario = scenarios::stacked_boxes();
+            for body in &mut scenario.bodies {
+                body.properties = body.properties.clone().with_friction(0.5, 0.5);
+            }
+            scenario.bodies[3].properties.mass *= heavier;
+            scenario.bodies[3].properties.moi *= heavier;
+            let mut engine = scenario.build().unwrap();
+            engine.set_shock_propagation(propagation.then(|| Column::vector([0.0, 1.0])));
+
+            let (mut lowest, mut furthest) = (f64::INFINITY, 0.0f64);
+            for _ in 0..600 {
+                engine.tick().unwrap();
+                for body in &engine.bodies()[1..] {
+                    let [x, y] = [0, 1].map(|i| *body.linear.displacement.get(i));
+                    lowest = lowest.min(y);
+                    furthest = furthest.max(x.abs());
+                }
+            }
+            (lowest, furthest)
+        };
+
+        // The stack stays upright, and none of it sinks into the floor
+        let (lowest, furthest) = run(true, 1.0);
+        assert!(lowest > -271.0, "{}", lowest);
+        assert!(furthest < 15.0, "{}", furthest);
+
+        // Without it a heavy box landing on top drives the bottom one into the floor
+        let (lowest, _) = run(false, 100.0);
+        assert!(lowest < -272.5, "{}", lowest);
+        let (lowest, _) = run(true, 100.0);
+        assert!(lowest > -271.5, "{}", lowest);
+    }
+
+    #[test]
+    fn test_joint_and_contact_solved_together() {
+        // A wheel resting on a rough floor and geared to a kinematic one spun by its equation, so
+        // friction at the floor holds back the spin the gear drives
+        let body = |name: &str, shape: Shape<Space2D>, y: f64, kind: BodyKind| {
+            let properties = BodyProperties::from_density(1.0, &shape).with_kind(kind);
+            Body::at_rest(
+                name.to_string(),
+                shape,
+                Column::vector([0.0, y]),
+                Column::vector([0.0]),
+                properties.with_friction(1.0, 1.0),
+            )
+        };
+        let mut engine = Engine::new(
+            vec![
+                body("Floor", Shape::Rec(400.0, 20.0), -10.0, BodyKind::Static),
+                body("Wheel", Shape::Ellipse(10.0, 10.0), 4.99, BodyKind::Dynamic),
+                body("Driver", Shape::Ellipse(10.0, 10.0), 100.0, BodyKind::Kinematic),
+            ],
+            Environment::build(
+                vec!["a_Wheel=-100*hatj", "omega_Driver=2*hatk"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(Dispatch::default()),
+            1.0 / 60.0,
+            0.0,
+        );
+        let [wheel, driver] = ["Wheel", "Driver"].map(|x| engine.id_of(x).unwrap());
+        engine.add_gear(Gear::new(wheel, driver, 1.0));
+
+        for _ in 0..60 {
+            engine.tick().unwrap();
+
+            // The gear holds even though friction pushes back on the wheel's spin, only giving a
+            // little the tick the wheel stops sliding, where resolving the friction after the
+            // gear would let it slip by a third
+            let omega = *engine.bodies()[1].angular.velocity.get(0);
+            assert!((omega + 2.0).abs() < 0.05, "{}", omega);
+        }
+        // And the wheel rolls along the floor rather than spinning in place, at its radius of 5
+        // times its spin
+        let wheel = &engine.bodies()[1];
+        assert!(*wheel.linear.velocity.get(0) > 9.0, "{:?}", wheel.linear.velocity);
+    }
+
+    #[test]
+    fn test_rolling_resistance() {
+        let run = |coefficient: f64| {
+            // The vehicle's rear wheel, spinning in place on its floor. Made round, since a
+            // polygon spinning on a flat floor knocks against it with every vertex
+            let bodies = scenarios::vehicle().bodies;
+            let mut wheel = bodies[2].clone();
+            wheel.shape = Shape::Ellipse(40.0, 40.0);
+            wheel.linear.displacement = Column::vector([0.0, -269.0]);
+            wheel.angular.velocity = Column::vector([5.0]);
+
+            let mut engine = Engine::new(
+                vec![bodies[0].clone(), wheel],
+                Environment::build(
+                    vec!["a_RearWheel=-100*hatj"],
+                    builtin::functions(),
+                    builtin::constants(),
+                )
+                .unwrap(),
+                Box::new(Dispatch::default()),
+                1.0 / 60.0,
+                0.2,
+            );
+            engine.set_rolling_resistance(coefficient);
+            for _ in 0..600 {
+                engine.tick().unwrap();
+            }
+
+            *engine.bodies()[1].angular.velocity.get(0)
+        };
+
+        assert!(run(0.0) > 1.0);
+        let omega = run(0.1);
+        assert!((0.0..0.01).contains(&omega), "{}", omega);
+    }
+
+    #[test]
+    fn test_invalid_tick() {
+        let mut engine = falling_engine();
+        let body = engine.bodies()[0].id.unwrap();
+        engine.schedule(
+            At::Tick(0),
+            Command::Impulse {
+                body,
+                impulse: Column::vector([1.0, 0.0]),
+            },
+        );
+        engine.bodies_mut()[0].properties.mass = -1.0;
+        let velocity = engine.bodies()[0].linear.velocity.clone();
+
+        // Left invalid since the last tick, so nothing is run
+        assert!(engine.tick().is_err());
+        assert_eq!(engine.scheduled(), 1);
+        assert_eq!(engine.ticks(), 0);
+        assert_eq!(engine.bodies()[0].linear.velocity, velocity);
+
+        engine.bodies_mut()[0].properties.mass = 1.0;
+        engine.tick().unwrap();
+        assert_eq!(engine.scheduled(), 0);
+    }
+
+    #[test]
+    fn test_validate_bodies() {
+        let body = |shape: Shape<Space2D>, properties: BodyProperties| {
+            Body::at_rest(
+                "A".to_string(),
+                shape,
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                properties,
+            )
+        };
+        let unit = || BodyProperties::rectangle(1.0, 1.0, 1.0);
+        let outline = |points: &[[f64; 2]]| {
+            Shape::Manifold(points.iter().map(|x| Column::vector(*x)).collect())
+        };
+
+        assert!(body(Shape::Rec(1.0, 1.0), unit()).validate().is_ok());
+        assert!(body(Shape::Rec(1.0, 1.0), BodyProperties::weightless()).validate().is_ok());
+        let triangle = [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]];
+        assert!(body(outline(&triangle), unit()).validate().is_ok());
+
+        let invalid = [
+            body(Shape::Rec(0.0, 1.0), unit()),
+            body(Shape::Ellipse(1.0, f64::NAN), unit()),
+            body(outline(&triangle[..2]), unit()),
+            // Clockwise
+            body(outline(&[[0.0, 0.0], [0.0, 1.0], [1.0, 0.0]]), unit()),
+            // Dented
+            body(
+                outline(&[[0.0, 0.0], [2.0, 0.0], [1.0, 0.5], [2.0, 2.0], [0.0, 2.0]]),
+                unit(),
+            ),
+            body(Shape::Rec(1.0, 1.0), BodyProperties::rectangle(-1.0, 1.0, 1.0)),
+            body(
+                Shape::Rec(1.0, 1.0),
+                BodyProperties::rectangle(1.0, 0.0, 0.0),
+            ),
+        ];
+        for body in invalid {
+            let error = body.validate().unwrap_err();
+            assert!(matches!(error.kind, ErrorKind::InvalidBody { .. }), "{}", error);
+        }
+
+        // Shapes on their own, with what's wrong with them
+        let reason = |points: &[[f64; 2]]| match outline(points).validate() {
+            Err(Error {
+                kind: ErrorKind::InvalidShape(x),
+                ..
+            }) => x,
+            x => panic!("{:?}", x.map(|_| ())),
+        };
+        assert!(reason(&triangle[..2]).contains("at least 3 points"));
+        let square = [[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [2.0, 2.0], [0.0, 2.0]];
+        assert!(reason(&square).contains("point 1 is in line"));
+        assert!(reason(&[[0.0, 0.0], [1.0, 0.0], [1.0, 0.0], [0.0, 1.0]]).contains("in line"));
+        // Each point joined to the one two round, so it goes round twice
+        let star = (0..5)
+            .map(|i| {
+                let angle = 4.0 * PI * i as f64 / 5.0;
+                [angle.cos(), angle.sin()]
+            })
+            .collect::<Vec<_>>();
+        assert!(reason(&star).contains("cross"));
+        assert!(outline(&triangle).validate().is_ok());
+
+        // Checked again before the next tick after the bodies change
+        let mut engine = falling_engine();
+        engine.tick().unwrap();
+        engine.bodies_mut()[0].shape = Shape::Rec(1.0, -1.0);
+        assert!(engine.tick().is_err());
+        assert_eq!(engine.time(), 0.1);
+    }
+
+    #[test]
+    fn test_space_3d() {
+        use crate::collide::NoCollide;
+        use crate::spaces::Space3D;
+
+        let (i, j, k) = (
+            Column::vector([1.0, 0.0, 0.0]),
+            Column::vector([0.0, 1.0, 0.0]),
+            Column::vector([0.0, 0.0, 1.0]),
+        );
+        assert_eq!(Space3D::cross_linear(&i, &j), k);
+        let turned = Space3D::rotate(&k.scale(PI / 2.0), &i);
+        assert!(turned.plus(&j.scale(-1.0)).magnitude() < 1e-12, "{}", turned);
+
+        let body = Body::<Space3D>::at_rest(
+            "A".to_string(),
+            Shape::Ellipse(1.0, 1.0),
+            Column::vector([0.0, 0.0, 0.0]),
+            Column::vector([0.0, 0.0, 0.0]),
+            BodyProperties::rectangle(1.0, 1.0, 1.0),
+        );
+        let mut engine = Engine::new(
+            vec![body],
+            Environment::build(
+                vec!["a_A=-10*hatk", "omega_A=2*hatj"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(NoCollide),
+            0.1,
+            1.0,
+        );
+        for _ in 0..10 {
+            engine.tick().unwrap();
+        }
+
+        let body = &engine.bodies()[0];
+        // Falls just as it does in 2D
+        let mut flat = falling_engine();
+        for _ in 0..10 {
+            flat.tick().unwrap();
+        }
+        assert_eq!(body.linear.displacement.get(2), flat.bodies()[0].linear.displacement.get(1));
+        assert_eq!(*body.linear.displacement.get(0), 0.0);
+        assert!((engine.probe("theta_y_A").unwrap() - 2.0).abs() < 1e-9);
+        assert_eq!(engine.probe("theta_z_A").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_space_3d_changing_axis() {
+        use crate::collide::NoCollide;
+        use crate::spaces::Space3D;
+
+        let body = Body::<Space3D>::at_rest(
+            "A".to_string(),
+            Shape::Ellipse(1.0, 1.0),
+            Column::vector([0.0, 0.0, 0.0]),
+            Column::vector([0.0, 0.0, 0.0]),
+            BodyProperties::rectangle(1.0, 1.0, 1.0),
+        );
+        let mut engine = Engine::new(
+            vec![body],
+            Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+            Box::new(NoCollide),
+            0.1,
+            1.0,
+        );
+
+        // A quarter turn about x, then one about y
+        for omega in [[5.0 * PI, 0.0, 0.0], [0.0, 5.0 * PI, 0.0]] {
+            engine.bodies_mut()[0].angular.velocity = Column::vector(omega);
+            engine.tick().unwrap();
+        }
+
+        // Which takes y to z and then z to x, where adding the turns up would have left it
+        // pointing somewhere in between
+        let rotation = &engine.bodies()[0].angular.displacement;
+        let turned = Space3D::rotate(rotation, &Column::vector([0.0, 1.0, 0.0]));
+        let x = Column::vector([1.0, 0.0, 0.0]);
+        assert!(turned.plus(&x.scale(-1.0)).magnitude() < 1e-9, "{}", turned);
+        let summed = Column::vector([PI / 2.0, PI / 2.0, 0.0]);
+        let wrong = Space3D::rotate(&summed, &Column::vector([0.0, 1.0, 0.0]));
+        assert!(wrong.plus(&x.scale(-1.0)).magnitude() > 0.1, "{}", wrong);
+    }
+
+    #[test]
+    fn test_probe() {
+        let mut engine = falling_engine();
+        engine.bodies_mut()[0].linear.velocity = Column::vector([3.0, 4.0]);
+        assert_eq!(engine.probe("0.5*m_A*(v_x_A^2+v_y_A^2)").unwrap(), 12.5);
+        // Equations and constants are available just as they are to each other
+        engine.set_environment(
+            Environment::build(
+                vec!["a_A=-g*hatj", "g=10"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+        );
+        assert_eq!(engine.probe("m_A*g").unwrap(), 10.0);
+        assert_eq!(engine.probe("y_A+pi").unwrap(), std::f64::consts::PI);
+
+        engine.tick().unwrap();
+        assert_eq!(engine.probe("y_A").unwrap(), *engine.bodies()[0].linear.displacement.get(1));
+        assert!(engine.probe("v_x_B").is_err());
+    }
+
+    #[test]
+    fn test_state_hash() {
+        let (mut a, mut b) = (falling_engine(), falling_engine());
+        assert_eq!(a.state_hash(), b.state_hash());
+        // A known value, so the hash can't change between builds unnoticed
+        assert_eq!(a.state_hash(), 11644481910610191236);
+
+        for _ in 0..10 {
+            a.tick().unwrap();
+            b.tick().unwrap();
+        }
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        b.bodies_mut()[0].linear.velocity = Column::vector([1e-12, 0.0]);
+        assert_ne!(a.state_hash(), b.state_hash());
+        b.bodies_mut()[0].linear.velocity = a.bodies()[0].linear.velocity.scale(1.0);
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        b.bodies_mut()[0].name = "B".to_string();
+        assert_ne!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        for _ in 0..20 {
+            engine.tick().unwrap();
+        }
+        let state = engine.snapshot();
+        for _ in 0..30 {
+            engine.tick().unwrap();
+        }
+        let (hash, time) = (engine.state_hash(), engine.time());
+
+        // Going back and running the same ticks again ends up in the same place
+        engine.restore(state);
+        assert_eq!(engine.ticks(), 20);
+        for _ in 0..30 {
+            engine.tick().unwrap();
+        }
+        assert_eq!(engine.state_hash(), hash);
+        assert_eq!(engine.time(), time);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let run = || {
+            let mut engine = scenarios::stacked_boxes().build().unwrap();
+            engine.set_deterministic(true);
+            engine.set_seed(7);
+            let id = engine.bodies()[1].id.unwrap();
+            engine.set_noise(id, Some(Noise::White { strength: 5.0 }));
+            engine.set_broadphase(Box::new(SpatialHash::new(20.0)));
+
+            let mut ticks = (0..100)
+                .map(|_| {
+                    let tick = engine.tick().unwrap();
+                    format!("{:?} {:?}", tick.stats, tick.collisions)
+                })
+                .collect::<Vec<_>>();
+            ticks.push(engine.state_hash().to_string());
+            ticks
+        };
+
+        // Everything down to the stats, which take no time
+        let ticks = run();
+        assert_eq!(ticks, run());
+        assert!(ticks[0].contains("evaluation_time: 0.0, collision_time: 0.0"));
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn test_parallel() {
+        let run = |parallel: bool| {
+            let mut engine = scenarios::pyramid(6).build().unwrap();
+            engine.set_parallel(parallel);
+            engine.set_integrator(Integrator::RungeKutta4);
+
+            let contacts = (0..100)
+                .map(|_| engine.tick().unwrap().collisions)
+                .collect::<Vec<_>>();
+            (contacts, engine.state_hash())
+        };
+
+        // Bit for bit the same, contacts included
+        assert_eq!(run(true), run(false));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_state() {
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        for _ in 0..20 {
+            engine.tick().unwrap();
+        }
+        let json = serde_json::to_string(&engine.snapshot()).unwrap();
+        let state: EngineState<Space2D> = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&state).unwrap(), json);
+
+        // Restored into a fresh engine, it carries on just as the one it was saved from
+        let mut restored = scenarios::newtons_cradle().build().unwrap();
+        restored.restore(state);
+        assert_eq!(restored.state_hash(), engine.state_hash());
+        for _ in 0..30 {
+            engine.tick().unwrap();
+            restored.tick().unwrap();
+        }
+        assert_eq!(restored.state_hash(), engine.state_hash());
+
+        let shape: Shape<Space2D> = serde_json::from_str(r#"{"Manifold":[[0,0],[1,0],[0,1]]}"#)
+            .unwrap();
+        assert_eq!(shape.area(), 0.5);
+        assert!(serde_json::from_str::<Column<2>>("[1.0, 2.0, 3.0]").is_err());
+    }
+
+    #[test]
+    fn test_body_lookup() {
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        assert_eq!(engine.index_of("BallB"), Some(2));
+        assert!(engine.body("Ball").is_none());
+
+        engine.body_mut("BallB").unwrap().properties.tag = Some("Middle".to_string());
+        engine.body_mut("BallC").unwrap().properties.tag = Some("Middle".to_string());
+        let tagged = engine.tagged("Middle").map(|(x, _)| x).collect::<Vec<_>>();
+        assert_eq!(tagged, ["BallB", "BallC"].map(|x| engine.id_of(x).unwrap()));
+
+        // The striker and the first two balls, by their centroids
+        let left = Region::Box {
+            min: Column::vector([-500.0, -100.0]),
+            max: Column::vector([50.0, 100.0]),
+        };
+        let names = engine.within(&left).map(|(_, x)| x.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Striker", "BallA", "BallB"]);
+
+        let moving = engine.bodies_where(|x| x.linear.velocity.magnitude() > 0.0);
+        assert_eq!(moving.count(), 1);
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let mut engine = falling_engine();
+        let name = engine.unique_name("A");
+        assert_eq!(name, "AB");
+        assert_eq!(engine.unique_name("B"), "B");
+
+        let mut copy = engine.bodies()[0].clone();
+        engine.bodies_mut().push(copy.clone());
+        match engine.tick() {
+            Err(error) => assert!(matches!(error.kind, ErrorKind::DuplicateName(x) if x == "A")),
+            Ok(_) => panic!("Duplicate names weren't caught"),
+        }
+
+        copy.name = name;
+        engine.bodies_mut()[1] = copy;
+        assert!(engine.tick().is_ok());
+        assert_eq!(engine.unique_name("A"), "AC");
+    }
+
+    #[test]
+    fn test_body_ids() {
+        let mut engine = falling_engine();
+        let a = engine.bodies()[0].id.unwrap();
+
+        let mut b = engine.bodies()[0].clone();
+        b.name = "B".to_string();
+        let b = engine.add_body(b);
+        assert_ne!(a, b);
+        assert_eq!(engine.get(b).unwrap().name, "B");
+
+        // Kept through renaming and removing the bodies before it
+        engine.get_mut(b).unwrap().name = "C".to_string();
+        engine.bodies_mut().remove(0);
+        assert_eq!(engine.index_of_id(b), Some(0));
+        assert_eq!(engine.get(a).map(|x| x.name.as_str()), None);
+
+        // A copy pushed by hand is given its own, and unnamed bodies don't clash
+        let mut copy = engine.bodies()[0].clone();
+        copy.name = String::new();
+        engine.bodies_mut().push(copy.clone());
+        engine.bodies_mut().push(copy);
+        engine.tick().unwrap();
+        let ids = engine.bodies().iter().map(|x| x.id.unwrap()).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), 3);
+        assert!(!ids.contains(&a));
+    }
+
+    #[test]
+    fn test_box_settles_flat() {
+        // Dropped tilted, it lands on a corner and should then come to rest on its face rather
+        // than rock about the middle of it
+        let mut scenario = scenarios::pyramid(1);
+        scenario.bodies[1].angular.displacement = Column::vector([0.1]);
+        let mut engine = scenario.build().unwrap();
+        for tick in 0..600 {
+            engine.tick().unwrap();
+            let body = &engine.bodies()[1];
+            if tick >= 100 {
+                assert!(body.angular.velocity.get(0).abs() < 0.01, "{}", tick);
+                assert!(body.angular.displacement.get(0).abs() < 0.01, "{}", tick);
+            }
+        }
+    }
+
+    #[test]
+    fn test_warm_starting() {
+        let run = |warm: bool| {
+            let mut engine = scenarios::pyramid(1).build().unwrap();
+            engine.set_warm_starting(warm);
+            let (mut depth, mut carried) = (0.0f64, 0.0);
+            for tick in 0..600 {
+                let stats = engine.tick().unwrap().stats;
+                if tick >= 200 {
+                    depth = depth.max(stats.max_penetration);
+                    let bodies = engine.bodies();
+                    carried += engine.pairs.impulses(&bodies[0], &bodies[1]).iter().sum::<f64>()
+                        / 400.0;
+                }
+            }
+            (depth, carried)
+        };
+
+        let (cold, nothing) = run(false);
+        let (warm, carried) = run(true);
+        assert!(warm < cold, "{} {}", warm, cold);
+        assert_eq!(nothing, 0.0);
+        // Holding the box up against gravity, m * g * delta_t on average
+        assert!((carried - 100.0 / 60.0).abs() < 0.2, "{}", carried);
+    }
+
+    #[test]
+    fn test_correction() {
+        let run = |correction: Correction| {
+            // Sunk 0.1 into a static floor, with nothing pulling it down
+            let mut engine = falling_engine();
+            engine.set_environment(
+                Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+            );
+            let mut floor = engine.bodies()[0].clone();
+            floor.name = "Floor".to_string();
+            floor.shape = Shape::Rec(10.0, 1.0);
+            floor.linear.displacement = Column::vector([0.0, -0.9]);
+            floor.properties.kind = BodyKind::Static;
+            engine.add_body(floor);
+            engine.set_correction(correction);
+            engine.tick().unwrap();
+
+            // Nothing was moving, so nothing should be afterwards either
+            assert!(engine.bodies().iter().all(|x| x.linear.velocity.magnitude() == 0.0));
+            *engine.bodies()[0].linear.displacement.get(1)
+        };
+
+        // All of it is removed at once by default
+        let risen = run(Correction::default());
+        assert!((risen - 0.1).abs() < 1e-9, "{}", risen);
+        let risen = run(Correction {
+            slop: 0.02,
+            factor: 0.5,
+        });
+        assert!((risen - 0.04).abs() < 1e-9, "{}", risen);
+        assert_eq!(run(Correction { slop: 0.5, factor: 1.0 }), 0.0);
+    }
+
+    #[test]
+    fn test_materials() {
+        let concrete = Material {
+            restitution: 0.1,
+            friction: 0.8,
+            density: 0.05,
+        };
+        let rubber = Material {
+            restitution: 0.9,
+            friction: 0.9,
+            density: 0.01,
+        };
+        let clay = Material {
+            restitution: 0.0,
+            friction: 0.5,
+            density: 0.02,
+        };
+
+        // How fast a ball and a lump of clay dropped onto a concrete floor bounce back up
+        let run = |combine: Combine| {
+            let body = |name: &str, x: f64, y: f64, shape: Shape<Space2D>, material| {
+                let properties = BodyProperties::from_material(material, &shape);
+                Body::at_rest(
+                    name.to_string(),
+                    shape,
+                    Column::vector([x, y]),
+                    Column::vector([0.0]),
+                    properties,
+                )
+            };
+            let mut floor = body("Floor", 0.0, -100.0, Shape::Rec(400.0, 20.0), concrete);
+            floor.properties.kind = BodyKind::Static;
+            let ball = body("Ball", -50.0, 0.0, Shape::Ellipse(20.0, 20.0), rubber);
+            assert_eq!(ball.properties.mass, 0.01 * ball.shape.area());
+
+            let mut engine = Engine::new(
+                vec![floor, ball, body("Clay", 50.0, 0.0, Shape::Ellipse(20.0, 20.0), clay)],
+                Environment::build(
+                    vec!["a_Ball=-100*hatj", "a_Clay=-100*hatj"],
+                    builtin::functions(),
+                    builtin::constants(),
+                )
+                .unwrap(),
+                Box::new(Dispatch::default()),
+                1.0 / 60.0,
+                1.0,
+            );
+            engine.set_combine(combine, Combine::GeometricMean);
+
+            let mut rebound = [0.0f64; 2];
+            for _ in 0..90 {
+                engine.tick().unwrap();
+                for (i, x) in rebound.iter_mut().enumerate() {
+                    *x = x.max(*engine.bodies()[i + 1].linear.velocity.get(1));
+                }
+            }
+            rebound
+        };
+
+        // They land at about 126, the ball bounces back with its own restitution and the clay
+        // with the floor's
+        let [ball, clay] = run(Combine::Max);
+        assert!((100.0..126.0).contains(&ball), "{}", ball);
+        assert!((5.0..20.0).contains(&clay), "{}", clay);
+
+        let [ball, clay] = run(Combine::Min);
+        assert!((5.0..20.0).contains(&ball), "{}", ball);
+        assert!(clay < 1.0, "{}", clay);
+    }
+
+    #[test]
+    fn test_query_point() {
+        let mut engine = falling_engine();
+        // Turned 45 degrees, so it reaches further along the axes and less far along diagonals
+        let mut b = engine.bodies()[0].clone();
+        b.name = "B".to_string();
+        b.linear.displacement = Column::vector([2.0, 0.0]);
+        b.angular.displacement = Column::vector([PI / 4.0]);
+        let b = engine.add_body(b);
+        let a = engine.bodies()[0].id.unwrap();
+
+        assert_eq!(engine.query_point(&Column::vector([0.1, -0.4])), vec![a]);
+        assert_eq!(engine.query_point(&Column::vector([2.6, 0.0])), vec![b]);
+        assert!(engine.query_point(&Column::vector([2.45, 0.45])).is_empty());
+        assert!(engine.query_point(&Column::vector([1.0, 0.0])).is_empty());
+    }
+
+    #[test]
+    fn test_raycast() {
+        // A floor of a thousand static tiles with a box over the middle of it
+        let tile = |i: usize| {
+            Body::at_rest(
+                scenarios::label("Tile", i),
+                Shape::Rec(1.0, 1.0),
+                Column::vector([i as f64, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::weightless().with_kind(BodyKind::Static),
+            )
+        };
+        let mut bodies = (0..1000).map(tile).collect::<Vec<_>>();
+        bodies.push(Body::at_rest(
+            "A".to_string(),
+            Shape::Rec(1.0, 1.0),
+            Column::vector([500.0, 3.0]),
+            Column::vector([0.0]),
+            BodyProperties::rectangle(1.0, 1.0, 1.0),
+        ));
+        let env = Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap();
+        let mut engine = Engine::<Space2D>::new(bodies, env, Box::new(Collide2D::new()), 0.1, 1.0);
+        let down = Column::vector([0.0, -1.0]);
+        let cast = |engine: &Engine<Space2D>, x: f64, length: f64| {
+            let hit = engine.raycast(&Column::vector([x, 10.0]), &down, length)?;
+            assert!((hit.point[1] - (10.0 - hit.distance)).abs() < 1e-9);
+            Some((engine.index_of_id(hit.body)?, (hit.distance * 1e6).round() / 1e6))
+        };
+
+        // Every body is tested until the engine is validated, the tree after
+        assert_eq!(cast(&engine, 500.0, 100.0), Some((1000, 6.5)));
+        engine.validate().unwrap();
+        assert_eq!(cast(&engine, 500.0, 100.0), Some((1000, 6.5)));
+        assert_eq!(cast(&engine, 700.2, 100.0), Some((700, 9.5)));
+        assert_eq!(cast(&engine, 700.2, 9.0), None);
+        assert_eq!(cast(&engine, 1200.0, 100.0), None);
+        let inside = engine.raycast(&Column::vector([3.0, 0.0]), &Column::vector([1.0, 0.0]), 1.0);
+        let inside = inside.map(|x| (engine.index_of_id(x.body), x.distance));
+        assert_eq!(inside, Some((Some(3), 0.0)));
+
+        // A tile moved by hand is found where it's been moved to
+        engine.bodies_mut()[700].linear.displacement = Column::vector([700.0, 5.0]);
+        engine.validate().unwrap();
+        assert_eq!(cast(&engine, 700.2, 100.0), Some((700, 4.5)));
+        let moved = engine.bodies()[700].id.unwrap();
+        assert_eq!(engine.query_point(&Column::vector([700.0, 5.2])), vec![moved]);
+        let region = Region::Box {
+            min: Column::vector([699.0, -1.0]),
+            max: Column::vector([701.0, 1.0]),
+        };
+        let within = engine.within(&region).map(|(x, _)| engine.index_of_id(x));
+        assert_eq!(within.collect::<Vec<_>>(), vec![Some(699), Some(701)]);
+    }
+
+    #[test]
+    fn test_delta_t_checked() {
+        let mut engine = falling_engine();
+        let delta_t = engine.delta_t();
+        for x in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let e = engine.set_delta_t(x).unwrap_err();
+            assert!(matches!(e.kind, ErrorKind::InvalidTimeStep(_)), "{}", x);
+        }
+        assert_eq!(engine.delta_t(), delta_t);
+        engine.set_delta_t(1e300).unwrap();
+
+        let mut scenario = scenarios::stacked_boxes();
+        scenario.delta_t = f64::NAN;
+        assert!(scenario.build().is_err());
+    }
+
+    #[test]
+    fn test_substeps() {
+        // A spring far too stiff for the time step, leapfrog blows up once its angular frequency
+        // times the step is past 2 (here it's 3.3)
+        let run = |substeps: u32| {
+            let mut engine = falling_engine();
+            engine.set_environment(
+                Environment::build(
+                    vec!["a_A=-40000*x_A*hati"],
+                    builtin::functions(),
+                    builtin::constants(),
+                )
+                .unwrap(),
+            );
+            engine.bodies_mut()[0].linear.displacement = Column::vector([1.0, 0.0]);
+            engine.set_delta_t(1.0 / 60.0).unwrap();
+            engine.set_substeps(substeps);
+
+            let mut furthest = 0.0f64;
+            for _ in 0..15 {
+                // It can blow up so far that evaluating it fails
+                if engine.tick().is_err() {
+                    return f64::INFINITY;
+                }
+                furthest = furthest.max(engine.bodies()[0].linear.displacement.get(0).abs());
+            }
+            assert_eq!(engine.ticks(), 15);
+            assert!((engine.time() - 0.25).abs() < 1e-9);
+            furthest
+        };
+
+        assert!(run(1) > 100.0);
+        let furthest = run(8);
+        assert!(furthest < 1.1, "{}", furthest);
+    }
+
+    #[test]
+    fn test_runge_kutta4() {
+        // A unit spring, which should be at cos(10) after 10 seconds
+        let run = |integrator: Integrator| {
+            let mut engine = falling_engine();
+            engine.set_environment(
+                Environment::build(
+                    vec!["F_A=-m_A*x_A*hati"],
+                    builtin::functions(),
+                    builtin::constants(),
+                )
+                .unwrap(),
+            );
+            engine.bodies_mut()[0].linear.displacement = Column::vector([1.0, 0.0]);
+            engine.set_integrator(integrator);
+            for _ in 0..100 {
+                engine.tick().unwrap();
+            }
+            (engine.bodies()[0].linear.displacement.get(0) - 10.0f64.cos()).abs()
+        };
+
+        let (leapfrog, runge_kutta) = (run(Integrator::Leapfrog), run(Integrator::RungeKutta4));
+        assert!(runge_kutta < 1e-5, "{}", runge_kutta);
+        assert!(leapfrog > 100.0 * runge_kutta, "{} {}", leapfrog, runge_kutta);
+    }
+
+    #[test]
+    fn test_apply_force() {
+        let mut engine = falling_engine();
+        engine.set_environment(
+            Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+        );
+        engine.validate().unwrap();
+        let id = engine.bodies()[0].id.unwrap();
+
+        // Pushed right at its top edge, so it turns clockwise too
+        let body = engine.get_mut(id).unwrap();
+        body.apply_force(Column::vector([10.0, 0.0]), &Column::vector([0.0, 0.5]));
+        engine.tick().unwrap();
+        engine.tick().unwrap();
+
+        let body = engine.get(id).unwrap();
+        let (mass, moi) = (body.properties.mass, body.properties.moi);
+        assert!((body.linear.velocity.get(0) - 10.0 / mass * 0.1).abs() < 1e-9);
+        assert!((body.angular.velocity.get(0) + 5.0 / moi * 0.1).abs() < 1e-9);
+        assert_eq!(body.force, Column::vector([0.0, 0.0]));
+
+        // The same change over the next tick, however it's split up
+        let at = body.linear.displacement.plus(&Column::vector([0.0, 0.5]));
+        engine.apply_impulse(id, Column::vector([0.5, 0.0]), &at);
+        engine.apply_impulse(id, Column::vector([0.5, 0.0]), &at);
+        assert!((engine.get(id).unwrap().linear.velocity.get(0) - 1.0 / mass).abs() < 1e-9);
+        engine.set_substeps(4);
+        engine.tick().unwrap();
+        let body = engine.get(id).unwrap();
+        assert!((body.linear.velocity.get(0) - 2.0 / mass).abs() < 1e-9);
+        assert!((body.angular.velocity.get(0) + 1.0 / moi).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_body_kinds() {
+        let mut engine = falling_engine();
+        engine.bodies_mut()[0].properties.kind = BodyKind::Static;
+        engine.bodies_mut()[0].linear.velocity = Column::vector([5.0, 0.0]);
+        engine.tick().unwrap();
+        assert_eq!(engine.bodies()[0].linear.displacement, Column::vector([0.0, 0.0]));
+
+        // A kinematic paddle sweeping into a box knocks it away without slowing down
+        let mut engine = falling_engine();
+        engine.set_environment(
+            Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+        );
+        let mut paddle = engine.bodies()[0].clone();
+        paddle.name = "B".to_string();
+        paddle.properties.kind = BodyKind::Kinematic;
+        paddle.linear.displacement = Column::vector([-1.5, 0.0]);
+        paddle.linear.velocity = Column::vector([10.0, 0.0]);
+        engine.add_body(paddle);
+        for _ in 0..3 {
+            engine.tick().unwrap();
+        }
+        assert!(*engine.bodies()[0].linear.velocity.get(0) > 0.0);
+        assert_eq!(engine.bodies()[1].linear.velocity, Column::vector([10.0, 0.0]));
+    }
+
+    #[test]
+    fn test_from_density() {
+        let rectangle = BodyProperties::from_density(2.0, &Shape::Rec(3.0, 4.0));
+        let expected = BodyProperties::rectangle(24.0, 3.0, 4.0);
+        assert_eq!(rectangle.mass, expected.mass);
+        assert!((rectangle.moi - expected.moi).abs() < 1e-9);
+
+        // The same rectangle as a manifold
+        let outline = Shape::convex(
+            [[1.5, 2.0], [-1.5, 2.0], [-1.5, -2.0], [1.5, -2.0]]
+                .map(Column::vector)
+                .to_vec(),
+        );
+        let manifold = BodyProperties::from_density(2.0, &outline);
+        assert!((manifold.mass - 24.0).abs() < 1e-9);
+        assert!((manifold.moi - expected.moi).abs() < 1e-9);
+        assert_eq!(outline.centroid(), Column::vector([0.0, 0.0]));
+
+        // A right triangle with legs 3 and 6 well away from the origin, m*(a^2+b^2)/18 about its
+        // centroid
+        let triangle = [[10.0, 10.0], [13.0, 10.0], [10.0, 16.0]].map(Column::vector);
+        let properties = BodyProperties::from_polygon(2.0, &triangle);
+        assert_eq!(properties.mass, 2.0);
+        assert!((properties.moi - 2.0 * 45.0 / 18.0).abs() < 1e-9, "{}", properties.moi);
+        let reversed = triangle.iter().rev().cloned().collect::<Vec<_>>();
+        assert!((BodyProperties::from_polygon(2.0, &reversed).moi - properties.moi).abs() < 1e-9);
+        // Centred, from_density agrees
+        let centred = Shape::Manifold(triangle.to_vec()).centred();
+        assert_eq!(centred.centroid(), Column::vector([0.0, 0.0]));
+        let dense = BodyProperties::from_density(2.0 / 9.0, &centred);
+        assert!((dense.moi - properties.moi).abs() < 1e-9);
+
+        // A disc of radius 1, m*r^2/2
+        let disc = BodyProperties::from_density(1.0, &Shape::Ellipse(2.0, 2.0));
+        assert!((disc.mass - PI).abs() < 1e-9);
+        assert!((disc.moi - PI / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_standard_moments() {
+        let close = |x: BodyProperties, y: BodyProperties| {
+            assert!((x.mass - y.mass).abs() < 1e-9 && (x.moi - y.moi).abs() < 1e-9);
+        };
+
+        close(
+            BodyProperties::circle(PI, 1.0),
+            BodyProperties::from_density(1.0, &Shape::Ellipse(2.0, 2.0)),
+        );
+        close(BodyProperties::rod(3.0, 2.0), BodyProperties::rectangle(3.0, 2.0, 0.0));
+        let side = 2.0;
+        let corners = [0.0, 1.0, 2.0].map(|i: f64| {
+            let angle = 2.0 * PI * i / 3.0;
+            // Circumradius of an equilateral triangle
+            Column::vector([angle.cos(), angle.sin()]).scale(side / 3.0f64.sqrt())
+        });
+        close(
+            BodyProperties::triangle(5.0, side),
+            BodyProperties::from_polygon(5.0, &corners),
+        );
+
+        // A ring is an annulus with no thickness, and a disc one with no hole
+        assert_eq!(BodyProperties::ring(2.0, 3.0).moi, 18.0);
+        assert_eq!(BodyProperties::annulus(2.0, 1.0, 3.0).moi, 10.0);
+        assert_eq!(BodyProperties::sphere(5.0, 1.0).moi, 2.0);
+        assert!((BodyProperties::shell(3.0, 1.0).moi - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_tick_stats() {
+        let mut engine = falling_engine();
+
+        let tick = engine.tick().unwrap();
+        assert!(tick.stats.root_iterations > 0);
+        assert_eq!(tick.stats.pairs_tested, 0);
+        assert_eq!(tick.stats.contacts_resolved, 0);
+        assert_eq!(tick.stats.max_penetration, 0.0);
+
+        // Until the striker reaches the first ball
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        let tick = (0..120)
+            .map(|_| engine.tick().unwrap())
+            .find(|x| x.stats.contacts_resolved > 0)
+            .unwrap();
+        assert!(tick.stats.max_penetration > 0.0);
+        // At least one correction for every contact
+        assert!(tick.stats.correction_iterations >= tick.stats.contacts_resolved);
+    }
+}
@@ -0,0 +1,91 @@
+//! Hooks into the stages of a tick, for game code that has to react to the simulation as it runs
+//! rather than poll the bodies between ticks (see `Engine::set_listener`).
+
+use crate::behavior::TickContext;
+use crate::events::CollisionEvent;
+use crate::{Body, Space, Tick};
+
+/// Called by the engine as it runs each tick. Every method does nothing by default, so a listener
+/// only implements the stages it cares about.
+pub trait EngineListener<S: Space> {
+    /// Before the tick's behaviors and equations, once scheduled commands have run. Free to
+    /// change the bodies, which are checked again afterwards and whose changes the equations see.
+    fn pre_tick(&mut self, _bodies: &mut [Body<S>], _ctx: &TickContext) {}
+
+    /// Once the bodies have been moved by their equations, before their joints and contacts are
+    /// solved. Called for every substep, with the time and `delta_t` of that substep.
+    fn post_integrate(&mut self, _bodies: &[Body<S>], _ctx: &TickContext) {}
+
+    /// For each contact once they've all been solved, with the same event as in
+    /// [`Tick::collision_events`].
+    fn on_collision(&mut self, _event: &CollisionEvent<S>) {}
+
+    /// Once the tick is over, with everything it reports.
+    fn post_tick(&mut self, _bodies: &[Body<S>], _tick: &Tick<S>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector;
+    use crate::scenarios;
+    use crate::spaces::Space2D;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    // Writes down every call it gets
+    struct Log(Rc<RefCell<Vec<String>>>);
+
+    impl EngineListener<Space2D> for Log {
+        fn pre_tick(&mut self, bodies: &mut [Body<Space2D>], ctx: &TickContext) {
+            self.0.borrow_mut().push(format!("pre {}", ctx.ticks));
+            // Sets the striker going twice as fast before the first tick
+            if ctx.ticks == 0 {
+                bodies[0].linear.velocity = bodies[0].linear.velocity.scale(2.0);
+            }
+        }
+
+        fn post_integrate(&mut self, _: &[Body<Space2D>], ctx: &TickContext) {
+            self.0.borrow_mut().push(format!("integrated {}", ctx.time));
+        }
+
+        fn on_collision(&mut self, _: &CollisionEvent<Space2D>) {
+            self.0.borrow_mut().push("collided".to_string());
+        }
+
+        fn post_tick(&mut self, _: &[Body<Space2D>], tick: &Tick<Space2D>) {
+            let collisions = tick.collision_events.len();
+            self.0.borrow_mut().push(format!("post {}", collisions));
+        }
+    }
+
+    #[test]
+    fn test_listener() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        let speed = *engine.bodies()[0].linear.velocity.get(0);
+        let delta_t = engine.delta_t();
+        engine.set_listener(Box::new(Log(log.clone())));
+        engine.set_substeps(2);
+
+        engine.tick().unwrap();
+        assert_eq!(*engine.bodies()[0].linear.velocity.get(0), 2.0 * speed);
+        assert_eq!(
+            log.borrow()[..4],
+            [
+                "pre 0".to_string(),
+                "integrated 0".to_string(),
+                format!("integrated {}", delta_t / 2.0),
+                "post 0".to_string(),
+            ]
+        );
+
+        // Every contact reported as it's resolved, before the tick it's in ends
+        while !log.borrow().iter().any(|x| x == "collided") {
+            engine.tick().unwrap();
+        }
+        let log = log.borrow();
+        let collided = log.iter().filter(|x| *x == "collided").count();
+        assert_eq!(log.last(), Some(&format!("post {}", collided)));
+    }
+}
@@ -0,0 +1,132 @@
+//! Expressions flattened into a single arena. Children are referred to by index rather than boxed,
+//! so an environment's equations live in one allocation and evaluating them never clones a tree.
+
+use crate::math::parse::{Node, Operation};
+use crate::math::symbol::Symbol;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeId(u32);
+
+/// The arguments of a function call, a contiguous run of [`Arena::args`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Args {
+    start: u32,
+    len: u32,
+}
+
+/// A [`Node`] whose children are stored in an [`Arena`].
+#[derive(Debug, Clone, Copy)]
+pub enum ArenaNode {
+    Arithmetic {
+        operation: Operation,
+        left: NodeId,
+        right: NodeId,
+    },
+    Number(f64),
+    Variable(Symbol),
+    Function {
+        name: Symbol,
+        args: Args,
+    },
+    Comparison {
+        left: NodeId,
+        right: NodeId,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Arena {
+    nodes: Vec<ArenaNode>,
+    args: Vec<NodeId>,
+}
+
+impl Arena {
+    pub fn push(&mut self, node: ArenaNode) -> NodeId {
+        self.nodes.push(node);
+
+        NodeId(self.nodes.len() as u32 - 1)
+    }
+
+    /// Copies a tree into the arena, returning the id of its root.
+    pub fn insert(&mut self, node: &Node<Symbol>) -> NodeId {
+        let node = match node {
+            Node::Arithmetic {
+                operation,
+                left,
+                right,
+            } => ArenaNode::Arithmetic {
+                operation: *operation,
+                left: self.insert(left),
+                right: self.insert(right),
+            },
+            Node::Number(x) => ArenaNode::Number(*x),
+            Node::Variable(name) => ArenaNode::Variable(*name),
+            Node::Function { name, args } => {
+                // Arguments may be calls themselves, so they all go in before any is listed
+                let ids = args.iter().map(|x| self.insert(x)).collect::<Vec<_>>();
+                let start = self.args.len() as u32;
+                self.args.extend(ids);
+
+                ArenaNode::Function {
+                    name: *name,
+                    args: Args {
+                        start,
+                        len: args.len() as u32,
+                    },
+                }
+            }
+            Node::Comparison { left, right } => ArenaNode::Comparison {
+                left: self.insert(left),
+                right: self.insert(right),
+            },
+        };
+
+        self.push(node)
+    }
+
+    pub fn get(&self, id: NodeId) -> &ArenaNode {
+        &self.nodes[id.0 as usize]
+    }
+
+    pub fn args(&self, args: Args) -> &[NodeId] {
+        &self.args[args.start as usize..(args.start + args.len) as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::parse::{Lexer, parse};
+    use crate::math::symbol::Symbols;
+
+    #[test]
+    fn test_insert() {
+        let mut symbols = Symbols::default();
+        let node = symbols.resolve(parse(Lexer::new("f(x,g(y))+2")).unwrap());
+
+        let mut arena = Arena::default();
+        let root = arena.insert(&node);
+        assert_eq!(arena.len(), 6);
+
+        let ArenaNode::Arithmetic { left, .. } = arena.get(root) else {
+            panic!("Expected addition");
+        };
+        let ArenaNode::Function { name, args } = arena.get(*left) else {
+            panic!("Expected a call");
+        };
+        assert_eq!(symbols.name(*name), "f");
+
+        let args = arena.args(*args);
+        assert_eq!(args.len(), 2);
+        assert!(matches!(arena.get(args[0]), ArenaNode::Variable(x) if symbols.name(*x) == "x"));
+        assert!(matches!(arena.get(args[1]), ArenaNode::Function { .. }));
+    }
+}
@@ -0,0 +1,495 @@
+//! Expressions lowered from the arena into flat programs for a stack machine. Every operation of
+//! a program is in one slice, in the order its values are needed (children before their parent),
+//! so evaluating one is a single loop over it with a stack of numbers, and only calling a function
+//! or solving for a variable goes any deeper.
+//!
+//! Subexpressions that give the same value every time, like `-g/L` given `g=100` and `L=200`, are
+//! compiled into programs of their own, reached through [`Op::Cached`] so the environment can keep
+//! their values from one evaluation to the next.
+
+use crate::math::arena::{Arena, ArenaNode, NodeId};
+use crate::math::parse::Operation;
+use crate::math::solve::Function;
+use crate::math::symbol::{Bindings, Symbol};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Op {
+    Number(f64),
+    Variable(Symbol),
+    /// Combines the two values on top of the stack, the left one below the right.
+    Arithmetic(Operation),
+    /// Calls a function defined by an equation (by its index, see [`Bytecode::function`]) with its
+    /// arguments on top of the stack.
+    Call(u32),
+    /// Calls a baked function with its `args` arguments on top of the stack.
+    Baked {
+        call_site: fn(Vec<f64>) -> f64,
+        args: u32,
+    },
+    /// A subexpression whose value only changes if one of the symbols it depends on does (by its
+    /// index, see [`Bytecode::cached`]).
+    Cached(u32),
+    /// Something that can't be evaluated, failing once it's reached.
+    Fail(Failure),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Failure {
+    UnsatisfiedFunction(Symbol),
+    WrongNumberOfArguments {
+        name: Symbol,
+        expected: usize,
+        found: usize,
+    },
+    UnexpectedComparison,
+}
+
+/// A compiled expression, a run of [`Bytecode::ops`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Program {
+    start: u32,
+    len: u32,
+    // The most values it ever has on the stack at once
+    depth: u32,
+}
+
+impl Program {
+    pub fn depth(&self) -> usize {
+        self.depth as usize
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bytecode {
+    ops: Vec<Op>,
+    // The body and parameters of each function defined by an equation
+    functions: Vec<(Program, Vec<Symbol>)>,
+    // Index of each of those functions by name
+    index: HashMap<Symbol, u32>,
+    // Every subexpression compiled to Op::Cached, with the symbols its value depends on
+    cached: Vec<(Program, Vec<Symbol>)>,
+    // The index of each variable cached on its own, so it has one entry however often it's used
+    // and by whatever
+    variables: HashMap<Symbol, u32>,
+    // Cached subexpressions whose programs are compiled after the one being compiled
+    pending: Vec<(u32, NodeId)>,
+    // What's known never to change: the constants, variables defined by an equation from nothing
+    // else (`g=100`) and functions giving the same value for the same arguments, each with what it
+    // depends on. A variable depends on itself, a function not on its parameters
+    constants: Bindings,
+    fixed: HashMap<Symbol, Vec<Symbol>>,
+    pure: HashMap<Symbol, Vec<Symbol>>,
+}
+
+impl Bytecode {
+    /// Compiles the body of every function in `functions` defined by an equation, for the
+    /// programs compiled after to call. Each of `definitions` is a variable and the expression an
+    /// equation defines it as, the first its value is solved from, so that any defined from
+    /// `constants` alone (or from others that are) can be cached.
+    pub fn new(
+        arena: &Arena,
+        functions: &HashMap<Symbol, Function>,
+        constants: &Bindings,
+        definitions: &[(Symbol, NodeId)],
+    ) -> Self {
+        let mut defined = functions
+            .iter()
+            .filter_map(|(name, x)| match x {
+                Function::Mathematical { node, arg_names } => Some((*name, *node, arg_names)),
+                Function::Baked { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        // Sorted so the same functions are always compiled the same way
+        defined.sort_by_key(|(name, ..)| *name);
+
+        // Every function is listed before any is compiled, as they may call each other
+        let mut code = Bytecode {
+            functions: defined
+                .iter()
+                .map(|(_, _, params)| (Program::default(), params.to_vec()))
+                .collect(),
+            index: defined
+                .iter()
+                .enumerate()
+                .map(|(i, (name, ..))| (*name, i as u32))
+                .collect(),
+            constants: constants.clone(),
+            ..Bytecode::default()
+        };
+
+        // Until nothing more is found to never change, as what is may depend on each other
+        loop {
+            let mut changed = false;
+            for (name, node) in definitions {
+                if !code.fixed.contains_key(name)
+                    && let Some(mut dependencies) = code.invariant(arena, *node, functions, &[])
+                {
+                    dependencies.push(*name);
+                    dependencies.sort();
+                    dependencies.dedup();
+                    code.fixed.insert(*name, dependencies);
+                    changed = true;
+                }
+            }
+            for (name, node, params) in &defined {
+                if !code.pure.contains_key(name)
+                    && let Some(mut dependencies) = code.invariant(arena, *node, functions, params)
+                {
+                    dependencies.retain(|x| !params.contains(x));
+                    code.pure.insert(*name, dependencies);
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        // Each of those variables is cached up front, for anything compiled later to share
+        let mut fixed = code.fixed.keys().copied().collect::<Vec<_>>();
+        fixed.sort();
+        for name in fixed {
+            let start = code.ops.len() as u32;
+            code.ops.push(Op::Variable(name));
+            code.variables.insert(name, code.cached.len() as u32);
+            code.cached.push((
+                Program {
+                    start,
+                    len: 1,
+                    depth: 1,
+                },
+                code.fixed[&name].clone(),
+            ));
+        }
+
+        for (i, (_, node, params)) in defined.into_iter().enumerate() {
+            code.functions[i].0 = code.program(arena, node, functions, params, true);
+        }
+
+        code
+    }
+
+    /// Compiles the expression at `node`, returning the program to run it. Calls are checked
+    /// against `functions` now, and any that can't be made fail once they're reached.
+    pub fn compile(
+        &mut self,
+        arena: &Arena,
+        node: NodeId,
+        functions: &HashMap<Symbol, Function>,
+    ) -> Program {
+        self.program(arena, node, functions, &[], true)
+    }
+
+    // Compiles `node` in a function with `params`, caching what it can if `cache` is set
+    fn program(
+        &mut self,
+        arena: &Arena,
+        node: NodeId,
+        functions: &HashMap<Symbol, Function>,
+        params: &[Symbol],
+        cache: bool,
+    ) -> Program {
+        let start = self.ops.len();
+        self.lower(arena, node, functions, params, cache);
+
+        let (mut height, mut depth) = (0, 0);
+        for op in &self.ops[start..] {
+            height = match op {
+                Op::Number(_) | Op::Variable(_) | Op::Cached(_) => height + 1,
+                Op::Arithmetic(_) => height - 1,
+                Op::Call(i) => height + 1 - self.functions[*i as usize].1.len(),
+                Op::Baked { args, .. } => height + 1 - *args as usize,
+                // Counted as the value it stands in for, though it never gives one
+                Op::Fail(Failure::WrongNumberOfArguments { found, .. }) => height + 1 - found,
+                Op::Fail(_) => height + 1,
+            };
+            depth = depth.max(height);
+        }
+
+        let program = Program {
+            start: start as u32,
+            len: (self.ops.len() - start) as u32,
+            depth: depth as u32,
+        };
+
+        // Never cached within, the whole of each is
+        for (index, node) in std::mem::take(&mut self.pending) {
+            self.cached[index as usize].0 = self.program(arena, node, functions, &[], false);
+        }
+
+        program
+    }
+
+    // Appends the operations of `node`, its children's first
+    fn lower(
+        &mut self,
+        arena: &Arena,
+        node: NodeId,
+        functions: &HashMap<Symbol, Function>,
+        params: &[Symbol],
+        cache: bool,
+    ) {
+        if cache && let Some(index) = self.cache(arena, node, functions, params) {
+            self.ops.push(Op::Cached(index));
+            return;
+        }
+
+        match *arena.get(node) {
+            ArenaNode::Arithmetic {
+                operation,
+                left,
+                right,
+            } => {
+                self.lower(arena, left, functions, params, cache);
+                self.lower(arena, right, functions, params, cache);
+                self.ops.push(Op::Arithmetic(operation));
+            }
+            ArenaNode::Number(x) => self.ops.push(Op::Number(x)),
+            ArenaNode::Variable(name) => self.ops.push(Op::Variable(name)),
+            ArenaNode::Function { name, args } => {
+                // An unknown function fails before its arguments are evaluated, a wrong number of
+                // them after
+                let Some(function) = functions.get(&name) else {
+                    self.ops.push(Op::Fail(Failure::UnsatisfiedFunction(name)));
+                    return;
+                };
+                let args = arena.args(args);
+                for x in args {
+                    self.lower(arena, *x, functions, params, cache);
+                }
+
+                let (expected, op) = match function {
+                    Function::Mathematical { arg_names, .. } => {
+                        (arg_names.len(), Op::Call(self.index[&name]))
+                    }
+                    Function::Baked {
+                        call_site,
+                        expected,
+                    } => (
+                        *expected,
+                        Op::Baked {
+                            call_site: *call_site,
+                            args: *expected as u32,
+                        },
+                    ),
+                };
+                self.ops.push(match args.len() == expected {
+                    true => op,
+                    false => Op::Fail(Failure::WrongNumberOfArguments {
+                        name,
+                        expected,
+                        found: args.len(),
+                    }),
+                });
+            }
+            ArenaNode::Comparison { .. } => {
+                self.ops.push(Op::Fail(Failure::UnexpectedComparison));
+            }
+        }
+    }
+
+    // The index of the entry caching the value at `node`, if it never changes and is worth caching
+    // at all, pending its program if it's new
+    fn cache(
+        &mut self,
+        arena: &Arena,
+        node: NodeId,
+        functions: &HashMap<Symbol, Function>,
+        params: &[Symbol],
+    ) -> Option<u32> {
+        match *arena.get(node) {
+            ArenaNode::Number(_) | ArenaNode::Comparison { .. } => return None,
+            // Those never changing already have entries, and a constant is as quick to look up
+            ArenaNode::Variable(name) if params.contains(&name) => return None,
+            ArenaNode::Variable(name) => return self.variables.get(&name).copied(),
+            _ => {}
+        }
+
+        // A parameter changes with every call
+        let dependencies = self.invariant(arena, node, functions, params)?;
+        if dependencies.iter().any(|x| params.contains(x)) {
+            return None;
+        }
+
+        let index = self.cached.len() as u32;
+        self.cached.push((Program::default(), dependencies));
+        self.pending.push((index, node));
+
+        Some(index)
+    }
+
+    // The symbols the value at `node` depends on, or `None` if it could change even with all of
+    // them the same. `params` are the parameters of the function it's in, depended on like the
+    // rest
+    fn invariant(
+        &self,
+        arena: &Arena,
+        node: NodeId,
+        functions: &HashMap<Symbol, Function>,
+        params: &[Symbol],
+    ) -> Option<Vec<Symbol>> {
+        let mut dependencies = Vec::new();
+        self.depends(arena, node, functions, params, &mut dependencies)
+            .then(|| {
+                dependencies.sort();
+                dependencies.dedup();
+                dependencies
+            })
+    }
+
+    fn depends(
+        &self,
+        arena: &Arena,
+        node: NodeId,
+        functions: &HashMap<Symbol, Function>,
+        params: &[Symbol],
+        dependencies: &mut Vec<Symbol>,
+    ) -> bool {
+        match *arena.get(node) {
+            ArenaNode::Arithmetic { left, right, .. } => {
+                self.depends(arena, left, functions, params, dependencies)
+                    && self.depends(arena, right, functions, params, dependencies)
+            }
+            ArenaNode::Number(_) => true,
+            // In the order a variable is looked up in
+            ArenaNode::Variable(name) => {
+                if params.contains(&name) || self.constants.get(name).is_some() {
+                    dependencies.push(name);
+                } else if let Some(x) = self.fixed.get(&name) {
+                    dependencies.extend(x);
+                } else {
+                    return false;
+                }
+
+                true
+            }
+            ArenaNode::Function { name, args } => {
+                let args = arena.args(args);
+                // Baked functions are taken to be pure, there's no telling otherwise
+                let pure = match functions.get(&name) {
+                    Some(Function::Baked { expected, .. }) => *expected == args.len(),
+                    Some(Function::Mathematical { arg_names, .. }) => {
+                        arg_names.len() == args.len()
+                            && self.pure.get(&name).is_some_and(|x| {
+                                dependencies.extend(x);
+                                true
+                            })
+                    }
+                    None => false,
+                };
+
+                pure && args
+                    .iter()
+                    .all(|x| self.depends(arena, *x, functions, params, dependencies))
+            }
+            ArenaNode::Comparison { .. } => false,
+        }
+    }
+
+    pub fn ops(&self, program: Program) -> &[Op] {
+        &self.ops[program.start as usize..(program.start + program.len) as usize]
+    }
+
+    /// The body and parameters of the function [`Op::Call`] calls.
+    pub fn function(&self, index: u32) -> (Program, &[Symbol]) {
+        let (program, params) = &self.functions[index as usize];
+        (*program, params)
+    }
+
+    /// The program computing the subexpression [`Op::Cached`] stands in for, and the symbols its
+    /// value depends on. It can only be cached while none of them are given other values.
+    pub fn cached(&self, index: u32) -> (Program, &[Symbol]) {
+        let (program, dependencies) = &self.cached[index as usize];
+        (*program, dependencies)
+    }
+
+    /// How many subexpressions are cached.
+    pub fn caches(&self) -> usize {
+        self.cached.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::parse::{Lexer, parse};
+    use crate::math::solve::builtin;
+    use crate::math::symbol::Symbols;
+
+    #[test]
+    fn test_compile() {
+        let mut symbols = Symbols::default();
+        let mut arena = Arena::default();
+        let f = symbols.intern("f");
+        let x = symbols.intern("x");
+        let body = symbols.resolve(parse(Lexer::new("x^2")).unwrap());
+        let functions = HashMap::from([
+            (
+                f,
+                Function::Mathematical {
+                    node: arena.insert(&body),
+                    arg_names: vec![x],
+                },
+            ),
+            (symbols.intern("sin"), builtin::SIN.clone()),
+        ]);
+
+        let mut code = Bytecode::new(&arena, &functions, &Bindings::default(), &[]);
+        let (body, params) = code.function(0);
+        assert_eq!(params, [x]);
+        assert_eq!(code.ops(body).len(), 3);
+
+        let node = symbols.resolve(parse(Lexer::new("1+f(sin(x)*2)-g(x)-sin(x,x)")).unwrap());
+        let node = arena.insert(&node);
+        let program = code.compile(&arena, node, &functions);
+        let ops = code.ops(program);
+        assert_eq!(ops.len(), 13);
+        assert!(matches!(ops[0], Op::Number(1.0)));
+        assert!(matches!(ops[4], Op::Arithmetic(Operation::Multi)));
+        assert!(matches!(ops[5], Op::Call(0)));
+        // Neither fails until it's reached
+        assert!(matches!(ops[7], Op::Fail(Failure::UnsatisfiedFunction(_))));
+        assert!(matches!(
+            ops[11],
+            Op::Fail(Failure::WrongNumberOfArguments {
+                expected: 1,
+                found: 2,
+                ..
+            })
+        ));
+        // 1, sin(x), 2 at once
+        assert_eq!(program.depth(), 3);
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut symbols = Symbols::default();
+        let mut arena = Arena::default();
+        let mut insert = |x: &str| arena.insert(&symbols.resolve(parse(Lexer::new(x)).unwrap()));
+        let hundred = insert("100");
+        let node = insert("k*g*x+g-sin(g)*x");
+        let (g, k) = (symbols.get("g").unwrap(), symbols.get("k").unwrap());
+        let functions = HashMap::from([(symbols.intern("sin"), builtin::SIN.clone())]);
+        let mut constants = Bindings::default();
+        constants.set(k, 1.0);
+
+        let mut code = Bytecode::new(&arena, &functions, &constants, &[(g, hundred)]);
+        let program = code.compile(&arena, node, &functions);
+        let ops = code.ops(program);
+        assert_eq!(ops.len(), 9);
+        // g is cached once, however many times it's used
+        assert!(matches!(ops[0], Op::Cached(1)));
+        assert!(matches!(ops[3], Op::Cached(0)));
+        assert!(matches!(ops[5], Op::Cached(2)));
+        assert!(matches!(ops[6], Op::Variable(_)));
+
+        let (program, dependencies) = code.cached(1);
+        assert_eq!(code.ops(program).len(), 3);
+        assert_eq!(dependencies, [k, g]);
+        assert_eq!(code.cached(0).1, [g]);
+        assert_eq!(code.cached(2).1, [g]);
+        assert_eq!(code.caches(), 3);
+    }
+}
@@ -0,0 +1,376 @@
+//! Planar geometry on points and polygons, as used by the collider. Lines are given as a point and
+//! a direction, segments as a start point and the vector to their end, and polygons as their
+//! vertices in order.
+
+use crate::math::{Column, Matrix, Vector};
+
+/// Where the lines `a + t_a * a_direction` and `b + t_b * b_direction` cross, as `(t_a, t_b)`.
+/// `None` for parallel lines, including ones that lie on top of each other.
+pub fn intersect_lines(
+    a: &Column<2>,
+    a_direction: &Column<2>,
+    b: &Column<2>,
+    b_direction: &Column<2>,
+) -> Option<(f64, f64)> {
+    // If they are parallel (or either direction is zero) the system has no single solution, and t
+    // would come out inf or NaN. Comparing the determinant rather than slopes also catches vertical
+    // directions pointing opposite ways, whose slopes are inf and -inf
+    let determinant =
+        b_direction.get(0) * a_direction.get(1) - a_direction.get(0) * b_direction.get(1);
+    if determinant == 0.0 {
+        return None;
+    }
+
+    // Ax=B => x=A^-1*B
+    // Of the system of equations: (where a_direction, a, b_direction, and b are all column vectors)
+    // t_a * a_direction + a = t_b * b_direction + b
+    let product = Matrix::vector([b.get(0) - a.get(0), b.get(1) - a.get(1)]);
+    let a_inv = Matrix::new([
+        [-*b_direction.get(1), *b_direction.get(0)],
+        [-*a_direction.get(1), *a_direction.get(0)],
+    ])
+    .scale(1.0 / determinant);
+
+    let x = a_inv.multiply(&product);
+
+    Some((*x.get(0), *x.get(1)))
+}
+
+/// Where the segments from `a` to `a + a_span` and from `b` to `b + b_span` cross, if they do.
+pub fn intersect_segments(
+    a: &Column<2>,
+    a_span: &Column<2>,
+    b: &Column<2>,
+    b_span: &Column<2>,
+) -> Option<Column<2>> {
+    let (t_a, t_b) = intersect_lines(a, a_span, b, b_span)?;
+
+    if (0.0..=1.0).contains(&t_a) && (0.0..=1.0).contains(&t_b) {
+        Some(a.plus(&a_span.scale(t_a)))
+    } else {
+        None
+    }
+}
+
+/// Distance from `point` to the line through `a` and `b`.
+pub fn line_distance(point: &Column<2>, a: &Column<2>, b: &Column<2>) -> f64 {
+    let numerator = (b[1] - a[1]) * point[0] - (b[0] - a[0]) * point[1] + b[0] * a[1] - b[1] * a[0];
+
+    let denominator = ((b[1] - a[1]).powi(2) + (b[0] - a[0]).powi(2)).sqrt();
+
+    numerator.abs() / denominator
+}
+
+/// The point on the segment from `a` to `b` nearest to `point`.
+pub fn closest_on_segment(point: &Column<2>, a: &Column<2>, b: &Column<2>) -> Column<2> {
+    let span = b.plus(&a.scale(-1.0));
+    let length = span.dot(&span);
+    if length == 0.0 {
+        return a.clone();
+    }
+
+    let t = (point.plus(&a.scale(-1.0)).dot(&span) / length).clamp(0.0, 1.0);
+    a.plus(&span.scale(t))
+}
+
+/// Area of a polygon by the shoelace formula, positive when its vertices run counterclockwise.
+pub fn signed_area(polygon: &[Column<2>]) -> f64 {
+    // https://en.wikipedia.org/wiki/Shoelace_formula
+    polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| Matrix::new([[a[0], b[0]], [a[1], b[1]]]).det())
+        .sum::<f64>()
+        / 2.0
+}
+
+pub fn area(polygon: &[Column<2>]) -> f64 {
+    signed_area(polygon).abs()
+}
+
+/// The centre of mass of a polygon of uniform density. `None` if it has no area.
+pub fn centroid(polygon: &[Column<2>]) -> Option<Column<2>> {
+    let area = signed_area(polygon);
+    if area == 0.0 || !area.is_finite() {
+        return None;
+    }
+
+    let sum =
+        polygon
+            .iter()
+            .zip(polygon.iter().cycle().skip(1))
+            .fold(Column::empty(), |acc, (a, b)| {
+                let cross = a[0] * b[1] - b[0] * a[1];
+                acc.plus(&a.plus(b).scale(cross))
+            });
+
+    Some(sum.scale(1.0 / (6.0 * area)))
+}
+
+/// Polar second moment of area of a polygon about the origin, ie. its moment of inertia at unit
+/// density.
+pub fn second_moment(polygon: &[Column<2>]) -> f64 {
+    let sum = polygon
+        .iter()
+        .zip(polygon.iter().cycle().skip(1))
+        .map(|(a, b)| {
+            let cross = a[0] * b[1] - b[0] * a[1];
+            cross
+                * (a[0].powi(2)
+                    + a[0] * b[0]
+                    + b[0].powi(2)
+                    + a[1].powi(2)
+                    + a[1] * b[1]
+                    + b[1].powi(2))
+        })
+        .sum::<f64>();
+
+    // Negative for clockwise polygons, like the area
+    (sum / 12.0).abs()
+}
+
+/// The part of a convex polygon behind the line through `point` facing `normal`, ie. where
+/// `(x - point) . normal <= 0`. Empty if none of it is.
+pub fn clip(polygon: &[Column<2>], point: &Column<2>, normal: &Column<2>) -> Vec<Column<2>> {
+    let side = |x: &Column<2>| x.plus(&point.scale(-1.0)).dot(normal);
+
+    // Sutherland-Hodgman against a single edge
+    let mut clipped = Vec::new();
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        let (side_a, side_b) = (side(a), side(b));
+
+        if side_a <= 0.0 {
+            clipped.push(a.clone());
+        }
+        if (side_a < 0.0 && side_b > 0.0) || (side_a > 0.0 && side_b < 0.0) {
+            let t = side_a / (side_a - side_b);
+            clipped.push(a.plus(&b.plus(&a.scale(-1.0)).scale(t)));
+        }
+    }
+
+    clipped
+}
+
+/// Whether `point` is inside the polygon, by the even-odd rule so it works for any simple polygon,
+/// convex or not.
+pub fn contains(polygon: &[Column<2>], point: &Column<2>) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a[1] > point[1]) != (b[1] > point[1])
+            && point[0] < (b[0] - a[0]) * (point[1] - a[1]) / (b[1] - a[1]) + a[0]
+        {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+/// The smallest convex polygon containing every point, counterclockwise (as the collider expects).
+/// Points inside it or on its edges are dropped, so the result may be degenerate (fewer than three
+/// points) if every point lies on one line.
+pub fn convex_hull(points: &[Column<2>]) -> Vec<Column<2>> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    points.dedup();
+
+    if points.len() < 3 {
+        return points;
+    }
+
+    // Whether o -> a -> b turns counterclockwise
+    let left_turn = |o: &Column<2>, a: &Column<2>, b: &Column<2>| {
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0]) > 0.0
+    };
+
+    // Andrew's monotone chain, the lower half left to right then the upper half back
+    let mut hull: Vec<Column<2>> = Vec::with_capacity(points.len() * 2);
+    for pass in [
+        points.iter().collect::<Vec<_>>(),
+        points.iter().rev().collect(),
+    ] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2
+                && !left_turn(&hull[hull.len() - 2], &hull[hull.len() - 1], point)
+            {
+                hull.pop();
+            }
+            hull.push(point.clone());
+        }
+        // The last point of each half is the first of the other
+        hull.pop();
+    }
+
+    hull
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square() -> Vec<Column<2>> {
+        vec![
+            Column::vector([0.0, 0.0]),
+            Column::vector([2.0, 0.0]),
+            Column::vector([2.0, 2.0]),
+            Column::vector([0.0, 2.0]),
+        ]
+    }
+
+    #[test]
+    fn test_intersect_segments() {
+        let crossing = intersect_segments(
+            &Column::vector([0.0, 0.0]),
+            &Column::vector([2.0, 2.0]),
+            &Column::vector([0.0, 2.0]),
+            &Column::vector([2.0, -2.0]),
+        );
+        assert_eq!(crossing, Some(Column::vector([1.0, 1.0])));
+
+        // The lines cross, but past the end of the second segment
+        assert!(
+            intersect_segments(
+                &Column::vector([0.0, 0.0]),
+                &Column::vector([2.0, 2.0]),
+                &Column::vector([0.0, 2.0]),
+                &Column::vector([0.5, -0.5]),
+            )
+            .is_none()
+        );
+        assert!(
+            intersect_lines(
+                &Column::vector([0.0, 0.0]),
+                &Column::vector([1.0, 1.0]),
+                &Column::vector([0.0, 1.0]),
+                &Column::vector([2.0, 2.0]),
+            )
+            .is_none()
+        );
+        // Vertical and pointing opposite ways, or with no direction at all
+        for direction in [[0.0, -1.0], [0.0, 0.0]] {
+            assert!(
+                intersect_lines(
+                    &Column::vector([0.0, 0.0]),
+                    &Column::vector([0.0, 1.0]),
+                    &Column::vector([1.0, 0.0]),
+                    &Column::vector(direction),
+                )
+                .is_none()
+            );
+        }
+        assert_eq!(
+            intersect_lines(
+                &Column::vector([0.0, 0.0]),
+                &Column::vector([0.0, 1.0]),
+                &Column::vector([1.0, 2.0]),
+                &Column::vector([-1.0, 0.0]),
+            ),
+            Some((2.0, 1.0))
+        );
+
+        assert_eq!(
+            line_distance(
+                &Column::vector([1.0, 3.0]),
+                &Column::vector([0.0, 0.0]),
+                &Column::vector([1.0, 0.0])
+            ),
+            3.0
+        );
+    }
+
+    #[test]
+    fn test_area_and_centroid() {
+        let square = square();
+        assert_eq!(signed_area(&square), 4.0);
+
+        let reversed = square.iter().rev().cloned().collect::<Vec<_>>();
+        assert_eq!(signed_area(&reversed), -4.0);
+        assert_eq!(area(&reversed), 4.0);
+
+        assert_eq!(centroid(&square), Some(Column::vector([1.0, 1.0])));
+        assert_eq!(centroid(&reversed), Some(Column::vector([1.0, 1.0])));
+        assert!(centroid(&square[..2]).is_none());
+
+        // A 2x2 square about its corner, b*h*(b^2+h^2)/3
+        assert!((second_moment(&reversed) - 32.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_convex_hull() {
+        // Clockwise, with a point inside, one on an edge and a repeat
+        let mut points = square();
+        points.reverse();
+        points.extend([
+            Column::vector([1.0, 1.0]),
+            Column::vector([1.0, 0.0]),
+            Column::vector([2.0, 2.0]),
+        ]);
+
+        let hull = convex_hull(&points);
+        assert_eq!(hull.len(), 4);
+        assert_eq!(signed_area(&hull), 4.0);
+        for corner in square() {
+            assert!(hull.contains(&corner));
+        }
+
+        // A dent is filled in
+        let dented = vec![
+            Column::vector([0.0, 0.0]),
+            Column::vector([2.0, 0.0]),
+            Column::vector([1.0, 0.5]),
+            Column::vector([2.0, 2.0]),
+            Column::vector([0.0, 2.0]),
+        ];
+        assert_eq!(area(&convex_hull(&dented)), 4.0);
+
+        let line = [0.0, 1.0, 2.0].map(|x| Column::vector([x, x]));
+        assert_eq!(convex_hull(&line).len(), 2);
+    }
+
+    #[test]
+    fn test_clip() {
+        let square = square();
+
+        // Keeps the half left of x = 0.5
+        let clipped = clip(
+            &square,
+            &Column::vector([0.5, 0.0]),
+            &Column::vector([1.0, 0.0]),
+        );
+        assert_eq!(clipped.len(), 4);
+        assert_eq!(area(&clipped), 1.0);
+        assert_eq!(centroid(&clipped), Some(Column::vector([0.25, 1.0])));
+
+        // A corner
+        let clipped = clip(
+            &square,
+            &Column::vector([1.0, 0.0]),
+            &Column::vector([1.0, -1.0]),
+        );
+        assert_eq!(area(&clipped), 3.5);
+
+        assert_eq!(
+            clip(
+                &square,
+                &Column::vector([-1.0, 0.0]),
+                &Column::vector([1.0, 0.0])
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_contains() {
+        let square = square();
+        assert!(contains(&square, &Column::vector([0.5, 1.0])));
+        assert!(!contains(&square, &Column::vector([2.5, 1.0])));
+
+        // Concave, the notch cut out of the top isn't inside
+        let notched = [[0.0, 0.0], [2.0, 0.0], [2.0, 2.0], [1.0, 1.0], [0.0, 2.0]]
+            .map(Column::vector)
+            .to_vec();
+        assert!(contains(&notched, &Column::vector([1.0, 0.5])));
+        assert!(!contains(&notched, &Column::vector([1.0, 1.5])));
+    }
+}
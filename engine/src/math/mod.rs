@@ -2,9 +2,16 @@ use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 use std::ops::Index;
 use crate::err::{Error, ErrorKind};
+use crate::math::arena::NodeId;
+use crate::math::symbol::Symbol;
 
+pub mod arena;
+pub mod bytecode;
+pub mod geometry;
 pub mod parse;
 pub mod solve;
+pub mod symbol;
+pub mod validate;
 
 // Rows x Columns
 #[derive(PartialEq, Clone, Debug)]
@@ -122,9 +129,36 @@ impl<const M: usize, const N: usize> Display for Matrix<M, N> {
     }
 }
 
+// A flat list of the entries row by row, so a column is just its components (`[1.0, 2.0]`)
+#[cfg(feature = "serde")]
+impl<const M: usize, const N: usize> serde::Serialize for Matrix<M, N> {
+    fn serialize<T: serde::Serializer>(&self, serializer: T) -> Result<T::Ok, T::Error> {
+        serializer.collect_seq(self.content.iter().flatten())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const M: usize, const N: usize> serde::Deserialize<'de> for Matrix<M, N> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = Vec::<f64>::deserialize(deserializer)?;
+        if entries.len() != M * N {
+            return Err(serde::de::Error::invalid_length(
+                entries.len(),
+                &format!("{} entries", M * N).as_str(),
+            ));
+        }
+
+        let mut matrix = Matrix::empty();
+        for (i, x) in entries.into_iter().enumerate() {
+            matrix.content[i / N][i % N] = x;
+        }
+        Ok(matrix)
+    }
+}
+
 pub type Column<const M: usize> = Matrix<M, 1>;
 
-pub trait Vector: Clone + Display {
+pub trait Vector: Clone + Display + Send + Sync {
     fn dof() -> usize;
     fn new(value: Vec<f64>) -> Result<Self, Error>;
     fn empty() -> Self;
@@ -218,9 +252,19 @@ impl<const D: usize> TryFrom<Vec<f64>> for Matrix<D, 1> {
 #[derive(Debug, Clone)]
 pub struct Equation {
     id: u8,
-    node: parse::Node,
+    // left - right, in the arena the environment was built from
+    root: NodeId,
+    // The variable alone on the left hand side, if any
+    defined: Option<Symbol>,
     // Dependencies on other variables
-    dependencies: HashSet<String>,
+    dependencies: HashSet<Symbol>,
+}
+
+impl Equation {
+    /// Whether the equation is written as `var = ...`.
+    fn defines(&self, var: Symbol) -> bool {
+        self.defined == Some(var)
+    }
 }
 
 #[cfg(test)]
@@ -303,6 +347,71 @@ pub mod integration {
         velocity + accel
     }
 
+    /// How the velocity of a body moved by a displacement equation is estimated from its positions.
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum Reconstruction {
+        /// From the last two positions. Lags half a tick behind the true velocity.
+        #[default]
+        BackwardDifference,
+        /// From the last three positions, a central difference about the middle one carried
+        /// forward to the newest. Exact for constant acceleration, but a body needs to have been
+        /// moved by its equation for a tick already, until then the backward difference is used.
+        CentralDifference,
+    }
+
+    /// How bodies driven by an acceleration (or force) are integrated over a tick.
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum Integrator {
+        /// Evaluates the acceleration once a tick, and conserves energy well over long runs.
+        #[default]
+        Leapfrog,
+        /// Classical fourth order Runge-Kutta, evaluating the acceleration four times a tick.
+        /// Far more accurate for smooth forces (orbits, springs), though its energy slowly drifts.
+        RungeKutta4,
+    }
+
+    /// A classical Runge-Kutta step of `x'' = f(x, x')` over `delta`, for every component at once.
+    /// `acceleration` is `f` at the start, which the caller already has, so `f` is only called for
+    /// the three stages after it.
+    pub fn runge_kutta4<E>(
+        delta: f64,
+        displacement: &[f64],
+        velocity: &[f64],
+        acceleration: &[f64],
+        mut f: impl FnMut(&[f64], &[f64]) -> Result<Vec<f64>, E>,
+    ) -> Result<(Vec<f64>, Vec<f64>), E> {
+        // x + k * h for every component
+        let step = |x: &[f64], k: &[f64], h: f64| {
+            x.iter().zip(k).map(|(x, k)| x + k * h).collect::<Vec<_>>()
+        };
+
+        let (k1x, k1v) = (velocity.to_vec(), acceleration.to_vec());
+        let k2x = step(velocity, &k1v, delta / 2.0);
+        let k2v = f(&step(displacement, &k1x, delta / 2.0), &k2x)?;
+        let k3x = step(velocity, &k2v, delta / 2.0);
+        let k3v = f(&step(displacement, &k2x, delta / 2.0), &k3x)?;
+        let k4x = step(velocity, &k3v, delta);
+        let k4v = f(&step(displacement, &k3x, delta), &k4x)?;
+
+        let combine = |x: &[f64], k: [&Vec<f64>; 4]| {
+            (0..x.len())
+                .map(|i| x[i] + delta / 6.0 * (k[0][i] + 2.0 * k[1][i] + 2.0 * k[2][i] + k[3][i]))
+                .collect::<Vec<_>>()
+        };
+        Ok((
+            combine(displacement, [&k1x, &k2x, &k3x, &k4x]),
+            combine(velocity, [&k1v, &k2v, &k3v, &k4v]),
+        ))
+    }
+
+    pub fn backward_difference(delta: f64, previous: f64, next: f64) -> f64 {
+        (next - previous) / delta
+    }
+
+    pub fn central_difference(delta: f64, before: f64, previous: f64, next: f64) -> f64 {
+        (3.0 * next - 4.0 * previous + before) / (2.0 * delta)
+    }
+
     #[cfg(test)]
     mod tests {
         use super::*;
@@ -320,5 +429,28 @@ pub mod integration {
                 println!("{}, {}", displacement, velocity);
             }
         }
+
+        #[test]
+        fn test_runge_kutta4() {
+            // A unit spring, x = cos(t)
+            let spring = |x: &[f64], _: &[f64]| Ok::<_, ()>(vec![-x[0]]);
+            let (mut x, mut v) = (vec![1.0], vec![0.0]);
+            for _ in 0..100 {
+                let a = spring(&x, &v).unwrap();
+                (x, v) = runge_kutta4(0.1, &x, &v, &a, spring).unwrap();
+            }
+
+            assert!((x[0] - 10.0f64.cos()).abs() < 1e-5, "{}", x[0]);
+            assert!((v[0] + 10.0f64.sin()).abs() < 1e-5, "{}", v[0]);
+        }
+
+        #[test]
+        fn test_differences() {
+            let position = |t: f64| 3.0 * t - 5.0 * t * t;
+            let (before, previous, next) = (position(0.8), position(0.9), position(1.0));
+
+            assert!((backward_difference(0.1, previous, next) - -6.5).abs() < 1e-9);
+            assert!((central_difference(0.1, before, previous, next) - -7.0).abs() < 1e-9);
+        }
     }
 }
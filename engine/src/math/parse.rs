@@ -1,5 +1,6 @@
 use crate::err::{EngineResult, Error, ErrorKind};
 use std::iter::Peekable;
+use std::ops::Range;
 use std::str::Chars;
 
 #[derive(PartialEq, Clone, Copy, Debug)]
@@ -11,25 +12,54 @@ pub enum Operation {
     Exp,
 }
 
+/// A parsed expression. Names are strings straight out of the parser, and interned symbols once an
+/// environment has resolved them (see [`crate::math::symbol`]).
 #[derive(Debug, Clone)]
-pub enum Node {
+pub enum Node<V = String> {
     Arithmetic {
         operation: Operation,
-        left: Box<Node>,
-        right: Box<Node>,
+        left: Box<Node<V>>,
+        right: Box<Node<V>>,
     },
     Number(f64),
-    Variable(String),
+    Variable(V),
     Function {
-        name: String,
-        args: Vec<Box<Node>>,
+        name: V,
+        args: Vec<Box<Node<V>>>,
     },
     Comparison {
-        left: Box<Node>,
-        right: Box<Node>,
+        left: Box<Node<V>>,
+        right: Box<Node<V>>,
     },
 }
 
+impl<V> Node<V> {
+    /// Rebuilds the tree with every variable and function name passed through `f`.
+    pub fn map_names<W>(self, f: &mut impl FnMut(V) -> W) -> Node<W> {
+        match self {
+            Node::Arithmetic {
+                operation,
+                left,
+                right,
+            } => Node::Arithmetic {
+                operation,
+                left: Box::new(left.map_names(f)),
+                right: Box::new(right.map_names(f)),
+            },
+            Node::Number(x) => Node::Number(x),
+            Node::Variable(name) => Node::Variable(f(name)),
+            Node::Function { name, args } => Node::Function {
+                name: f(name),
+                args: args.into_iter().map(|x| Box::new(x.map_names(f))).collect(),
+            },
+            Node::Comparison { left, right } => Node::Comparison {
+                left: Box::new(left.map_names(f)),
+                right: Box::new(right.map_names(f)),
+            },
+        }
+    }
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum Token {
     // Static
@@ -66,6 +96,10 @@ macro_rules! success {
 pub struct Lexer<'a> {
     input: Peekable<Chars<'a>>,
     current: Option<Token>,
+    // Characters consumed so far
+    offset: usize,
+    // Character range of the most recently lexed token
+    span: Range<usize>,
 }
 
 impl<'a> Lexer<'a> {
@@ -73,9 +107,32 @@ impl<'a> Lexer<'a> {
         Lexer {
             input: input.chars().peekable(),
             current: None,
+            offset: 0,
+            span: 0..0,
         }
     }
 
+    fn bump(&mut self) -> Option<char> {
+        let next = self.input.next();
+        if next.is_some() {
+            self.offset += 1;
+        }
+        next
+    }
+
+    fn bump_if(&mut self, func: impl FnOnce(&char) -> bool) -> Option<char> {
+        let next = self.input.next_if(func);
+        if next.is_some() {
+            self.offset += 1;
+        }
+        next
+    }
+
+    /// The character range of the most recently lexed token.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
     fn is_digit(c: char) -> bool {
         c >= '0' && c <= '9'
     }
@@ -88,9 +145,10 @@ impl<'a> Lexer<'a> {
         let mut character: char;
 
         loop {
-            if let Some(char) = self.input.next() {
+            if let Some(char) = self.bump() {
                 character = char;
             } else {
+                self.span = self.offset..self.offset;
                 return Ok(None);
             }
 
@@ -99,24 +157,29 @@ impl<'a> Lexer<'a> {
             }
         }
 
+        let start = self.offset - 1;
+
         if Self::is_digit(character) {
             let mut number = Vec::new();
 
             loop {
                 number.push(character);
 
-                if let Some(char) = self.input.next_if(|x| Self::is_digit(*x) || *x == '.') {
+                if let Some(char) = self.bump_if(|x| Self::is_digit(*x) || *x == '.') {
                     character = char;
                 } else {
                     break;
                 }
             }
 
+            self.span = start..self.offset;
+
             let str = number.iter().map(|c| *c).collect::<String>();
             let float = str.parse::<f64>().map_err(|_x| {
                 Error::new(ErrorKind::InvalidMathSyntax(
                     "Unable to convert number to float. ",
                 ))
+                .with_span(self.span())
             })?;
 
             return Ok(Some(Token::Number(float)));
@@ -128,19 +191,23 @@ impl<'a> Lexer<'a> {
             loop {
                 name.push(character);
 
-                if let Some(char) = self.input.next_if(|x| Self::is_character(*x) || *x == '_') {
+                if let Some(char) = self.bump_if(|x| Self::is_character(*x) || *x == '_') {
                     character = char;
                 } else {
                     break;
                 }
             }
 
+            self.span = start..self.offset;
+
             // let slice = &self.input[index..index + len];
             let str = name.iter().map(|c| *c as char).collect::<String>();
 
             return Ok(Some(Token::Text(str)));
         }
 
+        self.span = start..self.offset;
+
         let token = match character {
             // Arithmetic operations
             '+' => Token::Op(Operation::Add),
@@ -154,7 +221,11 @@ impl<'a> Lexer<'a> {
             // Misc
             ',' => Token::Comma,
             '=' => Token::Equals,
-            _ => return Err(Error::new(ErrorKind::InvalidToken(character.to_string()))),
+            _ => {
+                return Err(
+                    Error::new(ErrorKind::InvalidToken(character.to_string())).with_span(self.span())
+                );
+            }
         };
 
         Ok(Some(token))
@@ -186,13 +257,22 @@ impl<'a> Lexer<'a> {
 }
 
 pub fn parse(mut lexer: Lexer) -> EngineResult<Node> {
-    if let Some(x) = expression(&mut lexer)? {
-        Ok(x)
-    } else {
-        Err(Error::new(ErrorKind::InvalidMathSyntax(
+    let result = match expression(&mut lexer) {
+        Ok(Some(x)) => Ok(x),
+        Ok(None) => Err(Error::new(ErrorKind::InvalidMathSyntax(
             "Premature end of input",
-        )))
-    }
+        ))),
+        Err(e) => Err(e),
+    };
+
+    // Errors not raised by the lexer itself point at the token the parser stopped on
+    result.map_err(|e| {
+        if e.span.is_none() {
+            e.with_span(lexer.span())
+        } else {
+            e
+        }
+    })
 }
 
 fn expression(lexer: &mut Lexer) -> Outcome<Node> {
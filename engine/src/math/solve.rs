@@ -1,18 +1,24 @@
 use crate::err;
-use crate::err::ErrorKind::{ExpectedComparison, UnexpectedComparison};
+use crate::err::ErrorKind::UnexpectedComparison;
 use crate::err::{Error, ErrorKind};
 use crate::math::Equation;
+use crate::math::arena::{Arena, ArenaNode, NodeId};
+use crate::math::bytecode::{Bytecode, Failure, Op, Program};
 use crate::math::parse::{Lexer, Node, Operation, parse};
+use crate::math::symbol::{Bindings, Symbol, Symbols};
 use err::EngineResult;
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicU8, AtomicU64, AtomicUsize, Ordering};
 
 #[derive(Debug, Clone)]
 pub enum Function {
+    // Only ever created by Environment::build, which interns its names
     Mathematical {
-        node: Node,
-        arg_names: Vec<String>,
+        // In the arena the environment was built from, compiled along with it
+        node: NodeId,
+        arg_names: Vec<Symbol>,
     },
     Baked {
         call_site: fn(Vec<f64>) -> f64,
@@ -92,36 +98,180 @@ pub mod builtin {
 #[derive(Debug)]
 pub struct Environment {
     equations: Vec<Equation>,
-    functions: HashMap<String, Function>,
-    constants: HashMap<String, f64>,
+    // The equations and mathematical functions compiled from the arena they were parsed into, each
+    // equation's program by its index
+    code: Bytecode,
+    programs: Vec<Program>,
+    // The value of each subexpression the code caches, by its index
+    cache: Vec<Cached>,
+    // Indices of the equations mentioning each symbol, those defining it directly on their left
+    // hand side first
+    index: HashMap<Symbol, Vec<usize>>,
+    functions: HashMap<Symbol, Function>,
+    constants: Bindings,
+    // Every name appearing in the equations, functions and constants
+    symbols: Symbols,
+    // Newton iterations performed since last taken, for diagnostics
+    root_iterations: AtomicUsize,
 }
 
 impl Environment {
     pub fn new(
         equations: Vec<Equation>,
-        functions: HashMap<String, Function>,
-        constants: HashMap<String, f64>,
+        arena: Arena,
+        functions: HashMap<Symbol, Function>,
+        constants: Bindings,
+        symbols: Symbols,
     ) -> Environment {
+        let mut index = HashMap::<Symbol, Vec<usize>>::new();
+        for (i, x) in equations.iter().enumerate() {
+            for symbol in &x.dependencies {
+                index.entry(*symbol).or_default().push(i);
+            }
+        }
+        for (symbol, indices) in index.iter_mut() {
+            // Stable, so equations otherwise keep the order they were written in
+            indices.sort_by_key(|i| !equations[*i].defines(*symbol));
+        }
+
+        // Each variable some equation defines (`g=100`) before any other mentions it, and what
+        // it's defined as. Those defined from constants alone always come out the same
+        let definitions = index
+            .iter()
+            .filter(|(symbol, _)| constants.get(**symbol).is_none())
+            .filter_map(|(symbol, indices)| {
+                let equation = &equations[*indices.first()?];
+                match *arena.get(equation.root) {
+                    ArenaNode::Arithmetic { right, .. } if equation.defines(*symbol) => {
+                        Some((*symbol, right))
+                    }
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let mut code = Bytecode::new(&arena, &functions, &constants, &definitions);
+        let programs = equations
+            .iter()
+            .map(|x| code.compile(&arena, x.root, &functions))
+            .collect();
+
         Environment {
             equations,
+            cache: (0..code.caches()).map(|_| Cached::default()).collect(),
+            code,
+            programs,
+            index,
             functions,
             constants,
+            symbols,
+            root_iterations: AtomicUsize::new(0),
         }
     }
 
-    pub fn evaluate(&self, var: String, overrides: HashMap<String, f64>) -> EngineResult<f64> {
-        evaluate(
-            &Node::Variable(var),
-            Frame {
-                environment: self,
-                stack: Default::default(),
-                memo: Rc::new(RefCell::new(overrides)),
-                local: Default::default(),
-            },
-        )
+    /// Returns the number of root finder iterations performed since the last call, and resets it.
+    pub fn take_root_iterations(&self) -> usize {
+        self.root_iterations.swap(0, Ordering::Relaxed)
+    }
+
+    /// The symbol `name` was interned as, or `None` if nothing in the environment mentions it.
+    pub fn symbol(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name)
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        self.symbols.name(symbol)
+    }
+
+    /// The value of a constant, eg. `pi`.
+    pub fn constant(&self, name: &str) -> Option<f64> {
+        self.constants.get(self.symbol(name)?)
+    }
+
+    /// Defines or changes a constant. Constants take precedence over equations, so this also
+    /// overrides an equation like `g=100`.
+    pub fn set_constant(&mut self, name: &str, value: f64) {
+        let symbol = self.symbols.intern(name);
+        self.constants.set(symbol, value);
+
+        // Anything cached may have depended on it
+        for x in &mut self.cache {
+            *x = Cached::default();
+        }
+    }
+
+    // The value of the subexpression `program` cached at `index` computes, on its own the first
+    // time it's needed. `None` if it can't be, to evaluate it where it's used instead.
+    fn cached(&self, index: u32, program: Program) -> Option<f64> {
+        // Only the entries of the environment's own code, not of anything compiled after it
+        let entry = self.cache.get(index as usize)?;
+
+        // Whichever thread gets here first computes it. Any other, or this one again while it's
+        // being computed, evaluates it in place
+        match entry.state.compare_exchange(
+            Cached::EMPTY,
+            Cached::PENDING,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                let value = run(&self.code, program, &Frame::empty(self)).ok();
+                if let Some(x) = value {
+                    entry.value.store(x.to_bits(), Ordering::Relaxed);
+                }
+                let state = value.map_or(Cached::FAILED, |_| Cached::READY);
+                entry.state.store(state, Ordering::Release);
+
+                value
+            }
+            Err(Cached::READY) => Some(f64::from_bits(entry.value.load(Ordering::Relaxed))),
+            Err(_) => None,
+        }
     }
 
-    fn analyze(node: &Node, dependencies: &mut HashSet<String>) {
+    /// Whether any equation mentions `symbol`, which is the only way it could ever be solved for.
+    pub fn mentions(&self, symbol: Symbol) -> bool {
+        self.index.contains_key(&symbol)
+    }
+
+    /// Solves for `var` given the known values in `known`, without touching a single string.
+    pub fn evaluate_symbol(&self, var: Symbol, known: &Bindings) -> EngineResult<f64> {
+        variable(var, &Frame::new(self, &self.symbols, known))
+    }
+
+    /// Solves for `var` given the known values in `overrides`.
+    pub fn evaluate(&self, var: &str, overrides: &HashMap<String, f64>) -> EngineResult<f64> {
+        self.evaluate_node(Node::Variable(var.to_string()), overrides)
+    }
+
+    /// Parses and evaluates an expression with the given known values.
+    pub fn evaluate_expression(
+        &self,
+        expression: &str,
+        overrides: &HashMap<String, f64>,
+    ) -> EngineResult<f64> {
+        self.evaluate_node(parse(Lexer::new(expression))?, overrides)
+    }
+
+    fn evaluate_node(&self, node: Node, overrides: &HashMap<String, f64>) -> EngineResult<f64> {
+        // Names the environment has never seen get symbols of their own, past all of its own
+        let mut symbols = self.symbols.clone();
+        let node = symbols.resolve(node);
+
+        let mut known = Bindings::default();
+        for (name, value) in overrides {
+            if let Some(symbol) = symbols.get(name) {
+                known.set(symbol, *value);
+            }
+        }
+
+        let mut arena = Arena::default();
+        let root = arena.insert(&node);
+
+        evaluate(&arena, root, Frame::new(self, &symbols, &known))
+    }
+
+    fn analyze(node: &Node<Symbol>, dependencies: &mut HashSet<Symbol>) {
         match node {
             Node::Arithmetic {
                 operation: _,
@@ -132,10 +282,10 @@ impl Environment {
                 Self::analyze(right, dependencies);
             }
             Node::Variable(name) => {
-                dependencies.insert(name.clone());
+                dependencies.insert(*name);
             }
             Node::Function { name, args } => {
-                dependencies.insert(name.clone());
+                dependencies.insert(*name);
                 for x in args {
                     Self::analyze(x, dependencies);
                 }
@@ -150,7 +300,7 @@ impl Environment {
 
     pub fn build(
         expressions: Vec<&str>,
-        mut functions: HashMap<String, Function>,
+        functions: HashMap<String, Function>,
         constants: HashMap<String, f64>,
     ) -> EngineResult<Environment> {
         let expressions = expressions
@@ -158,17 +308,42 @@ impl Environment {
             .map(|t| parse(Lexer::new(t)))
             .collect::<EngineResult<Vec<_>>>()?;
 
+        let mut symbols = Symbols::default();
+        let mut functions = functions
+            .into_iter()
+            .map(|(name, x)| (symbols.intern(&name), x))
+            .collect::<HashMap<_, _>>();
+        let mut values = Bindings::default();
+        for (name, x) in constants {
+            values.set(symbols.intern(&name), x);
+        }
+
         let mut equations = Vec::<Equation>::new();
+        let mut arena = Arena::default();
 
         let mut id: u8 = 0;
 
         macro_rules! eq {
-            ($node:expr) => {{
-                let mut dependencies = HashSet::<String>::new();
+            ($left:expr, $right:expr) => {{
+                let mut dependencies = HashSet::<Symbol>::new();
+                Self::analyze(&$left, &mut dependencies);
+                Self::analyze(&$right, &mut dependencies);
+
+                let defined = match $left.as_ref() {
+                    Node::Variable(x) => Some(*x),
+                    _ => None,
+                };
+
+                // Solving for any variable means finding a root of left - right
+                let root = ArenaNode::Arithmetic {
+                    operation: Operation::Sub,
+                    left: arena.insert(&$left),
+                    right: arena.insert(&$right),
+                };
 
-                Self::analyze(&$node, &mut dependencies);
                 equations.push(Equation {
-                    node: $node,
+                    root: arena.push(root),
+                    defined,
                     dependencies,
                     id: id,
                 });
@@ -178,13 +353,15 @@ impl Environment {
         }
 
         for x in expressions {
-            if let Node::Comparison { left, right } = x.clone() {
-                if let Node::Function { name, args } = *left {
+            let x = symbols.resolve(x);
+
+            if let Node::Comparison { left, right } = x {
+                if let Node::Function { name, args } = left.as_ref() {
                     let params = args
                         .iter()
                         .map(|it| {
                             if let Node::Variable(name) = it.as_ref() {
-                                Some(name.clone())
+                                Some(*name)
                             } else {
                                 None
                             }
@@ -193,43 +370,71 @@ impl Environment {
 
                     if let Some(params) = params {
                         functions.insert(
-                            name.clone(),
+                            *name,
                             Function::Mathematical {
-                                node: *right.clone(),
+                                node: arena.insert(&right),
                                 arg_names: params,
                             },
                         );
                     } else {
-                        eq!(x)
+                        eq!(left, right)
                     }
                 } else {
-                    eq!(x)
+                    eq!(left, right)
                 }
             }
         }
 
-        Ok(Environment {
-            equations,
-            functions,
-            constants,
-        })
+        Ok(Environment::new(equations, arena, functions, values, symbols))
     }
 }
 
+// A value computed at most once, until the constants change
+#[derive(Debug, Default)]
+struct Cached {
+    state: AtomicU8,
+    value: AtomicU64,
+}
+
+impl Cached {
+    const EMPTY: u8 = 0;
+    const PENDING: u8 = 1;
+    const READY: u8 = 2;
+    // Couldn't be computed on its own
+    const FAILED: u8 = 3;
+}
+
 #[derive(Clone)]
 pub struct Frame<'a> {
     environment: &'a Environment,
-    stack: HashSet<u8>,
+    // The environment's symbols, possibly extended with names only the evaluated expression uses
+    symbols: &'a Symbols,
+    // Equations being solved, innermost last. Kept in order rather than hashed, like the locals,
+    // so nothing about an evaluation depends on how a hasher is seeded
+    stack: Vec<u8>,
+    // Values supplied by the caller
+    known: Option<&'a Bindings>,
     // Memoized global variables
-    memo: Rc<RefCell<HashMap<String, f64>>>,
-    // Local variables
-    local: HashMap<String, f64>,
+    memo: Rc<RefCell<Bindings>>,
+    // Local variables, a function's arguments
+    local: Vec<(Symbol, f64)>,
 }
 
 impl<'a> Frame<'a> {
+    fn new(environment: &'a Environment, symbols: &'a Symbols, known: &'a Bindings) -> Frame<'a> {
+        Frame {
+            environment,
+            symbols,
+            stack: Default::default(),
+            known: Some(known),
+            memo: Default::default(),
+            local: Default::default(),
+        }
+    }
+
     pub fn push(&self, equation: &Equation) -> Frame<'_> {
         let mut clone = self.clone();
-        clone.stack.insert(equation.id);
+        clone.stack.push(equation.id);
         clone.clear_locals();
         clone
     }
@@ -238,30 +443,48 @@ impl<'a> Frame<'a> {
         self.stack.contains(&equation.id)
     }
 
-    pub fn memo<T: Into<String>>(&mut self, name: T, value: f64) {
-        self.memo.borrow_mut().insert(name.into(), value);
+    pub fn memo(&mut self, name: Symbol, value: f64) {
+        self.memo.borrow_mut().set(name, value);
     }
 
-    pub fn local(&mut self, name: String, value: f64) {
-        self.local.insert(name, value);
+    pub fn local(&mut self, name: Symbol, value: f64) {
+        match self.local.iter_mut().find(|(x, _)| *x == name) {
+            Some((_, x)) => *x = value,
+            None => self.local.push((name, value)),
+        }
     }
 
     pub fn clear_locals(&mut self) {
         self.local.clear();
     }
 
-    pub fn lookup(&self, name: &String) -> Option<f64> {
+    pub fn lookup(&self, name: Symbol) -> Option<f64> {
         self.local
-            .get(name)
-            .cloned()
-            .or(self.memo.borrow().get(name).cloned())
+            .iter()
+            .find(|(x, _)| *x == name)
+            .map(|(_, x)| *x)
+            .or(self.known.and_then(|x| x.get(name)))
+            .or_else(|| self.memo.borrow().get(name))
     }
 
-    #[cfg(test)]
+    /// Whether `name` is given a value here, which may not be the one the environment would find
+    /// for it.
+    pub fn overrides(&self, name: Symbol) -> bool {
+        self.local.iter().any(|(x, _)| *x == name)
+            || self.known.is_some_and(|x| x.get(name).is_some())
+    }
+
+    fn name(&self, symbol: Symbol) -> String {
+        self.symbols.name(symbol).to_string()
+    }
+
+    // A frame given nothing, evaluating as the environment would on its own
     pub(crate) fn empty(environment: &'a Environment) -> Frame<'a> {
         Frame {
             environment,
+            symbols: &environment.symbols,
             stack: Default::default(),
+            known: None,
             memo: Default::default(),
             local: Default::default(),
         }
@@ -274,177 +497,183 @@ enum VariableResolution {
     Ignore,
 }
 
-pub fn evaluate(node: &Node, frame: Frame) -> EngineResult<f64> {
-    match node {
-        Node::Arithmetic {
-            operation,
-            left,
-            right,
-        } => {
-            let left = evaluate(left, frame.clone())?;
-            let right = evaluate(right, frame.clone())?;
-
-            let result = match operation {
-                Operation::Add => left + right,
-                Operation::Sub => left - right,
-                Operation::Multi => left * right,
-                Operation::Div => left / right,
-                Operation::Exp => left.powf(right),
-            };
+/// Evaluates the expression at `node` on its own, compiling it first. Equations are compiled once
+/// when the environment is built, this is for anything else.
+pub fn evaluate(arena: &Arena, node: NodeId, frame: Frame) -> EngineResult<f64> {
+    let environment = frame.environment;
+    let mut code = environment.code.clone();
+    let program = code.compile(arena, node, &environment.functions);
 
-            Ok(result)
-        }
-        Node::Number(n) => Ok(*n),
-        Node::Variable(name) => {
-            if let Some(x) = frame.lookup(name) {
-                return Ok(x);
-            }
+    run(&code, program, &frame)
+}
 
-            if let Some(x) = frame.environment.constants.get(name) {
-                return Ok(*x);
+/// Runs a compiled expression to its value.
+fn run(code: &Bytecode, program: Program, frame: &Frame) -> EngineResult<f64> {
+    let mut stack: Vec<f64> = Vec::with_capacity(program.depth());
+    for op in code.ops(program) {
+        let value = match *op {
+            Op::Number(x) => x,
+            Op::Variable(name) => variable(name, frame)?,
+            Op::Arithmetic(operation) => {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+
+                match operation {
+                    Operation::Add => left + right,
+                    Operation::Sub => left - right,
+                    Operation::Multi => left * right,
+                    Operation::Div => left / right,
+                    Operation::Exp => left.powf(right),
+                }
             }
+            Op::Call(function) => {
+                let (body, params) = code.function(function);
+                let args = stack.split_off(stack.len() - params.len());
+
+                let mut frame = frame.clone();
+                frame.clear_locals();
+                for (x, name) in args.iter().zip(params) {
+                    frame.local(*name, *x);
+                }
 
-            let ret = frame
-                .environment
-                .equations
-                .iter()
-                .filter(|x| x.dependencies.contains(name))
-                .map(|eq| {
-                    // TODO Multivariate roots
-                    if frame.visited(eq) {
-                        return Ok(VariableResolution::Ignore);
+                run(code, body, &frame)?
+            }
+            Op::Baked { call_site, args } => {
+                let args = stack.split_off(stack.len() - args as usize);
+                call_site(args)
+            }
+            Op::Cached(index) => {
+                let (program, dependencies) = code.cached(index);
+                let cached = match dependencies.iter().any(|x| frame.overrides(*x)) {
+                    true => None,
+                    false => frame.environment.cached(index, program),
+                };
+
+                match cached {
+                    Some(x) => x,
+                    None => run(code, program, frame)?,
+                }
+            }
+            Op::Fail(failure) => {
+                return Err(Error::new(match failure {
+                    Failure::UnsatisfiedFunction(name) => {
+                        ErrorKind::UnsatisfiedFunction(frame.name(name))
                     }
+                    Failure::WrongNumberOfArguments {
+                        name,
+                        expected,
+                        found,
+                    } => ErrorKind::WrongNumberOfArguments {
+                        name: frame.name(name),
+                        expected,
+                        found,
+                    },
+                    Failure::UnexpectedComparison => UnexpectedComparison,
+                }));
+            }
+        };
+        stack.push(value);
+    }
 
-                    let root_expr = if let Node::Comparison { left, right } = eq.node.clone() {
-                        Node::Arithmetic {
-                            operation: Operation::Sub,
-                            right: right.clone(),
-                            left: left.clone(),
-                        }
-                    } else {
-                        return Err(Error::new(ExpectedComparison));
-                    };
+    Ok(stack.pop().unwrap())
+}
 
-                    let root = find_root(&root_expr, name, 0.0, frame.push(eq));
+/// Finds the value of a variable, either known or by solving an equation that mentions it.
+fn variable(name: Symbol, frame: &Frame) -> EngineResult<f64> {
+    if let Some(x) = frame.lookup(name) {
+        return Ok(x);
+    }
 
-                    // TODO better errors here
-                    match root {
-                        Ok(val) => Ok(VariableResolution::Success(val)),
-                        Err(e) => match e.kind {
-                            ErrorKind::UnsatisfiedVariable(x) => {
-                                Ok(VariableResolution::UnsatisfiedVariable(x))
-                            }
-                            _ => Err(e),
-                        },
-                    }
-                })
-                .collect::<EngineResult<Vec<VariableResolution>>>()?;
-            // .iter()
-            // .filter(|x| !matches!(x, VariableResolution::Ignore))
-            // .collect::<Vec<VariableResolution>>();
-            let mut unsatisfied_variables = Vec::new();
-
-            for x in ret {
-                match x {
-                    VariableResolution::Success(x) => {
-                        return Ok(x);
-                    }
-                    VariableResolution::UnsatisfiedVariable(name) => {
-                        unsatisfied_variables.push(name);
-                    }
-                    VariableResolution::Ignore => {
-                        // Ignore
-                    }
-                }
-            }
+    if let Some(x) = frame.environment.constants.get(name) {
+        return Ok(x);
+    }
 
-            if unsatisfied_variables.is_empty() {
-                Err(Error::new(ErrorKind::UnsatisfiedVariable(name.clone())))
-            } else {
-                Err(Error::new(ErrorKind::UnsatisfiedVariable(
-                    unsatisfied_variables.first().unwrap().clone(),
-                )))
+    let environment = frame.environment;
+    let candidates = environment
+        .index
+        .get(&name)
+        .map(|x| x.as_slice())
+        .unwrap_or_default();
+
+    let ret = candidates
+        .iter()
+        .map(|i| (&environment.equations[*i], environment.programs[*i]))
+        .map(|(eq, program)| {
+            // TODO Multivariate roots
+            if frame.visited(eq) {
+                return Ok(VariableResolution::Ignore);
             }
-            //
-            // if let Some(ret) = ret.iter().find(|x| matches!(x, VariableResolution::Success(_))) {
-            //     Ok(ret)
-            // } else {
-            //     Err(Error::new(ErrorKind::UnsatisfiedVariable(name.clone())))
-            // }
-        }
-        Node::Function { name, args } => {
-            let f = frame
-                .environment
-                .functions
-                .get(name)
-                .ok_or_else(|| Error::new(ErrorKind::UnsatisfiedFunction(name.clone())))?;
-
-            let args = args
-                .iter()
-                .map(|exp| evaluate(exp, frame.clone()))
-                .collect::<Result<Vec<f64>, Error>>()?;
-
-            match f {
-                Function::Mathematical { node, arg_names } => {
-                    if args.len() != arg_names.len() {
-                        Err(Error::new(ErrorKind::WrongNumberOfArguments {
-                            name: name.clone(),
-                            expected: arg_names.len(),
-                            found: args.len(),
-                        }))
-                    } else {
-                        let mut frame = frame.clone();
-                        frame.clear_locals();
 
-                        for (x, name) in args.iter().zip(arg_names.iter()) {
-                            frame.local(name.clone(), *x);
-                        }
+            let root = find_root(&environment.code, program, name, 0.0, frame.push(eq));
 
-                        evaluate(node, frame)
+            // TODO better errors here
+            match root {
+                Ok(val) => Ok(VariableResolution::Success(val)),
+                Err(e) => match e.kind {
+                    ErrorKind::UnsatisfiedVariable(x) => {
+                        Ok(VariableResolution::UnsatisfiedVariable(x))
                     }
-                }
-                Function::Baked {
-                    call_site,
-                    expected,
-                } => {
-                    if args.len() != *expected {
-                        Err(Error::new(ErrorKind::WrongNumberOfArguments {
-                            name: name.clone(),
-                            expected: *expected,
-                            found: args.len(),
-                        }))
-                    } else {
-                        Ok(call_site(args))
-                    }
-                }
+                    _ => Err(e),
+                },
+            }
+        })
+        .collect::<EngineResult<Vec<VariableResolution>>>()?;
+    let mut unsatisfied_variables = Vec::new();
+
+    for x in ret {
+        match x {
+            VariableResolution::Success(x) => {
+                return Ok(x);
+            }
+            VariableResolution::UnsatisfiedVariable(name) => {
+                unsatisfied_variables.push(name);
+            }
+            VariableResolution::Ignore => {
+                // Ignore
             }
         }
-        Node::Comparison { left:_, right:_ } => Err(Error::new(UnexpectedComparison)),
+    }
+
+    if unsatisfied_variables.is_empty() {
+        Err(Error::new(ErrorKind::UnsatisfiedVariable(frame.name(name))))
+    } else {
+        Err(Error::new(ErrorKind::UnsatisfiedVariable(
+            unsatisfied_variables.first().unwrap().clone(),
+        )))
     }
 }
 
 ///
 /// Newton's method implementation of root finding
 ///
-fn find_root(node: &Node, target: &str, guess: f64, mut frame: Frame) -> EngineResult<f64> {
+fn find_root(
+    code: &Bytecode,
+    program: Program,
+    target: Symbol,
+    guess: f64,
+    mut frame: Frame,
+) -> EngineResult<f64> {
     const EPSILON: f64 = 0.00001;
     const MAX_DEPTH: usize = 10000;
 
     let mut last = guess;
 
     for _ in 0..MAX_DEPTH {
-        frame.local(target.to_string(), last);
-        let x_i = evaluate(node, frame.clone())?;
+        let iterations = &frame.environment.root_iterations;
+        iterations.fetch_add(1, Ordering::Relaxed);
+
+        frame.local(target, last);
+        let x_i = run(code, program, &frame)?;
 
-        frame.local(target.to_string(), last + EPSILON);
-        let x_i_epsilon = evaluate(node, frame.clone())?;
+        frame.local(target, last + EPSILON);
+        let x_i_epsilon = run(code, program, &frame)?;
 
         let slope = (x_i_epsilon - x_i) / (EPSILON);
 
         let next = last - x_i / slope;
 
         if (last - next).abs() < EPSILON {
-            frame.memo(target.to_string(), next);
+            frame.memo(target, next);
 
             return Ok(next);
         }
@@ -467,11 +696,17 @@ mod tests {
             .iter()
             .map(|x| *x as char)
             .collect::<Vec<_>>();
-        let node = parse(Lexer::new(input)).unwrap();
         let env = Environment::build(vec![], HashMap::new(), HashMap::new()).unwrap();
-        let frame = Frame::empty(&env);
+        let mut symbols = env.symbols.clone();
+        let node = symbols.resolve(parse(Lexer::new(input)).unwrap());
+        let mut arena = Arena::default();
+        let node = arena.insert(&node);
+        let mut code = Bytecode::default();
+        let program = code.compile(&arena, node, &HashMap::new());
+        let known = Bindings::default();
+        let frame = Frame::new(&env, &symbols, &known);
 
-        let root = find_root(&node, "x", -1.0, frame);
+        let root = find_root(&code, program, symbols.get("x").unwrap(), -1.0, frame);
 
         println!("{:?}", root);
     }
@@ -480,12 +715,56 @@ mod tests {
     fn test_evaluate_multiple_equations() {
         let env = Environment::build(vec![""], HashMap::new(), HashMap::new()).unwrap();
 
+        let mut symbols = env.symbols.clone();
+        let node = symbols.resolve(Node::Variable("a".to_string()));
+        let mut arena = Arena::default();
+        let node = arena.insert(&node);
         let mut frame = Frame::empty(&env);
-        frame.memo("hati", 1.0);
-        frame.memo("hatj", 0.0);
+        frame.memo(symbols.intern("hati"), 1.0);
+        frame.memo(symbols.intern("hatj"), 0.0);
 
-        let res = evaluate(&Node::Variable("a".to_string()), frame).unwrap();
+        let res = evaluate(&arena, node, frame).unwrap();
 
         println!("res: {:?}", res);
     }
+
+    #[test]
+    fn test_index_prefers_definitions() {
+        let env = Environment::build(vec!["y=2*x", "x=3"], HashMap::new(), HashMap::new()).unwrap();
+        let x = env.symbol("x").unwrap();
+
+        assert_eq!(env.index[&x], vec![1, 0]);
+        assert!(env.mentions(x));
+        assert_eq!(env.evaluate("y", &HashMap::new()).unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut env = Environment::build(
+            vec!["g=100", "L=2*g", "a=-g/L*theta"],
+            builtin::functions(),
+            builtin::constants(),
+        )
+        .unwrap();
+        let close = |x: f64, y: f64| (x - y).abs() < 1e-6;
+
+        // Solved once, then kept
+        assert!(close(env.evaluate("L", &HashMap::new()).unwrap(), 200.0));
+        assert!(env.take_root_iterations() > 0);
+        assert!(close(env.evaluate("L", &HashMap::new()).unwrap(), 200.0));
+        assert_eq!(env.take_root_iterations(), 0);
+        let theta = HashMap::from([("theta".to_string(), 2.0)]);
+        assert!(close(env.evaluate("a", &theta).unwrap(), -1.0));
+
+        // Never used while what it depends on is given another value
+        let g = HashMap::from([("g".to_string(), 1.0)]);
+        assert!(close(env.evaluate("L", &g).unwrap(), 2.0));
+        let theta_g = HashMap::from([("theta".to_string(), 2.0), ("g".to_string(), 1.0)]);
+        assert!(close(env.evaluate("a", &theta_g).unwrap(), -1.0));
+
+        // Nor once it's changed
+        env.set_constant("g", 5.0);
+        assert!(close(env.evaluate("L", &HashMap::new()).unwrap(), 10.0));
+        assert!(env.take_root_iterations() > 0);
+    }
 }
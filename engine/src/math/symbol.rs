@@ -0,0 +1,102 @@
+//! Interned variable and function names. An environment resolves every name in its equations to a
+//! `Symbol` once when it is built, so evaluation compares and indexes small integers rather than
+//! hashing strings.
+
+use crate::math::parse::Node;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    names: Vec<String>,
+    ids: HashMap<String, Symbol>,
+}
+
+impl Symbols {
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(x) = self.ids.get(name) {
+            return *x;
+        }
+
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.to_string());
+        self.ids.insert(name.to_string(), symbol);
+
+        symbol
+    }
+
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.ids.get(name).cloned()
+    }
+
+    pub fn name(&self, symbol: Symbol) -> &str {
+        &self.names[symbol.index()]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Interns every variable and function name in a parsed expression.
+    pub fn resolve(&mut self, node: Node) -> Node<Symbol> {
+        node.map_names(&mut |x| self.intern(&x))
+    }
+}
+
+/// Values for some set of symbols, stored densely by symbol index.
+#[derive(Debug, Clone, Default)]
+pub struct Bindings {
+    values: Vec<Option<f64>>,
+}
+
+impl Bindings {
+    pub fn get(&self, symbol: Symbol) -> Option<f64> {
+        self.values.get(symbol.index()).cloned().flatten()
+    }
+
+    pub fn set(&mut self, symbol: Symbol, value: f64) {
+        if self.values.len() <= symbol.index() {
+            self.values.resize(symbol.index() + 1, None);
+        }
+
+        self.values[symbol.index()] = Some(value);
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut symbols = Symbols::default();
+        let a = symbols.intern("v_x_A");
+        let b = symbols.intern("hati");
+
+        assert_eq!(symbols.intern("v_x_A"), a);
+        assert_ne!(a, b);
+        assert_eq!(symbols.name(b), "hati");
+        assert_eq!(symbols.get("x_A"), None);
+
+        let mut bindings = Bindings::default();
+        bindings.set(b, 1.0);
+        assert_eq!(bindings.get(b), Some(1.0));
+        assert_eq!(bindings.get(a), None);
+    }
+}
@@ -0,0 +1,280 @@
+use crate::err::{Error, ErrorKind};
+use crate::math::parse::{Lexer, Node, Token, parse};
+use crate::math::solve::Function;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// A problem found in one of the equations handed to [`validate`].
+#[derive(Debug)]
+pub struct Diagnostic {
+    // Index of the offending equation in the input list
+    pub equation: usize,
+    pub error: Error,
+}
+
+enum Expression {
+    Definition {
+        args: Vec<String>,
+        variables: HashSet<String>,
+        calls: HashSet<String>,
+    },
+    Equation {
+        variables: HashSet<String>,
+        calls: HashSet<String>,
+    },
+}
+
+fn collect(node: &Node, variables: &mut HashSet<String>, calls: &mut HashSet<String>) {
+    match node {
+        Node::Arithmetic { left, right, .. } | Node::Comparison { left, right } => {
+            collect(left, variables, calls);
+            collect(right, variables, calls);
+        }
+        Node::Variable(name) => {
+            variables.insert(name.clone());
+        }
+        Node::Function { name, args } => {
+            calls.insert(name.clone());
+            for x in args {
+                collect(x, variables, calls);
+            }
+        }
+        Node::Number(_) => {}
+    }
+}
+
+fn classify(node: &Node) -> Option<(Option<String>, Expression)> {
+    let Node::Comparison { left, right } = node else {
+        return None;
+    };
+
+    // Mirrors Environment::build, function definitions are f(x, y) = ...
+    if let Node::Function { name, args } = left.as_ref() {
+        let args = args
+            .iter()
+            .map(|it| match it.as_ref() {
+                Node::Variable(name) => Some(name.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+
+        if let Some(args) = args {
+            let mut variables = HashSet::new();
+            let mut calls = HashSet::new();
+            collect(right, &mut variables, &mut calls);
+
+            return Some((
+                Some(name.clone()),
+                Expression::Definition {
+                    args,
+                    variables,
+                    calls,
+                },
+            ));
+        }
+    }
+
+    let mut variables = HashSet::new();
+    let mut calls = HashSet::new();
+    collect(node, &mut variables, &mut calls);
+
+    Some((None, Expression::Equation { variables, calls }))
+}
+
+/// Finds where `name` is used in `source`, either as a function call or a plain variable.
+fn locate(source: &str, name: &str, call: bool) -> Option<Range<usize>> {
+    let mut lexer = Lexer::new(source);
+
+    while let Ok(Some(token)) = lexer.next() {
+        if let Token::Text(text) = token
+            && text == name
+        {
+            let span = lexer.span();
+            let is_call = matches!(lexer.lex(), Ok(Some(Token::OpenParen)));
+
+            if is_call == call {
+                return Some(span);
+            }
+        }
+    }
+
+    None
+}
+
+///
+/// Statically checks a list of expressions without building an environment: reports syntax errors,
+/// calls to unknown functions, and variables that no combination of equations can ever solve for.
+///
+/// `provided` decides whether a variable is supplied externally at evaluation time (eg. the body
+/// state the engine injects), and `queried` whether it is one the engine will ask the environment
+/// for. Queried variables are only reported when nothing else in their equation is to blame.
+///
+pub fn validate(
+    expressions: &[&str],
+    functions: &HashMap<String, Function>,
+    constants: &HashMap<String, f64>,
+    provided: impl Fn(&str) -> bool,
+    queried: impl Fn(&str) -> bool,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut parsed = Vec::new();
+
+    for (i, source) in expressions.iter().enumerate() {
+        match parse(Lexer::new(source)) {
+            Ok(node) => match classify(&node) {
+                Some(x) => parsed.push((i, x)),
+                None => diagnostics.push(Diagnostic {
+                    equation: i,
+                    error: Error::new(ErrorKind::ExpectedComparison).with_span(0..source.chars().count()),
+                }),
+            },
+            Err(error) => diagnostics.push(Diagnostic { equation: i, error }),
+        }
+    }
+
+    let defined = parsed
+        .iter()
+        .filter_map(|(_, (name, _))| name.clone())
+        .collect::<HashSet<_>>();
+
+    let mut known = HashSet::<String>::new();
+    let mut unknowns = Vec::<(usize, HashSet<String>)>::new();
+
+    for (i, (_, expression)) in &parsed {
+        let (calls, variables) = match expression {
+            Expression::Definition {
+                args,
+                variables,
+                calls,
+            } => (
+                calls,
+                variables
+                    .iter()
+                    .filter(|x| !args.contains(x))
+                    .cloned()
+                    .collect::<HashSet<_>>(),
+            ),
+            Expression::Equation { variables, calls } => (calls, variables.clone()),
+        };
+
+        let mut calls = calls.iter().collect::<Vec<_>>();
+        calls.sort();
+        for name in calls {
+            if !functions.contains_key(name) && !defined.contains(name) {
+                let mut error = Error::new(ErrorKind::UnsatisfiedFunction(name.clone()));
+                if let Some(span) = locate(expressions[*i], name, true) {
+                    error = error.with_span(span);
+                }
+                diagnostics.push(Diagnostic {
+                    equation: *i,
+                    error,
+                });
+            }
+        }
+
+        for name in &variables {
+            if constants.contains_key(name) || provided(name) {
+                known.insert(name.clone());
+            }
+        }
+
+        unknowns.push((*i, variables));
+    }
+
+    // Any equation with a single unknown left can be solved for it, repeat until nothing changes.
+    let mut work = true;
+    while work {
+        work = false;
+
+        for (_, variables) in &unknowns {
+            let mut remaining = variables.iter().filter(|x| !known.contains(*x));
+
+            if let (Some(only), None) = (remaining.next(), remaining.next()) {
+                known.insert(only.clone());
+                work = true;
+            }
+        }
+    }
+
+    let mut reported = HashSet::new();
+    for (i, variables) in &unknowns {
+        let mut remaining = variables
+            .iter()
+            .filter(|x| !known.contains(*x))
+            .collect::<Vec<_>>();
+        remaining.sort();
+
+        if remaining.iter().any(|x| !queried(x)) {
+            remaining.retain(|x| !queried(x));
+        }
+
+        for name in remaining {
+            if !reported.insert(name.clone()) {
+                continue;
+            }
+
+            let mut error = Error::new(ErrorKind::UnsatisfiedVariable(name.clone()));
+            if let Some(span) = locate(expressions[*i], name, false) {
+                error = error.with_span(span);
+            }
+            diagnostics.push(Diagnostic {
+                equation: *i,
+                error,
+            });
+        }
+    }
+
+    diagnostics.sort_by_key(|x| x.equation);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::solve::builtin;
+
+    fn check(expressions: &[&str]) -> Vec<Diagnostic> {
+        validate(
+            expressions,
+            &builtin::functions(),
+            &builtin::constants(),
+            |x| x.starts_with("hat"),
+            |x| x.starts_with("a_"),
+        )
+    }
+
+    #[test]
+    fn test_valid_equations() {
+        let diagnostics = check(&["g=100", "f(x)=x^2", "a_B=-f(g)*hatj"]);
+
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_syntax_error_span() {
+        let diagnostics = check(&["g=100", "a_B=g $ 2"]);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].equation, 1);
+        assert_eq!(diagnostics[0].error.span, Some(6..7));
+    }
+
+    #[test]
+    fn test_unknown_function_and_variable() {
+        let diagnostics = check(&["a_B=foo(2)*k*hatj"]);
+
+        assert!(matches!(
+            &diagnostics[0].error.kind,
+            ErrorKind::UnsatisfiedFunction(x) if x == "foo"
+        ));
+        assert_eq!(diagnostics[0].error.span, Some(4..7));
+
+        // a_B is what the engine asks for, k is the actual culprit
+        assert_eq!(diagnostics.len(), 2);
+        assert!(matches!(
+            &diagnostics[1].error.kind,
+            ErrorKind::UnsatisfiedVariable(x) if x == "k"
+        ));
+        assert_eq!(diagnostics[1].error.span, Some(11..12));
+    }
+}
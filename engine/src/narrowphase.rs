@@ -0,0 +1,826 @@
+//! Colliders that work on the convex shapes themselves rather than on where their outlines cross,
+//! as `Collide2D` does. They find how far the bodies have to be moved apart however deep the
+//! overlap, including one body wholly inside another, where no outlines cross at all.
+
+use crate::collide::{Collide, Collision, VertexCache};
+use crate::math::{Column, Vector};
+use crate::spaces::Space2D;
+use crate::{Body, Shape, Space};
+use std::sync::Arc;
+
+// Most steps either search takes, only ever reached by curved shapes
+const ITERATIONS: usize = 64;
+// How close EPA gets to the surface of the difference before it stops
+const TOLERANCE: f64 = 1e-9;
+
+/// The point of `body` furthest along `direction`, in world space, where `outline` is its vertices
+/// as `Collide::vertices` gives them. Ellipses are exact rather than their outline, so any other
+/// shape with a support point (a capsule, a rounded box) could be collided the same way.
+pub fn support(body: &Body<Space2D>, outline: &[Column<2>], direction: &Column<2>) -> Column<2> {
+    let offset = match body.shape {
+        Shape::Ellipse(major, minor) => {
+            // Axes are full lengths
+            let rotation = &body.angular.displacement;
+            let local = Space2D::rotate(&rotation.scale(-1.0), direction);
+            let (x, y) = (
+                (major / 2.0).powi(2) * local[0],
+                (minor / 2.0).powi(2) * local[1],
+            );
+            let length = (x * local[0] + y * local[1]).sqrt();
+
+            match length {
+                0.0 => Column::empty(),
+                _ => Space2D::rotate(rotation, &Column::vector([x / length, y / length])),
+            }
+        }
+        _ => outline
+            .iter()
+            .max_by(|x, y| x.dot(direction).total_cmp(&y.dot(direction)))
+            .cloned()
+            .unwrap_or_else(Column::empty),
+    };
+
+    offset.plus(&body.linear.displacement)
+}
+
+// A point of the difference A - B, with the points of A and B it's from
+#[derive(Clone)]
+struct Vertex {
+    at: Column<2>,
+    a: Column<2>,
+    b: Column<2>,
+}
+
+// The outline of a body where it is in the world
+fn world(outline: &[Column<2>], body: &Body<Space2D>) -> Vec<Column<2>> {
+    outline
+        .iter()
+        .map(|x| x.plus(&body.linear.displacement))
+        .collect()
+}
+
+// Turned a quarter counterclockwise
+fn perpendicular(x: &Column<2>) -> Column<2> {
+    Column::vector([-x[1], x[0]])
+}
+
+// Looks for a triangle of points of the difference around the origin, starting along `direction`.
+// None if there isn't one, when the bodies are apart or only just touch.
+fn gjk(support: &impl Fn(&Column<2>) -> Vertex, direction: Column<2>) -> Option<Vec<Vertex>> {
+    let mut simplex = vec![support(&direction)];
+    let mut direction = simplex[0].at.scale(-1.0);
+
+    for _ in 0..ITERATIONS {
+        if direction.magnitude() == 0.0 {
+            return None;
+        }
+        let next = support(&direction);
+        // Nothing past the origin that way, so it's outside
+        if next.at.dot(&direction) <= 0.0 {
+            return None;
+        }
+        simplex.push(next);
+
+        // Newest last
+        let a = simplex[simplex.len() - 1].at.clone();
+        let to_origin = a.scale(-1.0);
+        match simplex.len() {
+            2 => {
+                let ab = simplex[0].at.plus(&to_origin);
+                let across = perpendicular(&ab);
+                // With the origin on the line either side will do
+                direction = match across.dot(&to_origin) < 0.0 {
+                    true => across.scale(-1.0),
+                    false => across,
+                };
+            }
+            _ => {
+                let (ab, ac) = (
+                    simplex[1].at.plus(&to_origin),
+                    simplex[0].at.plus(&to_origin),
+                );
+                // Facing away from the other corner
+                let out = |x: &Column<2>, other: &Column<2>| {
+                    let across = perpendicular(x);
+                    match across.dot(other) > 0.0 {
+                        true => across.scale(-1.0),
+                        false => across,
+                    }
+                };
+                let (ab_out, ac_out) = (out(&ab, &ac), out(&ac, &ab));
+
+                if ab_out.dot(&to_origin) > 0.0 {
+                    simplex.remove(0);
+                    direction = ab_out;
+                } else if ac_out.dot(&to_origin) > 0.0 {
+                    simplex.remove(1);
+                    direction = ac_out;
+                } else {
+                    return Some(simplex);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Grows the triangle out to the surface of the difference until it finds the face of it nearest
+// the origin: its outward normal, how far it is from the origin, and its two ends
+fn epa(
+    mut polytope: Vec<Vertex>,
+    support: &impl Fn(&Column<2>) -> Vertex,
+) -> Option<(Column<2>, f64, Vertex, Vertex)> {
+    // Counterclockwise, so every face's outward normal is it turned clockwise
+    let (ab, ac) = (
+        polytope[1].at.plus(&polytope[0].at.scale(-1.0)),
+        polytope[2].at.plus(&polytope[0].at.scale(-1.0)),
+    );
+    if perpendicular(&ab).dot(&ac) < 0.0 {
+        polytope.reverse();
+    }
+
+    let mut nearest = None;
+    for _ in 0..ITERATIONS {
+        let (i, normal, distance) = (0..polytope.len())
+            .filter_map(|i| {
+                let edge = polytope[(i + 1) % polytope.len()]
+                    .at
+                    .plus(&polytope[i].at.scale(-1.0));
+                let length = edge.magnitude();
+                if length == 0.0 {
+                    return None;
+                }
+                let normal = Column::vector([edge[1] / length, -edge[0] / length]);
+                let distance = normal.dot(&polytope[i].at);
+                Some((i, normal, distance))
+            })
+            .min_by(|x, y| x.2.total_cmp(&y.2))?;
+
+        let ends = (
+            polytope[i].clone(),
+            polytope[(i + 1) % polytope.len()].clone(),
+        );
+        let next = support(&normal);
+        let done = next.at.dot(&normal) - distance <= TOLERANCE;
+        nearest = Some((normal, distance, ends.0, ends.1));
+        if done {
+            break;
+        }
+        polytope.insert(i + 1, next);
+    }
+
+    nearest
+}
+
+// The part of the segment from `p` to `q` behind the line through `point` facing `normal`
+fn clip_segment(
+    (p, q): (Column<2>, Column<2>),
+    point: &Column<2>,
+    normal: &Column<2>,
+) -> Option<(Column<2>, Column<2>)> {
+    let side = |x: &Column<2>| x.plus(&point.scale(-1.0)).dot(normal);
+    let (side_p, side_q) = (side(&p), side(&q));
+
+    match (side_p <= 0.0, side_q <= 0.0) {
+        (true, true) => Some((p, q)),
+        (false, false) => None,
+        (behind, _) => {
+            let x = p.plus(&q.plus(&p.scale(-1.0)).scale(side_p / (side_p - side_q)));
+            Some(if behind { (p, x) } else { (x, q) })
+        }
+    }
+}
+
+/// Where two convex outlines (in world space and counterclockwise) pressed together along `normal`,
+/// from `a` towards `b`, touch. The face of each facing the other most squarely is found, and the
+/// ends of the one facing less squarely are clipped to the sides of the other and kept where
+/// they're behind it, halfway back to it. Gives the points and the faces of `a` and of `b`, face
+/// `i` running from vertex `i` to `i + 1`, or None if no points are left.
+pub fn face_contact(
+    a: &[Column<2>],
+    b: &[Column<2>],
+    normal: &Column<2>,
+) -> Option<(Vec<Column<2>>, (usize, usize))> {
+    let facing = |outline: &[Column<2>], direction: &Column<2>| {
+        (0..outline.len())
+            .map(|i| {
+                let edge = outline[(i + 1) % outline.len()].plus(&outline[i].scale(-1.0));
+                (i, Column::vector([edge[1], -edge[0]]).unit().dot(direction))
+            })
+            .max_by(|x, y| x.1.total_cmp(&y.1))
+    };
+    let ((face_a, along_a), (face_b, along_b)) =
+        (facing(a, normal)?, facing(b, &normal.scale(-1.0))?);
+
+    // Favouring `a` so two faces lying flat on each other don't flip between ticks
+    let flip = along_b > along_a + 1e-3;
+    let (reference, incident, (r, i)) = match flip {
+        false => (a, b, (face_a, face_b)),
+        true => (b, a, (face_b, face_a)),
+    };
+
+    let (start, end) = (&reference[r], &reference[(r + 1) % reference.len()]);
+    let tangent = end.plus(&start.scale(-1.0)).unit();
+    let outward = Column::vector([tangent[1], -tangent[0]]);
+    let segment = (
+        incident[i].clone(),
+        incident[(i + 1) % incident.len()].clone(),
+    );
+    let (p, q) = clip_segment(segment, start, &tangent.scale(-1.0))
+        .and_then(|x| clip_segment(x, end, &tangent))?;
+
+    let points = [p, q]
+        .into_iter()
+        .filter_map(|x| {
+            let depth = -x.plus(&start.scale(-1.0)).dot(&outward);
+            (depth >= 0.0).then(|| x.plus(&outward.scale(depth / 2.0)))
+        })
+        .collect::<Vec<_>>();
+    if points.is_empty() {
+        return None;
+    }
+
+    Some((points, if flip { (i, r) } else { (r, i) }))
+}
+
+/// Finds whether two convex shapes overlap with GJK, searching the difference of their points for
+/// a triangle around the origin, then how far apart they have to be moved with EPA, growing that
+/// triangle out to the face of the difference nearest the origin. Shapes are only ever asked for
+/// the point furthest along a direction (see `support`), so ellipses are collided exactly rather
+/// than by their outline, and a body sunk wholly inside another still gets the depth it has to be
+/// pushed out by. Polygons pressed together touch along a face (see `face_contact`), anything
+/// round at a single point. Manifolds have to be convex, as `Shape::validate` checks.
+#[derive(Default)]
+pub struct CollideGjk {
+    vertices: VertexCache,
+}
+
+impl CollideGjk {
+    pub fn new() -> Self {
+        CollideGjk::default()
+    }
+}
+
+// The contact between two bodies with the given outlines, as `CollideGjk` finds it
+fn overlap(
+    a: &Body<Space2D>,
+    outline_a: &[Column<2>],
+    b: &Body<Space2D>,
+    outline_b: &[Column<2>],
+) -> Option<Collision<Space2D>> {
+    let difference = |direction: &Column<2>| {
+        let (a, b) = (
+            support(a, outline_a, direction),
+            support(b, outline_b, &direction.scale(-1.0)),
+        );
+        Vertex {
+            at: a.plus(&b.scale(-1.0)),
+            a,
+            b,
+        }
+    };
+
+    let start = b
+        .linear
+        .displacement
+        .plus(&a.linear.displacement.scale(-1.0));
+    let start = match start.magnitude() {
+        0.0 => Column::vector([1.0, 0.0]),
+        _ => start,
+    };
+    let simplex = gjk(&difference, start)?;
+    let (normal, depth, p, q) = epa(simplex, &difference)?;
+
+    // Where on the nearest face the origin is closest to, and the points of each body there
+    let edge = q.at.plus(&p.at.scale(-1.0));
+    let t =
+        (normal.scale(depth).plus(&p.at.scale(-1.0)).dot(&edge) / edge.dot(&edge)).clamp(0.0, 1.0);
+    let on = |p: &Column<2>, q: &Column<2>| p.plus(&q.plus(&p.scale(-1.0)).scale(t));
+    let point = on(&p.a, &q.a).plus(&on(&p.b, &q.b)).scale(0.5);
+
+    let mut collision = Collision::new(point, normal.clone(), depth, a, b);
+    let round = |x: &Body<Space2D>| matches!(x.shape, Shape::Ellipse(..));
+    if !round(a)
+        && !round(b)
+        && let Some((points, faces)) =
+            face_contact(&world(outline_a, a), &world(outline_b, b), &normal)
+    {
+        collision.points = points;
+        collision.faces = Some(faces);
+    }
+
+    Some(collision)
+}
+
+impl Collide<Space2D> for CollideGjk {
+    fn collide(&self, a: &Body<Space2D>, b: &Body<Space2D>) -> Option<Collision<Space2D>> {
+        overlap(a, &self.vertices.get(a), b, &self.vertices.get(b))
+    }
+
+    fn vertices(&self, body: &Body<Space2D>) -> Arc<[Column<2>]> {
+        self.vertices.get(body)
+    }
+
+    fn retain(&self, bodies: &[Body<Space2D>]) {
+        self.vertices.retain(bodies);
+    }
+}
+
+/// Contacts between a chain (as `a`) and any other shape. Each of its segments is collided in turn
+/// as a shape of its own by GJK and EPA (see `CollideGjk`), and the deepest contact is kept, with
+/// the segment as the chain's face. A segment has no inside, so a body is pushed back out of
+/// whichever side of it it's nearer, and is only kept on one side while it sinks less than halfway
+/// through. `Dispatch::default` uses it for every pair with a chain, and finds no contact between
+/// two chains.
+#[derive(Default)]
+pub struct CollideChain {
+    vertices: VertexCache,
+}
+
+impl CollideChain {
+    pub fn new() -> Self {
+        CollideChain::default()
+    }
+}
+
+impl Collide<Space2D> for CollideChain {
+    fn collide(&self, a: &Body<Space2D>, b: &Body<Space2D>) -> Option<Collision<Space2D>> {
+        let (outline_a, outline_b) = (self.vertices.get(a), self.vertices.get(b));
+        // Not given a chain first, so the other as a whole
+        if !matches!(a.shape, Shape::Chain(_)) {
+            return overlap(a, &outline_a, b, &outline_b);
+        }
+
+        (0..outline_a.len().saturating_sub(1))
+            .filter_map(|i| {
+                let mut collision = overlap(a, &outline_a[i..i + 2], b, &outline_b)?;
+                collision.faces = collision.faces.map(|(_, face)| (i, face));
+                Some(collision)
+            })
+            .max_by(|x, y| x.depth.total_cmp(&y.depth))
+    }
+
+    fn vertices(&self, body: &Body<Space2D>) -> Arc<[Column<2>]> {
+        self.vertices.get(body)
+    }
+
+    fn retain(&self, bodies: &[Body<Space2D>]) {
+        self.vertices.retain(bodies);
+    }
+}
+
+/// Contacts between a half-plane (as `a`) and any other shape, from however far the point of the
+/// other reaching furthest behind it has sunk. Polygons touch at their two corners behind it
+/// furthest apart along it, and ellipses at their deepest point, halfway back to the surface.
+/// `Dispatch::default` uses it for every pair with a half-plane, and finds no contact between two.
+#[derive(Default)]
+pub struct CollideHalfPlane {
+    vertices: VertexCache,
+}
+
+impl CollideHalfPlane {
+    pub fn new() -> Self {
+        CollideHalfPlane::default()
+    }
+}
+
+impl Collide<Space2D> for CollideHalfPlane {
+    fn collide(&self, a: &Body<Space2D>, b: &Body<Space2D>) -> Option<Collision<Space2D>> {
+        let outline_b = self.vertices.get(b);
+        // Not given a half-plane first, so the other as a whole
+        let Shape::HalfPlane(normal, offset) = &a.shape else {
+            return overlap(a, &self.vertices.get(a), b, &outline_b);
+        };
+
+        let normal = Space2D::rotate(&a.angular.displacement, &normal.unit());
+        let surface = normal.dot(&a.linear.displacement) + offset;
+        let depth = |x: &Column<2>| surface - x.dot(&normal);
+        let up = |x: &Column<2>| x.plus(&normal.scale(depth(x) / 2.0));
+
+        let deepest = support(b, &outline_b, &normal.scale(-1.0));
+        if depth(&deepest) <= 0.0 {
+            return None;
+        }
+        let mut collision = Collision::new(up(&deepest), normal.clone(), depth(&deepest), a, b);
+
+        if !matches!(b.shape, Shape::Ellipse(..)) {
+            let tangent = perpendicular(&normal);
+            let behind = world(&outline_b, b)
+                .into_iter()
+                .filter(|x| depth(x) > 0.0)
+                .collect::<Vec<_>>();
+            let along = |x: &&Column<2>| x.dot(&tangent);
+            let ends = behind
+                .iter()
+                .min_by(|x, y| along(x).total_cmp(&along(y)))
+                .zip(behind.iter().max_by(|x, y| along(x).total_cmp(&along(y))))
+                .filter(|(first, last)| along(first) < along(last));
+            if let Some((first, last)) = ends {
+                collision.points = vec![up(first), up(last)];
+            }
+        }
+
+        Some(collision)
+    }
+
+    fn vertices(&self, body: &Body<Space2D>) -> Arc<[Column<2>]> {
+        self.vertices.get(body)
+    }
+
+    fn retain(&self, bodies: &[Body<Space2D>]) {
+        self.vertices.retain(bodies);
+    }
+}
+
+/// Finds the minimum translation vector of two convex polygons by the separating axis theorem:
+/// they overlap unless some face of one has all of the other in front of it, and the face the
+/// other has sunk least deep behind gives the normal and the depth directly, the least the bodies
+/// have to be moved apart. Only the faces of either outline are tried, so ellipses are collided as
+/// their outline (see `Collide2D::bases`). Polygons touch along a face as with `CollideGjk`.
+#[derive(Default)]
+pub struct CollideSat {
+    vertices: VertexCache,
+}
+
+impl CollideSat {
+    pub fn new() -> Self {
+        CollideSat::default()
+    }
+}
+
+// The face of `outline` the `other` is sunk least deep behind: its outward normal and how deep the
+// deepest point of `other` is behind it, negative if it's all in front
+fn shallowest(outline: &[Column<2>], other: &[Column<2>]) -> Option<(Column<2>, f64)> {
+    (0..outline.len())
+        .map(|i| {
+            let edge = outline[(i + 1) % outline.len()].plus(&outline[i].scale(-1.0));
+            let normal = Column::vector([edge[1], -edge[0]]).unit();
+            let depth = other
+                .iter()
+                .map(|x| -x.plus(&outline[i].scale(-1.0)).dot(&normal))
+                .fold(f64::NEG_INFINITY, f64::max);
+            (normal, depth)
+        })
+        .min_by(|x, y| x.1.total_cmp(&y.1))
+}
+
+impl Collide<Space2D> for CollideSat {
+    fn collide(&self, a: &Body<Space2D>, b: &Body<Space2D>) -> Option<Collision<Space2D>> {
+        let (outline_a, outline_b) = (
+            world(&self.vertices.get(a), a),
+            world(&self.vertices.get(b), b),
+        );
+
+        let (normal_a, depth_a) = shallowest(&outline_a, &outline_b)?;
+        let (normal_b, depth_b) = shallowest(&outline_b, &outline_a)?;
+        if depth_a <= 0.0 || depth_b <= 0.0 {
+            return None;
+        }
+        // A face of B faces back towards A. Favouring A's faces, like `face_contact`
+        let (normal, depth) = match depth_b < depth_a - 1e-9 {
+            true => (normal_b.scale(-1.0), depth_b),
+            false => (normal_a, depth_a),
+        };
+
+        let contact = face_contact(&outline_a, &outline_b, &normal);
+        // Failing that, the deepest point of B
+        let point = match &contact {
+            Some((points, _)) => points
+                .iter()
+                .fold(Column::empty(), |sum, x| sum.plus(x))
+                .scale(1.0 / points.len() as f64),
+            None => outline_b
+                .iter()
+                .min_by(|x, y| x.dot(&normal).total_cmp(&y.dot(&normal)))?
+                .plus(&normal.scale(depth / 2.0)),
+        };
+
+        let mut collision = Collision::new(point, normal, depth, a, b);
+        if let Some((points, faces)) = contact {
+            collision.points = points;
+            collision.faces = Some(faces);
+        }
+
+        Some(collision)
+    }
+
+    fn vertices(&self, body: &Body<Space2D>) -> Arc<[Column<2>]> {
+        self.vertices.get(body)
+    }
+
+    fn retain(&self, bodies: &[Body<Space2D>]) {
+        self.vertices.retain(bodies);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broadphase::SpatialHash;
+    use crate::collide::Dispatch;
+    use crate::math::solve::{Environment, builtin};
+    use crate::{BodyProperties, Engine, scenarios};
+    use std::f64::consts::PI;
+
+    fn body(shape: Shape<Space2D>, position: [f64; 2], rotation: f64) -> Body<Space2D> {
+        Body::at_rest(
+            String::new(),
+            shape,
+            Column::vector(position),
+            Column::vector([rotation]),
+            BodyProperties::rectangle(1.0, 1.0, 1.0),
+        )
+    }
+
+    // EPA stops short of the exact normal to a curve
+    fn close(x: &Column<2>, y: [f64; 2]) -> bool {
+        (x[0] - y[0]).abs() + (x[1] - y[1]).abs() < 1e-4
+    }
+
+    #[test]
+    fn test_resting_face() {
+        let floor = body(Shape::Rec(10.0, 1.0), [0.0, 0.0], 0.0);
+        let block = body(Shape::Rec(1.0, 1.0), [2.0, 0.9], 0.0);
+
+        let collision = CollideGjk::new().collide(&floor, &block).unwrap();
+        assert!(
+            close(&collision.normal, [0.0, 1.0]),
+            "{:?}",
+            collision.normal
+        );
+        assert!((collision.depth - 0.1).abs() < 1e-9);
+        // Both bottom corners, halfway into the overlap
+        let mut points = collision.points.clone();
+        points.sort_by(|x, y| x[0].total_cmp(&y[0]));
+        assert_eq!(points.len(), 2);
+        assert!(close(&points[0], [1.5, 0.45]), "{:?}", points);
+        assert!(close(&points[1], [2.5, 0.45]), "{:?}", points);
+
+        let apart = body(Shape::Rec(1.0, 1.0), [2.0, 1.1], 0.0);
+        assert!(CollideGjk::new().collide(&floor, &apart).is_none());
+    }
+
+    #[test]
+    fn test_contained() {
+        // No outlines cross, so the edge collider sees nothing
+        let outer = body(Shape::Rec(10.0, 10.0), [0.0, 0.0], 0.0);
+        let inner = body(Shape::Rec(1.0, 1.0), [3.0, 0.5], 0.3);
+        assert!(Dispatch::default().collide(&outer, &inner).is_none());
+
+        // Pushed out through the nearest side
+        let collision = CollideGjk::new().collide(&outer, &inner).unwrap();
+        assert!(
+            close(&collision.normal, [1.0, 0.0]),
+            "{:?}",
+            collision.normal
+        );
+        let reach = (0.3f64.cos() + 0.3f64.sin()) / 2.0;
+        assert!(
+            (collision.depth - (2.0 + reach)).abs() < 1e-9,
+            "{}",
+            collision.depth
+        );
+    }
+
+    #[test]
+    fn test_ellipses() {
+        let circle = |x: f64, y: f64| body(Shape::Ellipse(2.0, 2.0), [x, y], 0.0);
+        let collision = CollideGjk::new()
+            .collide(&circle(0.0, 0.0), &circle(0.6, 0.8))
+            .unwrap();
+        assert!(
+            close(&collision.normal, [0.6, 0.8]),
+            "{:?}",
+            collision.normal
+        );
+        assert!((collision.depth - 1.0).abs() < 1e-6, "{}", collision.depth);
+        assert_eq!(collision.points.len(), 1);
+        assert!(
+            CollideGjk::new()
+                .collide(&circle(0.0, 0.0), &circle(1.5, 1.6))
+                .is_none()
+        );
+
+        // Lying flat on the floor, exactly rather than by its outline
+        let floor = body(Shape::Rec(10.0, 1.0), [0.0, 0.0], 0.0);
+        let ellipse = body(Shape::Ellipse(4.0, 1.0), [0.0, 0.9], 0.0);
+        let collision = CollideGjk::new().collide(&floor, &ellipse).unwrap();
+        assert!(
+            close(&collision.normal, [0.0, 1.0]),
+            "{:?}",
+            collision.normal
+        );
+        assert!((collision.depth - 0.1).abs() < 1e-6, "{}", collision.depth);
+    }
+
+    #[test]
+    fn test_separating_axis() {
+        // Anywhere two polygons overlap the translation is the same however it's found
+        let mut rng = crate::noise::Rng::new(7);
+        let mut random = || rng.uniform();
+        let (mut overlaps, mut deep) = (0, 0);
+        for _ in 0..500 {
+            let mut shape = || match random() < 0.5 {
+                true => Shape::Rec(random() * 4.0 + 0.5, random() * 4.0 + 0.5),
+                false => Shape::convex(
+                    (0..6)
+                        .map(|_| Column::vector([random() * 4.0 - 2.0, random() * 4.0 - 2.0]))
+                        .collect(),
+                ),
+            };
+            let (first, second) = (shape(), shape());
+            if [&first, &second].iter().any(|x| x.validate().is_err()) {
+                continue;
+            }
+            let a = body(first, [0.0, 0.0], random() * 6.0);
+            let b = body(
+                second,
+                [random() * 4.0 - 2.0, random() * 4.0 - 2.0],
+                random() * 6.0,
+            );
+
+            let (sat, gjk) = (
+                CollideSat::new().collide(&a, &b),
+                CollideGjk::new().collide(&a, &b),
+            );
+            let (Some(sat), Some(gjk)) = (sat, gjk) else {
+                continue;
+            };
+            overlaps += 1;
+            if Dispatch::default().collide(&a, &b).is_none() {
+                deep += 1;
+            }
+            assert!(
+                (sat.depth - gjk.depth).abs() < 1e-6,
+                "{} {}",
+                sat.depth,
+                gjk.depth
+            );
+            assert!(close(&sat.normal, [gjk.normal[0], gjk.normal[1]]));
+        }
+        assert!(overlaps > 100, "{}", overlaps);
+        // Some wholly inside the other
+        assert!(deep > 0);
+
+        let outer = body(Shape::Rec(10.0, 10.0), [0.0, 0.0], 0.0);
+        let inner = body(Shape::Rec(1.0, 1.0), [0.0, 4.0], 0.0);
+        let collision = CollideSat::new().collide(&outer, &inner).unwrap();
+        assert!(
+            close(&collision.normal, [0.0, 1.0]),
+            "{:?}",
+            collision.normal
+        );
+        assert!((collision.depth - 1.5).abs() < 1e-9);
+        assert_eq!(collision.points.len(), 2);
+    }
+
+    #[test]
+    fn test_chain() {
+        // A floor with a wall at its right end, which is never closed off into a triangle
+        let chain = Shape::Chain(
+            [[-20.0, 0.0], [20.0, 0.0], [20.0, 10.0]]
+                .map(Column::vector)
+                .to_vec(),
+        );
+        assert!(chain.validate().is_ok());
+        let floor = body(chain, [0.0, 0.0], 0.0);
+
+        let resting = body(Shape::Rec(2.0, 2.0), [0.0, 0.9], 0.0);
+        let collision = CollideChain::new().collide(&floor, &resting).unwrap();
+        assert!(
+            close(&collision.normal, [0.0, 1.0]),
+            "{:?}",
+            collision.normal
+        );
+        assert!((collision.depth - 0.1).abs() < 1e-9);
+        assert_eq!(collision.points.len(), 2);
+        assert_eq!(collision.faces.map(|x| x.0), Some(0));
+
+        let leaning = body(Shape::Ellipse(2.0, 2.0), [19.5, 5.0], 0.0);
+        let collision = Dispatch::default().collide(&leaning, &floor).unwrap();
+        assert!(
+            close(&collision.normal, [1.0, 0.0]),
+            "{:?}",
+            collision.normal
+        );
+        assert!((collision.depth - 0.5).abs() < 1e-6, "{}", collision.depth);
+        // Where the closing edge would have been
+        let inside = body(Shape::Rec(1.0, 1.0), [10.0, 3.0], 0.0);
+        assert!(Dispatch::default().collide(&floor, &inside).is_none());
+
+        for points in [vec![[0.0, 0.0]], vec![[0.0, 0.0], [1.0, 0.0], [1.0, 0.0]]] {
+            let chain = Shape::<Space2D>::Chain(points.into_iter().map(Column::vector).collect());
+            assert!(chain.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_box_lands_on_boundary() {
+        let boundaries = [
+            Shape::segment(Column::vector([-20.0, 0.0]), Column::vector([20.0, 0.0])),
+            Shape::HalfPlane(Column::vector([0.0, 1.0]), 0.0),
+        ];
+        for (shape, hash) in boundaries
+            .into_iter()
+            .flat_map(|x| [(x.clone(), false), (x, true)])
+        {
+            let floor = Body::at_rest(
+                "Floor".to_string(),
+                shape.clone(),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::from_density(1.0, &shape),
+            );
+            let mut engine = Engine::new(
+                vec![floor, body(Shape::Rec(1.0, 1.0), [0.0, 3.0], 0.0)],
+                Environment::build(
+                    vec!["a_A=-10*hatj"],
+                    builtin::functions(),
+                    builtin::constants(),
+                )
+                .unwrap(),
+                Box::new(Dispatch::default()),
+                0.02,
+                0.0,
+            );
+            engine.bodies_mut()[1].name = "A".to_string();
+            if hash {
+                engine.set_broadphase(Box::new(SpatialHash::new(2.0)));
+            }
+            for _ in 0..200 {
+                engine.tick().unwrap();
+            }
+
+            let [floor, block] = [0, 1].map(|i| engine.bodies()[i].clone());
+            assert!(
+                (block.linear.displacement[1] - 0.5).abs() < 0.05,
+                "{:?}",
+                block.linear.displacement
+            );
+            assert!(block.linear.velocity.magnitude() < 0.1);
+            assert_eq!(floor.linear.displacement, Column::vector([0.0, 0.0]));
+        }
+    }
+
+    #[test]
+    fn test_half_plane() {
+        // Everything left of x = 2, once the plane's turned a quarter
+        let wall = body(
+            Shape::HalfPlane(Column::vector([0.0, 3.0]), 2.0),
+            [0.0, 0.0],
+            -PI / 2.0,
+        );
+        let block = body(Shape::Rec(1.0, 2.0), [2.4, 7.0], 0.0);
+        let collision = Dispatch::default().collide(&block, &wall).unwrap();
+        assert!(
+            close(&collision.normal, [-1.0, 0.0]),
+            "{:?}",
+            collision.normal
+        );
+        assert!((collision.depth - 0.1).abs() < 1e-9);
+        let mut points = collision.points.clone();
+        points.sort_by(|x, y| x[1].total_cmp(&y[1]));
+        assert!(close(&points[0], [1.95, 6.0]) && close(&points[1], [1.95, 8.0]));
+
+        let ball = body(Shape::Ellipse(1.0, 1.0), [1.0, -1e6], 0.0);
+        let collision = CollideHalfPlane::new().collide(&wall, &ball).unwrap();
+        assert!((collision.depth - 1.5).abs() < 1e-6);
+        assert_eq!(collision.points.len(), 1);
+        let ball = body(Shape::Ellipse(1.0, 1.0), [2.6, 0.0], 0.0);
+        assert!(CollideHalfPlane::new().collide(&wall, &ball).is_none());
+
+        let flat = Shape::<Space2D>::HalfPlane(Column::vector([0.0, 0.0]), 0.0);
+        assert!(flat.validate().is_err());
+    }
+
+    #[test]
+    fn test_pyramid_stands() {
+        let scenario = scenarios::pyramid(3);
+        let mut engine = Engine::new(
+            scenario.bodies.clone(),
+            Environment::build(
+                scenario.equations.iter().map(|x| x.as_str()).collect(),
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(CollideGjk::new()),
+            scenario.delta_t,
+            scenario.restitution,
+        );
+        for _ in 0..300 {
+            engine.tick().unwrap();
+        }
+
+        for (body, start) in engine.bodies().iter().zip(&scenario.bodies).skip(1) {
+            let moved = body
+                .linear
+                .displacement
+                .plus(&start.linear.displacement.scale(-1.0));
+            // Boxes 40 wide, spreading a little as they settle
+            assert!(moved.magnitude() < 5.0, "{} {:?}", body.name, moved);
+            assert!(body.angular.displacement[0].abs() < 0.05, "{}", body.name);
+        }
+    }
+}
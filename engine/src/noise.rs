@@ -0,0 +1,126 @@
+//! Random forces on bodies (Brownian motion, gusts, sensor-like jitter for testing controllers),
+//! drawn from a generator seeded per engine so a run can always be reproduced.
+
+use crate::math::Vector;
+use crate::{BodyId, Space};
+
+/// xorshift64, the exact sequence doesn't matter as long as a seed always gives the same numbers.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng {
+            state: seed.wrapping_mul(0x9E3779B97F4A7C15) | 1,
+        }
+    }
+
+    /// Uniformly distributed in [0, 1).
+    pub fn uniform(&mut self) -> f64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Normally distributed with a mean of 0 and standard deviation of 1 (Box-Muller).
+    pub fn gaussian(&mut self) -> f64 {
+        let (u, v) = (1.0 - self.uniform(), self.uniform());
+        (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Noise {
+    /// A new random force every tick, scaled with the time step so the spread of velocities it
+    /// causes over a second is `strength` over the body's mass whatever the step.
+    White { strength: f64 },
+    /// A random force that wanders rather than jumping about, pulled back towards zero at a rate of
+    /// `reversion` per second. It settles to a spread of `strength` / sqrt(2 * `reversion`).
+    OrnsteinUhlenbeck { strength: f64, reversion: f64 },
+}
+
+pub(crate) struct Noisy<S: Space> {
+    pub body: BodyId,
+    pub noise: Noise,
+    // Current force, only carried between ticks by Ornstein-Uhlenbeck noise
+    force: S::Linear,
+}
+
+impl<S: Space> Noisy<S> {
+    pub fn new(body: BodyId, noise: Noise) -> Self {
+        Noisy {
+            body,
+            noise,
+            force: S::Linear::empty(),
+        }
+    }
+
+    /// The force over the next tick.
+    pub fn sample(&mut self, rng: &mut Rng, delta_t: f64) -> S::Linear {
+        let gaussian = S::Linear::new((0..S::Linear::dof()).map(|_| rng.gaussian()).collect())
+            .unwrap_or_else(|_| S::Linear::empty());
+
+        self.force = match self.noise {
+            Noise::White { strength } => gaussian.scale(strength / delta_t.sqrt()),
+            Noise::OrnsteinUhlenbeck {
+                strength,
+                reversion,
+            } => self
+                .force
+                .scale(1.0 - reversion * delta_t)
+                .plus(&gaussian.scale(strength * delta_t.sqrt())),
+        };
+
+        self.force.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenarios;
+
+    #[test]
+    fn test_gaussian() {
+        let mut rng = Rng::new(7);
+        let samples = (0..10000).map(|_| rng.gaussian()).collect::<Vec<_>>();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance =
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.05, "{}", mean);
+        assert!((variance - 1.0).abs() < 0.05, "{}", variance);
+    }
+
+    #[test]
+    fn test_seeded_noise() {
+        let run = |seed: u64| {
+            let mut engine = scenarios::newtons_cradle().build().unwrap();
+            engine.set_seed(seed);
+            let [a, b] = [1, 2].map(|i| engine.bodies()[i].id.unwrap());
+            engine.set_noise(
+                a,
+                Some(Noise::OrnsteinUhlenbeck {
+                    strength: 10.0,
+                    reversion: 1.0,
+                }),
+            );
+            engine.set_noise(b, Some(Noise::White { strength: 10.0 }));
+            for _ in 0..30 {
+                engine.tick().unwrap();
+            }
+
+            [1, 2].map(|i| {
+                let position = &engine.bodies()[i].linear.displacement;
+                [*position.get(0), *position.get(1)]
+            })
+        };
+
+        assert_eq!(run(1), run(1));
+        assert_ne!(run(1), run(2));
+        assert_ne!(run(1)[0], [0.0, 0.0]);
+        assert_ne!(run(1)[1], [42.0, 0.0]);
+    }
+}
@@ -0,0 +1,222 @@
+//! Subscriptions to changes of particular bodies, so a UI can update what changed instead of
+//! diffing every body after every tick. Callbacks run at the end of the tick that caused them.
+
+use crate::math::Vector;
+use crate::events::Event;
+use crate::{Body, BodyId, Space};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subject {
+    /// The body with this name.
+    Body(String),
+    /// The body with this id, whatever it's named.
+    Id(BodyId),
+    /// Every body with this tag.
+    Tag(String),
+}
+
+impl Subject {
+    fn matches<S: Space>(&self, body: &Body<S>) -> bool {
+        match self {
+            Subject::Body(name) => body.name == *name,
+            Subject::Id(id) => body.id == Some(*id),
+            Subject::Tag(tag) => body.properties.tag.as_ref() == Some(tag),
+        }
+    }
+}
+
+pub enum Change<S: Space> {
+    /// The body has moved further than the subscription's threshold since it was last reported.
+    Moved { body: BodyId, position: S::Linear },
+    /// The body touched `other`.
+    Collided { body: BodyId, other: BodyId },
+    /// The body came to rest and stopped being simulated, see `Engine::set_sleeping`.
+    FellAsleep { body: BodyId },
+    /// The body started being simulated again.
+    Woke { body: BodyId },
+}
+
+pub type Callback<S> = Box<dyn FnMut(&Change<S>)>;
+
+struct Subscription<S: Space> {
+    subject: Subject,
+    epsilon: f64,
+    // Where each body was when it was last reported
+    reported: HashMap<BodyId, S::Linear>,
+    callback: Callback<S>,
+}
+
+pub(crate) struct Observers<S: Space> {
+    // Indexed by subscription id, None once unsubscribed
+    subscriptions: Vec<Option<Subscription<S>>>,
+}
+
+impl<S: Space> Default for Observers<S> {
+    fn default() -> Self {
+        Observers {
+            subscriptions: Vec::new(),
+        }
+    }
+}
+
+impl<S: Space> Observers<S> {
+    pub fn subscribe(&mut self, subject: Subject, epsilon: f64, callback: Callback<S>) -> usize {
+        self.subscriptions.push(Some(Subscription {
+            subject,
+            epsilon,
+            reported: HashMap::new(),
+            callback,
+        }));
+
+        self.subscriptions.len() - 1
+    }
+
+    pub fn unsubscribe(&mut self, id: usize) {
+        if let Some(x) = self.subscriptions.get_mut(id) {
+            *x = None;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.iter().all(|x| x.is_none())
+    }
+
+    /// Reports changes after a tick, given the pairs of bodies that collided during it and the
+    /// tick's events.
+    pub fn notify(&mut self, bodies: &[Body<S>], collided: &[(usize, usize)], events: &[Event]) {
+        for subscription in self.subscriptions.iter_mut().flatten() {
+            // Every body has an id once validated
+            for (body, id) in bodies.iter().filter_map(|x| Some((x, x.id?))) {
+                if !subscription.subject.matches(body) {
+                    continue;
+                }
+
+                let position = &body.linear.displacement;
+                // Bodies aren't reported the first time they are seen, only once they move
+                let moved = match subscription.reported.get(&id) {
+                    Some(last) => {
+                        position.plus(&last.scale(-1.0)).magnitude() > subscription.epsilon
+                    }
+                    None => {
+                        subscription.reported.insert(id, position.clone());
+                        false
+                    }
+                };
+
+                if moved {
+                    subscription.reported.insert(id, position.clone());
+                    (subscription.callback)(&Change::Moved {
+                        body: id,
+                        position: position.clone(),
+                    });
+                }
+            }
+
+            for (a, b) in collided {
+                for (body, other) in [(*a, *b), (*b, *a)] {
+                    if subscription.subject.matches(&bodies[body])
+                        && let (Some(body), Some(other)) = (bodies[body].id, bodies[other].id)
+                    {
+                        (subscription.callback)(&Change::Collided { body, other });
+                    }
+                }
+            }
+
+            for event in events {
+                let (Event::FellAsleep { body: id } | Event::Woke { body: id }) = event else {
+                    continue;
+                };
+                if !bodies
+                    .iter()
+                    .any(|x| x.id == Some(*id) && subscription.subject.matches(x))
+                {
+                    continue;
+                }
+
+                (subscription.callback)(&match event {
+                    Event::FellAsleep { .. } => Change::FellAsleep { body: *id },
+                    _ => Change::Woke { body: *id },
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenarios;
+    use crate::sleep::Sleeping;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_observers() {
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        let ball = engine.bodies()[1].id.unwrap();
+
+        let (moves, hits) = (Rc::new(RefCell::new(0)), Rc::new(RefCell::new(Vec::new())));
+        let (m, h) = (moves.clone(), hits.clone());
+        let id = engine.subscribe(
+            Subject::Body("Striker".to_string()),
+            10.0,
+            Box::new(move |change| match change {
+                Change::Moved { .. } => *m.borrow_mut() += 1,
+                Change::Collided { other, .. } => h.borrow_mut().push(*other),
+                _ => {}
+            }),
+        );
+
+        // The striker is 160 from the first ball and stops dead on hitting it, covering 100 units a
+        // second so 11.7 every seventh tick, when it is first past the threshold
+        for _ in 0..120 {
+            engine.tick().unwrap();
+        }
+        assert!((13..=14).contains(&*moves.borrow()), "{}", moves.borrow());
+        assert!(!hits.borrow().is_empty());
+        assert!(hits.borrow().iter().all(|x| *x == ball));
+
+        let count = *moves.borrow();
+        engine.unsubscribe(id);
+        for _ in 0..60 {
+            engine.tick().unwrap();
+        }
+        assert_eq!(*moves.borrow(), count);
+    }
+
+    #[test]
+    fn test_observe_sleeping() {
+        // With friction, so the stack comes to rest rather than sliding apart
+        let mut scenario = scenarios::stacked_boxes();
+        for body in &mut scenario.bodies {
+            body.properties = body.properties.clone().with_friction(0.5, 0.5);
+        }
+        let mut engine = scenario.build().unwrap();
+        engine.set_sleeping(Some(Sleeping::default()));
+        let top = engine.bodies().len() - 1;
+        let id = engine.bodies()[top].id.unwrap();
+
+        let changes = Rc::new(RefCell::new(Vec::new()));
+        let c = changes.clone();
+        engine.subscribe(
+            Subject::Id(id),
+            f64::INFINITY,
+            Box::new(move |change| match change {
+                Change::FellAsleep { body } => c.borrow_mut().push((*body, true)),
+                Change::Woke { body } => c.borrow_mut().push((*body, false)),
+                _ => {}
+            }),
+        );
+
+        for _ in 0..600 {
+            engine.tick().unwrap();
+        }
+        assert!(engine.is_asleep(id));
+        assert!(changes.borrow().contains(&(id, true)));
+
+        engine.wake(id);
+        engine.tick().unwrap();
+        assert_eq!(changes.borrow().last(), Some(&(id, false)));
+    }
+}
@@ -0,0 +1,284 @@
+//! Collision results remembered between ticks. Pairs that were in contact last tick are tested first,
+//! and, given a tolerance, pairs that were apart and have barely moved relative to each other since
+//! they were last tested are not tested at all. Contacts carry over the impulses they were resolved
+//! with, for warm starting the solver.
+
+use crate::collide::Collision;
+use crate::math::Vector;
+use crate::{Body, BodyId, Shape, Space};
+use std::collections::HashMap;
+
+/// Relative motion (in world units) below which a separated pair is assumed to still be separated.
+/// Zero, so every pair is tested unless a scene opts in with `Engine::set_pair_tolerance`.
+pub const DEFAULT_TOLERANCE: f64 = 0.0;
+
+struct PairEntry<S: Space> {
+    // Where both bodies were when the pair was last tested
+    a: (S::Linear, S::Angular),
+    b: (S::Linear, S::Angular),
+    // The contact found then, kept for warm starting the solver
+    contact: Option<Collision<S>>,
+    // Normal impulse at each of the contact's points, those it was resolved with once it has been
+    // and until then those carried over from the same points last tick
+    impulses: Vec<f64>,
+}
+
+pub(crate) struct PairCache<S: Space> {
+    // One per candidate pair, by the ids of its bodies in the order the broadphase gave them
+    entries: HashMap<(BodyId, BodyId), PairEntry<S>>,
+    pub tolerance: f64,
+}
+
+impl<S: Space> Default for PairCache<S> {
+    fn default() -> Self {
+        PairCache {
+            entries: HashMap::new(),
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+}
+
+// The pair's key, if both bodies have been given ids
+fn key<S: Space>(a: &Body<S>, b: &Body<S>) -> Option<(BodyId, BodyId)> {
+    Some((a.id?, b.id?))
+}
+
+impl<S: Space> PairCache<S> {
+    /// The `candidates` (pairs of `bodies` the broadphase found close enough to touch), with those
+    /// in contact last tick first. Pairs that are no longer candidates are forgotten, so only the
+    /// pairs near each other are ever remembered.
+    pub fn order(
+        &mut self,
+        bodies: &[Body<S>],
+        candidates: Vec<(usize, usize)>,
+    ) -> Vec<(usize, usize)> {
+        let mut kept = HashMap::with_capacity(candidates.len());
+        for &(i, j) in &candidates {
+            if let Some(key) = key(&bodies[i], &bodies[j])
+                && let Some(entry) = self.entries.remove(&key)
+            {
+                kept.insert(key, entry);
+            }
+        }
+        self.entries = kept;
+
+        let (mut touching, apart): (Vec<_>, Vec<_>) =
+            candidates.into_iter().partition(|&(i, j)| {
+                self.entry(&bodies[i], &bodies[j])
+                    .is_some_and(|x| x.contact.is_some())
+            });
+
+        touching.extend(apart);
+        touching
+    }
+
+    fn entry(&self, a: &Body<S>, b: &Body<S>) -> Option<&PairEntry<S>> {
+        self.entries.get(&key(a, b)?)
+    }
+
+    /// Forgets every pair, so all of them are tested again.
+    pub fn forget(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Whether the pair has to be tested again, given where its bodies are now.
+    pub fn needs_test(&self, a: &Body<S>, b: &Body<S>) -> bool {
+        let Some(entry) = self.entry(a, b) else {
+            return true;
+        };
+        if entry.contact.is_some() {
+            return true;
+        }
+
+        let moved = |body: &Body<S>, (s, q): &(S::Linear, S::Angular)| {
+            (
+                body.linear.displacement.plus(&s.scale(-1.0)),
+                // No point of the body can have moved further than this by rotating, and one
+                // that reaches forever hasn't moved at all if it hasn't turned
+                match body.angular.displacement.plus(&q.scale(-1.0)).magnitude() {
+                    0.0 => 0.0,
+                    x => x * reach(&body.shape),
+                },
+            )
+        };
+
+        let (s_a, q_a) = moved(a, &entry.a);
+        let (s_b, q_b) = moved(b, &entry.b);
+
+        s_a.plus(&s_b.scale(-1.0)).magnitude() + q_a + q_b >= self.tolerance
+    }
+
+    /// Remembers the result of testing the pair. Bodies without ids aren't remembered at all.
+    pub fn record(&mut self, a: &Body<S>, b: &Body<S>, contact: Option<Collision<S>>) {
+        let Some(key) = key(a, b) else {
+            return;
+        };
+        let at = |x: &Body<S>| {
+            (
+                x.linear.displacement.clone(),
+                x.angular.displacement.clone(),
+            )
+        };
+
+        let impulses = match (self.entries.get(&key), &contact) {
+            (Some(previous), Some(contact)) => carried(previous, contact, a),
+            _ => Vec::new(),
+        };
+        self.entries.insert(
+            key,
+            PairEntry {
+                a: at(a),
+                b: at(b),
+                contact,
+                impulses,
+            },
+        );
+    }
+
+    /// The impulses the pair's contact was resolved with, or those carried over from last tick
+    /// to start resolving it from. Empty if there's nothing to carry over.
+    pub fn impulses(&self, a: &Body<S>, b: &Body<S>) -> &[f64] {
+        match self.entry(a, b) {
+            Some(entry) => &entry.impulses,
+            None => &[],
+        }
+    }
+
+    /// Remembers the impulses the pair's contact was resolved with, one per point.
+    pub fn set_impulses(&mut self, a: &Body<S>, b: &Body<S>, impulses: Vec<f64>) {
+        if let Some(entry) = key(a, b).and_then(|x| self.entries.get_mut(&x)) {
+            entry.impulses = impulses;
+        }
+    }
+
+    /// The contact the bodies with ids `a` and `b` had when they were last tested, in that order.
+    pub fn contact(&self, a: BodyId, b: BodyId) -> Option<&Collision<S>> {
+        self.entries.get(&(a, b))?.contact.as_ref()
+    }
+}
+
+// The impulse at each point of `contact` carried over from the point of the previous contact
+// nearest it. Points are only the same from one tick to the next if they're between the same faces,
+// contacts on other faces (or without any) start from nothing.
+fn carried<S: Space>(previous: &PairEntry<S>, contact: &Collision<S>, a: &Body<S>) -> Vec<f64> {
+    let Some(old) = &previous.contact else {
+        return Vec::new();
+    };
+    // The collider may have flipped the normal around, and the impulses with it
+    if old.faces != contact.faces
+        || old.normal.dot(&contact.normal) <= 0.0
+        || previous.impulses.len() != old.points.len()
+    {
+        return Vec::new();
+    }
+
+    // Relative to A, which has barely moved or turned since
+    let offset = |x: &S::Linear, from: &S::Linear| x.plus(&from.scale(-1.0));
+    contact
+        .points
+        .iter()
+        .map(|point| {
+            let point = offset(point, &a.linear.displacement);
+            let distance = |x: &S::Linear| {
+                offset(x, &previous.a.0)
+                    .plus(&point.scale(-1.0))
+                    .magnitude()
+            };
+            old.points
+                .iter()
+                .zip(&previous.impulses)
+                .min_by(|x, y| distance(x.0).total_cmp(&distance(y.0)))
+                .map_or(0.0, |(_, impulse)| *impulse)
+        })
+        .collect()
+}
+
+/// Farthest any point of the shape is from its origin, infinite for a half-plane.
+pub(crate) fn reach<S: Space>(shape: &Shape<S>) -> f64 {
+    match shape {
+        Shape::Rec(width, height) => (width.powi(2) + height.powi(2)).sqrt() / 2.0,
+        // Axes are full lengths
+        Shape::Ellipse(major, minor) => major.max(*minor) / 2.0,
+        Shape::Manifold(points) | Shape::Chain(points) => {
+            points.iter().map(|x| x.magnitude()).fold(0.0, f64::max)
+        }
+        Shape::HalfPlane(..) => f64::INFINITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BodyProperties;
+    use crate::collide::{Collide, Collide2D};
+    use crate::math::Column;
+    use crate::spaces::Space2D;
+
+    fn rec(id: u64, x: f64) -> Body<Space2D> {
+        let mut body = Body::at_rest(
+            String::new(),
+            Shape::Rec(2.0, 2.0),
+            Column::vector([x, 0.0]),
+            Column::vector([0.1]),
+            BodyProperties::rectangle(1.0, 2.0, 2.0),
+        );
+        body.id = Some(BodyId(id));
+        body
+    }
+
+    fn all(n: usize) -> Vec<(usize, usize)> {
+        (0..n)
+            .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+            .collect()
+    }
+
+    #[test]
+    fn test_skips_still_pairs() {
+        let mut bodies = vec![rec(0, 0.0), rec(1, 10.0), rec(2, 1.5)];
+        let mut cache = PairCache::<Space2D>::default();
+        let (a, b, c) = (BodyId(0), BodyId(1), BodyId(2));
+
+        for (i, j) in cache.order(&bodies, all(3)) {
+            let contact = Collide2D::new().collide(&bodies[i], &bodies[j]);
+            cache.record(&bodies[i], &bodies[j], contact);
+        }
+        assert!(cache.contact(a, c).is_some());
+
+        // The pair in contact goes first and always gets tested, as does every other without a
+        // tolerance
+        assert_eq!(cache.order(&bodies, all(3))[0], (0, 2));
+        assert!(cache.needs_test(&bodies[0], &bodies[2]));
+        assert!(cache.needs_test(&bodies[0], &bodies[1]));
+        cache.tolerance = 0.01;
+        assert!(!cache.needs_test(&bodies[0], &bodies[1]));
+
+        // Moving together isn't relative motion, but turning is
+        bodies[0].linear.displacement = Column::vector([5.0, 0.0]);
+        bodies[1].linear.displacement = Column::vector([15.0, 0.0]);
+        assert!(!cache.needs_test(&bodies[0], &bodies[1]));
+        bodies[1].angular.displacement = Column::vector([0.2]);
+        assert!(cache.needs_test(&bodies[0], &bodies[1]));
+
+        // A body joining leaves the others' pairs alone
+        bodies.push(rec(3, 100.0));
+        cache.order(&bodies, all(4));
+        assert!(cache.contact(a, c).is_some());
+
+        // Pairs that are no longer candidates are forgotten
+        cache.order(&bodies, vec![(0, 1), (1, 2)]);
+        assert!(cache.contact(a, c).is_none());
+        assert_eq!(cache.entries.len(), 2);
+        assert!(cache.entries.contains_key(&(a, b)) && cache.entries.contains_key(&(b, c)));
+
+        // Bodies are known by their ids wherever they are
+        bodies[0].linear.displacement = Column::vector([0.0, 0.0]);
+        cache.order(&bodies, all(4));
+        for (i, j) in all(4) {
+            let contact = Collide2D::new().collide(&bodies[i], &bodies[j]);
+            cache.record(&bodies[i], &bodies[j], contact);
+        }
+        bodies.swap(1, 2);
+        cache.order(&bodies, vec![(0, 1)]);
+        assert!(cache.contact(a, c).is_some());
+    }
+}
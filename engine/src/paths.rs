@@ -0,0 +1,217 @@
+//! Routes for kinematic bodies (moving platforms, patrolling enemies) to follow, moved along by the
+//! engine each tick instead of being driven by piecewise `s` equations. A body on a path ignores its
+//! linear equations, and its velocity is whatever it took to get to its next position so whatever
+//! it pushes still gets the right impulse.
+
+use crate::math::Vector;
+use crate::{BodyId, Space};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    /// Constant speed from one waypoint to the next.
+    Linear,
+    /// Slows to a stop at every waypoint (smoothstep).
+    Smooth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Repeat {
+    /// Stops at the last waypoint.
+    Once,
+    /// Goes from the last waypoint straight back to the first.
+    Loop,
+    /// Goes back through the waypoints in reverse.
+    PingPong,
+}
+
+#[derive(Clone)]
+pub enum Path<S: Space> {
+    Waypoints {
+        points: Vec<S::Linear>,
+        // Average speed between waypoints, in world units per second
+        speed: f64,
+        easing: Easing,
+        repeat: Repeat,
+    },
+    /// Position as a function of seconds since the body started following it.
+    Curve(fn(f64) -> S::Linear),
+}
+
+impl<S: Space> Path<S> {
+    pub fn waypoints(points: Vec<S::Linear>, speed: f64) -> Self {
+        Path::Waypoints {
+            points,
+            speed,
+            easing: Easing::Linear,
+            repeat: Repeat::Once,
+        }
+    }
+
+    pub fn with_easing(mut self, with: Easing) -> Self {
+        if let Path::Waypoints { easing, .. } = &mut self {
+            *easing = with;
+        }
+        self
+    }
+
+    pub fn with_repeat(mut self, with: Repeat) -> Self {
+        if let Path::Waypoints { repeat, .. } = &mut self {
+            *repeat = with;
+        }
+        self
+    }
+
+    /// Where a body is `t` seconds after it started following the path.
+    pub fn position(&self, t: f64) -> S::Linear {
+        let (points, speed, easing, repeat) = match self {
+            Path::Waypoints {
+                points,
+                speed,
+                easing,
+                repeat,
+            } => (points, *speed, *easing, *repeat),
+            Path::Curve(f) => return f(t),
+        };
+
+        let Some(first) = points.first() else {
+            return S::Linear::empty();
+        };
+
+        // The waypoints in the order they are visited over one cycle
+        let mut route = points.iter().collect::<Vec<_>>();
+        match repeat {
+            Repeat::Once => {}
+            Repeat::Loop => route.push(first),
+            Repeat::PingPong => route.extend(points.iter().rev().skip(1)),
+        }
+
+        let lengths = route
+            .windows(2)
+            .map(|x| x[1].plus(&x[0].scale(-1.0)).magnitude())
+            .collect::<Vec<_>>();
+        let total = lengths.iter().sum::<f64>();
+        if total == 0.0 || speed <= 0.0 {
+            return first.clone();
+        }
+
+        let mut distance = t.max(0.0) * speed;
+        distance = match repeat {
+            Repeat::Once => distance.min(total),
+            Repeat::Loop | Repeat::PingPong => distance % total,
+        };
+
+        for (i, length) in lengths.iter().enumerate() {
+            if distance <= *length && *length > 0.0 {
+                let u = distance / length;
+                let u = match easing {
+                    Easing::Linear => u,
+                    Easing::Smooth => u * u * (3.0 - 2.0 * u),
+                };
+
+                return route[i].plus(&route[i + 1].plus(&route[i].scale(-1.0)).scale(u));
+            }
+            distance -= length;
+        }
+
+        route[route.len() - 1].clone()
+    }
+}
+
+pub(crate) struct Follower<S: Space> {
+    pub body: BodyId,
+    pub path: Path<S>,
+    // Simulated time the body started following the path
+    pub start: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::Collide2D;
+    use crate::math::Column;
+    use crate::math::solve::{Environment, builtin};
+    use crate::spaces::Space2D;
+    use crate::{Body, BodyProperties, Engine, Shape};
+
+    fn patrol() -> Path<Space2D> {
+        Path::waypoints(
+            vec![
+                Column::vector([0.0, 0.0]),
+                Column::vector([10.0, 0.0]),
+                Column::vector([10.0, 10.0]),
+            ],
+            5.0,
+        )
+    }
+
+    #[test]
+    fn test_waypoints() {
+        let x = |path: &Path<Space2D>, t: f64| {
+            let position = path.position(t);
+            // Rounded, a loop's length isn't exact
+            [0, 1].map(|i| (position.get(i) * 1e6).round() / 1e6)
+        };
+
+        let once = patrol();
+        assert_eq!(x(&once, 1.0), [5.0, 0.0]);
+        assert_eq!(x(&once, 3.0), [10.0, 5.0]);
+        assert_eq!(x(&once, 10.0), [10.0, 10.0]);
+
+        // Back along the diagonal, which is sqrt(200) long
+        let looped = patrol().with_repeat(Repeat::Loop);
+        let diagonal = 200f64.sqrt() / 5.0;
+        assert_eq!(x(&looped, 4.0 + diagonal / 2.0), [5.0, 5.0]);
+        assert_eq!(x(&looped, 4.0 + diagonal + 1.0), [5.0, 0.0]);
+
+        let ping_pong = patrol().with_repeat(Repeat::PingPong);
+        assert_eq!(x(&ping_pong, 5.0), [10.0, 5.0]);
+        assert_eq!(x(&ping_pong, 8.0), [0.0, 0.0]);
+
+        // Eased, it is behind at the start of a leg and ahead at the end
+        let smooth = patrol().with_easing(Easing::Smooth);
+        assert!(x(&smooth, 0.5)[0] < 2.5);
+        assert!(x(&smooth, 1.5)[0] > 7.5);
+        assert_eq!(x(&smooth, 1.0), [5.0, 0.0]);
+
+        let curve = Path::<Space2D>::Curve(|t| Column::vector([t.cos(), t.sin()]));
+        assert_eq!(x(&curve, 0.0), [1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_follow_path() {
+        let mut engine = Engine::new(
+            vec![Body::at_rest(
+                "Platform".to_string(),
+                Shape::Rec(4.0, 1.0),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::rectangle(1.0, 4.0, 1.0),
+            )],
+            Environment::build(
+                vec!["a_Platform=-10*hatj"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        );
+        let id = engine.id_of("Platform").unwrap();
+        engine.follow_path(id, patrol());
+
+        for _ in 0..10 {
+            engine.tick().unwrap();
+        }
+        // One second along, ignoring gravity
+        let platform = &engine.bodies()[0];
+        assert!((platform.linear.displacement.get(0) - 5.0).abs() < 1e-9);
+        assert_eq!(*platform.linear.displacement.get(1), 0.0);
+        assert!((platform.linear.velocity.get(0) - 5.0).abs() < 1e-9);
+
+        // Falls from wherever it was left
+        engine.stop_following(id);
+        engine.tick().unwrap();
+        assert!(*engine.bodies()[0].linear.displacement.get(1) < 0.0);
+    }
+}
@@ -0,0 +1,37 @@
+//! Everything needed to build and run a simulation, for glob importing:
+//!
+//! ```
+//! use engine::prelude::*;
+//!
+//! let env = Environment::build(vec!["a_A=-10*hatj"], builtin::functions(), builtin::constants())
+//!     .unwrap();
+//! let body = Body::at_rest(
+//!     "A".to_string(),
+//!     Shape::Rec(1.0, 1.0),
+//!     Column::vector([0.0, 0.0]),
+//!     Column::vector([0.0]),
+//!     BodyProperties::rectangle(1.0, 1.0, 1.0),
+//! );
+//!
+//! let mut engine = Engine::<Space2D>::new(vec![body], env, Box::new(Collide2D::new()), 0.1, 1.0);
+//! engine.tick().unwrap();
+//! assert!(*engine.bodies()[0].linear.velocity.get(1) < 0.0);
+//! ```
+
+pub use crate::collide::{Collide, Collide2D, Collision, Dispatch, NoCollide};
+pub use crate::constraint::{Constraint, Limit, Row};
+pub use crate::err::{EngineResult, Error, ErrorKind};
+pub use crate::events::{CollisionEvent, Event};
+pub use crate::joints::{Anchor, Axle, Gear, Spring};
+pub use crate::listener::EngineListener;
+pub use crate::math::integration::{Integrator, Reconstruction};
+pub use crate::math::solve::{Environment, Function, builtin};
+pub use crate::math::{Column, Vector};
+pub use crate::narrowphase::{CollideChain, CollideGjk, CollideHalfPlane, CollideSat};
+pub use crate::observe::{Change, Subject};
+pub use crate::sleep::Sleeping;
+pub use crate::spaces::{Space2D, Space3D};
+pub use crate::{
+    Body, BodyId, BodyKind, BodyProperties, Combine, ContactOverride, Correction, Engine,
+    EngineState, Material, RayHit, Shape, ShapeKind, Space, Tick,
+};
@@ -0,0 +1,208 @@
+//! Records everything done to an engine from outside its own ticks, so that a run can be replayed
+//! exactly from the same starting state.
+
+use crate::err::EngineResult;
+use crate::math::Vector;
+use crate::math::solve::{Environment, builtin};
+use crate::{Engine, Space, Tick};
+
+/// A change made to an engine between ticks.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// Overwrites the velocity of a body, eg. while it is being dragged around.
+    SetVelocity {
+        body: usize,
+        linear: Vec<f64>,
+        angular: Vec<f64>,
+    },
+    /// Rebuilds the environment from a new set of equations.
+    SetEquations(Vec<String>),
+}
+
+impl Input {
+    pub fn apply<S: Space + Clone>(&self, engine: &mut Engine<S>) -> EngineResult<()> {
+        match self {
+            Input::SetVelocity {
+                body,
+                linear,
+                angular,
+            } => {
+                if let Some(body) = engine.bodies_mut().get_mut(*body) {
+                    body.linear.velocity = S::Linear::new(linear.clone())?;
+                    body.angular.velocity = S::Angular::new(angular.clone())?;
+                }
+            }
+            Input::SetEquations(equations) => {
+                engine.set_environment(Environment::build(
+                    equations.iter().map(|x| x.as_str()).collect(),
+                    builtin::functions(),
+                    builtin::constants(),
+                )?);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedTick {
+    pub delta_t: f64,
+    // Applied in order right before the tick
+    pub inputs: Vec<Input>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Recording {
+    pub ticks: Vec<RecordedTick>,
+}
+
+/// Builds a [`Recording`] as an engine runs. Inputs are noted as they are applied and attached to
+/// the next tick, pausing and resuming don't need recording as they don't change the outcome.
+#[derive(Default)]
+pub struct Recorder {
+    recording: Recording,
+    pending: Vec<Input>,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn input(&mut self, input: Input) {
+        self.pending.push(input);
+    }
+
+    /// Notes that the engine just advanced by a tick.
+    pub fn tick<S: Space + Clone>(&mut self, engine: &Engine<S>) {
+        self.recording.ticks.push(RecordedTick {
+            delta_t: engine.delta_t(),
+            inputs: std::mem::take(&mut self.pending),
+        });
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn finish(self) -> Recording {
+        self.recording
+    }
+}
+
+/// Drives an engine (built from the same starting state as the recorded one) through a recording,
+/// a tick at a time.
+pub struct Replay {
+    recording: Recording,
+    position: usize,
+}
+
+impl Replay {
+    pub fn new(recording: Recording) -> Self {
+        Replay {
+            recording,
+            position: 0,
+        }
+    }
+
+    /// Applies the next tick's inputs and steps the engine, returning `None` once the recording
+    /// is exhausted. The engine is stepped even if paused, pausing playback is up to the caller.
+    pub fn advance<S: Space + Clone>(
+        &mut self,
+        engine: &mut Engine<S>,
+    ) -> EngineResult<Option<Tick<S>>> {
+        let Some(tick) = self.recording.ticks.get(self.position) else {
+            return Ok(None);
+        };
+        self.position += 1;
+
+        for input in &tick.inputs {
+            input.apply(engine)?;
+        }
+        engine.set_delta_t(tick.delta_t)?;
+
+        engine.step().map(Some)
+    }
+
+    /// Number of ticks replayed so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.recording.ticks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recording.ticks.is_empty()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.recording.ticks.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::Collide2D;
+    use crate::math::Column;
+    use crate::spaces::Space2D;
+    use crate::{Body, BodyProperties, Shape};
+
+    fn engine() -> Engine<Space2D> {
+        Engine::new(
+            vec![Body::at_rest(
+                "A".to_string(),
+                Shape::Rec(1.0, 1.0),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::rectangle(1.0, 1.0, 1.0),
+            )],
+            Environment::build(vec!["a_A=-10*hatj"], builtin::functions(), builtin::constants())
+                .unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        )
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut original = engine();
+        let mut recorder = Recorder::new();
+
+        for i in 0..20 {
+            if i == 5 {
+                let input = Input::SetVelocity {
+                    body: 0,
+                    linear: vec![4.0, 2.0],
+                    angular: vec![1.0],
+                };
+                input.apply(&mut original).unwrap();
+                recorder.input(input);
+            }
+            if i == 10 {
+                original.set_delta_t(0.05).unwrap();
+                let input = Input::SetEquations(vec!["a_A=5*hati".to_string()]);
+                input.apply(&mut original).unwrap();
+                recorder.input(input);
+            }
+
+            original.tick().unwrap();
+            recorder.tick(&original);
+        }
+
+        let mut copy = engine();
+        let mut replay = Replay::new(recorder.finish());
+        while replay.advance(&mut copy).unwrap().is_some() {}
+
+        assert!(replay.is_finished());
+        assert_eq!(copy.time(), original.time());
+        for (a, b) in copy.bodies().iter().zip(original.bodies()) {
+            assert_eq!(a.linear.displacement, b.linear.displacement);
+            assert_eq!(a.angular.displacement, b.angular.displacement);
+        }
+    }
+}
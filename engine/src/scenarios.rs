@@ -0,0 +1,484 @@
+//! Canonical scenes, both as examples of describing a simulation through equations and as regression
+//! fixtures for the solver.
+
+use crate::collide::Dispatch;
+use crate::err::EngineResult;
+use crate::math::Column;
+use crate::math::solve::{Environment, builtin};
+use crate::noise::Rng;
+use crate::spaces::Space2D;
+use crate::vehicle::Vehicle;
+use crate::{Body, BodyKind, BodyProperties, Engine, Shape};
+
+const DELTA_T: f64 = 1.0 / 60.0;
+
+/// The bodies, equations and solver settings of a scene, ready to be built into an engine.
+#[derive(Clone)]
+pub struct Scenario {
+    pub bodies: Vec<Body<Space2D>>,
+    pub equations: Vec<String>,
+    pub delta_t: f64,
+    pub restitution: f64,
+    // Hung from their chassis once the bodies are in the engine
+    pub vehicles: Vec<Vehicle>,
+}
+
+impl Scenario {
+    pub fn build(&self) -> EngineResult<Engine<Space2D>> {
+        let mut engine = Engine::new(
+            self.bodies.clone(),
+            Environment::build(
+                self.equations.iter().map(|x| x.as_str()).collect(),
+                builtin::functions(),
+                builtin::constants(),
+            )?,
+            Box::new(Dispatch::default()),
+            self.delta_t,
+            self.restitution,
+        );
+        for vehicle in &self.vehicles {
+            vehicle.add_joints(&mut engine);
+        }
+        engine.validate()?;
+
+        Ok(engine)
+    }
+}
+
+/// Names accepted by [`by_name`].
+pub const NAMES: &[&str] = &["pendulum", "orbit", "stack", "cradle", "incline", "vehicle"];
+
+pub fn by_name(name: &str) -> Option<Scenario> {
+    match name {
+        "pendulum" => Some(pendulum()),
+        "orbit" => Some(orbit()),
+        "stack" => Some(stacked_boxes()),
+        "cradle" => Some(newtons_cradle()),
+        "incline" => Some(inclined_plane()),
+        "vehicle" => Some(vehicle()),
+        _ => None,
+    }
+}
+
+fn rec(
+    name: &str,
+    width: f64,
+    height: f64,
+    mass: f64,
+    position: [f64; 2],
+    rotation: f64,
+) -> Body<Space2D> {
+    Body::at_rest(
+        name.to_string(),
+        Shape::Rec(width, height),
+        Column::vector(position),
+        Column::vector([rotation]),
+        BodyProperties::rectangle(mass, width, height),
+    )
+}
+
+// Scenery nothing moves
+fn fixed(name: &str, width: f64, height: f64, position: [f64; 2], rotation: f64) -> Body<Space2D> {
+    let mut body = rec(name, width, height, 0.0, position, rotation);
+    body.properties.kind = BodyKind::Static;
+    body
+}
+
+// Body names can't contain digits (the parser would read `a_Box1` as `a_Box*1`), so generated
+// bodies are numbered with letters instead: A, B, ..., Z, BA, BB and so on
+pub(crate) fn label(prefix: &str, mut i: usize) -> String {
+    let mut letters = Vec::new();
+    loop {
+        letters.push((b'A' + (i % 26) as u8) as char);
+        i /= 26;
+        if i == 0 {
+            break;
+        }
+    }
+
+    format!("{}{}", prefix, letters.iter().rev().collect::<String>())
+}
+
+fn equations(equations: &[&str]) -> Vec<String> {
+    equations.iter().map(|x| x.to_string()).collect()
+}
+
+/// A bob on a massless rod of length 200 swinging about the origin, released from 0.8 radians.
+pub fn pendulum() -> Scenario {
+    Scenario {
+        bodies: vec![rec("Bob", 20.0, 20.0, 1.0, [0.0, 0.0], 0.8)],
+        equations: equations(&[
+            "g=100",
+            "L=200",
+            "s_Bob=L*sin(theta_Bob)*hati-L*cos(theta_Bob)*hatj",
+            "alpha_Bob=-g/L*sin(theta_Bob)*hatk",
+        ]),
+        delta_t: DELTA_T,
+        restitution: 1.0,
+        vehicles: Vec::new(),
+    }
+}
+
+/// A planet on a circular orbit of radius 200 around a (much heavier) sun.
+pub fn orbit() -> Scenario {
+    let mut planet = rec("Planet", 10.0, 10.0, 1.0, [200.0, 0.0], 0.0);
+    // Circular orbit speed, sqrt(k/r)
+    planet.linear.velocity = Column::vector([0.0, (200000.0f64 / 200.0).sqrt()]);
+
+    Scenario {
+        bodies: vec![fixed("Sun", 40.0, 40.0, [0.0, 0.0], 0.0), planet],
+        equations: equations(&[
+            "k=200000",
+            "r=sqrt((x_Planet-x_Sun)^2+(y_Planet-y_Sun)^2)",
+            "a_Planet=-k*((x_Planet-x_Sun)*hati+(y_Planet-y_Sun)*hatj)/r^3",
+        ]),
+        delta_t: DELTA_T,
+        restitution: 1.0,
+        vehicles: Vec::new(),
+    }
+}
+
+/// Three boxes dropped in a column onto the floor.
+pub fn stacked_boxes() -> Scenario {
+    Scenario {
+        bodies: vec![
+            fixed("Floor", 400.0, 20.0, [0.0, -300.0], 0.0),
+            // Slightly staggered, the collider can't handle perfectly parallel faces
+            rec("A", 40.0, 40.0, 1.0, [0.0, -260.0], 0.01),
+            rec("B", 40.0, 40.0, 1.0, [5.0, -200.0], -0.01),
+            rec("C", 40.0, 40.0, 1.0, [-5.0, -140.0], 0.01),
+        ],
+        equations: equations(&["g=100", "a_A=-g*hatj", "a_B=-g*hatj", "a_C=-g*hatj"]),
+        delta_t: DELTA_T,
+        restitution: 0.2,
+        vehicles: Vec::new(),
+    }
+}
+
+/// A row of five resting boxes struck end on by a sixth, without gravity.
+pub fn newtons_cradle() -> Scenario {
+    let mut bodies = (0..5)
+        .map(|i| {
+            rec(
+                &label("Ball", i),
+                40.0,
+                40.0,
+                1.0,
+                [i as f64 * 42.0, 0.0],
+                0.0,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut striker = rec("Striker", 40.0, 40.0, 1.0, [-200.0, 0.0], 0.0);
+    striker.linear.velocity = Column::vector([100.0, 0.0]);
+    bodies.insert(0, striker);
+
+    Scenario {
+        bodies,
+        equations: Vec::new(),
+        delta_t: DELTA_T,
+        restitution: 1.0,
+        vehicles: Vec::new(),
+    }
+}
+
+/// A block sliding down a 0.5 radian ramp under gravity, held back by the friction between them
+/// (mu = 0.3, less than the tan(0.5) it would take to hold it still).
+pub fn inclined_plane() -> Scenario {
+    let angle: f64 = 0.5;
+    // Resting on the ramp's surface with a hair of clearance, 200 units up the slope
+    let (normal, slope) = ([-angle.sin(), angle.cos()], [angle.cos(), angle.sin()]);
+    let block = [
+        normal[0] * 31.0 + slope[0] * 200.0,
+        normal[1] * 31.0 + slope[1] * 200.0,
+    ];
+
+    let mut ramp = fixed("Ramp", 600.0, 20.0, [0.0, 0.0], angle);
+    ramp.properties = ramp.properties.with_friction(0.3, 0.3);
+    let mut block = rec("Block", 40.0, 40.0, 1.0, block, angle);
+    block.properties = block.properties.with_friction(0.3, 0.3);
+
+    Scenario {
+        bodies: vec![ramp, block],
+        equations: equations(&["g=100", "a_Block=-g*hatj"]),
+        delta_t: DELTA_T,
+        restitution: 0.0,
+        vehicles: Vec::new(),
+    }
+}
+
+/// A two wheeled vehicle on suspension dropped onto the floor, its wheels driven to the right. The
+/// rubber wheels grip the rough floor, so it drives off along it.
+pub fn vehicle() -> Scenario {
+    let vehicle = Vehicle::new(rec("Chassis", 160.0, 30.0, 4.0, [0.0, -210.0], 0.0))
+        .with_wheel("RearWheel", [-60.0, -15.0], 20.0, 1.0)
+        .with_wheel("FrontWheel", [60.0, -15.0], 20.0, 1.0)
+        .with_motor(-2.0);
+
+    let mut ground = floor(800.0);
+    ground.properties = ground.properties.with_friction(0.8, 0.6);
+
+    let mut bodies = vec![ground];
+    bodies.extend(vehicle.bodies());
+
+    let mut equations = vec!["g=100".to_string()];
+    equations.extend(
+        bodies
+            .iter()
+            .skip(1)
+            .map(|x| format!("a_{}=-g*hatj", x.name)),
+    );
+
+    Scenario {
+        bodies,
+        equations,
+        delta_t: DELTA_T,
+        restitution: 0.2,
+        vehicles: vec![vehicle],
+    }
+}
+
+// Stress scenes, parameterized by size for benchmarking the broadphase and solver
+
+/// Gravity on every body but the first (the floor).
+fn falling(bodies: Vec<Body<Space2D>>, restitution: f64) -> Scenario {
+    let mut equations = vec!["g=100".to_string()];
+    equations.extend(
+        bodies
+            .iter()
+            .skip(1)
+            .map(|x| format!("a_{}=-g*hatj", x.name)),
+    );
+
+    Scenario {
+        bodies,
+        equations,
+        delta_t: DELTA_T,
+        restitution,
+        vehicles: Vec::new(),
+    }
+}
+
+fn floor(width: f64) -> Body<Space2D> {
+    fixed("Floor", width, 20.0, [0.0, -300.0], 0.0)
+}
+
+/// `count` boxes dropped from a grid ten columns wide.
+pub fn falling_boxes(count: usize) -> Scenario {
+    let mut bodies = vec![floor(800.0)];
+    bodies.extend((0..count).map(|i| {
+        let (column, row) = ((i % 10) as f64, (i / 10) as f64);
+        // Alternating tilt so no two faces are ever parallel
+        let tilt = if i % 2 == 0 { 0.05 } else { -0.05 };
+
+        rec(
+            &label("Box", i),
+            30.0,
+            30.0,
+            1.0,
+            [column * 60.0 - 270.0, row * 60.0 - 200.0],
+            tilt,
+        )
+    }));
+
+    falling(bodies, 0.2)
+}
+
+/// A pyramid of boxes `rows` high, resting just above the floor.
+pub fn pyramid(rows: usize) -> Scenario {
+    let mut bodies = vec![floor(rows as f64 * 40.0 + 200.0)];
+    for row in 0..rows {
+        for i in 0..rows - row {
+            let x = (i as f64 - (rows - row - 1) as f64 / 2.0) * 41.0;
+            let tilt = if (row + i) % 2 == 0 { 0.01 } else { -0.01 };
+
+            bodies.push(rec(
+                &label("Box", bodies.len() - 1),
+                40.0,
+                40.0,
+                1.0,
+                [x, -269.0 + row as f64 * 41.0],
+                tilt,
+            ));
+        }
+    }
+
+    falling(bodies, 0.2)
+}
+
+/// `count` small particles scattered above the floor, placed pseudo-randomly from `seed`.
+pub fn particle_rain(count: usize, seed: u64) -> Scenario {
+    let mut rng = Rng::new(seed);
+    let mut random = move || rng.uniform();
+
+    let mut bodies = vec![floor(800.0)];
+    bodies.extend((0..count).map(|i| {
+        rec(
+            &label("P", i),
+            6.0,
+            6.0,
+            0.1,
+            [random() * 600.0 - 300.0, random() * 600.0],
+            random() * std::f64::consts::PI,
+        )
+    }));
+
+    falling(bodies, 0.5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vector;
+
+    fn run(scenario: Scenario, ticks: usize) -> Engine<Space2D> {
+        let mut engine = scenario.build().unwrap();
+        for _ in 0..ticks {
+            engine.tick().unwrap();
+        }
+
+        engine
+    }
+
+    #[test]
+    fn test_every_scenario_runs() {
+        for name in NAMES {
+            let engine = run(by_name(name).unwrap(), 120);
+
+            for body in engine.bodies() {
+                assert!(body.linear.displacement.magnitude().is_finite(), "{}", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stress_scenes() {
+        assert_eq!(falling_boxes(12).bodies.len(), 13);
+        assert_eq!(pyramid(3).bodies.len(), 7);
+
+        let rain = particle_rain(12, 7);
+        assert_eq!(rain.equations.len(), 13);
+        assert_eq!(
+            rain.bodies[5].linear.displacement,
+            particle_rain(12, 7).bodies[5].linear.displacement
+        );
+
+        assert_eq!(rain.bodies[12].name, "PL");
+        assert_eq!(label("Box", 27), "BoxBB");
+
+        // Everything but the floor is falling
+        let engine = run(falling_boxes(12), 30);
+        assert!(engine.bodies()[12].linear.velocity.get(1) < &0.0);
+        for scenario in [pyramid(3), rain] {
+            run(scenario, 30);
+        }
+    }
+
+    #[test]
+    fn test_orbit_keeps_radius() {
+        let engine = run(orbit(), 600);
+        let radius = engine.bodies()[1]
+            .linear
+            .displacement
+            .plus(&engine.bodies()[0].linear.displacement.scale(-1.0))
+            .magnitude();
+
+        assert!((radius - 200.0).abs() < 10.0, "{}", radius);
+    }
+
+    #[test]
+    fn test_incline_friction() {
+        // How fast the block is sliding down the slope
+        let sliding = |engine: &Engine<Space2D>| {
+            let v = &engine.bodies()[1].linear.velocity;
+            -(v.get(0) * 0.5f64.cos() + v.get(1) * 0.5f64.sin())
+        };
+        let mut engine = run(inclined_plane(), 60);
+        let before = sliding(&engine);
+        for _ in 0..60 {
+            engine.tick().unwrap();
+        }
+
+        // A second at g(sin(phi) - mu*cos(phi)), long after landing on the ramp
+        let acceleration = 100.0 * (0.5f64.sin() - 0.3 * 0.5f64.cos());
+        let gained = sliding(&engine) - before;
+        assert!((gained - acceleration).abs() < 0.2, "{}", gained);
+        // Sliding flat on its face rather than tumbling
+        assert!((engine.bodies()[1].angular.displacement.get(0) - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_ball_comes_to_rest() {
+        let mut ball = floor(800.0);
+        ball.name = "Ball".to_string();
+        ball.shape = Shape::Ellipse(40.0, 40.0);
+        ball.properties = BodyProperties::from_density(0.01, &ball.shape);
+        ball.linear.displacement = Column::vector([0.0, 0.0]);
+
+        let engine = run(
+            Scenario {
+                bodies: vec![floor(800.0), ball],
+                equations: equations(&["a_Ball=-100*hatj"]),
+                delta_t: DELTA_T,
+                restitution: 0.5,
+                vehicles: Vec::new(),
+            },
+            600,
+        );
+
+        // Resting on its radius, the floor's top being at -290
+        let y = *engine.bodies()[1].linear.displacement.get(1);
+        assert!((y - -270.0).abs() < 0.5, "{}", y);
+    }
+
+    #[test]
+    fn test_vehicle_drives() {
+        let engine = run(vehicle(), 300);
+        let chassis = engine.body("Chassis").unwrap();
+
+        let x = *chassis.linear.displacement.get(0);
+        assert!(x > 100.0, "{}", x);
+        assert!(chassis.angular.displacement.get(0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_friction() {
+        let slide = |friction: (f64, f64)| {
+            let mut floor = floor(800.0);
+            let mut block = rec("Block", 40.0, 40.0, 1.0, [0.0, -270.0], 0.0);
+            for x in [&mut floor, &mut block] {
+                x.properties = x.properties.clone().with_friction(friction.0, friction.1);
+            }
+            block.linear.velocity = Column::vector([100.0, 0.0]);
+
+            Scenario {
+                bodies: vec![floor, block],
+                equations: equations(&["a_Block=-100*hatj"]),
+                delta_t: DELTA_T,
+                restitution: 0.0,
+                vehicles: Vec::new(),
+            }
+            .build()
+            .unwrap()
+        };
+
+        let mut frictionless = slide((0.0, 0.0));
+        let mut engine = slide((0.6, 0.5));
+        for _ in 0..180 {
+            frictionless.tick().unwrap();
+            engine.tick().unwrap();
+        }
+        assert!(*frictionless.bodies()[1].linear.velocity.get(0) > 99.0);
+
+        // Slowed at 0.5g it stops within about two seconds, having gone 100^2 / (2 * 50) = 100
+        let stopped = *engine.bodies()[1].linear.displacement.get(0);
+        assert!((stopped - 100.0).abs() < 10.0, "{}", stopped);
+        // Then stays put, rocking a little on one corner and the other
+        for _ in 0..120 {
+            engine.tick().unwrap();
+            let x = *engine.bodies()[1].linear.displacement.get(0);
+            assert!((x - stopped).abs() < 2.0, "{} {}", x, stopped);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Commands queued to run at a given point of simulated time, so scripted sequences (a launch at
+//! three seconds, a crate dropped in on tick 100) follow the physics clock whatever rate the host
+//! runs at.
+
+use crate::{Body, BodyId, BodyProperties, Space};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum At {
+    /// Before the tick that starts once this many ticks have run.
+    Tick(u64),
+    /// Before the first tick starting at or after this many simulated seconds.
+    Time(f64),
+}
+
+pub enum Command<S: Space> {
+    /// Changes the velocity of a body by `impulse` over its mass during the tick, applied at its
+    /// centroid so it doesn't turn it. See `Engine::apply_impulse`.
+    Impulse { body: BodyId, impulse: S::Linear },
+    /// Changes the angular velocity of a body by `impulse` over its moment of inertia during the
+    /// tick, see `Engine::apply_angular_impulse`.
+    AngularImpulse { body: BodyId, impulse: S::Angular },
+    SetProperties {
+        body: BodyId,
+        properties: BodyProperties,
+    },
+    /// Adds a body after all the others.
+    Spawn(Body<S>),
+}
+
+pub(crate) struct Schedule<S: Space> {
+    pending: Vec<(At, Command<S>)>,
+}
+
+impl<S: Space> Default for Schedule<S> {
+    fn default() -> Self {
+        Schedule {
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<S: Space> Schedule<S> {
+    pub fn push(&mut self, at: At, command: Command<S>) {
+        self.pending.push((at, command));
+    }
+
+    /// Removes and returns every command due by `ticks` and `time`, in the order they were
+    /// scheduled.
+    pub fn due(&mut self, ticks: u64, time: f64) -> Vec<Command<S>> {
+        let (due, pending) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(at, _)| match at {
+                At::Tick(x) => *x <= ticks,
+                At::Time(x) => *x <= time,
+            });
+        self.pending = pending;
+
+        due.into_iter().map(|(_, x)| x).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::Collide2D;
+    use crate::math::solve::{Environment, builtin};
+    use crate::math::{Column, Vector};
+    use crate::spaces::Space2D;
+    use crate::{BodyId, Engine, Shape};
+
+    fn crate_at(name: &str, x: f64) -> Body<Space2D> {
+        Body::at_rest(
+            name.to_string(),
+            Shape::Rec(1.0, 1.0),
+            Column::vector([x, 0.0]),
+            Column::vector([0.0]),
+            BodyProperties::rectangle(2.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn test_schedule() {
+        let mut engine = Engine::new(
+            vec![crate_at("A", 0.0)],
+            Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        );
+
+        let a = engine.id_of("A").unwrap();
+        engine.schedule(
+            At::Time(0.25),
+            Command::Impulse {
+                body: a,
+                impulse: Column::vector([4.0, 0.0]),
+            },
+        );
+        engine.schedule(At::Tick(1), Command::Spawn(crate_at("B", 10.0)));
+        engine.schedule(
+            At::Tick(2),
+            Command::SetProperties {
+                // The id B will be given, the one after A's
+                body: BodyId(1),
+                properties: BodyProperties::rectangle(5.0, 1.0, 1.0),
+            },
+        );
+
+        engine.tick().unwrap();
+        assert_eq!(engine.bodies().len(), 1);
+        engine.tick().unwrap();
+        assert_eq!(engine.bodies().len(), 2);
+        engine.tick().unwrap();
+        assert_eq!(engine.bodies()[1].properties.mass, 5.0);
+        assert_eq!(engine.bodies()[0].linear.velocity.magnitude(), 0.0);
+
+        // Three ticks in is 0.3 seconds, so it's the fourth tick that starts after 0.25
+        engine.tick().unwrap();
+        assert_eq!(*engine.bodies()[0].linear.velocity.get(0), 2.0);
+        assert_eq!(engine.scheduled(), 0);
+    }
+}
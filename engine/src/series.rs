@@ -0,0 +1,140 @@
+//! Time series of arbitrary expressions sampled as a simulation runs, for checking conservation
+//! laws, oscillation periods and the like.
+
+use crate::err::EngineResult;
+use crate::{Engine, Space};
+use std::io;
+use std::io::Write;
+
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub expression: String,
+    // (time, value) pairs
+    pub samples: Vec<(f64, f64)>,
+}
+
+impl Series {
+    /// Smallest and largest value sampled so far.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        self.samples.iter().fold(None, |acc, (_, x)| match acc {
+            None => Some((*x, *x)),
+            Some((min, max)) => Some((min.min(*x), max.max(*x))),
+        })
+    }
+}
+
+pub struct SeriesRecorder {
+    series: Vec<Series>,
+    // Oldest samples are dropped past this many
+    capacity: Option<usize>,
+}
+
+impl SeriesRecorder {
+    pub fn new(expressions: Vec<String>) -> Self {
+        SeriesRecorder {
+            series: expressions
+                .into_iter()
+                .map(|expression| Series {
+                    expression,
+                    samples: Vec::new(),
+                })
+                .collect(),
+            capacity: None,
+        }
+    }
+
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Evaluates every expression against the engine's current state.
+    pub fn sample<S: Space + Clone>(&mut self, engine: &Engine<S>) -> EngineResult<()> {
+        for series in self.series.iter_mut() {
+            let value = engine.probe(&series.expression)?;
+            series.samples.push((engine.time(), value));
+
+            if let Some(capacity) = self.capacity
+                && series.samples.len() > capacity
+            {
+                let excess = series.samples.len() - capacity;
+                series.samples.drain(0..excess);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn series(&self) -> &[Series] {
+        &self.series
+    }
+
+    /// Writes whitespace separated columns (time, then one per expression) with a commented header,
+    /// which gnuplot, numpy.loadtxt and most spreadsheet tools read as is.
+    pub fn write_columns<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "# t")?;
+        for x in &self.series {
+            write!(writer, "\t{}", x.expression)?;
+        }
+        writeln!(writer)?;
+
+        let rows = self.series.iter().map(|x| x.samples.len()).min().unwrap_or(0);
+        for row in 0..rows {
+            write!(writer, "{}", self.series[0].samples[row].0)?;
+            for x in &self.series {
+                write!(writer, "\t{}", x.samples[row].1)?;
+            }
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::Collide2D;
+    use crate::math::solve::{Environment, builtin};
+    use crate::math::Column;
+    use crate::spaces::Space2D;
+    use crate::{Body, BodyProperties, Shape};
+
+    #[test]
+    fn test_sample_energy() {
+        let mut body = Body::<Space2D>::at_rest(
+            "A".to_string(),
+            Shape::Rec(1.0, 1.0),
+            Column::vector([0.0, 0.0]),
+            Column::vector([0.0]),
+            BodyProperties::rectangle(2.0, 1.0, 1.0),
+        );
+        body.linear.velocity = Column::vector([3.0, 0.0]);
+
+        let mut engine = Engine::new(
+            vec![body],
+            Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        );
+
+        let mut recorder =
+            SeriesRecorder::new(vec!["0.5*m_A*(v_x_A^2+v_y_A^2)".to_string(), "x_A".to_string()])
+                .with_capacity(2);
+        for _ in 0..3 {
+            engine.tick().unwrap();
+            recorder.sample(&engine).unwrap();
+        }
+
+        let energy = &recorder.series()[0];
+        assert_eq!(energy.samples.len(), 2);
+        assert_eq!(energy.range(), Some((9.0, 9.0)));
+
+        let mut output = Vec::new();
+        recorder.write_columns(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("# t\t0.5*m_A*(v_x_A^2+v_y_A^2)\tx_A\n"));
+        assert_eq!(output.lines().count(), 3);
+    }
+}
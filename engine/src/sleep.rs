@@ -0,0 +1,255 @@
+//! Putting bodies that have come to rest to sleep, so a settled stack costs next to nothing each
+//! tick until something disturbs it.
+
+use crate::events::Event;
+use crate::math::Vector;
+use crate::{Body, BodyId, Space};
+use std::collections::HashMap;
+
+/// When a body counts as having come to rest, see `Engine::set_sleeping`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sleeping {
+    /// Speed below which a body is resting.
+    pub speed: f64,
+    /// Angular speed below which a body is resting.
+    pub angular_speed: f64,
+    /// Ticks in a row a body has to be resting for before it falls asleep.
+    pub ticks: u32,
+}
+
+impl Default for Sleeping {
+    fn default() -> Self {
+        Sleeping {
+            speed: 5.0,
+            angular_speed: 0.5,
+            ticks: 30,
+        }
+    }
+}
+
+impl Sleeping {
+    fn resting<S: Space>(&self, body: &Body<S>) -> bool {
+        body.linear.velocity.magnitude() < self.speed
+            && body.angular.velocity.magnitude() < self.angular_speed
+    }
+}
+
+/// Which bodies are asleep, and how long the others have been resting.
+#[derive(Default)]
+pub(crate) struct Sleepers {
+    pub settings: Option<Sleeping>,
+    // Substeps each body has been resting for, or None while it's asleep
+    resting: HashMap<BodyId, Option<u32>>,
+    // Woken between ticks, reported at the start of the next one
+    woken: Vec<BodyId>,
+}
+
+impl Sleepers {
+    pub fn is_asleep<S: Space>(&self, body: &Body<S>) -> bool {
+        body.id
+            .is_some_and(|x| matches!(self.resting.get(&x), Some(None)))
+    }
+
+    pub fn wake<S: Space>(&mut self, body: &Body<S>) {
+        if let Some(id) = body.id.filter(|_| self.is_asleep(body)) {
+            self.resting.insert(id, Some(0));
+            self.woken.push(id);
+        }
+    }
+
+    /// Wakes every body without reporting it, for when sleeping is turned off.
+    pub fn clear(&mut self) {
+        self.resting.clear();
+        self.woken.clear();
+    }
+
+    /// Forgets bodies that are gone, for when bodies have been changed between ticks. A sleeping
+    /// body that's been set moving by hand is woken without reporting it, the rest are left as
+    /// they were.
+    pub fn retain<S: Space>(&mut self, bodies: &[Body<S>]) {
+        self.resting = bodies
+            .iter()
+            .filter_map(|body| {
+                let id = body.id?;
+                let ticks = (*self.resting.get(&id)?).or_else(|| {
+                    let moving = body.linear.velocity.magnitude() > 0.0
+                        || body.angular.velocity.magnitude() > 0.0;
+                    moving.then_some(0)
+                });
+                Some((id, ticks))
+            })
+            .collect();
+    }
+
+    /// Wakes whichever of two joined bodies is asleep if the other is moving, a joint at rest
+    /// doesn't.
+    pub fn wake_joined<S: Space>(&mut self, a: &Body<S>, b: &Body<S>) {
+        if self.is_asleep(a) != self.is_asleep(b) {
+            let (asleep, awake) = if self.is_asleep(a) { (a, b) } else { (b, a) };
+            if self.disturbs(awake) {
+                self.wake(asleep);
+            }
+        }
+    }
+
+    /// Whether a body moving like this should wake a sleeping body it touches. One that's resting
+    /// itself doesn't, so a stack can fall asleep a body at a time.
+    pub fn disturbs<S: Space>(&self, body: &Body<S>) -> bool {
+        self.settings.is_some_and(|x| !x.resting(body))
+    }
+
+    /// Puts the bodies that have been resting long enough to sleep, after each of a tick's
+    /// `substeps`. `eligible` says which may, bodies moved by anything but their equations and
+    /// contacts never do and are woken if they were asleep.
+    pub fn update<S: Space>(
+        &mut self,
+        bodies: &mut [Body<S>],
+        substeps: u32,
+        eligible: impl Fn(usize) -> bool,
+        events: &mut Vec<Event>,
+    ) {
+        let Some(settings) = self.settings else {
+            return;
+        };
+
+        // Only bodies still there are reported or kept track of, and every one has an id once
+        // validated
+        let mut resting = HashMap::with_capacity(bodies.len());
+        for body in self.woken.drain(..) {
+            if bodies.iter().any(|x| x.id == Some(body)) {
+                events.push(Event::Woke { body });
+            }
+        }
+
+        for (i, body) in bodies.iter_mut().enumerate() {
+            let Some(id) = body.id else {
+                continue;
+            };
+            let Some(ticks) = self.resting.get(&id).copied().unwrap_or(Some(0)) else {
+                if eligible(i) {
+                    // Contacts may have nudged it while it was held still
+                    body.linear.velocity = S::Linear::empty();
+                    body.angular.velocity = S::Angular::empty();
+                    resting.insert(id, None);
+                } else {
+                    resting.insert(id, Some(0));
+                    events.push(Event::Woke { body: id });
+                }
+                continue;
+            };
+
+            if !eligible(i) || !settings.resting(body) {
+                resting.insert(id, Some(0));
+            } else if ticks + 1 < settings.ticks.saturating_mul(substeps) {
+                resting.insert(id, Some(ticks + 1));
+            } else {
+                resting.insert(id, None);
+                body.linear.velocity = S::Linear::empty();
+                body.angular.velocity = S::Angular::empty();
+                events.push(Event::FellAsleep { body: id });
+            }
+        }
+        self.resting = resting;
+    }
+
+    pub fn asleep(&self) -> usize {
+        self.resting.values().filter(|x| x.is_none()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Column;
+    use crate::scenarios::Scenario;
+    use crate::{BodyKind, BodyProperties, Shape};
+
+    // A ball dropped onto the floor, and another dropped onto it long after it settles
+    fn balls() -> Scenario {
+        let ball = |name: &str, x: f64, y: f64| {
+            let shape = Shape::Ellipse(40.0, 40.0);
+            let properties = BodyProperties::from_density(0.01, &shape);
+            Body::at_rest(
+                name.to_string(),
+                shape,
+                Column::vector([x, y]),
+                Column::vector([0.0]),
+                properties,
+            )
+        };
+        let floor = Body::at_rest(
+            "Floor".to_string(),
+            Shape::Rec(800.0, 20.0),
+            Column::vector([0.0, -300.0]),
+            Column::vector([0.0]),
+            BodyProperties::weightless().with_kind(BodyKind::Static),
+        );
+
+        Scenario {
+            bodies: vec![floor, ball("Ball", 0.0, -260.0), ball("Drop", 30.0, 2000.0)],
+            equations: ["g=100", "a_Ball=-g*hatj", "a_Drop=-g*hatj"]
+                .map(|x| x.to_string())
+                .to_vec(),
+            delta_t: 1.0 / 60.0,
+            restitution: 0.5,
+            vehicles: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_falls_asleep() {
+        let mut engine = balls().build().unwrap();
+        engine.set_sleeping(Some(Sleeping::default()));
+        let ball = engine.bodies()[1].id.unwrap();
+
+        let mut events = Vec::new();
+        let mut settled = None;
+        for _ in 0..600 {
+            let tick = engine.tick().unwrap();
+            events.extend(tick.events);
+            if settled.is_none() && engine.is_asleep(ball) {
+                settled = Some(engine.bodies()[1].linear.displacement.clone());
+                // The floor can't move, so there's nothing left to test
+                assert_eq!(engine.tick().unwrap().stats.pairs_tested, 0);
+            }
+        }
+
+        let settled = settled.expect("The ball never fell asleep");
+        assert!(events.contains(&Event::FellAsleep { body: ball }));
+        // Knocked by the other ball, not by anything below it
+        assert!(events.contains(&Event::Woke { body: ball }));
+        assert_ne!(engine.bodies()[1].linear.displacement, settled);
+    }
+
+    #[test]
+    fn test_impulse_wakes() {
+        let mut engine = balls().build().unwrap();
+        engine.set_sleeping(Some(Sleeping::default()));
+        let ball = engine.bodies()[1].id.unwrap();
+        while !engine.is_asleep(ball) {
+            engine.tick().unwrap();
+        }
+
+        engine.apply_angular_impulse(ball, Column::vector([1000.0]));
+        assert!(!engine.is_asleep(ball));
+        let events = engine.tick().unwrap().events;
+        assert!(events.contains(&Event::Woke { body: ball }));
+    }
+
+    #[test]
+    fn test_stays_asleep_when_spawning() {
+        let mut engine = balls().build().unwrap();
+        engine.set_sleeping(Some(Sleeping::default()));
+        let ball = engine.bodies()[1].id.unwrap();
+        while !engine.is_asleep(ball) {
+            engine.tick().unwrap();
+        }
+
+        let mut crate_ = engine.bodies()[0].clone();
+        crate_.name = "Crate".to_string();
+        crate_.linear.displacement = Column::vector([300.0, 0.0]);
+        engine.add_body(crate_);
+        engine.tick().unwrap();
+        assert!(engine.is_asleep(ball));
+    }
+}
@@ -0,0 +1,289 @@
+//! The table of values the engine injects into the environment when evaluating body state. Names are
+//! resolved to the environment's symbols once per body and the table is updated in place each tick,
+//! rather than rebuilt.
+
+use crate::math::Vector;
+use crate::math::solve::Environment;
+use crate::math::symbol::{Bindings, Symbol};
+use crate::{Basis, Body, Space};
+use std::collections::HashMap;
+
+// Symbols are None for names the environment never mentions, which can't affect evaluation
+pub(crate) struct BodyKeys {
+    name: String,
+    // Displacement and velocity of each basis, eg. (x_A, v_x_A)
+    pub linear: Vec<(Option<Symbol>, Option<Symbol>)>,
+    pub angular: Vec<(Option<Symbol>, Option<Symbol>)>,
+    mass: Option<Symbol>,
+    moi: Option<Symbol>,
+    // Custom scalars by name, eg. ("charge", charge_A)
+    custom: Vec<(String, Option<Symbol>)>,
+    pub linear_form: StateForm,
+    pub angular_form: StateForm,
+}
+
+impl BodyKeys {
+    fn new<S: Space>(body: &Body<S>, env: &Environment) -> Self {
+        let name = body.name.as_str();
+        // Unnamed bodies have no variables
+        let symbol = |var: &str| {
+            (!name.is_empty())
+                .then(|| env.symbol(&format!("{}_{}", var, name)))
+                .flatten()
+        };
+        let keys = |bases: &[Basis]| {
+            bases
+                .iter()
+                .map(|x| (symbol(x.axis), symbol(&format!("v_{}", x.axis))))
+                .collect()
+        };
+
+        BodyKeys {
+            name: name.to_string(),
+            linear: keys(S::LINEAR_BASES),
+            angular: keys(S::ANGULAR_BASES),
+            mass: symbol("m"),
+            moi: symbol("I"),
+            custom: body
+                .properties
+                .custom
+                .keys()
+                .map(|x| (x.clone(), symbol(x)))
+                .collect(),
+            linear_form: StateForm::resolve(env, body, ["s", "v", "a", "F"]),
+            angular_form: StateForm::resolve(env, body, ["q", "omega", "alpha", "tau"]),
+        }
+    }
+
+    // Whether these keys were resolved for a body with this name and these custom scalars
+    fn matches<S: Space>(&self, body: &Body<S>) -> bool {
+        self.name == body.name
+            && self.custom.len() == body.properties.custom.len()
+            && self
+                .custom
+                .iter()
+                .zip(body.properties.custom.keys())
+                .all(|((x, _), y)| x == y)
+    }
+}
+
+/// Which of a body's displacement, velocity or acceleration its equations define, decided once per
+/// environment so each tick evaluates only that one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum StateForm {
+    // Along with a velocity equation, if there is one, used instead of estimating it
+    Displacement(Symbol, Option<Symbol>),
+    Velocity(Symbol),
+    Acceleration(Symbol),
+    // Net force (or torque), divided by mass (or moment of inertia) to give the acceleration
+    Force(Symbol),
+    // Both an acceleration and a force, which add up
+    AccelerationAndForce(Symbol, Symbol),
+    // Nothing is defined, the body keeps its velocity
+    Free,
+}
+
+impl StateForm {
+    // Displacement takes precedence over velocity (only used to move the body when there's no
+    // displacement), which takes precedence over acceleration and force. A form shadowed by a custom scalar (eg. a charge called `q`) is a value, not a
+    // definition.
+    fn resolve<S: Space>(env: &Environment, body: &Body<S>, [s, v, a, f]: [&str; 4]) -> Self {
+        let defined = |var: &str| {
+            if body.properties.custom.contains_key(var) || body.name.is_empty() {
+                return None;
+            }
+
+            env.symbol(&format!("{}_{}", var, body.name))
+                .filter(|x| env.mentions(*x))
+        };
+
+        if let Some(x) = defined(s) {
+            StateForm::Displacement(x, defined(v))
+        } else if let Some(x) = defined(v) {
+            StateForm::Velocity(x)
+        } else {
+            match (defined(a), defined(f)) {
+                (Some(a), Some(f)) => StateForm::AccelerationAndForce(a, f),
+                (Some(a), None) => StateForm::Acceleration(a),
+                (None, Some(f)) => StateForm::Force(f),
+                (None, None) => StateForm::Free,
+            }
+        }
+    }
+
+    /// Whether the form gives an acceleration to integrate.
+    pub fn is_dynamic(&self) -> bool {
+        matches!(
+            self,
+            StateForm::Acceleration(_) | StateForm::Force(_) | StateForm::AccelerationAndForce(..)
+        )
+    }
+}
+
+/// Belongs to a single environment, it has to be thrown away when the environment is replaced.
+#[derive(Default)]
+pub(crate) struct StateTable {
+    pub values: Bindings,
+    // One per body, in the same order
+    pub keys: Vec<BodyKeys>,
+    pub linear_bases: Vec<Option<Symbol>>,
+    pub angular_bases: Vec<Option<Symbol>>,
+}
+
+impl StateTable {
+    /// Writes the state of `bodies` into the table: every basis set to 0, plus the position
+    /// (`x_A`), velocity (`v_x_A`), mass (`m_A`), moment of inertia (`I_A`) and custom scalars
+    /// (`charge_A`) of each body. Names are only resolved again when the set of bodies changes.
+    pub fn update<S: Space>(&mut self, bodies: &[Body<S>], env: &Environment) {
+        let stale = self.keys.len() != bodies.len()
+            || self.keys.iter().zip(bodies).any(|(k, b)| !k.matches(b));
+
+        if stale {
+            self.keys = bodies.iter().map(|x| BodyKeys::new(x, env)).collect();
+            self.linear_bases = S::LINEAR_BASES.iter().map(|x| env.symbol(x.name)).collect();
+            self.angular_bases = S::ANGULAR_BASES.iter().map(|x| env.symbol(x.name)).collect();
+            self.values.clear();
+        }
+
+        // Set all bases to 0 (hati, hatj, hatk etc.)
+        for basis in self.linear_bases.iter().chain(self.angular_bases.iter()) {
+            set(&mut self.values, *basis, 0.0);
+        }
+
+        for (keys, x) in self.keys.iter().zip(bodies) {
+            for (i, (s, v)) in keys.linear.iter().enumerate() {
+                set(&mut self.values, *s, *x.linear.displacement.get(i));
+                set(&mut self.values, *v, *x.linear.velocity.get(i));
+            }
+
+            for (i, (s, v)) in keys.angular.iter().enumerate() {
+                set(&mut self.values, *s, *x.angular.displacement.get(i));
+                set(&mut self.values, *v, *x.angular.velocity.get(i));
+            }
+
+            set(&mut self.values, keys.mass, x.properties.mass);
+            set(&mut self.values, keys.moi, x.properties.moi);
+            for ((_, symbol), value) in keys.custom.iter().zip(x.properties.custom.values()) {
+                set(&mut self.values, *symbol, *value);
+            }
+        }
+    }
+}
+
+pub(crate) fn set(values: &mut Bindings, symbol: Option<Symbol>, value: f64) {
+    if let Some(symbol) = symbol {
+        values.set(symbol, value);
+    }
+}
+
+/// The same values as [`StateTable`] but keyed by name, for evaluating arbitrary expressions which
+/// may mention state the environment itself never does.
+pub(crate) fn named_values<S: Space>(bodies: &[Body<S>]) -> HashMap<String, f64> {
+    let mut values = HashMap::new();
+
+    for basis in S::LINEAR_BASES.iter().chain(S::ANGULAR_BASES.iter()) {
+        values.insert(basis.name.to_string(), 0.0);
+    }
+
+    for x in bodies.iter().filter(|x| !x.name.is_empty()) {
+        for (i, basis) in S::LINEAR_BASES.iter().enumerate() {
+            values.insert(format!("{}_{}", basis.axis, x.name), *x.linear.displacement.get(i));
+            values.insert(format!("v_{}_{}", basis.axis, x.name), *x.linear.velocity.get(i));
+        }
+
+        for (i, basis) in S::ANGULAR_BASES.iter().enumerate() {
+            values.insert(format!("{}_{}", basis.axis, x.name), *x.angular.displacement.get(i));
+            values.insert(format!("v_{}_{}", basis.axis, x.name), *x.angular.velocity.get(i));
+        }
+
+        values.insert(format!("m_{}", x.name), x.properties.mass);
+        values.insert(format!("I_{}", x.name), x.properties.moi);
+        for (name, value) in &x.properties.custom {
+            values.insert(format!("{}_{}", name, x.name), *value);
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Column;
+    use crate::math::solve::builtin;
+    use crate::spaces::Space2D;
+    use crate::{BodyProperties, Shape};
+
+    #[test]
+    fn test_update_in_place() {
+        let env = Environment::build(
+            vec!["k=y_A+theta_A+m_A+v_x_A+x_B+omega_A"],
+            builtin::functions(),
+            builtin::constants(),
+        )
+        .unwrap();
+        let value = |table: &StateTable, name: &str| table.values.get(env.symbol(name).unwrap());
+
+        let mut bodies = vec![Body::<Space2D>::at_rest(
+            "A".to_string(),
+            Shape::Rec(1.0, 1.0),
+            Column::vector([1.0, 2.0]),
+            Column::vector([0.5]),
+            BodyProperties::rectangle(3.0, 1.0, 1.0),
+        )];
+
+        let mut table = StateTable::default();
+        table.update(&bodies, &env);
+        assert_eq!(value(&table, "y_A"), Some(2.0));
+        assert_eq!(value(&table, "theta_A"), Some(0.5));
+        assert_eq!(value(&table, "m_A"), Some(3.0));
+        assert_eq!(
+            table.keys[0].angular_form,
+            StateForm::Velocity(env.symbol("omega_A").unwrap())
+        );
+        assert_eq!(table.keys[0].linear_form, StateForm::Free);
+        // Never mentioned, so never resolved
+        assert_eq!(table.keys[0].linear[0].0, None);
+
+        bodies[0].linear.velocity = Column::vector([4.0, 0.0]);
+        table.update(&bodies, &env);
+        assert_eq!(value(&table, "v_x_A"), Some(4.0));
+
+        bodies[0].name = "B".to_string();
+        table.update(&bodies, &env);
+        assert_eq!(value(&table, "y_A"), None);
+        assert_eq!(value(&table, "x_B"), Some(1.0));
+    }
+
+    #[test]
+    fn test_custom_scalars() {
+        let env = Environment::build(
+            vec!["k=q_A+charge_A", "q_B=2*hatk"],
+            builtin::functions(),
+            builtin::constants(),
+        )
+        .unwrap();
+
+        let body = |name: &str| {
+            Body::<Space2D>::at_rest(
+                name.to_string(),
+                Shape::Rec(1.0, 1.0),
+                Column::vector([0.0, 0.0]),
+                Column::vector([0.0]),
+                BodyProperties::rectangle(1.0, 1.0, 1.0).with("q", 3.0),
+            )
+        };
+        let mut bodies = vec![body("A"), body("B")];
+
+        let mut table = StateTable::default();
+        table.update(&bodies, &env);
+        assert_eq!(table.values.get(env.symbol("q_A").unwrap()), Some(3.0));
+        assert_eq!(table.values.get(env.symbol("charge_A").unwrap()), None);
+        // The custom q shadows the angular displacement form
+        assert_eq!(table.keys[1].angular_form, StateForm::Free);
+
+        bodies[0].properties.custom.insert("charge".to_string(), -1.0);
+        table.update(&bodies, &env);
+        assert_eq!(table.values.get(env.symbol("charge_A").unwrap()), Some(-1.0));
+    }
+}
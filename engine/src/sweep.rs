@@ -0,0 +1,243 @@
+//! Runs one scene over a grid of parameter values, each combination as its own simulation from the
+//! same starting state, and summarises a few expressions per run. For tuning constants and for
+//! experiments like "how far does it bounce as restitution goes from 0 to 1".
+
+use crate::err::EngineResult;
+use crate::math::Vector;
+use crate::scenarios::Scenario;
+use crate::series::SeriesRecorder;
+use std::io;
+use std::io::Write;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Parameter {
+    /// A constant of the equations, eg. `g`. Overrides an equation defining it.
+    Constant(String),
+    Restitution,
+    /// One component of a body's starting velocity.
+    Velocity {
+        body: String,
+        axis: usize,
+    },
+}
+
+impl Parameter {
+    fn apply(&self, scenario: &mut Scenario, value: f64) {
+        match self {
+            // Set on the built engine instead, see Sweep::run_one
+            Parameter::Constant(_) => {}
+            Parameter::Restitution => scenario.restitution = value,
+            Parameter::Velocity { body, axis } => {
+                let Some(body) = scenario.bodies.iter_mut().find(|x| x.name == *body) else {
+                    return;
+                };
+
+                let velocity = &mut body.linear.velocity;
+                let mut components = (0..2).map(|i| *velocity.get(i)).collect::<Vec<_>>();
+                if let Some(x) = components.get_mut(*axis) {
+                    *x = value;
+                    *velocity = Vector::new(components).unwrap();
+                }
+            }
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Parameter::Constant(name) => name.clone(),
+            Parameter::Restitution => "restitution".to_string(),
+            Parameter::Velocity { body, axis } => format!("v{}_{}", axis, body),
+        }
+    }
+}
+
+/// What an expression did over one run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub last: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Run {
+    // One value per parameter, in the order they were added
+    pub values: Vec<f64>,
+    // One summary per metric, or why the run stopped
+    pub result: Result<Vec<Summary>, String>,
+}
+
+pub struct Sweep {
+    scenario: Scenario,
+    parameters: Vec<(Parameter, Vec<f64>)>,
+    // Expressions summarised for each run
+    metrics: Vec<String>,
+    ticks: u64,
+}
+
+impl Sweep {
+    pub fn new(scenario: Scenario, ticks: u64) -> Self {
+        Sweep {
+            scenario,
+            parameters: Vec::new(),
+            metrics: Vec::new(),
+            ticks,
+        }
+    }
+
+    /// Adds a parameter to vary, every run takes one of `values` for it.
+    pub fn vary(mut self, parameter: Parameter, values: Vec<f64>) -> Self {
+        self.parameters.push((parameter, values));
+        self
+    }
+
+    /// Adds an expression (as for `Engine::probe`) to summarise over each run.
+    pub fn measure(mut self, expression: &str) -> Self {
+        self.metrics.push(expression.to_string());
+        self
+    }
+
+    /// Every combination of parameter values, the last parameter varying fastest.
+    pub fn grid(&self) -> Vec<Vec<f64>> {
+        self.parameters
+            .iter()
+            .fold(vec![Vec::new()], |grid, (_, values)| {
+                grid.iter()
+                    .flat_map(|x| {
+                        values.iter().map(move |value| {
+                            let mut x = x.clone();
+                            x.push(*value);
+                            x
+                        })
+                    })
+                    .collect()
+            })
+    }
+
+    /// Runs every combination in turn. A run whose engine fails to build or tick is recorded with
+    /// the error rather than stopping the sweep.
+    pub fn run(&self) -> Vec<Run> {
+        self.grid()
+            .into_iter()
+            .map(|values| Run {
+                result: self.run_one(&values).map_err(|x| x.to_string()),
+                values,
+            })
+            .collect()
+    }
+
+    fn run_one(&self, values: &[f64]) -> EngineResult<Vec<Summary>> {
+        let mut scenario = self.scenario.clone();
+        for ((parameter, _), value) in self.parameters.iter().zip(values) {
+            parameter.apply(&mut scenario, *value);
+        }
+
+        let mut engine = scenario.build()?;
+        for ((parameter, _), value) in self.parameters.iter().zip(values) {
+            if let Parameter::Constant(name) = parameter {
+                engine.environment_mut().set_constant(name, *value);
+            }
+        }
+
+        let mut recorder = SeriesRecorder::new(self.metrics.clone());
+        recorder.sample(&engine)?;
+        for _ in 0..self.ticks {
+            engine.tick()?;
+            recorder.sample(&engine)?;
+        }
+
+        Ok(recorder
+            .series()
+            .iter()
+            .map(|x| {
+                let (min, max) = x.range().unwrap_or((f64::NAN, f64::NAN));
+                Summary {
+                    last: x.samples.last().map_or(f64::NAN, |(_, x)| *x),
+                    min,
+                    max,
+                }
+            })
+            .collect())
+    }
+
+    /// Writes one whitespace separated row per run (the parameter values, then the last, min and
+    /// max of each metric) with a commented header, like `SeriesRecorder::write_columns`. Failed
+    /// runs are written as comments.
+    pub fn write_table<W: Write>(&self, runs: &[Run], writer: &mut W) -> io::Result<()> {
+        let header =
+            self.parameters
+                .iter()
+                .map(|(x, _)| x.label())
+                .chain(self.metrics.iter().flat_map(|x| {
+                    ["last", "min", "max"].map(|summary| format!("{}({})", summary, x))
+                }))
+                .collect::<Vec<_>>();
+        writeln!(writer, "# {}", header.join("\t"))?;
+
+        for run in runs {
+            let values = run.values.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+            match &run.result {
+                Ok(summaries) => {
+                    let summaries = summaries
+                        .iter()
+                        .flat_map(|x| [x.last, x.min, x.max])
+                        .map(|x| x.to_string());
+                    let row = values.into_iter().chain(summaries).collect::<Vec<_>>();
+                    writeln!(writer, "{}", row.join("\t"))?;
+                }
+                Err(error) => writeln!(writer, "# {}\t{}", values.join("\t"), error)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenarios;
+
+    #[test]
+    fn test_sweep() {
+        let sweep = Sweep::new(scenarios::newtons_cradle(), 120)
+            .vary(Parameter::Restitution, vec![0.0, 1.0])
+            .vary(
+                Parameter::Velocity {
+                    body: "Striker".to_string(),
+                    axis: 0,
+                },
+                vec![100.0, 200.0, 400.0],
+            )
+            .measure("v_x_Striker");
+        assert_eq!(sweep.grid().len(), 6);
+        assert_eq!(sweep.grid()[4], vec![1.0, 200.0]);
+
+        let runs = sweep.run();
+        let summary = |i: usize| runs[i].result.as_ref().unwrap()[0];
+        // Inelastic, the striker ends up pushing the whole row along, elastic it passes its speed on
+        assert_eq!(summary(2).max, 400.0);
+        assert!((50.0..100.0).contains(&summary(2).last), "{:?}", summary(2));
+        assert!(summary(5).last < summary(2).last, "{:?}", summary(5));
+
+        let mut output = Vec::new();
+        sweep.write_table(&runs, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("# restitution\tv0_Striker\tlast(v_x_Striker)\t"));
+        assert_eq!(output.lines().count(), 7);
+    }
+
+    #[test]
+    fn test_sweep_constant() {
+        let mut scenario = scenarios::newtons_cradle();
+        scenario.equations = vec!["a_BallA=-g*hatj".to_string()];
+        let runs = Sweep::new(scenario, 10)
+            .vary(Parameter::Constant("g".to_string()), vec![0.0, 10.0])
+            .measure("v_y_BallA")
+            .run();
+
+        let last = |i: usize| runs[i].result.as_ref().unwrap()[0].last;
+        assert_eq!(last(0), 0.0);
+        assert!(last(1) < 0.0);
+    }
+}
@@ -0,0 +1,62 @@
+//! Targets the engine logs under through the `log` crate, one per subsystem, so a host can turn
+//! each up or down on its own (eg. `RUST_LOG=engine::collider=trace` with `env_logger`). Nothing
+//! is logged, or even formatted, until the host installs a logger.
+
+/// Contact impulses and constraint rows.
+pub const SOLVER: &str = "engine::solver";
+/// Pairs tested and the contacts found between them.
+pub const COLLIDER: &str = "engine::collider";
+/// Bodies moved by their equations.
+pub const INTEGRATOR: &str = "engine::integrator";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenarios;
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use std::cell::{Cell, RefCell};
+
+    thread_local! {
+        // Only the test listening records anything, whatever other tests log meanwhile
+        static LISTENING: Cell<bool> = const { Cell::new(false) };
+        static RECORDS: RefCell<Vec<(String, Level)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    struct Recorder;
+
+    impl Log for Recorder {
+        fn enabled(&self, _: &Metadata) -> bool {
+            LISTENING.get()
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                let entry = (record.target().to_string(), record.level());
+                RECORDS.with_borrow_mut(|x| x.push(entry));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn test_targets() {
+        log::set_logger(&Recorder).unwrap();
+        log::set_max_level(LevelFilter::Trace);
+        LISTENING.set(true);
+
+        let mut engine = scenarios::newtons_cradle().build().unwrap();
+        while engine.tick().unwrap().contacts.is_empty() {}
+        LISTENING.set(false);
+
+        let records = RECORDS.take();
+        for (target, level) in [
+            (SOLVER, Level::Trace),
+            (COLLIDER, Level::Trace),
+            (COLLIDER, Level::Debug),
+            (INTEGRATOR, Level::Debug),
+        ] {
+            assert!(records.iter().any(|x| *x == (target.to_string(), level)));
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! A chassis with motorized wheels hung from it on spring-damper suspension, put together from
+//! joints: each wheel turns on an [`Axle`] that keeps it under its anchor on the chassis and drives
+//! it round, and is held up by a [`Spring`] from the anchor.
+
+use crate::joints::{Anchor, Axle, Spring};
+use crate::math::{Column, Vector};
+use crate::spaces::Space2D;
+use crate::{Body, BodyKind, BodyProperties, Engine, Shape};
+
+// Rubber's grip, what a wheel is given unless told otherwise
+const WHEEL_FRICTION: f64 = 0.9;
+
+#[derive(Debug, Clone)]
+pub struct Wheel {
+    pub name: String,
+    // Where the suspension meets the chassis, relative to its centroid in its own orientation
+    pub anchor: [f64; 2],
+    pub radius: f64,
+    pub mass: f64,
+    // Used as both the static and kinetic coefficient
+    pub friction: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Suspension {
+    // Distance from the anchor down to the wheel's axle when the spring is relaxed
+    pub rest_length: f64,
+    pub stiffness: f64,
+    pub damping: f64,
+}
+
+impl Default for Suspension {
+    fn default() -> Self {
+        Suspension {
+            rest_length: 40.0,
+            stiffness: 80.0,
+            damping: 10.0,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Vehicle {
+    pub chassis: Body<Space2D>,
+    pub wheels: Vec<Wheel>,
+    pub suspension: Suspension,
+    // Angular velocity every wheel is driven at relative to the chassis, negative rolls to the right
+    pub motor_speed: f64,
+}
+
+impl Vehicle {
+    /// A vehicle with no wheels yet and the default suspension.
+    pub fn new(chassis: Body<Space2D>) -> Self {
+        Vehicle {
+            chassis,
+            wheels: Vec::new(),
+            suspension: Suspension::default(),
+            motor_speed: 0.0,
+        }
+    }
+
+    /// Hangs a rubber tyred wheel from `anchor` on the chassis, relative to its centroid in its own
+    /// orientation (the middle of its underside, for a rectangle `height` high, is
+    /// `[0, -height / 2]`). Names follow the same rules as body names.
+    pub fn with_wheel(mut self, name: &str, anchor: [f64; 2], radius: f64, mass: f64) -> Self {
+        self.wheels.push(Wheel {
+            name: name.to_string(),
+            anchor,
+            radius,
+            mass,
+            friction: WHEEL_FRICTION,
+        });
+        self
+    }
+
+    pub fn with_suspension(mut self, suspension: Suspension) -> Self {
+        self.suspension = suspension;
+        self
+    }
+
+    pub fn with_motor(mut self, speed: f64) -> Self {
+        self.motor_speed = speed;
+        self
+    }
+
+    /// The chassis followed by every wheel, each resting at its suspension's relaxed length.
+    pub fn bodies(&self) -> Vec<Body<Space2D>> {
+        let (x, y) = (
+            *self.chassis.linear.displacement.get(0),
+            *self.chassis.linear.displacement.get(1),
+        );
+        let theta = *self.chassis.angular.displacement.get(0);
+
+        let mut bodies = vec![self.chassis.clone()];
+        bodies.extend(self.wheels.iter().map(|wheel| {
+            let [ox, oy] = wheel.anchor;
+            let oy = oy - self.suspension.rest_length;
+
+            Body::at_rest(
+                wheel.name.clone(),
+                Shape::Ellipse(2.0 * wheel.radius, 2.0 * wheel.radius),
+                Column::vector([
+                    x + ox * theta.cos() - oy * theta.sin(),
+                    y + ox * theta.sin() + oy * theta.cos(),
+                ]),
+                Column::vector([0.0]),
+                BodyProperties {
+                    mass: wheel.mass,
+                    // Solid disc
+                    moi: wheel.mass * wheel.radius.powi(2) / 2.0,
+                    custom: Default::default(),
+                    tag: None,
+                    static_friction: wheel.friction,
+                    kinetic_friction: wheel.friction,
+                    kind: BodyKind::Dynamic,
+                    material: None,
+                    drag: 0.0,
+                },
+            )
+        }));
+
+        bodies
+    }
+
+    /// Hangs every wheel from the chassis in `engine`, which has to have their bodies as
+    /// [`Vehicle::bodies`] gives them (found by name): each on its suspension spring and an axle
+    /// square to the chassis, driven by the motor. Wheels it doesn't have are left off.
+    pub fn add_joints(&self, engine: &mut Engine<Space2D>) {
+        let Some(chassis) = engine.id_of(&self.chassis.name) else {
+            return;
+        };
+        let Suspension {
+            rest_length,
+            stiffness,
+            damping,
+        } = self.suspension;
+
+        for wheel in &self.wheels {
+            let Some(id) = engine.id_of(&wheel.name) else {
+                continue;
+            };
+            let anchor = Anchor::new(chassis, Column::vector(wheel.anchor));
+
+            engine.add_spring(Spring::new(
+                anchor.clone(),
+                Anchor::centroid(id),
+                rest_length,
+                stiffness,
+                damping,
+            ));
+            let axle = Axle::new(anchor, id, Column::vector([0.0, -1.0]))
+                .with_motor(Column::vector([self.motor_speed]));
+            engine.add_constraint(Box::new(axle));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenarios::vehicle;
+
+    #[test]
+    fn test_suspension_settles() {
+        let scenario = vehicle();
+        let chassis = scenario.bodies[1].linear.displacement.clone();
+
+        let mut engine = scenario.build().unwrap();
+        for _ in 0..300 {
+            engine.tick().unwrap();
+        }
+
+        let bodies = engine.bodies();
+        for body in bodies {
+            assert!(body.linear.displacement.magnitude().is_finite());
+        }
+
+        // Resting on its wheels rather than the ground, and the wheels still under their anchors
+        let [x, y] = [0, 1].map(|i| *bodies[1].linear.displacement.get(i));
+        assert!(y > *chassis.get(1) - 100.0, "{}", y);
+        for wheel in &bodies[2..] {
+            assert!(((wheel.linear.displacement.get(0) - x).abs() - 60.0).abs() < 5.0);
+            assert!(*wheel.linear.displacement.get(1) < y);
+        }
+
+        // Driven by the motor, and gripping the floor well enough to roll along it to the right
+        assert!(*bodies[2].angular.velocity.get(0) < 0.0);
+        assert!(x > *chassis.get(0) + 20.0, "{}", x);
+    }
+}
@@ -0,0 +1,306 @@
+//! Regions of the world with their own gravity, applied by the engine to every body whose centroid
+//! is inside one on top of whatever its equations give. Planets, anti-gravity lifts and wind tunnels
+//! then don't need an equation per body switching on its position.
+
+use crate::math::Vector;
+use crate::{Body, Space};
+
+#[derive(Clone)]
+pub enum Region<S: Space> {
+    /// Everything between two corners.
+    Box {
+        min: S::Linear,
+        max: S::Linear,
+    },
+    /// A convex polygon wound counterclockwise, in the plane of the first two axes.
+    Polygon(Vec<S::Linear>),
+    Everywhere,
+}
+
+impl<S: Space> Region<S> {
+    pub fn contains(&self, point: &S::Linear) -> bool {
+        match self {
+            Region::Box { min, max } => (0..S::Linear::dof())
+                .all(|i| min.get(i) <= point.get(i) && point.get(i) <= max.get(i)),
+            Region::Polygon(points) => {
+                let (x, y) = (point.get(0), point.get(1));
+
+                points
+                    .iter()
+                    .zip(points.iter().cycle().skip(1))
+                    .all(|(a, b)| {
+                        // Left of (or on) every edge
+                        (b.get(0) - a.get(0)) * (y - a.get(1))
+                            - (b.get(1) - a.get(1)) * (x - a.get(0))
+                            >= 0.0
+                    })
+            }
+            Region::Everywhere => true,
+        }
+    }
+
+    /// Opposite corners of a box around the region, or `None` if it has no bounds. A polygon's box
+    /// reaches forever along any axis after the first two.
+    pub fn bounds(&self) -> Option<(S::Linear, S::Linear)> {
+        match self {
+            Region::Box { min, max } => Some((min.clone(), max.clone())),
+            Region::Polygon(points) if !points.is_empty() => {
+                let corner = |pick: fn(f64, f64) -> f64, beyond: f64| {
+                    let axis = |k: usize| match k {
+                        0 | 1 => points.iter().map(|x| *x.get(k)).reduce(pick).unwrap(),
+                        _ => beyond,
+                    };
+                    S::Linear::new((0..S::Linear::dof()).map(axis).collect()).unwrap()
+                };
+                Some((corner(f64::min, f64::NEG_INFINITY), corner(f64::max, f64::INFINITY)))
+            }
+            Region::Polygon(_) | Region::Everywhere => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum Field<S: Space> {
+    /// The same acceleration throughout, eg. `[0, 200]` to cancel and reverse a gravity of 100.
+    Uniform(S::Linear),
+    /// An acceleration of `strength` towards `center`, for standing on a small planet.
+    Toward { center: S::Linear, strength: f64 },
+}
+
+#[derive(Clone)]
+pub struct GravityZone<S: Space> {
+    pub region: Region<S>,
+    pub field: Field<S>,
+}
+
+impl<S: Space> GravityZone<S> {
+    pub fn new(region: Region<S>, field: Field<S>) -> Self {
+        GravityZone { region, field }
+    }
+
+    /// The acceleration the zone gives `body`, if it is inside.
+    pub fn acceleration(&self, body: &Body<S>) -> Option<S::Linear> {
+        let position = &body.linear.displacement;
+        if !self.region.contains(position) {
+            return None;
+        }
+
+        Some(match &self.field {
+            Field::Uniform(x) => x.clone(),
+            Field::Toward { center, strength } => {
+                let offset = center.plus(&position.scale(-1.0));
+                if offset.magnitude() == 0.0 {
+                    return None;
+                }
+
+                offset.unit().scale(*strength)
+            }
+        })
+    }
+}
+
+/// The total acceleration of every zone `body` is in, `None` if it isn't in any.
+pub(crate) fn acceleration<S: Space>(
+    zones: &[GravityZone<S>],
+    body: &Body<S>,
+) -> Option<S::Linear> {
+    zones
+        .iter()
+        .filter_map(|x| x.acceleration(body))
+        .reduce(|a, b| a.plus(&b))
+}
+
+/// A force (rather than an acceleration) throughout a region, so heavier bodies are pushed about
+/// less, see `Engine::add_force_field`.
+#[derive(Clone)]
+pub enum Force<S: Space> {
+    /// The same force throughout, a steady wind.
+    Wind(S::Linear),
+    /// A force of `strength` straight away from `center`, or towards it if negative.
+    Radial { center: S::Linear, strength: f64 },
+    /// A force of `strength` around `center`, counterclockwise in the plane of the first two axes
+    /// (clockwise if negative), for whirlpools and tornadoes.
+    Vortex { center: S::Linear, strength: f64 },
+}
+
+#[derive(Clone)]
+pub struct ForceField<S: Space> {
+    pub region: Region<S>,
+    pub force: Force<S>,
+}
+
+impl<S: Space> ForceField<S> {
+    pub fn new(region: Region<S>, force: Force<S>) -> Self {
+        ForceField { region, force }
+    }
+
+    /// The force the field puts on `body`, if it is inside.
+    pub fn force(&self, body: &Body<S>) -> Option<S::Linear> {
+        let position = &body.linear.displacement;
+        if !self.region.contains(position) {
+            return None;
+        }
+
+        match &self.force {
+            Force::Wind(x) => Some(x.clone()),
+            Force::Radial { center, strength } => {
+                let offset = position.plus(&center.scale(-1.0));
+                (offset.magnitude() > 0.0).then(|| offset.unit().scale(*strength))
+            }
+            Force::Vortex { center, strength } => {
+                let offset = position.plus(&center.scale(-1.0));
+                let (x, y) = (*offset.get(0), *offset.get(1));
+                let distance = x.hypot(y);
+                if distance == 0.0 {
+                    return None;
+                }
+
+                let around = (0..S::Linear::dof())
+                    .map(|i| match i {
+                        0 => -y / distance,
+                        1 => x / distance,
+                        _ => 0.0,
+                    })
+                    .collect();
+                S::Linear::new(around).ok().map(|x| x.scale(*strength))
+            }
+        }
+    }
+}
+
+/// The total force of every field `body` is in, `None` if it isn't in any.
+pub(crate) fn force<S: Space>(fields: &[ForceField<S>], body: &Body<S>) -> Option<S::Linear> {
+    fields
+        .iter()
+        .filter_map(|x| x.force(body))
+        .reduce(|a, b| a.plus(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collide::Collide2D;
+    use crate::math::Column;
+    use crate::math::solve::{Environment, builtin};
+    use crate::spaces::Space2D;
+    use crate::{BodyProperties, Engine, Shape};
+
+    fn particle(name: &str, x: f64, y: f64) -> Body<Space2D> {
+        Body::at_rest(
+            name.to_string(),
+            Shape::Rec(1.0, 1.0),
+            Column::vector([x, y]),
+            Column::vector([0.0]),
+            BodyProperties::rectangle(1.0, 1.0, 1.0),
+        )
+    }
+
+    #[test]
+    fn test_regions() {
+        let triangle = Region::<Space2D>::Polygon(vec![
+            Column::vector([0.0, 0.0]),
+            Column::vector([2.0, 0.0]),
+            Column::vector([0.0, 2.0]),
+        ]);
+        assert!(triangle.contains(&Column::vector([0.5, 0.5])));
+        assert!(!triangle.contains(&Column::vector([1.5, 1.5])));
+
+        let lift = Region::<Space2D>::Box {
+            min: Column::vector([-1.0, -1.0]),
+            max: Column::vector([1.0, 1.0]),
+        };
+        assert!(lift.contains(&Column::vector([1.0, 0.0])));
+        assert!(!lift.contains(&Column::vector([0.0, 1.5])));
+    }
+
+    #[test]
+    fn test_gravity_zones() {
+        let mut engine = Engine::new(
+            vec![
+                particle("A", -50.0, 0.0),
+                particle("B", 50.0, 0.0),
+                particle("C", -5.0, 60.0),
+            ],
+            Environment::build(
+                vec!["a_A=-10*hatj", "a_B=-10*hatj"],
+                builtin::functions(),
+                builtin::constants(),
+            )
+            .unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        );
+
+        // Gravity reversed on the right, and a planet at the top that only C is near
+        engine.add_gravity_zone(GravityZone::new(
+            Region::Box {
+                min: Column::vector([0.0, -100.0]),
+                max: Column::vector([100.0, 100.0]),
+            },
+            Field::Uniform(Column::vector([0.0, 20.0])),
+        ));
+        engine.add_gravity_zone(GravityZone::new(
+            Region::Box {
+                min: Column::vector([-10.0, 50.0]),
+                max: Column::vector([10.0, 150.0]),
+            },
+            Field::Toward {
+                center: Column::vector([-5.0, 100.0]),
+                strength: 5.0,
+            },
+        ));
+        engine.tick().unwrap();
+
+        let velocity = |i: usize| *engine.bodies()[i].linear.velocity.get(1);
+        // Leapfrog averages the equations' acceleration with the last tick's (zero)
+        assert!((velocity(0) + 0.5).abs() < 1e-9);
+        assert!((velocity(1) - 1.5).abs() < 1e-9);
+        assert!((velocity(2) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_force_fields() {
+        let mut heavy = particle("C", 0.0, -5.0);
+        heavy.properties = BodyProperties::rectangle(2.0, 1.0, 1.0);
+        let mut engine = Engine::new(
+            vec![particle("A", -50.0, 0.0), particle("B", 0.0, 5.0), heavy],
+            Environment::build(vec![], builtin::functions(), builtin::constants()).unwrap(),
+            Box::new(Collide2D::new()),
+            0.1,
+            1.0,
+        );
+        engine.add_force_field(ForceField::new(
+            Region::Box {
+                min: Column::vector([-100.0, -100.0]),
+                max: Column::vector([-10.0, 100.0]),
+            },
+            Force::Wind(Column::vector([10.0, 0.0])),
+        ));
+        let around = Force::Vortex {
+            center: Column::vector([0.0, 0.0]),
+            strength: 4.0,
+        };
+        engine.add_force_field(ForceField::new(Region::Everywhere, around));
+        engine.add_force_field(ForceField::new(
+            Region::Everywhere,
+            Force::Radial {
+                center: Column::vector([0.0, 0.0]),
+                strength: -2.0,
+            },
+        ));
+        engine.tick().unwrap();
+
+        let velocity = |i: usize| {
+            let x = &engine.bodies()[i].linear.velocity;
+            [*x.get(0), *x.get(1)]
+        };
+        // The wind and the pull towards the centre, which the vortex turns aside from
+        let [x, y] = velocity(0);
+        assert!((x - 1.2).abs() < 1e-9, "{}", x);
+        assert!((y + 0.4).abs() < 1e-9, "{}", y);
+        // Above the centre the vortex pushes left, below it right, half as hard on twice the mass
+        assert_eq!(velocity(1).map(|x| (x * 1e9).round() / 1e9), [-0.4, -0.2]);
+        assert_eq!(velocity(2).map(|x| (x * 1e9).round() / 1e9), [0.2, 0.1]);
+    }
+}
@@ -1,8 +1,6 @@
-use engine::collide::Collide2D;
-use engine::math::solve::Environment;
-use engine::math::{Column, Vector};
-use engine::spaces::Space2D;
-use engine::{Body, BodyProperties, BodyState, Engine, Shape, Tick};
+use engine::BodyState;
+use engine::prelude::*;
+use protocol::SceneDescription;
 use wasm_bindgen::prelude::wasm_bindgen;
 
 pub type EngineError = String;
@@ -13,6 +11,14 @@ pub struct Vec2D {
     pub y: f64,
 }
 
+#[wasm_bindgen(getter_with_clone)]
+pub struct JsDiagnostic {
+    pub equation: usize,
+    pub start: Option<usize>,
+    pub end: Option<usize>,
+    pub message: String,
+}
+
 #[wasm_bindgen]
 pub struct Engine2D {
     inner: Engine<Space2D>,
@@ -23,6 +29,12 @@ pub struct Tick2D {
     inner: Tick<Space2D>,
 }
 
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = performance)]
+    fn now() -> f64;
+}
+
 #[wasm_bindgen]
 impl Tick2D {
     pub fn collisions(&self) -> Vec<Vec2D> {
@@ -35,6 +47,46 @@ impl Tick2D {
             })
             .collect()
     }
+
+    /// Seconds spent evaluating equations and integrating.
+    pub fn evaluation_time(&self) -> f64 {
+        self.inner.stats.evaluation_time
+    }
+
+    /// Seconds spent detecting and resolving collisions.
+    pub fn collision_time(&self) -> f64 {
+        self.inner.stats.collision_time
+    }
+
+    pub fn pairs_tested(&self) -> usize {
+        self.inner.stats.pairs_tested
+    }
+
+    pub fn contacts_resolved(&self) -> usize {
+        self.inner.stats.contacts_resolved
+    }
+
+    pub fn max_penetration(&self) -> f64 {
+        self.inner.stats.max_penetration
+    }
+
+    pub fn correction_iterations(&self) -> usize {
+        self.inner.stats.correction_iterations
+    }
+
+    pub fn root_iterations(&self) -> usize {
+        self.inner.stats.root_iterations
+    }
+}
+
+impl Engine2D {
+    fn wrap(mut inner: Engine<Space2D>) -> Self {
+        if cfg!(target_arch = "wasm32") {
+            inner.set_clock(|| now() / 1000.0);
+        }
+
+        Engine2D { inner }
+    }
 }
 
 #[wasm_bindgen]
@@ -44,20 +96,43 @@ impl Engine2D {
         equations: Vec<String>,
         delta_t: f64,
     ) -> Result<Self, EngineError> {
-        Ok(Engine2D {
-            inner: Engine::new(
-                bodies.into_iter().map(|b| b.inner).collect(),
-                Environment::build(
-                    equations.iter().map(|x| x.as_str()).collect(),
-                    engine::math::solve::builtin::functions(),
-                    engine::math::solve::builtin::constants(),
-                )
-                .map_err(|x| x.kind.to_string())?,
-                Box::new(Collide2D {}),
-                delta_t,
-                1.0,
-            ),
+        let mut inner = Engine::new(
+            bodies.into_iter().map(|b| b.inner).collect(),
+            Environment::build(
+                equations.iter().map(|x| x.as_str()).collect(),
+                engine::math::solve::builtin::functions(),
+                engine::math::solve::builtin::constants(),
+            )
+            .map_err(|x| x.kind.to_string())?,
+            Box::new(Dispatch::default()),
+            delta_t,
+            1.0,
+        );
+        inner.validate().map_err(|x| x.kind.to_string())?;
+
+        Ok(Engine2D::wrap(inner))
+    }
+
+    /// Builds the engine from a scene in JSON, the same format the viewer and server load.
+    pub fn from_scene(scene: &str) -> Result<Self, EngineError> {
+        let scene: SceneDescription = serde_json::from_str(scene).map_err(|x| x.to_string())?;
+        let inner = scene.build().map_err(|x| x.kind.to_string())?;
+
+        Ok(Engine2D::wrap(inner))
+    }
+
+    pub fn validate_equations(equations: Vec<String>) -> Vec<JsDiagnostic> {
+        Engine::<Space2D>::validate_equations(
+            &equations.iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+        )
+        .into_iter()
+        .map(|x| JsDiagnostic {
+            equation: x.equation,
+            start: x.error.span.as_ref().map(|x| x.start),
+            end: x.error.span.as_ref().map(|x| x.end),
+            message: x.error.kind.to_string(),
         })
+        .collect()
     }
 
     pub fn tick(&mut self) -> Result<Tick2D, EngineError> {
@@ -67,6 +142,55 @@ impl Engine2D {
             .map(|x| Tick2D { inner: x })
     }
 
+    pub fn step(&mut self) -> Result<Tick2D, EngineError> {
+        self.inner
+            .step()
+            .map_err(|x| x.kind.to_string())
+            .map(|x| Tick2D { inner: x })
+    }
+
+    pub fn pause(&mut self) {
+        self.inner.pause()
+    }
+
+    pub fn resume(&mut self) {
+        self.inner.resume()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.is_paused()
+    }
+
+    pub fn delta_t(&self) -> f64 {
+        self.inner.delta_t()
+    }
+
+    pub fn set_delta_t(&mut self, delta_t: f64) -> Result<(), EngineError> {
+        self.inner
+            .set_delta_t(delta_t)
+            .map_err(|x| x.kind.to_string())
+    }
+
+    pub fn probe(&self, expression: &str) -> Result<f64, EngineError> {
+        self.inner
+            .probe(expression)
+            .map_err(|x| x.kind.to_string())
+    }
+
+    pub fn get_body(&self, name: &str) -> Option<Body2D> {
+        self.inner.body(name).map(|x| Body2D { inner: x.clone() })
+    }
+
+    /// Bodies whose shape contains the point, eg. to select the one clicked on.
+    pub fn query_point(&self, x: f64, y: f64) -> Vec<Body2D> {
+        self.inner
+            .query_point(&Column::vector([x, y]))
+            .into_iter()
+            .filter_map(|x| self.inner.get(x))
+            .map(|x| Body2D { inner: x.clone() })
+            .collect()
+    }
+
     pub fn get_state(&self) -> Vec<Body2D> {
         self.inner
             .bodies()
@@ -99,6 +223,7 @@ impl Body2D {
         Body2D {
             inner: Body {
                 name: name.clone(),
+                id: None,
                 shape: Shape::Rec(width, height),
                 linear: BodyState {
                     displacement: Column::vector([x, y]),
@@ -111,6 +236,8 @@ impl Body2D {
                     acceleration: Column::empty(),
                 },
                 properties: BodyProperties::rectangle(mass, width, height),
+                force: Column::empty(),
+                torque: Column::empty(),
             },
         }
     }
@@ -130,4 +257,28 @@ impl Body2D {
     pub fn theta(&self) -> f64 {
         *self.inner.angular.displacement.get(0)
     }
+
+    pub fn vx(&self) -> f64 {
+        *self.inner.linear.velocity.get(0)
+    }
+
+    pub fn vy(&self) -> f64 {
+        *self.inner.linear.velocity.get(1)
+    }
+
+    pub fn omega(&self) -> f64 {
+        *self.inner.angular.velocity.get(0)
+    }
+
+    pub fn ax(&self) -> f64 {
+        *self.inner.linear.acceleration.get(0)
+    }
+
+    pub fn ay(&self) -> f64 {
+        *self.inner.linear.acceleration.get(1)
+    }
+
+    pub fn alpha(&self) -> f64 {
+        *self.inner.angular.acceleration.get(0)
+    }
 }
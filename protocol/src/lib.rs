@@ -0,0 +1,375 @@
+//! Messages exchanged between a headless simulation server and its viewers.
+//!
+//! Every message is a single line of JSON, so any client able to read lines off a TCP socket (the
+//! macroquad viewer, a websocket bridge for the web frontend, `nc`) can take part.
+
+use engine::collide::Dispatch;
+use engine::err::EngineResult;
+use engine::math::solve::{builtin, Environment};
+use engine::math::{Column, Vector};
+use engine::scenarios::Scenario;
+use engine::spaces::Space2D;
+use engine::{Body, BodyKind, BodyProperties, Engine, Shape};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{BufRead, Write};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind")]
+pub enum ShapeDescription {
+    Rec { width: f64, height: f64 },
+    Ellipse { major: f64, minor: f64 },
+    Manifold { points: Vec<[f64; 2]> },
+    Chain { points: Vec<[f64; 2]> },
+    HalfPlane { normal: [f64; 2], offset: f64 },
+}
+
+impl ShapeDescription {
+    pub fn build(&self) -> Shape<Space2D> {
+        match self {
+            ShapeDescription::Rec { width, height } => Shape::Rec(*width, *height),
+            ShapeDescription::Ellipse { major, minor } => Shape::Ellipse(*major, *minor),
+            // Scene files are written by hand, so the points may be in any order
+            ShapeDescription::Manifold { points } => {
+                Shape::convex(points.iter().map(|x| Column::vector(*x)).collect())
+            }
+            ShapeDescription::Chain { points } => {
+                Shape::Chain(points.iter().map(|x| Column::vector(*x)).collect())
+            }
+            ShapeDescription::HalfPlane { normal, offset } => {
+                Shape::HalfPlane(Column::vector(*normal), *offset)
+            }
+        }
+    }
+
+    pub fn describe(shape: &Shape<Space2D>) -> Self {
+        match shape {
+            Shape::Rec(width, height) => ShapeDescription::Rec {
+                width: *width,
+                height: *height,
+            },
+            Shape::Ellipse(major, minor) => ShapeDescription::Ellipse {
+                major: *major,
+                minor: *minor,
+            },
+            Shape::Manifold(points) => ShapeDescription::Manifold {
+                points: points.iter().map(|x| [*x.get(0), *x.get(1)]).collect(),
+            },
+            Shape::Chain(points) => ShapeDescription::Chain {
+                points: points.iter().map(|x| [*x.get(0), *x.get(1)]).collect(),
+            },
+            Shape::HalfPlane(normal, offset) => ShapeDescription::HalfPlane {
+                normal: [*normal.get(0), *normal.get(1)],
+                offset: *offset,
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum KindDescription {
+    Static,
+    #[default]
+    Dynamic,
+    Kinematic,
+}
+
+impl KindDescription {
+    pub fn build(&self) -> BodyKind {
+        match self {
+            KindDescription::Static => BodyKind::Static,
+            KindDescription::Dynamic => BodyKind::Dynamic,
+            KindDescription::Kinematic => BodyKind::Kinematic,
+        }
+    }
+
+    pub fn describe(kind: BodyKind) -> Self {
+        match kind {
+            BodyKind::Static => KindDescription::Static,
+            BodyKind::Dynamic => KindDescription::Dynamic,
+            BodyKind::Kinematic => KindDescription::Kinematic,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BodyDescription {
+    pub name: String,
+    pub shape: ShapeDescription,
+    #[serde(default)]
+    pub kind: KindDescription,
+    pub mass: f64,
+    // Defaults to that of the shape with its mass spread evenly over it
+    #[serde(default)]
+    pub moi: Option<f64>,
+    pub position: [f64; 2],
+    #[serde(default)]
+    pub rotation: f64,
+    #[serde(default)]
+    pub velocity: [f64; 2],
+    #[serde(default)]
+    pub angular_velocity: f64,
+    // Custom scalars exported to equations, eg. `charge = 2.0` gives `charge_A`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, f64>,
+}
+
+impl BodyDescription {
+    pub fn build(&self) -> Body<Space2D> {
+        let shape = self.shape.build();
+        // Scenes give a mass rather than a density, spread evenly over the shape. Chains have no
+        // area to spread it over and half-planes an endless one, so they have none
+        let density = match shape.area() {
+            0.0 => 0.0,
+            area => self.mass / area,
+        };
+        let mut properties = BodyProperties::from_density(density, &shape);
+        if let Some(moi) = self.moi {
+            properties.moi = moi;
+        }
+        properties.custom = self.properties.clone();
+        properties.kind = self.kind.build();
+
+        let mut body = Body::at_rest(
+            self.name.clone(),
+            shape,
+            Column::vector(self.position),
+            Column::vector([self.rotation]),
+            properties,
+        );
+        body.linear.velocity = Column::vector(self.velocity);
+        body.angular.velocity = Column::vector([self.angular_velocity]);
+
+        body
+    }
+
+    pub fn describe(body: &Body<Space2D>) -> Self {
+        BodyDescription {
+            name: body.name.clone(),
+            shape: ShapeDescription::describe(&body.shape),
+            kind: KindDescription::describe(body.properties.kind),
+            mass: body.properties.mass,
+            moi: Some(body.properties.moi),
+            position: [
+                *body.linear.displacement.get(0),
+                *body.linear.displacement.get(1),
+            ],
+            rotation: *body.angular.displacement.get(0),
+            velocity: [*body.linear.velocity.get(0), *body.linear.velocity.get(1)],
+            angular_velocity: *body.angular.velocity.get(0),
+            properties: body.properties.custom.clone(),
+        }
+    }
+}
+
+/// Everything needed to construct an engine: bodies, equations and solver settings.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SceneDescription {
+    pub bodies: Vec<BodyDescription>,
+    pub equations: Vec<String>,
+    pub delta_t: f64,
+    #[serde(default = "default_restitution")]
+    pub restitution: f64,
+}
+
+fn default_restitution() -> f64 {
+    1.0
+}
+
+impl SceneDescription {
+    pub fn build(&self) -> EngineResult<Engine<Space2D>> {
+        let mut engine = Engine::new(
+            self.bodies.iter().map(|x| x.build()).collect(),
+            Environment::build(
+                self.equations.iter().map(|x| x.as_str()).collect(),
+                builtin::functions(),
+                builtin::constants(),
+            )?,
+            Box::new(Dispatch::default()),
+            self.delta_t,
+            self.restitution,
+        );
+        engine.validate()?;
+
+        Ok(engine)
+    }
+
+    /// Describes one of the engine's built-in scenarios, eg. to save it alongside a replay.
+    pub fn describe(scenario: &Scenario) -> Self {
+        SceneDescription {
+            bodies: scenario.bodies.iter().map(BodyDescription::describe).collect(),
+            equations: scenario.equations.clone(),
+            delta_t: scenario.delta_t,
+            restitution: scenario.restitution,
+        }
+    }
+}
+
+/// The state of a single body at the end of a tick.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BodySnapshot {
+    pub name: String,
+    pub shape: ShapeDescription,
+    pub position: [f64; 2],
+    pub rotation: f64,
+    pub velocity: [f64; 2],
+    pub angular_velocity: f64,
+}
+
+impl BodySnapshot {
+    pub fn capture(body: &Body<Space2D>) -> Self {
+        BodySnapshot {
+            name: body.name.clone(),
+            shape: ShapeDescription::describe(&body.shape),
+            position: [
+                *body.linear.displacement.get(0),
+                *body.linear.displacement.get(1),
+            ],
+            rotation: *body.angular.displacement.get(0),
+            velocity: [*body.linear.velocity.get(0), *body.linear.velocity.get(1)],
+            angular_velocity: *body.angular.velocity.get(0),
+        }
+    }
+
+    /// Rebuilds the body for rendering. Snapshots carry no mass properties, so it is weightless.
+    pub fn body(&self) -> Body<Space2D> {
+        let mut body = Body::at_rest(
+            self.name.clone(),
+            self.shape.build(),
+            Column::vector(self.position),
+            Column::vector([self.rotation]),
+            BodyProperties::weightless(),
+        );
+        body.linear.velocity = Column::vector(self.velocity);
+        body.angular.velocity = Column::vector([self.angular_velocity]);
+
+        body
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    /// Replaces the simulation with a freshly built scene.
+    LoadScene { scene: SceneDescription },
+    Pause,
+    Resume,
+    /// Advances a paused simulation by a single tick.
+    Step,
+    SetDeltaT { delta_t: f64 },
+    /// Changes the velocity of the body named `body` by `impulse` over its mass during the next
+    /// tick, applied at its centroid.
+    ApplyImpulse { body: String, impulse: [f64; 2] },
+    Disconnect,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type")]
+pub enum ServerMessage {
+    SceneLoaded,
+    State {
+        tick: u64,
+        bodies: Vec<BodySnapshot>,
+        collisions: Vec<[f64; 2]>,
+    },
+    Error { message: String },
+}
+
+/// Writes a message as a single line of JSON.
+pub fn write_message<W: Write, T: Serialize>(writer: &mut W, message: &T) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, message)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Reads the next line of JSON as a message, returning `None` once the stream is closed.
+pub fn read_message<R: BufRead, T: DeserializeOwned>(reader: &mut R) -> io::Result<Option<T>> {
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        if !line.trim().is_empty() {
+            return serde_json::from_str(&line)
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    fn scene() -> SceneDescription {
+        SceneDescription {
+            bodies: vec![BodyDescription {
+                name: "A".to_string(),
+                shape: ShapeDescription::Rec {
+                    width: 1.0,
+                    height: 1.0,
+                },
+                kind: KindDescription::Dynamic,
+                mass: 1.0,
+                moi: None,
+                position: [0.0, 0.0],
+                rotation: 0.0,
+                velocity: [0.0, 0.0],
+                angular_velocity: 0.0,
+                properties: BTreeMap::new(),
+            }],
+            equations: vec!["a_A=-10*hatj".to_string()],
+            delta_t: 0.1,
+            restitution: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let mut buffer = Vec::new();
+        let sent = ClientMessage::LoadScene { scene: scene() };
+        write_message(&mut buffer, &sent).unwrap();
+        write_message(&mut buffer, &ClientMessage::Step).unwrap();
+        let impulse = ClientMessage::ApplyImpulse {
+            body: "A".to_string(),
+            impulse: [1.0, 0.0],
+        };
+        write_message(&mut buffer, &impulse).unwrap();
+
+        let mut reader = BufReader::new(buffer.as_slice());
+        assert_eq!(read_message(&mut reader).unwrap(), Some(sent));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(ClientMessage::Step));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(impulse));
+        assert_eq!(read_message::<_, ClientMessage>(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn test_build_scene() {
+        let mut engine = scene().build().unwrap();
+        engine.tick().unwrap();
+
+        let snapshot = BodySnapshot::capture(&engine.bodies()[0]);
+        assert!(snapshot.position[1] < 0.0);
+        assert_eq!(
+            snapshot.body().linear.displacement,
+            engine.bodies()[0].linear.displacement
+        );
+    }
+
+    #[test]
+    fn test_describe_scenario() {
+        let scenario = engine::scenarios::orbit();
+        let scene = SceneDescription::describe(&scenario);
+
+        assert_eq!(scene.bodies[1].velocity, [0.0, *scenario.bodies[1].linear.velocity.get(1)]);
+        assert_eq!(scene.bodies[1].build().properties.moi, scenario.bodies[1].properties.moi);
+        assert_eq!(scene.bodies[0].kind, KindDescription::Static);
+    }
+}
@@ -36,7 +36,7 @@ export const SimulationCanvas: React.FC<SimulationCanvasProps> = ({bodies, runni
 
         const track = trackRef.current
         if (track && engine) {
-            let body = engine.get_state().find((s) => s.name() == track);
+            let body = engine.get_body(track);
 
             if (body) {
                 viewTransform.current = {